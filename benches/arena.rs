@@ -0,0 +1,54 @@
+//! Parsing then dropping the tree, owned versus in an arena: what a
+//! language server pays on every reparse. Lexing happens in the setup, so
+//! only parsing and freeing are timed.
+
+use chasm::arena::AstArena;
+use chasm::parser::Parser;
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+// Only `program` is wanted here.
+#[allow(dead_code)]
+mod fixtures;
+
+fn parse_and_drop(c: &mut Criterion) {
+    let input = fixtures::program(100_000);
+    let mut group = c.benchmark_group("parse+drop");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("100k lines, owned", |b| {
+        b.iter_batched(
+            || Parser::new(&input),
+            |mut parser| drop(black_box(parser.parse())),
+            BatchSize::LargeInput,
+        )
+    });
+    // A fresh arena each time maps and faults in new memory for the tree.
+    group.bench_function("100k lines, new arena", |b| {
+        b.iter_batched(
+            || (Parser::new(&input), AstArena::new()),
+            |(mut parser, arena)| {
+                black_box(parser.parse_in(&arena));
+                drop(arena);
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    // How a language server would use one: reset between parses, keeping
+    // the memory.
+    let mut arena = AstArena::new();
+    group.bench_function("100k lines, reused arena", |b| {
+        b.iter_batched(
+            || Parser::new(&input),
+            |mut parser| {
+                black_box(parser.parse_in(&arena));
+                arena.reset();
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse_and_drop);
+criterion_main!(benches);
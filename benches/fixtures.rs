@@ -0,0 +1,45 @@
+//! Generated inputs for the benchmarks, so the repo doesn't carry megabytes
+//! of assembly.
+
+use std::fmt::Write;
+
+/// `lines` lines of ordinary code: labels, instructions with a few
+/// operands, consts and the occasional directive.
+pub fn program(lines: usize) -> String {
+    let mut out = String::with_capacity(lines * 24);
+    for i in 0..lines {
+        let _ = match i % 8 {
+            0 => writeln!(out, "label_{}:", i),
+            1 => writeln!(out, "const C{} = {}", i, i),
+            2 => writeln!(out, "@define D{} {}", i, i),
+            3 => writeln!(out, "    mov r{} r{}", i % 8, (i + 1) % 8),
+            4 => writeln!(out, "    add r1 r2 {}", i),
+            5 => writeln!(out, "    ld r3 \"str{}\"", i),
+            6 => writeln!(out, "    jmp label_{}", i - 6),
+            _ => writeln!(out, "    nop"),
+        };
+    }
+    out
+}
+
+/// `depth` nested blocks with an instruction at each level.
+pub fn nested(depth: usize) -> String {
+    let mut out = String::new();
+    for i in 0..depth {
+        let _ = writeln!(out, "{{ nop{}", i);
+    }
+    for _ in 0..depth {
+        out.push_str("}\n");
+    }
+    out
+}
+
+/// A single instruction with `operands` operands.
+pub fn long_operands(operands: usize) -> String {
+    let mut out = String::from("op");
+    for i in 0..operands {
+        let _ = write!(out, " r{}", i);
+    }
+    out.push('\n');
+    out
+}
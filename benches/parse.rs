@@ -0,0 +1,88 @@
+use chasm::export::{self, CHeaderOptions};
+use chasm::incremental::{ParseResult, TextEdit};
+use chasm::parser::{MAX_NESTING, Parser};
+use chasm::symbols::SymbolTable;
+use chasm::tokens::TokenKind;
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use logos::Logos;
+use std::hint::black_box;
+
+mod fixtures;
+
+fn lex(c: &mut Criterion) {
+    let input = fixtures::program(1_000_000);
+    let mut group = c.benchmark_group("lex");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("1M lines", |b| {
+        b.iter(|| TokenKind::lexer(black_box(&input)).count())
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let input = fixtures::program(100_000);
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("100k lines", |b| {
+        b.iter(|| Parser::new(black_box(&input)).parse())
+    });
+    group.finish();
+}
+
+/// Everything the pipeline does today: parse, collect symbols, emit.
+fn pipeline(c: &mut Criterion) {
+    let input = fixtures::program(10_000);
+    c.bench_function("pipeline/10k lines", |b| {
+        b.iter(|| {
+            let ast = Parser::new(black_box(&input)).parse();
+            export::c_header(&SymbolTable::collect(&ast), &CHeaderOptions::default())
+        })
+    });
+}
+
+/// Small edits to a 20k-line file, applied incrementally versus parsed
+/// from scratch: a one-token change in the middle, and a line typed in
+/// near the top, which shifts everything after it.
+fn reparse(c: &mut Criterion) {
+    let input = fixtures::program(20_000);
+    let at = input.find("add r1 r2 10004").unwrap() + "add r1 r2 ".len();
+    let token = TextEdit::new(at..at + "10004".len(), "7");
+    let at = input.find("label_80:").unwrap();
+    let line = TextEdit::new(at..at, "    mov r1 r2\n");
+
+    let mut group = c.benchmark_group("reparse");
+    group.sample_size(20);
+    for (name, edit) in [("one token", &token), ("new line", &line)] {
+        let mut edited = input.clone();
+        edited.replace_range(edit.range.clone(), &edit.text);
+        group.bench_function(format!("20k lines, {}, full", name), |b| {
+            b.iter(|| ParseResult::new(black_box(&edited)))
+        });
+        group.bench_function(format!("20k lines, {}, incremental", name), |b| {
+            b.iter_batched(
+                || ParseResult::new(&input),
+                |old| old.update(black_box(edit.clone())),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn worst_case(c: &mut Criterion) {
+    let nested = fixtures::nested(MAX_NESTING);
+    let long = fixtures::long_operands(10_000);
+    let mut group = c.benchmark_group("worst case");
+    group.bench_function("maximally nested blocks", |b| {
+        b.iter(|| Parser::new(black_box(&nested)).parse())
+    });
+    group.bench_function("10k operands", |b| {
+        b.iter(|| Parser::new(black_box(&long)).parse())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lex, parse, pipeline, reparse, worst_case);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+#![no_main]
+
+//! Properties of the lexer and the literal helpers in `chasm::tokens`, the
+//! same ones `tests/lex_props.rs` checks with proptest on every test run.
+
+use arbitrary::Arbitrary;
+use chasm::detokenize;
+use chasm::parser::{LexErrorKind, LexOptions, TokenStream};
+use chasm::source::{BOM, FileId, SourceFile};
+use chasm::tokens::{self, Radix, TokenKind};
+use libfuzzer_sys::fuzz_target;
+use logos::Logos;
+use std::rc::Rc;
+
+#[derive(Debug, Arbitrary)]
+enum AnyRadix {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+/// Characters worth putting in a string literal: plain text, everything
+/// that needs escaping, and something outside ASCII.
+const ALPHABET: &[char] = &[
+    'a', 'Z', '0', ' ', '\'', '"', '\\', '\n', '\r', '\t', '\0', 'é', '✓',
+];
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    value: i64,
+    radix: AnyRadix,
+    string: Vec<u8>,
+    source: &'a str,
+}
+
+fn int_roundtrip(value: i64, radix: AnyRadix) {
+    let radix = match radix {
+        AnyRadix::Bin => Radix::Bin,
+        AnyRadix::Oct => Radix::Oct,
+        AnyRadix::Dec => Radix::Dec,
+        AnyRadix::Hex => Radix::Hex,
+    };
+    let Some(text) = tokens::format_int(value, radix) else {
+        assert!(value < 0);
+        return;
+    };
+    let lexed: Vec<_> = TokenKind::lexer(&text).collect();
+    assert_eq!(lexed.len(), 1, "{} lexed as {:?}", text, lexed);
+    assert_eq!(
+        lexed[0].as_ref().ok().and_then(TokenKind::int_value),
+        Some(value),
+        "{}",
+        text
+    );
+}
+
+fn string_roundtrip(picks: &[u8]) {
+    let s: String = picks
+        .iter()
+        .map(|&i| ALPHABET[i as usize % ALPHABET.len()])
+        .collect();
+    let literal = tokens::escape_string(&s);
+    let lexed: Vec<_> = TokenKind::lexer(&literal).collect();
+    match lexed.as_slice() {
+        [Ok(TokenKind::StrLit(raw))] => assert_eq!(tokens::parse_string(raw), s),
+        other => panic!("{:?} lexed as {:?}", literal, other),
+    }
+}
+
+fn lex_invariants(source: &str) {
+    let mut end = 0;
+    for (_, span) in TokenKind::lexer(source).spanned() {
+        assert!(span.start >= end && span.start < span.end && span.end <= source.len());
+        end = span.end;
+    }
+}
+
+fn detokenize_roundtrip(source: &str) {
+    let tokens = TokenStream::new(source).into_tokens();
+    let text = detokenize(&tokens);
+    let relexed = TokenStream::new(&text).into_tokens();
+    let kinds = |t: &[chasm::parser::Token]| t.iter().map(|t| t.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds(&tokens),
+        kinds(&relexed),
+        "{:?} -> {:?}",
+        source,
+        text
+    );
+}
+
+/// With trivia kept, the tokens spell the source again, unless some of it
+/// didn't lex.
+fn trivia_roundtrip(source: &str) {
+    let opts = LexOptions {
+        trivia: true,
+        ..LexOptions::default()
+    };
+    let file = Rc::new(SourceFile::new("<input>", source));
+    let stream = TokenStream::with_options(FileId::default(), file, opts);
+    if stream.lex_errors().iter().any(|e| e.kind == LexErrorKind::Unrecognised) {
+        return;
+    }
+    let trailing: String = stream.trailing_trivia().iter().map(|t| t.text.as_str()).collect();
+    let text = detokenize(&stream.into_tokens()) + &trailing;
+    assert_eq!(text, source.strip_prefix(BOM).unwrap_or(source));
+}
+
+fuzz_target!(|input: Input| {
+    int_roundtrip(input.value, input.radix);
+    string_roundtrip(&input.string);
+    lex_invariants(input.source);
+    detokenize_roundtrip(input.source);
+    trivia_roundtrip(input.source);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+//! `Object::from_bytes` never panics, and whatever it accepts writes back
+//! out byte for byte.
+
+use chasm::object::Object;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(obj) = Object::from_bytes(data) {
+        assert_eq!(obj.to_bytes(), data);
+    }
+});
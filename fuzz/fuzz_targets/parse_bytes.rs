@@ -0,0 +1,12 @@
+#![no_main]
+
+use chasm::parser::Parser;
+use libfuzzer_sys::fuzz_target;
+
+// Syntax errors are recorded, not raised, so any panic is a crash.
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let _ = Parser::new(source).parse();
+});
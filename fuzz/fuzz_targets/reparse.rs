@@ -0,0 +1,90 @@
+#![no_main]
+
+//! Applies random edits through `ParseResult::update` and checks each result
+//! against parsing the edited text from scratch.
+
+use arbitrary::Arbitrary;
+use chasm::diff::{self, DiffOptions};
+use chasm::incremental::{ParseResult, TextEdit};
+use chasm::parser::Token;
+use libfuzzer_sys::fuzz_target;
+
+/// Source fragments to build text from, so that most programs parse and
+/// edits land on interesting boundaries.
+#[rustfmt::skip]
+const PIECES: &[&str] = &[
+    " ", "\n", "mov", "r0", "r1", "a", "b", "1", "0x", "F", "0b", "0", ":", "::", ".", ";", ",",
+    "{", "}", "(", ")", "\"", "'", "\\", "x", "var", "const", "=", "include", "@define",
+    "macro_rules!", "for!", "while!", "if!", "else", "<", "++", "for", "!", "é",
+];
+
+#[derive(Debug, Arbitrary)]
+struct Edit {
+    at: u16,
+    delete: u8,
+    insert: Vec<u8>,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    initial: Vec<u8>,
+    edits: Vec<Edit>,
+}
+
+fn text(picks: &[u8]) -> String {
+    picks
+        .iter()
+        .map(|&i| PIECES[i as usize % PIECES.len()])
+        .collect()
+}
+
+fn floor_boundary(s: &str, mut i: usize) -> usize {
+    i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+fn token_key(t: &Token) -> (String, std::ops::Range<usize>) {
+    (t.text.clone(), t.span.range.clone())
+}
+
+fuzz_target!(|input: Input| {
+    let initial = text(&input.initial);
+    let mut current = ParseResult::new(&initial);
+
+    for edit in input.edits {
+        let source = current.source();
+        let start = floor_boundary(source, edit.at as usize);
+        let end = floor_boundary(source, start + edit.delete as usize);
+        let edit = TextEdit::new(start..end, text(&edit.insert));
+
+        let mut expected_text = source.to_string();
+        expected_text.replace_range(edit.range.clone(), &edit.text);
+        let expected = ParseResult::new(&expected_text);
+        let actual = current.update(edit.clone());
+
+        assert_eq!(expected.source(), actual.source());
+        let keys = |r: &ParseResult| r.tokens().iter().map(token_key).collect::<Vec<_>>();
+        assert_eq!(
+            keys(&expected),
+            keys(&actual),
+            "tokens differ after {:?}",
+            edit
+        );
+        let diffs = diff::diff_with(
+            expected.statements(),
+            actual.statements(),
+            &DiffOptions { spans: true },
+        );
+        assert!(diffs.is_empty(), "after {:?}: {:?}", edit, diffs);
+        assert_eq!(
+            expected.errors(),
+            actual.errors(),
+            "syntax errors differ after {:?}",
+            edit
+        );
+        current = actual;
+    }
+});
@@ -0,0 +1,358 @@
+#![no_main]
+
+//! Generates well-formed programs, prints them with [`chasm::to_source`],
+//! and checks that parsing the text gives back the same tree.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use chasm::parser::{
+    AssignOp, BinaryOp, Comparison, DataWidth, Encoding, Expr, IfBranch, MemoryOperand, Number,
+    Operand, Operands, Parser, Statement, StatementKind, StructField, UnaryOp, Visibility,
+};
+use chasm::source::Span;
+use libfuzzer_sys::fuzz_target;
+use std::ops::ControlFlow;
+
+/// Identifiers the generator draws from. None are keywords or registers.
+const NAMES: &[&str] = &["a", "b", "foo", "bar", "x0", "t1", "_tmp", "loop2", "mov"];
+
+/// Nesting limit for generated blocks, well under the parser's own.
+const MAX_DEPTH: usize = 8;
+
+const MAX_STATEMENTS: u32 = 16;
+
+/// Nesting limit for generated expressions.
+const MAX_EXPR_DEPTH: usize = 6;
+
+#[derive(Debug)]
+struct Program(Vec<Statement>);
+
+impl<'a> Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        statements(u, 0).map(Program)
+    }
+}
+
+fn name(u: &mut Unstructured) -> Result<String> {
+    Ok(u.choose(NAMES)?.to_string())
+}
+
+fn int(u: &mut Unstructured) -> Result<i64> {
+    // The lexer has no negative literals.
+    Ok(i64::from(u.arbitrary::<u32>()?))
+}
+
+fn number(u: &mut Unstructured) -> Result<Number> {
+    Ok(match u.arbitrary::<bool>()? {
+        true => Number::Int(int(u)?),
+        // Finite and non-negative, like every float literal.
+        false => Number::Float(f64::from(u.arbitrary::<u32>()?) / 1024.0),
+    })
+}
+
+fn expr(u: &mut Unstructured, depth: usize) -> Result<Expr> {
+    let kinds = if depth < MAX_EXPR_DEPTH { 8 } else { 3 };
+    Ok(match u.choose_index(kinds)? {
+        // Negative numbers print as `-n` and parse back as one literal.
+        0 => Expr::Number(match number(u)? {
+            Number::Int(n) if u.arbitrary()? => Number::Int(-n),
+            n => n,
+        }),
+        1 => Expr::Symbol(name(u)?),
+        2 => Expr::Str(format!("\"{}\"", name(u)?)),
+        3 => Expr::Unary {
+            op: *u.choose(&[UnaryOp::Neg, UnaryOp::BitNot, UnaryOp::Not])?,
+            expr: Box::new(expr(u, depth + 1)?),
+        },
+        4 => Expr::Binary {
+            op: *u.choose(&[
+                BinaryOp::Add,
+                BinaryOp::Sub,
+                BinaryOp::Mul,
+                BinaryOp::Div,
+                BinaryOp::Rem,
+                BinaryOp::Shl,
+                BinaryOp::Shr,
+                BinaryOp::And,
+                BinaryOp::Or,
+                BinaryOp::Xor,
+                BinaryOp::Lt,
+                BinaryOp::Le,
+                BinaryOp::Gt,
+                BinaryOp::Ge,
+                BinaryOp::Eq,
+                BinaryOp::Ne,
+            ])?,
+            lhs: Box::new(expr(u, depth + 1)?),
+            rhs: Box::new(expr(u, depth + 1)?),
+        },
+        5 => Expr::Array(
+            (0..u.int_in_range(0..=3)?)
+                .map(|_| expr(u, depth + 1))
+                .collect::<Result<_>>()?,
+        ),
+        6 => Expr::Index {
+            base: Box::new(expr(u, depth + 1)?),
+            index: Box::new(expr(u, depth + 1)?),
+        },
+        _ => Expr::Call {
+            name: name(u)?,
+            args: (0..u.int_in_range(0..=3)?)
+                .map(|_| expr(u, depth + 1))
+                .collect::<Result<_>>()?,
+        },
+    })
+}
+
+fn assign_op(u: &mut Unstructured) -> Result<AssignOp> {
+    Ok(*u.choose(&[
+        AssignOp::Add,
+        AssignOp::Sub,
+        AssignOp::Mul,
+        AssignOp::Div,
+        AssignOp::Shl,
+        AssignOp::Shr,
+        AssignOp::And,
+        AssignOp::Or,
+    ])?)
+}
+
+fn args(u: &mut Unstructured) -> Result<chasm::parser::Args> {
+    let mut args = chasm::parser::Args::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+        args.push(match u.arbitrary::<bool>()? {
+            true => name(u)?,
+            false => int(u)?.to_string(),
+        });
+    }
+    Ok(args)
+}
+
+fn operands(u: &mut Unstructured) -> Result<Operands> {
+    let mut operands = Operands::new();
+    for _ in 0..u.int_in_range(0..=4)? {
+        operands.push(match u.choose_index(5)? {
+            0 => Operand::Register(register(u)?),
+            1 => Operand::Immediate(expr(u, 0)?),
+            2 => Operand::Char(*u.choose(&['A', 'z', '0', '_'])?),
+            3 => Operand::Memory(Box::new(memory(u)?)),
+            // A bare name or string parses back as its own kind.
+            _ => match expr(u, 0)? {
+                Expr::Symbol(name) => Operand::Symbol(name),
+                Expr::Str(literal) => Operand::String(literal),
+                expr => Operand::Expr(expr),
+            },
+        });
+    }
+    Ok(operands)
+}
+
+fn register(u: &mut Unstructured) -> Result<String> {
+    Ok(format!("r{}", u.int_in_range(0..=15)?))
+}
+
+fn memory(u: &mut Unstructured) -> Result<MemoryOperand> {
+    let mut base = match u.arbitrary::<bool>()? {
+        true => Some(register(u)?),
+        false => None,
+    };
+    let (mut index, scale) = match u.arbitrary::<bool>()? {
+        true => (Some(register(u)?), *u.choose(&[1, 2, 4, 8])?),
+        false => (None, 1),
+    };
+    // An unscaled register on its own is the base.
+    if base.is_none() && scale == 1 {
+        base = index.take();
+    }
+    // Something has to be inside the brackets.
+    let empty = base.is_none() && index.is_none();
+    let displacement = match empty || u.arbitrary::<bool>()? {
+        true => Some(expr(u, 0)?),
+        false => None,
+    };
+    Ok(MemoryOperand {
+        base,
+        index,
+        scale,
+        displacement,
+    })
+}
+
+fn statements(u: &mut Unstructured, depth: usize) -> Result<Vec<Statement>> {
+    let mut out = Vec::new();
+    u.arbitrary_loop(None, Some(MAX_STATEMENTS), |u| {
+        out.push(statement(u, depth)?);
+        Ok(ControlFlow::Continue(()))
+    })?;
+    Ok(out)
+}
+
+fn statement(u: &mut Unstructured, depth: usize) -> Result<Statement> {
+    let kinds = if depth < MAX_DEPTH { 27 } else { 19 };
+    let kind = match u.choose_index(kinds)? {
+        0 => StatementKind::VarAssign {
+            name: name(u)?,
+            expr: expr(u, 0)?,
+        },
+        1 => StatementKind::ConstAssign {
+            name: name(u)?,
+            expr: expr(u, 0)?,
+        },
+        2 => {
+            let visibility = *u.choose(Visibility::ALL)?;
+            let name = match visibility {
+                Visibility::Local => format!(".{}", name(u)?),
+                Visibility::File | Visibility::Global => name(u)?,
+            };
+            StatementKind::Label { name, visibility }
+        }
+        // Spans aren't compared.
+        3 => StatementKind::Instruction {
+            name: name(u)?,
+            args: operands(u)?,
+            spans: Vec::new(),
+        },
+        4 => StatementKind::Directive {
+            name: name(u)?,
+            args: args(u)?,
+        },
+        5 => StatementKind::Include(format!("\"{}.asm\"", name(u)?)),
+        6 => StatementKind::VarUpdate {
+            name: name(u)?,
+            op: assign_op(u)?,
+            expr: expr(u, 0)?,
+        },
+        7 => StatementKind::MacroCall {
+            name: name(u)?,
+            args: operands(u)?,
+        },
+        8 => StatementKind::Break,
+        9 => StatementKind::Continue,
+        10 => StatementKind::Data {
+            width: *u.choose(DataWidth::ALL)?,
+            values: (0..u.int_in_range(0..=3)?)
+                .map(|_| expr(u, 0))
+                .collect::<Result<_>>()?,
+            spans: Vec::new(),
+        },
+        11 => StatementKind::StringData {
+            encoding: *u.choose(Encoding::ALL)?,
+            nul: u.arbitrary()?,
+            literal: format!("\"{}\"", name(u)?),
+        },
+        12 => StatementKind::Section(name(u)?),
+        13 => StatementKind::Org(expr(u, 0)?),
+        14 => StatementKind::Equ {
+            name: name(u)?,
+            expr: expr(u, 0)?,
+        },
+        15 => StatementKind::Struct {
+            name: name(u)?,
+            fields: (0..u.int_in_range(0..=3)?)
+                .map(|i| {
+                    Ok(StructField {
+                        // Field names are unique within a struct.
+                        name: format!("{}{}", name(u)?, i),
+                        width: *u.choose(DataWidth::ALL)?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        },
+        16 => StatementKind::StructData {
+            name: name(u)?,
+            fields: (0..u.int_in_range(0..=3)?)
+                .map(|_| Ok((name(u)?, expr(u, 0)?)))
+                .collect::<Result<_>>()?,
+        },
+        17 => StatementKind::Enum {
+            name: name(u)?,
+            variants: (0..u.int_in_range(0..=3)?)
+                .map(|i| {
+                    let value = match u.arbitrary()? {
+                        true => Some(expr(u, 0)?),
+                        false => None,
+                    };
+                    Ok((format!("{}{}", name(u)?, i), value))
+                })
+                .collect::<Result<_>>()?,
+        },
+        18 => StatementKind::Align {
+            boundary: expr(u, 0)?,
+            fill: match u.arbitrary()? {
+                true => Some(expr(u, 0)?),
+                false => None,
+            },
+        },
+        19 => {
+            let mut params: Vec<String> = (0..u.int_in_range(0..=3)?)
+                .map(|_| name(u))
+                .collect::<Result<_>>()?;
+            if let Some(last) = params.last_mut()
+                && u.arbitrary()?
+            {
+                last.push_str("...");
+            }
+            let fixed = params.iter().filter(|p| !p.ends_with("...")).count();
+            let defaults = operands(u)?.into_iter().take(fixed).collect();
+            StatementKind::MacroDef {
+                name: name(u)?,
+                params,
+                defaults,
+                body: statements(u, depth + 1)?,
+            }
+        }
+        20 => StatementKind::ForLoop {
+            var: name(u)?,
+            start: expr(u, 0)?,
+            cmp: *u.choose(&[
+                Comparison::Lt,
+                Comparison::Le,
+                Comparison::Gt,
+                Comparison::Ge,
+                Comparison::Eq,
+                Comparison::Ne,
+            ])?,
+            end: expr(u, 0)?,
+            op: assign_op(u)?,
+            step: expr(u, 0)?,
+            body: statements(u, depth + 1)?,
+        },
+        21 => StatementKind::WhileLoop {
+            cond: expr(u, 0)?,
+            body: statements(u, depth + 1)?,
+        },
+        22 => StatementKind::Repeat {
+            count: expr(u, 0)?,
+            body: statements(u, depth + 1)?,
+        },
+        23 => StatementKind::If {
+            branches: (0..u.int_in_range(1..=3)?)
+                .map(|_| {
+                    Ok(IfBranch {
+                        cond: expr(u, 0)?,
+                        body: statements(u, depth + 1)?,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            otherwise: statements(u, depth + 1)?,
+        },
+        24 => StatementKind::Module {
+            name: name(u)?,
+            body: statements(u, depth + 1)?,
+        },
+        25 => StatementKind::Foreach {
+            param: name(u)?,
+            body: statements(u, depth + 1)?,
+        },
+        _ => StatementKind::Block(statements(u, depth + 1)?),
+    };
+    Ok(Statement {
+        kind,
+        span: Span::default(),
+    })
+}
+
+fuzz_target!(|program: Program| {
+    let text = chasm::to_source(&program.0);
+    let reparsed = Parser::new(&text).parse();
+    chasm::assert_ast_eq!(program.0, reparsed);
+});
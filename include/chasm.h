@@ -0,0 +1,51 @@
+#ifndef CHASM_H
+#define CHASM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CHASM_OK 0
+#define CHASM_DIAGNOSTICS 1
+#define CHASM_INVALID_ARGUMENT 2
+#define CHASM_INTERNAL_ERROR 3
+
+#define CHASM_ERROR 0
+#define CHASM_WARNING 1
+
+typedef struct ChasmDiagnostic {
+    int32_t severity;   /* CHASM_ERROR or CHASM_WARNING */
+    char *code;         /* like "E0304", or NULL */
+    char *message;
+    size_t line;        /* from 1, or 0 if it has no place in the source */
+    size_t column;
+} ChasmDiagnostic;
+
+typedef struct ChasmResult {
+    int32_t status;
+    /* chasm_parse: JSON text. chasm_assemble: the binary, or NULL. */
+    uint8_t *data;
+    size_t len;
+    /* chasm_assemble: what went wrong, or NULL. */
+    ChasmDiagnostic *diagnostics;
+    size_t diagnostic_count;
+} ChasmResult;
+
+/* Either field, or the whole struct, may be NULL for the default. */
+typedef struct ChasmOptions {
+    const char *target; /* a target name, like "generic" */
+    const char *isa;    /* an instruction table, as --isa reads */
+} ChasmOptions;
+
+int32_t chasm_parse(const char *src, ChasmResult **out);
+int32_t chasm_assemble(const char *src, const ChasmOptions *options, ChasmResult **out);
+void chasm_result_free(ChasmResult *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,396 @@
+use crate::parser::{Expr, Operand, Statement, StatementKind};
+use crate::source::{FileId, SourceManager, Span};
+use crate::symbols::{scope_local_labels, scope_modules};
+use crate::tokens::TokenKind;
+use crate::walk::{Visitor, bodies, walk_statement};
+use logos::Logos;
+use std::collections::HashSet;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Label,
+    Const,
+    Var,
+    Define,
+    Equ,
+    Macro,
+    MacroParam,
+    LoopVar,
+}
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The defining identifier.
+    pub span: Range<usize>,
+    /// Where the name is visible: the whole file, or a macro/loop body.
+    pub scope: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: String,
+    pub span: Range<usize>,
+    /// Index into [`Index::definitions`], if the name resolved.
+    pub definition: Option<usize>,
+}
+
+/// Every definition and identifier use in a source file.
+#[derive(Debug, Default)]
+pub struct Index {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+}
+
+struct Tok {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+/// Builds the definition/reference index for `source`.
+///
+/// Works on the token stream rather than the AST so it keeps going past
+/// syntax errors, which is what an editor needs while the user is typing.
+pub fn index(source: &str) -> Index {
+    let toks: Vec<Tok> = TokenKind::lexer(source)
+        .spanned()
+        .filter_map(|(kind, span)| kind.ok().map(|kind| Tok { kind, span }))
+        .collect();
+
+    let whole = 0..source.len();
+    let mut defs = Vec::new();
+    let mut def_tokens = HashSet::new();
+    // `equ` lexes as an identifier but isn't a use of anything.
+    let mut keywords = HashSet::new();
+
+    let ident = |i: usize| match toks.get(i) {
+        Some(Tok {
+            kind: TokenKind::Ident(name),
+            span,
+        }) => Some((name.clone(), span.clone())),
+        _ => None,
+    };
+    let is = |i: usize, kind: TokenKind| toks.get(i).is_some_and(|t| t.kind == kind);
+    let is_equ = |i: usize| ident(i).is_some_and(|(name, _)| name.eq_ignore_ascii_case("equ"));
+
+    let mut define =
+        |defs: &mut Vec<Definition>, i: usize, kind: SymbolKind, scope: Range<usize>| {
+            if let Some((name, span)) = ident(i)
+                && def_tokens.insert(i)
+            {
+                defs.push(Definition {
+                    name,
+                    kind,
+                    span,
+                    scope,
+                });
+            }
+        };
+
+    for i in 0..toks.len() {
+        match &toks[i].kind {
+            TokenKind::Var => define(&mut defs, i + 1, SymbolKind::Var, whole.clone()),
+            TokenKind::Const => define(&mut defs, i + 1, SymbolKind::Const, whole.clone()),
+            TokenKind::AtDirective if source[toks[i].span.clone()] == *"@define" => {
+                define(&mut defs, i + 1, SymbolKind::Define, whole.clone())
+            }
+            TokenKind::MacroRules => {
+                define(&mut defs, i + 1, SymbolKind::Macro, whole.clone());
+                if !is(i + 2, TokenKind::LeftParen) {
+                    continue;
+                }
+                let Some(close) = matching(&toks, i + 2) else {
+                    continue;
+                };
+                let scope = body_scope(&toks, close + 1, source.len());
+                for p in i + 3..close {
+                    define(&mut defs, p, SymbolKind::MacroParam, scope.clone());
+                }
+            }
+            TokenKind::ForBang => {
+                if !(is(i + 1, TokenKind::LeftParen) && is(i + 2, TokenKind::Var)) {
+                    continue;
+                }
+                let Some(close) = matching(&toks, i + 1) else {
+                    continue;
+                };
+                let body = body_scope(&toks, close + 1, source.len());
+                let scope = toks[i + 1].span.start..body.end;
+                define(&mut defs, i + 3, SymbolKind::LoopVar, scope);
+            }
+            TokenKind::Ident(_) if is_equ(i + 1) => {
+                define(&mut defs, i, SymbolKind::Equ, whole.clone());
+                keywords.insert(i + 1);
+            }
+            TokenKind::Ident(_) if is(i + 1, TokenKind::Colon) => {
+                define(&mut defs, i, SymbolKind::Label, whole.clone())
+            }
+            _ => {}
+        }
+    }
+
+    let references = toks
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !def_tokens.contains(i) && !keywords.contains(i))
+        .filter_map(|(_, t)| match &t.kind {
+            TokenKind::Ident(name) => Some(Reference {
+                name: name.clone(),
+                span: t.span.clone(),
+                definition: resolve(&defs, name, t.span.start),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    Index {
+        definitions: defs,
+        references,
+    }
+}
+
+/// Where the symbol at `offset` in `file` is defined: a label, constant,
+/// `var`, `equ`, `@define`, macro or macro parameter. `offset` may be on
+/// the definition itself or on any use of it.
+///
+/// `ast` is the parsed program, its `include`s spliced in (see
+/// [`splice`](crate::session::splice)), so the definition can be in
+/// another file than the use. Names resolve as the assembler resolves them:
+/// a `.local` label under the label before it, a name in a `module` under
+/// the module's. A macro parameter is only visible in its macro's body.
+pub fn definition_at(
+    ast: &[Statement],
+    sources: &SourceManager,
+    file: FileId,
+    offset: usize,
+) -> Option<Span> {
+    let sites = sites(ast, sources);
+    let site = sites
+        .iter()
+        .find(|site| site.span.file == file && site.span.range.contains(&offset))?;
+    sites
+        .iter()
+        .find(|def| def.defines && def.key == site.key)
+        .map(|def| def.span.clone())
+}
+
+/// Every use of the symbol defined at `definition`, which is a span
+/// [`definition_at`] gives.
+pub fn references_of(ast: &[Statement], sources: &SourceManager, definition: &Span) -> Vec<Span> {
+    let sites = sites(ast, sources);
+    let Some(target) = sites
+        .iter()
+        .find(|site| site.defines && site.span == *definition)
+    else {
+        return Vec::new();
+    };
+    sites
+        .iter()
+        .filter(|site| !site.defines && site.key == target.key)
+        .map(|site| site.span.clone())
+        .collect()
+}
+
+/// A name where it's defined or used.
+struct Site {
+    /// What it resolves to: the scoped name, `name!` for a macro and
+    /// `macro!param` for a macro parameter.
+    key: String,
+    /// The identifier.
+    span: Span,
+    defines: bool,
+}
+
+/// Every definition and use of a name in `ast`, once its local labels and
+/// modules are scoped.
+fn sites(ast: &[Statement], sources: &SourceManager) -> Vec<Site> {
+    let scoped = scope_local_labels(&scope_modules(ast));
+    let mut sites = Vec::new();
+    collect_sites(&scoped, sources, &[], &mut sites);
+    sites
+}
+
+/// Adds the sites in `stmts` to `sites`, matching each identifier in a
+/// statement's text to a name the statement defines or uses. `params` are
+/// the parameters of the macros `stmts` are in, with their keys.
+fn collect_sites(
+    stmts: &[Statement],
+    sources: &SourceManager,
+    params: &[(String, String)],
+    sites: &mut Vec<Site>,
+) {
+    for stmt in stmts {
+        let file = stmt.span.file;
+        let start = stmt.span.range.start;
+        let text = &sources.text(file)[stmt.span.range.clone()];
+        // The bodies' statements are matched against their own names.
+        let nested: Vec<_> = bodies(stmt)
+            .flatten()
+            .filter(|inner| inner.span.file == file)
+            .map(|inner| inner.span.range.clone())
+            .collect();
+
+        let (defined, mut used) = names(stmt);
+        let mut inner_params = params.to_vec();
+        if let StatementKind::MacroDef {
+            name,
+            params: declared,
+            ..
+        } = &stmt.kind
+        {
+            for param in declared {
+                let param = param.trim_end_matches("...");
+                inner_params.push((param.to_string(), format!("{}!{}", name, param)));
+            }
+        }
+        if let StatementKind::MacroCall { name, .. } = &stmt.kind {
+            used.insert(0, format!("{}!", name));
+        }
+
+        let mut after_dot = false;
+        for (tok, range) in TokenKind::lexer(text).spanned() {
+            let dot = matches!(tok, Ok(TokenKind::Dot));
+            let range = start + range.start..start + range.end;
+            let Ok(TokenKind::Ident(word)) = tok else {
+                after_dot = dot;
+                continue;
+            };
+            let raw = if after_dot {
+                format!(".{}", word)
+            } else {
+                word
+            };
+            after_dot = false;
+            if nested.iter().any(|inner| inner.contains(&range.start)) {
+                continue;
+            }
+
+            let site = |key: &str, defines| Site {
+                key: key.to_string(),
+                span: Span::new(file, range.clone()),
+                defines,
+            };
+            if let Some(key) = defined.iter().find(|key| means(key, &raw)) {
+                sites.push(site(key, true));
+            } else if let Some((_, key)) = inner_params.iter().find(|(param, _)| *param == raw) {
+                sites.push(site(key, inner_params.len() > params.len()));
+            } else if let Some((_, key)) = params.iter().find(|(param, _)| *param == raw) {
+                sites.push(site(key, false));
+            } else if let Some(key) = used.iter().find(|key| means(key, &raw)) {
+                sites.push(site(key, false));
+            }
+        }
+
+        for body in bodies(stmt) {
+            collect_sites(body, sources, &inner_params, sites);
+        }
+    }
+}
+
+/// What `stmt` itself defines and uses, leaving out its bodies.
+fn names(stmt: &Statement) -> (Vec<String>, Vec<String>) {
+    let defined = match &stmt.kind {
+        StatementKind::Label { name, .. }
+        | StatementKind::ConstAssign { name, .. }
+        | StatementKind::Equ { name, .. }
+        | StatementKind::VarAssign { name, .. } => vec![name.clone()],
+        StatementKind::MacroDef { name, .. } => vec![format!("{}!", name)],
+        StatementKind::Directive { name, args } if name == "define" => {
+            args.first().cloned().into_iter().collect()
+        }
+        _ => Vec::new(),
+    };
+
+    let mut uses = Uses {
+        names: Vec::new(),
+        entered: false,
+    };
+    uses.visit_statement(stmt);
+    let mut used = uses.names;
+    match &stmt.kind {
+        StatementKind::VarUpdate { name, .. } => used.push(name.clone()),
+        StatementKind::Directive { name, args } if name != "define" => {
+            used.extend(args.iter().cloned())
+        }
+        _ => {}
+    }
+    (defined, used)
+}
+
+/// The names one statement's operands and expressions use.
+struct Uses {
+    names: Vec<String>,
+    entered: bool,
+}
+
+impl<'ast> Visitor<'ast> for Uses {
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        if !self.entered {
+            self.entered = true;
+            walk_statement(self, stmt);
+        }
+    }
+
+    fn visit_operand(&mut self, operand: &'ast Operand) {
+        self.names
+            .extend(operand.symbols().into_iter().map(str::to_string));
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.names
+            .extend(expr.symbols().into_iter().map(str::to_string));
+    }
+}
+
+/// Whether the scoped name `key` is what `raw`, as written, refers to:
+/// `main.loop` is `.loop`, `io::putc` is `putc` and `push!` is `push`.
+fn means(key: &str, raw: &str) -> bool {
+    let key = key.strip_suffix('!').unwrap_or(key);
+    key == raw
+        || key.strip_suffix(raw).is_some_and(|scope| {
+            (raw.starts_with('.') && !scope.is_empty()) || scope.ends_with("::")
+        })
+}
+
+/// The innermost definition of `name` visible at `offset`.
+fn resolve(defs: &[Definition], name: &str, offset: usize) -> Option<usize> {
+    defs.iter()
+        .enumerate()
+        .filter(|(_, d)| d.name == name && d.scope.contains(&offset))
+        .min_by_key(|(_, d)| d.scope.len())
+        .map(|(i, _)| i)
+}
+
+/// Index of the token closing the bracket opened at `open`.
+fn matching(toks: &[Tok], open: usize) -> Option<usize> {
+    let (left, right) = match toks[open].kind {
+        TokenKind::LeftParen => (TokenKind::LeftParen, TokenKind::RightParen),
+        TokenKind::LeftBrace => (TokenKind::LeftBrace, TokenKind::RightBrace),
+        _ => return None,
+    };
+
+    let mut depth = 0;
+    for (i, tok) in toks.iter().enumerate().skip(open) {
+        if tok.kind == left {
+            depth += 1;
+        } else if tok.kind == right {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Byte range of the `{ ... }` body starting at or after token `from`.
+/// An unterminated body runs to the end of the file.
+fn body_scope(toks: &[Tok], from: usize, len: usize) -> Range<usize> {
+    let Some(open) = (from..toks.len()).find(|&i| toks[i].kind == TokenKind::LeftBrace) else {
+        return len..len;
+    };
+    let end = matching(toks, open).map_or(len, |close| toks[close].span.end);
+    toks[open].span.start..end
+}
@@ -0,0 +1,1893 @@
+//! Arena-backed AST for callers that reparse constantly (language servers).
+//!
+//! [`Parser::parse_in`] builds every node, string and list straight into
+//! one bump allocation, so dropping a tree is a single free instead of a
+//! walk over thousands of `String`s and `Vec`s. The types mirror the owned
+//! AST in [`crate::parser`], which stays the default, and come with their
+//! own [`Visitor`] and `Display`, printing the same text the owned tree
+//! does.
+
+use crate::codes::Code;
+use crate::parser::{
+    self, AssignOp, BinaryOp, Comparison, DataWidth, Encoding, MAX_NESTING, Number, ParseError,
+    Parser, SECTIONS, UnaryOp, Visibility,
+};
+use crate::source::Span;
+use crate::tokens::TokenKind;
+use crate::trace::{debug, phase};
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
+use std::fmt::{self, Write};
+use std::mem;
+
+const INDENT: &str = "    ";
+
+/// Owns the memory for every node produced by [`Parser::parse_in`].
+#[derive(Default)]
+pub struct AstArena {
+    bump: Bump,
+}
+
+impl AstArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes currently held by the arena.
+    pub fn allocated_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Frees every node at once, keeping the largest chunk for reuse.
+    /// Resetting one arena between parses is where it pays: a new arena
+    /// maps and faults in fresh memory for every tree.
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+
+    /// Copies an owned tree in, for statements built or expanded outside
+    /// the parser.
+    pub fn copy<'a>(&'a self, stmts: &[parser::Statement]) -> &'a [Statement<'a>] {
+        self.statements(stmts)
+    }
+
+    fn str(&self, s: &str) -> &str {
+        self.bump.alloc_str(s)
+    }
+
+    fn strs<'a>(&'a self, items: &[String]) -> &'a [&'a str] {
+        self.bump
+            .alloc_slice_fill_iter(items.iter().map(|s| self.str(s)))
+    }
+
+    fn expr<'a>(&'a self, expr: &parser::Expr) -> &'a Expr<'a> {
+        use parser::Expr as Owned;
+
+        let expr = match expr {
+            Owned::Number(n) => Expr::Number(*n),
+            Owned::Str(literal) => Expr::Str(self.str(literal)),
+            Owned::Symbol(name) => Expr::Symbol(self.str(name)),
+            Owned::Unary { op, expr } => Expr::Unary {
+                op: *op,
+                expr: self.expr(expr),
+            },
+            Owned::Binary { op, lhs, rhs } => Expr::Binary {
+                op: *op,
+                lhs: self.expr(lhs),
+                rhs: self.expr(rhs),
+            },
+            Owned::Call { name, args } => Expr::Call {
+                name: self.str(name),
+                args: self
+                    .bump
+                    .alloc_slice_fill_iter(args.iter().map(|arg| *self.expr(arg))),
+            },
+            Owned::Array(items) => Expr::Array(
+                self.bump
+                    .alloc_slice_fill_iter(items.iter().map(|item| *self.expr(item))),
+            ),
+            Owned::Index { base, index } => Expr::Index {
+                base: self.expr(base),
+                index: self.expr(index),
+            },
+        };
+        self.bump.alloc(expr)
+    }
+
+    fn operand<'a>(&'a self, operand: &parser::Operand) -> Operand<'a> {
+        use parser::Operand as Owned;
+
+        match operand {
+            Owned::Register(name) => Operand::Register(self.str(name)),
+            Owned::Immediate(expr) => Operand::Immediate(self.expr(expr)),
+            Owned::Symbol(name) => Operand::Symbol(self.str(name)),
+            Owned::Expr(expr) => Operand::Expr(self.expr(expr)),
+            Owned::Memory(memory) => Operand::Memory(self.bump.alloc(MemoryOperand {
+                base: memory.base.as_deref().map(|base| self.str(base)),
+                index: memory.index.as_deref().map(|index| self.str(index)),
+                scale: memory.scale,
+                displacement: memory.displacement.as_ref().map(|disp| self.expr(disp)),
+            })),
+            Owned::String(literal) => Operand::String(self.str(literal)),
+            Owned::Char(c) => Operand::Char(*c),
+        }
+    }
+
+    fn statements<'a>(&'a self, stmts: &[parser::Statement]) -> &'a [Statement<'a>] {
+        self.bump
+            .alloc_slice_fill_iter(stmts.iter().map(|s| self.statement(s)))
+    }
+
+    fn statement<'a>(&'a self, stmt: &parser::Statement) -> Statement<'a> {
+        Statement {
+            kind: self.kind(&stmt.kind),
+            span: stmt.span.clone(),
+        }
+    }
+
+    fn kind<'a>(&'a self, kind: &parser::StatementKind) -> StatementKind<'a> {
+        use parser::StatementKind as Owned;
+
+        match kind {
+            Owned::VarAssign { name, expr } => StatementKind::VarAssign {
+                name: self.str(name),
+                expr: self.expr(expr),
+            },
+            Owned::VarUpdate { name, op, expr } => StatementKind::VarUpdate {
+                name: self.str(name),
+                op: *op,
+                expr: self.expr(expr),
+            },
+            Owned::ConstAssign { name, expr } => StatementKind::ConstAssign {
+                name: self.str(name),
+                expr: self.expr(expr),
+            },
+            Owned::Equ { name, expr } => StatementKind::Equ {
+                name: self.str(name),
+                expr: self.expr(expr),
+            },
+            Owned::Label { name, visibility } => StatementKind::Label {
+                name: self.str(name),
+                visibility: *visibility,
+            },
+            Owned::Instruction { name, args, spans } => StatementKind::Instruction {
+                name: self.str(name),
+                args: self
+                    .bump
+                    .alloc_slice_fill_iter(args.iter().map(|arg| self.operand(arg))),
+                spans: self.bump.alloc_slice_clone(spans),
+            },
+            Owned::Directive { name, args } => StatementKind::Directive {
+                name: self.str(name),
+                args: self.strs(args),
+            },
+            Owned::Org(address) => StatementKind::Org(self.expr(address)),
+            Owned::Align { boundary, fill } => StatementKind::Align {
+                boundary: self.expr(boundary),
+                fill: fill.as_ref().map(|fill| self.expr(fill)),
+            },
+            Owned::Include(path) => StatementKind::Include(self.str(path)),
+            Owned::Data {
+                width,
+                values,
+                spans,
+            } => StatementKind::Data {
+                width: *width,
+                values: self
+                    .bump
+                    .alloc_slice_fill_iter(values.iter().map(|value| *self.expr(value))),
+                spans: self.bump.alloc_slice_clone(spans),
+            },
+            Owned::Section(name) => StatementKind::Section(self.str(name)),
+            Owned::StringData {
+                encoding,
+                nul,
+                literal,
+            } => StatementKind::StringData {
+                encoding: *encoding,
+                nul: *nul,
+                literal: self.str(literal),
+            },
+            Owned::MacroDef {
+                name,
+                params,
+                defaults,
+                body,
+            } => StatementKind::MacroDef {
+                name: self.str(name),
+                params: self.strs(params),
+                defaults: self
+                    .bump
+                    .alloc_slice_fill_iter(defaults.iter().map(|default| self.operand(default))),
+                body: self.statements(body),
+            },
+            Owned::MacroCall { name, args } => StatementKind::MacroCall {
+                name: self.str(name),
+                args: self
+                    .bump
+                    .alloc_slice_fill_iter(args.iter().map(|arg| self.operand(arg))),
+            },
+            Owned::ForLoop {
+                var,
+                start,
+                cmp,
+                end,
+                op,
+                step,
+                body,
+            } => StatementKind::ForLoop {
+                var: self.str(var),
+                start: self.expr(start),
+                cmp: *cmp,
+                end: self.expr(end),
+                op: *op,
+                step: self.expr(step),
+                body: self.statements(body),
+            },
+            Owned::WhileLoop { cond, body } => StatementKind::WhileLoop {
+                cond: self.expr(cond),
+                body: self.statements(body),
+            },
+            Owned::Repeat { count, body } => StatementKind::Repeat {
+                count: self.expr(count),
+                body: self.statements(body),
+            },
+            Owned::Foreach { param, body } => StatementKind::Foreach {
+                param: self.str(param),
+                body: self.statements(body),
+            },
+            Owned::Break => StatementKind::Break,
+            Owned::Continue => StatementKind::Continue,
+            Owned::If {
+                branches,
+                otherwise,
+            } => StatementKind::If {
+                branches: self
+                    .bump
+                    .alloc_slice_fill_iter(branches.iter().map(|branch| IfBranch {
+                        cond: self.expr(&branch.cond),
+                        body: self.statements(&branch.body),
+                    })),
+                otherwise: self.statements(otherwise),
+            },
+            Owned::Module { name, body } => StatementKind::Module {
+                name: self.str(name),
+                body: self.statements(body),
+            },
+            Owned::Struct { name, fields } => StatementKind::Struct {
+                name: self.str(name),
+                fields: self
+                    .bump
+                    .alloc_slice_fill_iter(fields.iter().map(|field| StructField {
+                        name: self.str(&field.name),
+                        width: field.width,
+                    })),
+            },
+            Owned::StructData { name, fields } => StatementKind::StructData {
+                name: self.str(name),
+                fields: self.bump.alloc_slice_fill_iter(
+                    fields
+                        .iter()
+                        .map(|(field, value)| (self.str(field), *self.expr(value))),
+                ),
+            },
+            Owned::Enum { name, variants } => StatementKind::Enum {
+                name: self.str(name),
+                variants: self.bump.alloc_slice_fill_iter(variants.iter().map(
+                    |(variant, value)| (self.str(variant), value.as_ref().map(|v| self.expr(v))),
+                )),
+            },
+            Owned::Block(body) => StatementKind::Block(self.statements(body)),
+        }
+    }
+}
+
+/// Borrowed counterpart of [`parser::Statement`].
+#[derive(Debug, Clone)]
+pub struct Statement<'a> {
+    pub kind: StatementKind<'a>,
+    pub span: Span,
+}
+
+/// Borrowed counterpart of [`parser::Expr`].
+#[derive(Debug, Clone, Copy)]
+pub enum Expr<'a> {
+    Number(Number),
+    Str(&'a str),
+    Symbol(&'a str),
+    Unary {
+        op: UnaryOp,
+        expr: &'a Expr<'a>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: &'a Expr<'a>,
+        rhs: &'a Expr<'a>,
+    },
+    Call {
+        name: &'a str,
+        args: &'a [Expr<'a>],
+    },
+    Array(&'a [Expr<'a>]),
+    Index {
+        base: &'a Expr<'a>,
+        index: &'a Expr<'a>,
+    },
+}
+
+/// Borrowed counterpart of [`parser::Operand`].
+#[derive(Debug, Clone, Copy)]
+pub enum Operand<'a> {
+    Register(&'a str),
+    Immediate(&'a Expr<'a>),
+    Symbol(&'a str),
+    Expr(&'a Expr<'a>),
+    Memory(&'a MemoryOperand<'a>),
+    String(&'a str),
+    Char(char),
+}
+
+/// Borrowed counterpart of [`parser::MemoryOperand`].
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryOperand<'a> {
+    pub base: Option<&'a str>,
+    pub index: Option<&'a str>,
+    pub scale: i64,
+    pub displacement: Option<&'a Expr<'a>>,
+}
+
+/// Borrowed counterpart of [`parser::StatementKind`], variant for variant.
+#[derive(Debug, Clone, Copy)]
+pub enum StatementKind<'a> {
+    VarAssign {
+        name: &'a str,
+        expr: &'a Expr<'a>,
+    },
+    VarUpdate {
+        name: &'a str,
+        op: AssignOp,
+        expr: &'a Expr<'a>,
+    },
+    ConstAssign {
+        name: &'a str,
+        expr: &'a Expr<'a>,
+    },
+    Equ {
+        name: &'a str,
+        expr: &'a Expr<'a>,
+    },
+    Label {
+        name: &'a str,
+        visibility: Visibility,
+    },
+    Instruction {
+        name: &'a str,
+        args: &'a [Operand<'a>],
+        spans: &'a [Span],
+    },
+    Directive {
+        name: &'a str,
+        args: &'a [&'a str],
+    },
+    Org(&'a Expr<'a>),
+    Align {
+        boundary: &'a Expr<'a>,
+        fill: Option<&'a Expr<'a>>,
+    },
+    Include(&'a str),
+    Data {
+        width: DataWidth,
+        values: &'a [Expr<'a>],
+        spans: &'a [Span],
+    },
+    Section(&'a str),
+    StringData {
+        encoding: Encoding,
+        nul: bool,
+        literal: &'a str,
+    },
+    MacroDef {
+        name: &'a str,
+        params: &'a [&'a str],
+        defaults: &'a [Operand<'a>],
+        body: &'a [Statement<'a>],
+    },
+    MacroCall {
+        name: &'a str,
+        args: &'a [Operand<'a>],
+    },
+    ForLoop {
+        var: &'a str,
+        start: &'a Expr<'a>,
+        cmp: Comparison,
+        end: &'a Expr<'a>,
+        op: AssignOp,
+        step: &'a Expr<'a>,
+        body: &'a [Statement<'a>],
+    },
+    WhileLoop {
+        cond: &'a Expr<'a>,
+        body: &'a [Statement<'a>],
+    },
+    Repeat {
+        count: &'a Expr<'a>,
+        body: &'a [Statement<'a>],
+    },
+    Foreach {
+        param: &'a str,
+        body: &'a [Statement<'a>],
+    },
+    Break,
+    Continue,
+    If {
+        branches: &'a [IfBranch<'a>],
+        otherwise: &'a [Statement<'a>],
+    },
+    Module {
+        name: &'a str,
+        body: &'a [Statement<'a>],
+    },
+    Struct {
+        name: &'a str,
+        fields: &'a [StructField<'a>],
+    },
+    StructData {
+        name: &'a str,
+        fields: &'a [(&'a str, Expr<'a>)],
+    },
+    Enum {
+        name: &'a str,
+        variants: &'a [(&'a str, Option<&'a Expr<'a>>)],
+    },
+    Block(&'a [Statement<'a>]),
+}
+
+/// Borrowed counterpart of [`parser::StructField`].
+#[derive(Debug, Clone, Copy)]
+pub struct StructField<'a> {
+    pub name: &'a str,
+    pub width: DataWidth,
+}
+
+/// Borrowed counterpart of [`parser::IfBranch`].
+#[derive(Debug, Clone, Copy)]
+pub struct IfBranch<'a> {
+    pub cond: &'a Expr<'a>,
+    pub body: &'a [Statement<'a>],
+}
+
+impl Parser {
+    /// Parses into `arena`, building each node there as it's parsed, so no
+    /// owned tree is made along the way. Syntax errors are recorded and
+    /// recovered from as [`parse`](Self::parse) does. The returned slice
+    /// lives as long as the arena.
+    pub fn parse_in<'a>(&mut self, arena: &'a AstArena) -> &'a [Statement<'a>] {
+        phase!("parse");
+        let mut build = Build {
+            parser: self,
+            arena,
+        };
+        // A file can run to a great many statements, and growing their list
+        // in the arena would leave every outgrown copy behind in it.
+        let mut stmts = Vec::new();
+
+        while !build.parser.stream.eof() {
+            build.parser.stream.release();
+            if let Some(stmt) = build.recovering() {
+                stmts.push(stmt);
+            }
+        }
+
+        arena.bump.alloc_slice_fill_iter(stmts)
+    }
+}
+
+/// The parser's grammar, building arena nodes. Lookaheads, token helpers
+/// and statements with nothing nested in them (labels, includes, plain
+/// directives) are the [`Parser`]'s own; everything that holds an
+/// expression, operand or body is built here.
+struct Build<'p, 'a> {
+    parser: &'p mut Parser,
+    arena: &'a AstArena,
+}
+
+/// A token [`Build::atom`] takes whole: a name, which may go on into a
+/// path or a call, or a value that's already complete.
+enum Atom<'a> {
+    Name(String),
+    Value(Expr<'a>),
+}
+
+impl<'a> Build<'_, 'a> {
+    fn str(&self, s: &str) -> &'a str {
+        self.arena.bump.alloc_str(s)
+    }
+
+    fn alloc(&self, expr: Expr<'a>) -> &'a Expr<'a> {
+        self.arena.bump.alloc(expr)
+    }
+
+    fn list<T>(&self) -> BumpVec<'a, T> {
+        BumpVec::new_in(&self.arena.bump)
+    }
+
+    fn recovering(&mut self) -> Option<Statement<'a>> {
+        let depth = self.parser.depth;
+        match self.spanned() {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                debug!(error = %err.message, "recovering from syntax error");
+                self.parser.errors.push(err);
+                self.parser.depth = depth;
+                self.parser.synchronize();
+                None
+            }
+        }
+    }
+
+    fn spanned(&mut self) -> Result<Option<Statement<'a>>, ParseError> {
+        let Some(start) = self.parser.stream.peek().map(|t| t.span.clone()) else {
+            return Ok(None);
+        };
+        let Some(kind) = self.statement()? else {
+            return Ok(None);
+        };
+        let span = start.to(&self.parser.stream.last_span());
+        Ok(Some(Statement { kind, span }))
+    }
+
+    /// Dispatches as [`Parser`] does, in the same order.
+    fn statement(&mut self) -> Result<Option<StatementKind<'a>>, ParseError> {
+        let Some(tok) = self.parser.stream.peek() else {
+            return Ok(None);
+        };
+        let kind = match &tok.kind {
+            TokenKind::Ident(_)
+            | TokenKind::IntLit(_)
+            | TokenKind::Dot
+            | TokenKind::DoubleColon
+            | TokenKind::Register(_)
+                if self.parser.lookahead_is_label() =>
+            {
+                return self.leaf();
+            }
+            TokenKind::Var => self.var(),
+            TokenKind::Const => self.constant(),
+            TokenKind::AtDirective => match self.nested_directive() {
+                Some(name) => self.directive(name),
+                None => return self.leaf(),
+            },
+            TokenKind::Dot => match self.data_width() {
+                Some(width) => self.data(width),
+                None => return self.leaf(),
+            },
+            TokenKind::MacroRules => self.macro_def(),
+            TokenKind::ForBang => self.for_loop(),
+            TokenKind::WhileBang => {
+                self.parser.stream.next();
+                let (cond, body) = self.guarded()?;
+                Ok(StatementKind::WhileLoop { cond, body })
+            }
+            TokenKind::RepeatBang => {
+                self.parser.stream.next();
+                let (count, body) = self.guarded()?;
+                Ok(StatementKind::Repeat { count, body })
+            }
+            TokenKind::IfBang => self.if_chain(),
+            TokenKind::LeftBrace => self.block().map(StatementKind::Block),
+            TokenKind::Ident(_) if self.parser.lookahead_is_module() => self.module(),
+            TokenKind::Ident(_) if self.parser.lookahead_is_struct() => return self.leaf(),
+            TokenKind::Ident(_) if self.parser.lookahead_is_enum() => self.enumeration(),
+            TokenKind::Ident(_) if self.parser.lookahead_is_struct_data() => self.struct_data(),
+            TokenKind::Ident(_) if self.parser.lookahead_is_equ() => self.equ(),
+            TokenKind::Ident(_) if self.parser.lookahead_is_macro_call() => self.macro_call(),
+            TokenKind::Ident(_) => self.instruction(),
+            _ => return self.leaf(),
+        };
+        kind.map(Some)
+    }
+
+    /// A statement with nothing nested in it, parsed by the parser and
+    /// copied in.
+    fn leaf(&mut self) -> Result<Option<StatementKind<'a>>, ParseError> {
+        let kind = self.parser.parse_statement()?;
+        Ok(kind.map(|kind| self.arena.kind(&kind)))
+    }
+
+    /// The `@` directive next if it holds an expression or a body:
+    /// `@org`, `@align` or `@foreach`.
+    fn nested_directive(&self) -> Option<&'static str> {
+        let name = self.parser.stream.peek()?.text.trim_start_matches('@');
+        let fold_case = self.parser.stream.fold_case;
+        ["org", "align", "foreach"].into_iter().find(|directive| {
+            if fold_case {
+                name.eq_ignore_ascii_case(directive)
+            } else {
+                name == *directive
+            }
+        })
+    }
+
+    /// The width of the `.` directive next if it's data, `.byte` through
+    /// `.quad`, rather than a section or a string.
+    fn data_width(&self) -> Option<DataWidth> {
+        match &self.parser.stream.iter().nth(1)?.kind {
+            TokenKind::Ident(name) if self.parser.stream.fold_case => {
+                DataWidth::from_name(&name.to_ascii_lowercase())
+            }
+            TokenKind::Ident(name) => DataWidth::from_name(name),
+            _ => None,
+        }
+    }
+
+    /// `@org address`, `@align boundary, fill` or `@foreach param { ... }`.
+    fn directive(&mut self, name: &str) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.next();
+        match name {
+            "org" => self.boxed_expr().map(StatementKind::Org),
+            "align" => {
+                let boundary = self.boxed_expr()?;
+                let mut fill = None;
+                if !self.parser.stream.newline_before_next()
+                    && self
+                        .parser
+                        .stream
+                        .peek()
+                        .is_some_and(|t| t.kind == TokenKind::Comma)
+                {
+                    self.parser.stream.next();
+                    fill = Some(self.boxed_expr()?);
+                }
+                Ok(StatementKind::Align { boundary, fill })
+            }
+            _ => {
+                let param = self.parser.ident("a variadic parameter")?;
+                let param = self.str(&param);
+                let body = self.block()?;
+                Ok(StatementKind::Foreach { param, body })
+            }
+        }
+    }
+
+    /// `.word a, b, c`, up to the end of the line.
+    fn data(&mut self, width: DataWidth) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.expect(TokenKind::Dot)?;
+        self.parser.ident("a directive name")?;
+
+        let mut values = self.list();
+        let mut spans = self.list();
+        while !self.parser.at_statement_end() {
+            let start = self.parser.stream.peek().map(|t| t.span.clone());
+            values.push(self.expr()?);
+            spans.extend(start.map(|start| start.to(&self.parser.stream.last_span())));
+            if self.parser.stream.newline_before_next()
+                || self
+                    .parser
+                    .stream
+                    .peek()
+                    .is_none_or(|t| t.kind != TokenKind::Comma)
+            {
+                break;
+            }
+            self.parser.stream.next();
+        }
+
+        Ok(StatementKind::Data {
+            width,
+            values: values.into_bump_slice(),
+            spans: spans.into_bump_slice(),
+        })
+    }
+
+    fn var(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.next(); // eat 'var'
+        let name = self.parser.ident("identifier")?;
+        let name = self.str(&name);
+
+        let Some(next) = self.parser.stream.next() else {
+            return self.parser.stream.unexpected_eof();
+        };
+        let op = match &next.kind {
+            TokenKind::Equal => None,
+            kind => match AssignOp::from_token(kind) {
+                Some(op) => Some(op),
+                None => {
+                    let found = kind.clone();
+                    return self.parser.stream.fail(format_args!(
+                        "expected `=` or a compound assignment like `+=`, got {:?}",
+                        found
+                    ));
+                }
+            },
+        };
+
+        let expr = self.boxed_expr()?;
+        Ok(match op {
+            None => StatementKind::VarAssign { name, expr },
+            Some(op) => StatementKind::VarUpdate { name, op, expr },
+        })
+    }
+
+    fn constant(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.next(); // eat 'const'
+        let name = self.parser.ident("identifier")?;
+        let name = self.str(&name);
+        self.parser.stream.expect(TokenKind::Equal)?;
+        let expr = self.boxed_expr()?;
+        Ok(StatementKind::ConstAssign { name, expr })
+    }
+
+    fn equ(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        let name = self.parser.ident("identifier")?;
+        let name = self.str(&name);
+        self.parser.stream.next(); // eat 'equ'
+        let expr = self.boxed_expr()?;
+        Ok(StatementKind::Equ { name, expr })
+    }
+
+    fn module(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.next(); // eat 'module'
+        let name = self.parser.ident("a module name")?;
+        let name = self.str(&name);
+        let body = self.block()?;
+        Ok(StatementKind::Module { name, body })
+    }
+
+    fn enumeration(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.next(); // eat 'enum'
+        let name = self.parser.ident("an enum name")?;
+        self.parser.stream.expect(TokenKind::LeftBrace)?;
+        let mut variants = self.list();
+        while self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind != TokenKind::RightBrace)
+        {
+            let variant = self.parser.ident("a variant name")?;
+            if variants.iter().any(|&(v, _)| v == variant) {
+                return self.parser.stream.fail(format_args!(
+                    "`{}` has two variants named `{}`",
+                    name, variant
+                ));
+            }
+            let value = if self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Equal)
+            {
+                self.parser.stream.next();
+                Some(self.boxed_expr()?)
+            } else {
+                None
+            };
+            variants.push((self.str(&variant), value));
+            if self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.parser.stream.next();
+            }
+        }
+        self.parser.stream.expect(TokenKind::RightBrace)?;
+        Ok(StatementKind::Enum {
+            name: self.str(&name),
+            variants: variants.into_bump_slice(),
+        })
+    }
+
+    fn struct_data(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        let mut name = self.parser.ident("a struct name")?;
+        while self.parser.lookahead_is_path() == Some("::") {
+            self.parser.stream.next();
+            name.push_str("::");
+            name.push_str(&self.parser.ident("a name")?);
+        }
+        self.parser.stream.expect(TokenKind::LeftBrace)?;
+        let mut fields = self.list();
+        while self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind != TokenKind::RightBrace)
+        {
+            let field = self.parser.ident("a field name")?;
+            let field = self.str(&field);
+            self.parser.stream.expect(TokenKind::Colon)?;
+            fields.push((field, self.expr()?));
+            if self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.parser.stream.next();
+            }
+        }
+        self.parser.stream.expect(TokenKind::RightBrace)?;
+        Ok(StatementKind::StructData {
+            name: self.str(&name),
+            fields: fields.into_bump_slice(),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        let mut name = self.parser.ident("a mnemonic")?;
+        if self.parser.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+
+        let mut args = self.list();
+        let mut spans = self.list();
+        while !self.parser.at_statement_end() {
+            let start = self.parser.stream.peek().map(|t| t.span.clone());
+            let Some(arg) = self.operand()? else {
+                break;
+            };
+            args.push(arg);
+            spans.extend(start.map(|start| start.to(&self.parser.stream.last_span())));
+            if !self.parser.stream.newline_before_next()
+                && self
+                    .parser
+                    .stream
+                    .peek()
+                    .is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.parser.stream.next();
+            }
+        }
+
+        Ok(StatementKind::Instruction {
+            name: self.str(&name),
+            args: args.into_bump_slice(),
+            spans: spans.into_bump_slice(),
+        })
+    }
+
+    fn macro_call(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        let mut name = self.parser.ident("a macro name")?;
+        if self.parser.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+        self.parser.stream.expect(TokenKind::Bang)?;
+        self.parser.stream.expect(TokenKind::LeftParen)?;
+
+        let mut args = self.list();
+        while self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind != TokenKind::RightParen)
+        {
+            let Some(arg) = self.operand()? else {
+                return self.parser.unexpected("a macro argument");
+            };
+            args.push(arg);
+            if self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.parser.stream.next();
+            } else {
+                break;
+            }
+        }
+        self.parser.stream.expect(TokenKind::RightParen)?;
+
+        Ok(StatementKind::MacroCall {
+            name: self.str(&name),
+            args: args.into_bump_slice(),
+        })
+    }
+
+    fn operand(&mut self) -> Result<Option<Operand<'a>>, ParseError> {
+        let Some(tok) = self.parser.stream.peek() else {
+            return Ok(None);
+        };
+        let operand = match tok.kind {
+            TokenKind::Register(_) => {
+                let name = self.parser.register()?;
+                Operand::Register(self.str(&name))
+            }
+            TokenKind::StrLit(_) => {
+                let literal = self.parser.string()?;
+                let literal = self.str(&literal);
+                match self.parser.binary_op() {
+                    Some(_) => {
+                        let expr = self.binary_from(Expr::Str(literal), 1)?;
+                        Operand::Expr(self.alloc(expr))
+                    }
+                    None => Operand::String(literal),
+                }
+            }
+            TokenKind::CharLit(c) => {
+                self.parser.stream.next();
+                match self.parser.binary_op() {
+                    Some(_) => {
+                        let code = Expr::Number(Number::Int(i64::from(u32::from(c))));
+                        let expr = self.binary_from(code, 1)?;
+                        Operand::Expr(self.alloc(expr))
+                    }
+                    None => Operand::Char(c),
+                }
+            }
+            TokenKind::Pound => {
+                self.parser.stream.next();
+                Operand::Immediate(self.boxed_expr()?)
+            }
+            TokenKind::LeftBracket => {
+                let memory = self.memory()?;
+                Operand::Memory(self.arena.bump.alloc(memory))
+            }
+            TokenKind::IntLit(_)
+            | TokenKind::HexLit(_)
+            | TokenKind::BinLit(_)
+            | TokenKind::OctLit(_)
+            | TokenKind::FloatLit(_)
+            | TokenKind::Ident(_)
+            | TokenKind::NumericLabelRef(_)
+            | TokenKind::Dot
+            | TokenKind::Dollar
+            | TokenKind::DollarDollar
+            | TokenKind::LeftParen
+            | TokenKind::Minus
+            | TokenKind::Tilde
+            | TokenKind::Bang => match self.expr()? {
+                Expr::Symbol(name) => Operand::Symbol(name),
+                expr => Operand::Expr(self.alloc(expr)),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(operand))
+    }
+
+    /// `[base + index * scale + displacement]`. The registers go through
+    /// the parser's own bookkeeping; the displacement is built here.
+    fn memory(&mut self) -> Result<MemoryOperand<'a>, ParseError> {
+        self.parser.stream.expect(TokenKind::LeftBracket)?;
+        let mut registers = parser::MemoryOperand {
+            base: None,
+            index: None,
+            scale: 1,
+            displacement: None,
+        };
+        let mut displacement: Option<Expr<'a>> = None;
+        let mut op = BinaryOp::Add;
+        loop {
+            if self.parser.register_term() {
+                if op == BinaryOp::Sub {
+                    return self
+                        .parser
+                        .stream
+                        .fail("registers can only be added in a memory operand");
+                }
+                self.parser.memory_register(&mut registers)?;
+            } else {
+                let term = self.binary(BinaryOp::Mul.precedence())?;
+                displacement = Some(match displacement.take() {
+                    Some(lhs) => Expr::Binary {
+                        op,
+                        lhs: self.alloc(lhs),
+                        rhs: self.alloc(term),
+                    },
+                    None if op == BinaryOp::Sub => match term {
+                        Expr::Number(Number::Int(n)) => Expr::Number(Number::Int(-n)),
+                        Expr::Number(Number::Float(x)) => Expr::Number(Number::Float(-x)),
+                        term => Expr::Unary {
+                            op: UnaryOp::Neg,
+                            expr: self.alloc(term),
+                        },
+                    },
+                    None => term,
+                });
+            }
+            op = match self.parser.stream.peek().map(|t| &t.kind) {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Sub,
+                _ => break,
+            };
+            self.parser.stream.next();
+        }
+        self.parser.stream.expect(TokenKind::RightBracket)?;
+        Ok(MemoryOperand {
+            base: registers.base.as_deref().map(|base| self.str(base)),
+            index: registers.index.as_deref().map(|index| self.str(index)),
+            scale: registers.scale,
+            displacement: displacement.map(|disp| self.alloc(disp)),
+        })
+    }
+
+    fn macro_def(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.expect(TokenKind::MacroRules)?;
+
+        let mut name = match self.parser.stream.take_ident() {
+            Some(Ok(n)) => n,
+            Some(Err(t)) => {
+                return self
+                    .parser
+                    .stream
+                    .fail(format_args!("Expected macro name, got {:?}", t));
+            }
+            None => return self.parser.stream.unexpected_eof(),
+        };
+        if self.parser.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+
+        self.parser.stream.expect(TokenKind::LeftParen)?;
+
+        let mut params: BumpVec<&str> = self.list();
+        let mut defaults = self.list();
+
+        loop {
+            let Some(tok) = self.parser.stream.peek() else {
+                return self.parser.stream.unexpected_eof();
+            };
+            match &tok.kind {
+                TokenKind::Ident(_) if params.last().is_some_and(|p| p.ends_with("...")) => {
+                    return self
+                        .parser
+                        .stream
+                        .fail("only the last parameter can be variadic");
+                }
+                TokenKind::Ident(_) => {
+                    let mut param = self.parser.ident("a parameter")?;
+                    let mut ahead = self.parser.stream.iter().map(|t| &t.kind);
+                    if (0..3).all(|_| ahead.next() == Some(&TokenKind::Dot)) {
+                        for _ in 0..3 {
+                            self.parser.stream.next();
+                        }
+                        param.push_str("...");
+                    }
+                    let variadic = param.ends_with("...");
+                    if self
+                        .parser
+                        .stream
+                        .peek()
+                        .is_some_and(|t| t.kind == TokenKind::Equal)
+                    {
+                        if variadic {
+                            return self
+                                .parser
+                                .stream
+                                .fail("a variadic parameter can't have a default");
+                        }
+                        self.parser.stream.next();
+                        match self.operand()? {
+                            Some(default) => defaults.push(default),
+                            None => return self.parser.unexpected("a default value"),
+                        }
+                    } else if !variadic && !defaults.is_empty() {
+                        return self.parser.stream.fail(format_args!(
+                            "`{}` needs a default, like the parameters before it",
+                            param
+                        ));
+                    }
+                    params.push(self.str(&param));
+                }
+                TokenKind::RightParen => {
+                    self.parser.stream.next();
+                    break;
+                }
+                TokenKind::Comma => {
+                    self.parser.stream.next();
+                }
+                t => {
+                    let t = t.clone();
+                    self.parser.stream.next();
+                    return self.parser.stream.fail(format_args!(
+                        "Unexpected token in macro param list: {:?}",
+                        t
+                    ));
+                }
+            }
+        }
+
+        let body = self.block()?;
+
+        debug!(name = %name, params = params.len(), "macro definition");
+        Ok(StatementKind::MacroDef {
+            name: self.str(&name),
+            params: params.into_bump_slice(),
+            defaults: defaults.into_bump_slice(),
+            body,
+        })
+    }
+
+    fn for_loop(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.expect(TokenKind::ForBang)?;
+        self.parser.stream.expect(TokenKind::LeftParen)?;
+
+        self.parser.stream.expect(TokenKind::Var)?;
+        let var = match self.parser.stream.take_ident() {
+            Some(Ok(n)) => n,
+            Some(Err(_)) => return self.parser.stream.fail("expected loop variable name"),
+            None => return self.parser.stream.unexpected_eof(),
+        };
+        self.parser.stream.expect(TokenKind::Equal)?;
+        let start = self.boxed_expr()?;
+
+        self.parser.stream.expect(TokenKind::Semicolon)?;
+
+        self.parser.stream.expect_ident(&var)?;
+        let cmp = self.parser.parse_comparison()?;
+        let end = self.boxed_expr()?;
+
+        self.parser.stream.expect(TokenKind::Semicolon)?;
+
+        self.parser.stream.expect_ident(&var)?;
+        let Some(next) = self.parser.stream.next() else {
+            return self.parser.stream.unexpected_eof();
+        };
+        let one = Expr::Number(Number::Int(1));
+        let (op, step) = match &next.kind {
+            TokenKind::PlusPlus => (AssignOp::Add, self.alloc(one)),
+            TokenKind::MinusMinus => (AssignOp::Sub, self.alloc(one)),
+            kind => match AssignOp::from_token(kind) {
+                Some(op) => (op, self.boxed_expr()?),
+                None => {
+                    let found = kind.clone();
+                    return self.parser.stream.fail(format_args!(
+                        "expected `++`, `--` or a compound assignment like `+=`, got {:?}",
+                        found
+                    ));
+                }
+            },
+        };
+
+        self.parser.stream.expect(TokenKind::RightParen)?;
+
+        let body = self.block()?;
+
+        Ok(StatementKind::ForLoop {
+            var: self.str(&var),
+            start,
+            cmp,
+            end,
+            op,
+            step,
+            body,
+        })
+    }
+
+    fn if_chain(&mut self) -> Result<StatementKind<'a>, ParseError> {
+        self.parser.stream.expect(TokenKind::IfBang)?;
+        let (cond, body) = self.guarded()?;
+        let mut branches = self.list();
+        branches.push(IfBranch { cond, body });
+        let mut otherwise: &[Statement] = &[];
+        while let Some(tok) = self.parser.stream.peek() {
+            match &tok.kind {
+                TokenKind::ElifBang => {
+                    self.parser.stream.next();
+                    let (cond, body) = self.guarded()?;
+                    branches.push(IfBranch { cond, body });
+                }
+                TokenKind::ElseBang => {
+                    self.parser.stream.next();
+                    otherwise = self.block()?;
+                    break;
+                }
+                TokenKind::Ident(name) if name == "else" => {
+                    self.parser.stream.next();
+                    otherwise = self.block()?;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        Ok(StatementKind::If {
+            branches: branches.into_bump_slice(),
+            otherwise,
+        })
+    }
+
+    /// `(cond) { ... }`.
+    fn guarded(&mut self) -> Result<(&'a Expr<'a>, &'a [Statement<'a>]), ParseError> {
+        self.parser.stream.expect(TokenKind::LeftParen)?;
+        let cond = self.boxed_expr()?;
+        self.parser.stream.expect(TokenKind::RightParen)?;
+        let body = self.block()?;
+        Ok((cond, body))
+    }
+
+    /// `{ ... }`, recovering from errors inside as the top level does.
+    fn block(&mut self) -> Result<&'a [Statement<'a>], ParseError> {
+        self.parser.stream.expect(TokenKind::LeftBrace)?;
+
+        self.parser.depth += 1;
+        if self.parser.depth > MAX_NESTING {
+            return self.parser.stream.fail_with(
+                Code::E0003,
+                format_args!("blocks nested more than {} deep", MAX_NESTING),
+            );
+        }
+
+        let mut body = self.list();
+        while let Some(tok) = self.parser.stream.peek() {
+            if let TokenKind::RightBrace = tok.kind {
+                break;
+            }
+            if let Some(stmt) = self.recovering() {
+                body.push(stmt);
+            }
+        }
+
+        self.parser.stream.expect(TokenKind::RightBrace)?;
+        self.parser.depth -= 1;
+
+        Ok(body.into_bump_slice())
+    }
+
+    fn boxed_expr(&mut self) -> Result<&'a Expr<'a>, ParseError> {
+        let expr = self.expr()?;
+        Ok(self.alloc(expr))
+    }
+
+    fn expr(&mut self) -> Result<Expr<'a>, ParseError> {
+        self.binary(1)
+    }
+
+    fn binary(&mut self, min: u8) -> Result<Expr<'a>, ParseError> {
+        let lhs = self.unary()?;
+        self.binary_from(lhs, min)
+    }
+
+    fn binary_from(&mut self, mut lhs: Expr<'a>, min: u8) -> Result<Expr<'a>, ParseError> {
+        let depth = self.parser.depth;
+        while let Some(op) = self.parser.binary_op()
+            && op.precedence() >= min
+        {
+            self.parser.stream.next();
+            self.parser.nest_expr()?;
+            let rhs = self.binary(op.precedence() + 1)?;
+            lhs = Expr::Binary {
+                op,
+                lhs: self.alloc(lhs),
+                rhs: self.alloc(rhs),
+            };
+        }
+        self.parser.depth = depth;
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr<'a>, ParseError> {
+        let Some(tok) = self.parser.stream.peek() else {
+            return self.parser.stream.unexpected_eof();
+        };
+        let op = match tok.kind {
+            TokenKind::Minus => UnaryOp::Neg,
+            TokenKind::Tilde => UnaryOp::BitNot,
+            TokenKind::Bang => UnaryOp::Not,
+            _ => return self.primary(),
+        };
+        self.parser.stream.next();
+
+        if op == UnaryOp::Neg
+            && let Some(n) = self.parser.number_literal()
+        {
+            return Ok(Expr::Number(match n {
+                Number::Int(n) => Number::Int(-n),
+                Number::Float(x) => Number::Float(-x),
+            }));
+        }
+
+        self.parser.nest_expr()?;
+        let expr = self.unary()?;
+        self.parser.depth -= 1;
+        Ok(Expr::Unary {
+            op,
+            expr: self.alloc(expr),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr<'a>, ParseError> {
+        let mut expr = self.atom()?;
+        while self.parser.lookahead_is_index() {
+            self.parser.stream.next();
+            self.parser.nest_expr()?;
+            let index = self.expr()?;
+            self.parser.stream.expect(TokenKind::RightBracket)?;
+            self.parser.depth -= 1;
+            expr = Expr::Index {
+                base: self.alloc(expr),
+                index: self.alloc(index),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn atom(&mut self) -> Result<Expr<'a>, ParseError> {
+        if let Some(n) = self.parser.number_literal() {
+            return Ok(Expr::Number(n));
+        }
+        if self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind == TokenKind::LeftParen)
+        {
+            self.parser.stream.next();
+            self.parser.nest_expr()?;
+            let expr = self.expr()?;
+            self.parser.stream.expect(TokenKind::RightParen)?;
+            self.parser.depth -= 1;
+            return Ok(expr);
+        }
+
+        if self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind == TokenKind::LeftBracket)
+        {
+            self.parser.stream.next();
+            self.parser.nest_expr()?;
+            let items = self.exprs_until(TokenKind::RightBracket)?;
+            self.parser.depth -= 1;
+            return Ok(Expr::Array(items));
+        }
+
+        if let Some(tok) = self.parser.stream.peek()
+            && matches!(tok.kind, TokenKind::Dollar | TokenKind::DollarDollar)
+        {
+            let name = self.str(&tok.text);
+            self.parser.stream.next();
+            return Ok(Expr::Symbol(name));
+        }
+
+        if self
+            .parser
+            .stream
+            .peek()
+            .is_some_and(|t| t.kind == TokenKind::Dot)
+        {
+            self.parser.stream.next();
+            let name = self.parser.ident("a local label")?;
+            let name = bumpalo::format!(in &self.arena.bump, ".{}", name);
+            return Ok(Expr::Symbol(name.into_bump_str()));
+        }
+
+        let arena = self.arena;
+        let taken = self.parser.stream.take_with(|kind| match kind {
+            TokenKind::Ident(name) | TokenKind::NumericLabelRef(name) => {
+                Some(Atom::Name(mem::take(name)))
+            }
+            TokenKind::Register(name) if name.starts_with('%') => Some(Atom::Name(mem::take(name))),
+            TokenKind::StrLit(literal) => {
+                Some(Atom::Value(Expr::Str(arena.bump.alloc_str(literal))))
+            }
+            TokenKind::CharLit(c) => Some(Atom::Value(Expr::Number(Number::Int(i64::from(
+                u32::from(*c),
+            ))))),
+            _ => None,
+        });
+        let mut name = match self.parser.taken("an expression", taken)? {
+            Atom::Name(name) => name,
+            Atom::Value(value) => return Ok(value),
+        };
+
+        if name == "stringify"
+            && self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Bang)
+        {
+            let param = self.parser.stringify()?;
+            let param = Expr::Symbol(self.str(&param));
+            return Ok(Expr::Call {
+                name: "stringify!",
+                args: self.arena.bump.alloc_slice_copy(&[param]),
+            });
+        }
+        while let Some(separator) = self.parser.lookahead_is_path() {
+            self.parser.stream.next();
+            name.push_str(separator);
+            name.push_str(&self.parser.ident("a name")?);
+        }
+        let name = self.str(&name);
+
+        if self.parser.stream.newline_before_next()
+            || self
+                .parser
+                .stream
+                .peek()
+                .is_none_or(|t| t.kind != TokenKind::LeftParen)
+        {
+            return Ok(Expr::Symbol(name));
+        }
+        self.parser.stream.next();
+        let args = self.exprs_until(TokenKind::RightParen)?;
+        Ok(Expr::Call { name, args })
+    }
+
+    /// Comma-separated expressions up to and including `close`.
+    fn exprs_until(&mut self, close: TokenKind) -> Result<&'a [Expr<'a>], ParseError> {
+        let mut items = self.list();
+        while self.parser.stream.peek().is_some_and(|t| t.kind != close) {
+            items.push(self.expr()?);
+            if self
+                .parser
+                .stream
+                .peek()
+                .is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.parser.stream.next();
+            } else {
+                break;
+            }
+        }
+        self.parser.stream.expect(close)?;
+        Ok(items.into_bump_slice())
+    }
+}
+
+/// [`crate::walk::Visitor`] for the arena AST: one `visit_*` method per
+/// kind of node, each defaulting to the matching `walk_*` function here.
+pub trait Visitor<'a> {
+    fn visit_statement(&mut self, stmt: &'a Statement<'a>) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_operand(&mut self, operand: &'a Operand<'a>) {
+        walk_operand(self, operand);
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr<'a>) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visits each of `stmts` in turn.
+pub fn walk_statements<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmts: &'a [Statement<'a>]) {
+    for stmt in stmts {
+        visitor.visit_statement(stmt);
+    }
+}
+
+/// Visits what `stmt` holds in the order [`crate::walk::walk_statement`]
+/// does.
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a Statement<'a>) {
+    match stmt.kind {
+        StatementKind::VarAssign { expr, .. }
+        | StatementKind::VarUpdate { expr, .. }
+        | StatementKind::ConstAssign { expr, .. }
+        | StatementKind::Equ { expr, .. }
+        | StatementKind::Org(expr) => visitor.visit_expr(expr),
+        StatementKind::Instruction { args, .. } | StatementKind::MacroCall { args, .. } => {
+            for arg in args {
+                visitor.visit_operand(arg);
+            }
+        }
+        StatementKind::Data { values, .. } => {
+            for value in values {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::StructData { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::Enum { variants, .. } => {
+            for &value in variants.iter().filter_map(|(_, value)| value.as_ref()) {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::Align { boundary, fill } => {
+            visitor.visit_expr(boundary);
+            if let Some(fill) = fill {
+                visitor.visit_expr(fill);
+            }
+        }
+        StatementKind::MacroDef { defaults, body, .. } => {
+            for default in defaults {
+                visitor.visit_operand(default);
+            }
+            walk_statements(visitor, body);
+        }
+        StatementKind::Block(body)
+        | StatementKind::Module { body, .. }
+        | StatementKind::Foreach { body, .. } => walk_statements(visitor, body),
+        StatementKind::ForLoop {
+            start,
+            end,
+            step,
+            body,
+            ..
+        } => {
+            for expr in [start, end, step] {
+                visitor.visit_expr(expr);
+            }
+            walk_statements(visitor, body);
+        }
+        StatementKind::WhileLoop { cond: expr, body }
+        | StatementKind::Repeat { count: expr, body } => {
+            visitor.visit_expr(expr);
+            walk_statements(visitor, body);
+        }
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            for branch in branches {
+                visitor.visit_expr(branch.cond);
+                walk_statements(visitor, branch.body);
+            }
+            walk_statements(visitor, otherwise);
+        }
+        StatementKind::Label { .. }
+        | StatementKind::Directive { .. }
+        | StatementKind::Include(_)
+        | StatementKind::Section(_)
+        | StatementKind::StringData { .. }
+        | StatementKind::Struct { .. }
+        | StatementKind::Break
+        | StatementKind::Continue => {}
+    }
+}
+
+/// Visits the expressions in `operand`: its value, or a memory operand's
+/// displacement.
+pub fn walk_operand<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, operand: &'a Operand<'a>) {
+    match *operand {
+        Operand::Immediate(expr) | Operand::Expr(expr) => visitor.visit_expr(expr),
+        Operand::Memory(memory) => {
+            if let Some(disp) = memory.displacement {
+                visitor.visit_expr(disp);
+            }
+        }
+        Operand::Register(_) | Operand::Symbol(_) | Operand::String(_) | Operand::Char(_) => {}
+    }
+}
+
+/// Visits `expr`'s subexpressions, left to right.
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a Expr<'a>) {
+    match *expr {
+        Expr::Unary { expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Call { args, .. } | Expr::Array(args) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::Index { base, index } => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        Expr::Number(_) | Expr::Str(_) | Expr::Symbol(_) => {}
+    }
+}
+
+/// `stmts` as source, one statement per line, as [`crate::print::to_source`]
+/// writes the owned AST.
+pub fn to_source(stmts: &[Statement]) -> String {
+    let mut out = String::new();
+    // Writing to a `String` can't fail.
+    let _ = write_body(&mut out, stmts, 0);
+    out
+}
+
+/// As chasm would write it, the same text the owned statement prints.
+impl fmt::Display for Statement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_statement(f, self, 0)
+    }
+}
+
+fn write_body(out: &mut impl Write, stmts: &[Statement], depth: usize) -> fmt::Result {
+    for stmt in stmts {
+        out.write_str(&INDENT.repeat(depth))?;
+        write_statement(out, stmt, depth)?;
+        out.write_char('\n')?;
+    }
+    Ok(())
+}
+
+fn write_block(out: &mut impl Write, body: &[Statement], depth: usize) -> fmt::Result {
+    out.write_str("{\n")?;
+    write_body(out, body, depth + 1)?;
+    write!(out, "{}}}", INDENT.repeat(depth))
+}
+
+fn write_list<T: fmt::Display>(out: &mut impl Write, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.write_str(", ")?;
+        }
+        write!(out, "{}", item)?;
+    }
+    Ok(())
+}
+
+fn write_statement(out: &mut impl Write, stmt: &Statement, depth: usize) -> fmt::Result {
+    match stmt.kind {
+        StatementKind::VarAssign { name, expr } => write!(out, "var {} = {}", name, expr),
+        StatementKind::VarUpdate { name, op, expr } => write!(out, "var {} {} {}", name, op, expr),
+        StatementKind::ConstAssign { name, expr } => write!(out, "const {} = {}", name, expr),
+        StatementKind::Equ { name, expr } => write!(out, "{} equ {}", name, expr),
+        StatementKind::Label { name, visibility } => match visibility {
+            Visibility::Global => write!(out, "::{}:", name),
+            Visibility::Local | Visibility::File => write!(out, "{}:", name),
+        },
+        StatementKind::Instruction { name, args, .. } => {
+            out.write_str(name)?;
+            if !args.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, args)?;
+            }
+            Ok(())
+        }
+        StatementKind::Directive { name, args } => {
+            write!(out, "@{}", name)?;
+            if !args.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, args)?;
+            }
+            Ok(())
+        }
+        StatementKind::Org(address) => write!(out, "@org {}", address),
+        StatementKind::Align { boundary, fill } => {
+            write!(out, "@align {}", boundary)?;
+            if let Some(fill) = fill {
+                write!(out, ", {}", fill)?;
+            }
+            Ok(())
+        }
+        StatementKind::Include(path) => write!(out, "include {}", path),
+        StatementKind::Data { width, values, .. } => {
+            out.write_str(width.as_str())?;
+            if !values.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, values)?;
+            }
+            Ok(())
+        }
+        StatementKind::Section(name) if SECTIONS.contains(&name) => write!(out, ".{}", name),
+        StatementKind::Section(name) => write!(out, ".section {}", name),
+        StatementKind::StringData {
+            encoding,
+            nul,
+            literal,
+        } => write!(out, "{} {}", encoding.directive(nul), literal),
+        StatementKind::MacroDef {
+            name,
+            params,
+            defaults,
+            body,
+        } => {
+            write!(out, "macro_rules! {}(", name)?;
+            let fixed =
+                params.len() - usize::from(params.last().is_some_and(|p| p.ends_with("...")));
+            let first_default = fixed - defaults.len();
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    out.write_str(", ")?;
+                }
+                out.write_str(param)?;
+                if let Some(default) = i.checked_sub(first_default).and_then(|i| defaults.get(i)) {
+                    write!(out, " = {}", default)?;
+                }
+            }
+            out.write_str(") ")?;
+            write_block(out, body, depth)
+        }
+        StatementKind::MacroCall { name, args } => {
+            write!(out, "{}!(", name)?;
+            write_list(out, args)?;
+            out.write_char(')')
+        }
+        StatementKind::ForLoop {
+            var,
+            start,
+            cmp,
+            end,
+            op,
+            step,
+            body,
+        } => {
+            write!(
+                out,
+                "for!(var {v} = {}; {v} {} {}; {v} {} {}) ",
+                start,
+                cmp,
+                end,
+                op,
+                step,
+                v = var
+            )?;
+            write_block(out, body, depth)
+        }
+        StatementKind::WhileLoop { cond, body } => {
+            write!(out, "while!({}) ", cond)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Repeat { count, body } => {
+            write!(out, "repeat!({}) ", count)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Foreach { param, body } => {
+            write!(out, "@foreach {} ", param)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Break => out.write_str("break!"),
+        StatementKind::Continue => out.write_str("continue!"),
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            for (i, branch) in branches.iter().enumerate() {
+                let keyword = if i == 0 { "if!" } else { " elif!" };
+                write!(out, "{}({}) ", keyword, branch.cond)?;
+                write_block(out, branch.body, depth)?;
+            }
+            if otherwise.is_empty() {
+                return Ok(());
+            }
+            out.write_str(" else ")?;
+            write_block(out, otherwise, depth)
+        }
+        StatementKind::Module { name, body } => {
+            write!(out, "module {} ", name)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Struct { name, fields } => {
+            write!(out, "struct {} {{ ", name)?;
+            for (i, field) in fields.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(
+                    out,
+                    "{}{}: {}",
+                    separator,
+                    field.name,
+                    &field.width.as_str()[1..]
+                )?;
+            }
+            out.write_str(" }")
+        }
+        StatementKind::StructData { name, fields } => {
+            write!(out, "{} {{ ", name)?;
+            for (i, (field, value)) in fields.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(out, "{}{}: {}", separator, field, value)?;
+            }
+            out.write_str(" }")
+        }
+        StatementKind::Enum { name, variants } => {
+            write!(out, "enum {} {{ ", name)?;
+            for (i, (variant, value)) in variants.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(out, "{}{}", separator, variant)?;
+                if let Some(value) = value {
+                    write!(out, " = {}", value)?;
+                }
+            }
+            out.write_str(" }")
+        }
+        StatementKind::Block(body) => write_block(out, body, depth),
+    }
+}
+
+/// As chasm would write it, parenthesised as the owned [`parser::Expr`]
+/// is.
+impl fmt::Display for Expr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Str(literal) => f.write_str(literal),
+            Expr::Symbol(name) => f.write_str(name),
+            Expr::Unary { op, expr } => {
+                let bare = match expr {
+                    Expr::Unary { .. } | Expr::Binary { .. } => false,
+                    Expr::Number(_) => op != UnaryOp::Neg,
+                    _ => true,
+                };
+                if bare {
+                    write!(f, "{}{}", op, expr)
+                } else {
+                    write!(f, "{}({})", op, expr)
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                for (i, side) in [lhs, rhs].into_iter().enumerate() {
+                    if i == 1 {
+                        write!(f, " {} ", op)?;
+                    }
+                    match side {
+                        Expr::Binary { .. } => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                f.write_str(")")
+            }
+            Expr::Array(items) => {
+                f.write_str("[")?;
+                write_list(f, items)?;
+                f.write_str("]")
+            }
+            Expr::Index { base, index } => match base {
+                Expr::Symbol(_) | Expr::Call { .. } | Expr::Array(_) | Expr::Index { .. } => {
+                    write!(f, "{}[{}]", base, index)
+                }
+                _ => write!(f, "({})[{}]", base, index),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::Register(name) | Operand::Symbol(name) => f.write_str(name),
+            Operand::Immediate(expr) => write!(f, "#{}", expr),
+            Operand::Expr(expr) => {
+                let text = expr.to_string();
+                if text.starts_with('[') {
+                    write!(f, "({})", text)
+                } else {
+                    f.write_str(&text)
+                }
+            }
+            Operand::Memory(memory) => write!(f, "{}", memory),
+            Operand::String(literal) => f.write_str(literal),
+            Operand::Char(c) => write!(f, "'{}'", c.escape_default()),
+        }
+    }
+}
+
+impl fmt::Display for MemoryOperand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        if let Some(base) = self.base {
+            f.write_str(base)?;
+        }
+        if let Some(index) = self.index {
+            if self.base.is_some() {
+                f.write_str(" + ")?;
+            }
+            f.write_str(index)?;
+            if self.scale != 1 {
+                write!(f, " * {}", self.scale)?;
+            }
+        }
+        if let Some(disp) = self.displacement {
+            let loose = match disp {
+                Expr::Binary { op, .. } => op.precedence() < BinaryOp::Add.precedence(),
+                _ => false,
+            };
+            let registers = self.base.is_some() || self.index.is_some();
+            let text = disp.to_string();
+            match *disp {
+                Expr::Number(_) if registers && text.starts_with('-') => {
+                    write!(f, " - {}", &text[1..])?
+                }
+                Expr::Unary {
+                    op: UnaryOp::Neg,
+                    expr,
+                } if registers && !matches!(expr, Expr::Number(_)) => match expr {
+                    Expr::Binary { .. } => write!(f, " - ({})", expr)?,
+                    _ => write!(f, " - {}", expr)?,
+                },
+                _ if registers && loose => write!(f, " + ({})", text)?,
+                _ if registers => write!(f, " + {}", text)?,
+                _ if loose => write!(f, "({})", text)?,
+                _ => f.write_str(&text)?,
+            }
+        }
+        f.write_str("]")
+    }
+}
@@ -0,0 +1,710 @@
+//! Turns statements into bytes.
+//!
+//! [`assemble`] lays the statements out with each instruction as big as
+//! the [`Isa`] says, so every label has its address, then encodes each
+//! section item by item:
+//!
+//! ```text
+//! start:  ldi r1 0x1234   // 11 01 34 12
+//!         beq start       // 23 fa: 6 bytes back from the end of the beq
+//!         .word start     // 00 00
+//! ```
+//!
+//! Operands are checked against their fields, and branch targets against
+//! what a branch can reach (see [`isa`](crate::isa)), so nothing is
+//! truncated without an error. A data value too big for its directive is
+//! truncated, with a warning the `data_range` lint controls (see
+//! [`check_data`]). Gaps an `@org` leaves in a section are zeros, and
+//! `@align` padding is its fill byte.
+//!
+//! An operand wrapped whole in `abs(...)` or `rel(...)` is encoded as an
+//! address or as a distance from the end of the instruction, whatever its
+//! field's own addressing: `jmp rel(start)` jumps by an offset.
+//!
+//! The result is an [`Object`] with a section for each one laid out, and
+//! [`Assembled::image`] puts the sections at their addresses for a flat
+//! binary.
+//!
+//! `@extern NAME` declares a symbol another object defines. Each reference
+//! to a label, or to one of those, that's written `label`, `label + n` or
+//! `label - n` gets a relocation, so the object can be linked anywhere; a
+//! branch within its section needs none. Labels have their addresses in
+//! the bytes, and externs zeros.
+
+use crate::builtins::Value;
+use crate::codes::Code;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::eval::eval;
+use crate::expand::{ExpandOptions, Origins, expand_traced};
+use crate::isa::{
+    Addressing, Isa, OperandSpec, RegisterPattern, Target, check_data, encode_string, encode_target,
+};
+use crate::layout::{Layout, layout_expanded};
+use crate::link::Image;
+use crate::lint::{Lint, LintLevels};
+use crate::object::{self, Object, ObjectSymbol, RelocKind, Relocation};
+use crate::parser::{
+    BinaryOp, Expr, LexError, LexOptions, Number, Operand, Parser, Statement, StatementKind,
+};
+use crate::source::{FileId, SourceManager, Span};
+use crate::symbols::{
+    SymbolKind, SymbolTable, name_numeric_labels, scope_local_labels, scope_modules,
+};
+use crate::trace::phase;
+use crate::walk;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// What [`assemble`] made of a program.
+#[derive(Debug, Clone, Default)]
+pub struct Assembled {
+    pub layout: Layout,
+    /// The program's symbols, labels at their addresses and `@size`s
+    /// measured.
+    pub symbols: SymbolTable,
+    /// A section for each of `layout`'s, in the same order, with a
+    /// relocation for each reference to a label a linker could move.
+    pub object: Object,
+}
+
+impl Assembled {
+    /// What an object exports, for output writers: the global labels, at
+    /// their addresses, and every constant.
+    pub fn exports(&self) -> SymbolTable {
+        let global = |name: &str| {
+            let sym = self.object.symbol(name);
+            sym.is_some_and(|sym| sym.global && sym.definition.is_some())
+        };
+        SymbolTable {
+            symbols: self
+                .symbols
+                .symbols
+                .iter()
+                .filter(|sym| sym.kind != SymbolKind::Label || global(&sym.name))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The sections at their addresses, lowest first, with the space
+    /// between them zeros, and listed among the gaps with what `@org`s
+    /// skipped. Sections that share addresses can't be, and a
+    /// program using an `@extern` symbol has to be linked instead.
+    pub fn image(&self) -> Result<Image, Diagnostic> {
+        let external = self.object.relocations.iter().find(|reloc| {
+            let sym = self.object.symbol(&reloc.symbol);
+            sym.is_some_and(|sym| sym.definition.is_none())
+        });
+        if let Some(reloc) = external {
+            return Err(
+                Diagnostic::error(format!("undefined reference to `{}`", reloc.symbol))
+                    .with_code(Code::E0402)
+                    .with_note(
+                        "it's `@extern`: link this program's object with the one defining it",
+                    ),
+            );
+        }
+        let mut placed: Vec<(&str, Range<u64>, &[u8])> = self
+            .layout
+            .sections
+            .iter()
+            .zip(&self.object.sections)
+            .filter(|(section, _)| section.size() > 0)
+            .map(|(section, data)| {
+                (
+                    section.name.as_str(),
+                    section.start..section.pc,
+                    &*data.data,
+                )
+            })
+            .collect();
+        placed.sort_by_key(|(_, range, _)| range.start);
+
+        let base = placed.first().map_or(0, |(_, range, _)| range.start);
+        let mut data = Vec::new();
+        let mut gaps = Vec::new();
+        for pair in placed.windows(2) {
+            let [(name, range, _), (next, next_range, _)] = pair else {
+                continue;
+            };
+            if next_range.start < range.end {
+                return Err(Diagnostic::error(format!(
+                    "`.{}` and `.{}` overlap at {:#x}..{:#x}",
+                    name,
+                    next,
+                    next_range.start,
+                    range.end.min(next_range.end)
+                ))
+                .with_code(Code::E0603)
+                .with_note(format!(
+                    "`.{}` holds {:#x}..{:#x}",
+                    name, range.start, range.end
+                ))
+                .with_note(format!(
+                    "`.{}` holds {:#x}..{:#x}",
+                    next, next_range.start, next_range.end
+                )));
+            }
+        }
+        for (_, range, bytes) in &placed {
+            let end = base + data.len() as u64;
+            if range.start > end {
+                gaps.push(end..range.start);
+            }
+            data.resize((range.start - base) as usize, 0);
+            data.extend_from_slice(bytes);
+        }
+        // And what an `@org` skipped within a section.
+        for section in &self.layout.sections {
+            let mut pc = section.start;
+            for ((address, _), info) in section.items.iter().zip(&section.info) {
+                if *address > pc {
+                    gaps.push(pc..*address);
+                }
+                pc = pc.max(address + info.size);
+            }
+        }
+        gaps.sort_by_key(|gap| gap.start);
+
+        let mut symbols: Vec<(String, u64)> = self
+            .object
+            .symbols
+            .iter()
+            .filter(|sym| sym.global)
+            .filter_map(|sym| {
+                let (section, offset) = sym.definition?;
+                let start = self.layout.sections[section as usize].start;
+                Some((sym.name.clone(), start + offset))
+            })
+            .collect();
+        symbols.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(Image {
+            base,
+            data,
+            symbols,
+            gaps,
+        })
+    }
+}
+
+/// Lays out and encodes statements
+/// [`expand_traced`](crate::expand::expand_traced) returned with `origins`,
+/// for instruction set `isa` with registers named like `registers`.
+/// Everything wrong is reported, each error in the macro call or loop that
+/// produced it; the bytes of what's wrong are zeros.
+pub fn assemble(
+    ast: &[Statement],
+    origins: &Origins,
+    isa: &Isa,
+    registers: &RegisterPattern,
+) -> (Assembled, Vec<Diagnostic>) {
+    let (layout, mut errors) = layout_expanded(ast, origins, |name, _| {
+        isa.instruction(name).map_or(0, |spec| spec.size())
+    });
+    // The same names layout gave the labels.
+    let mut table = SymbolTable::collect(&name_numeric_labels(&scope_local_labels(
+        &scope_modules(ast),
+    )));
+    errors.extend(table.resolve(|label| layout.address_of(label)));
+
+    let mut object = Object {
+        symbols: layout.object_symbols(),
+        ..Object::default()
+    };
+    let labels = object
+        .symbols
+        .iter()
+        .filter_map(|sym| Some((sym.name.clone(), sym.definition?.0)))
+        .collect();
+    // What `@extern` declares and nothing here defines.
+    let mut externs = Vec::new();
+    for stmt in walk::iter_deep(ast) {
+        if let StatementKind::Directive { name, args } = &stmt.kind
+            && name == "extern"
+        {
+            for arg in args {
+                if table.get(arg).is_none() && !externs.contains(arg) {
+                    externs.push(arg.clone());
+                }
+            }
+        }
+    }
+    object
+        .symbols
+        .extend(externs.iter().map(|name| ObjectSymbol {
+            name: name.clone(),
+            global: true,
+            definition: None,
+        }));
+
+    let mut encoder = Encoder {
+        isa,
+        registers,
+        table: &table,
+        labels,
+        externs: externs.into_iter().collect(),
+        levels: LintLevels::collect(ast),
+        layout: &layout,
+        section: 0,
+        start: 0,
+        bytes: Vec::new(),
+        relocations: Vec::new(),
+        errors: Vec::new(),
+    };
+    object.sections = encoder.sections();
+    object.relocations = encoder.relocations;
+    errors.append(&mut encoder.errors);
+    (
+        Assembled {
+            layout,
+            symbols: table,
+            object,
+        },
+        errors,
+    )
+}
+
+/// Parses, expands and assembles `file` of `sources` into a flat binary
+/// for `target`, with `isa`'s instructions; what it includes isn't read.
+/// If anything is an error, every diagnostic instead.
+pub fn assemble_file(
+    sources: &SourceManager,
+    file: FileId,
+    target: &Target,
+    isa: &Isa,
+) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let (ast, diags) = parse_file(sources, file, target);
+    if !diags.is_empty() {
+        return Err(diags);
+    }
+    assemble_statements(&ast, target, isa)
+}
+
+/// The statements of `file` of `sources`, lexed for `target`, and its
+/// lexing and syntax errors in the order they're written.
+pub fn parse_file(
+    sources: &SourceManager,
+    file: FileId,
+    target: &Target,
+) -> (Vec<Statement>, Vec<Diagnostic>) {
+    let mut parser = Parser::with_options(sources, file, LexOptions::for_target(target));
+    let mut ast = Vec::new();
+    let mut diags = Vec::new();
+    for item in parser.statements() {
+        match item {
+            Ok(stmt) => ast.push(stmt),
+            Err(err) => diags.push(err.to_diagnostic()),
+        }
+    }
+    diags.extend(parser.lex_errors().iter().map(LexError::to_diagnostic));
+    diags.sort_by_key(|diag| diag.span.as_ref().map(|span| span.range.start));
+    (ast, diags)
+}
+
+/// Expands and assembles parsed statements into a flat binary, the
+/// second half of [`assemble_file`].
+pub fn assemble_statements(
+    ast: &[Statement],
+    target: &Target,
+    isa: &Isa,
+) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let opts = ExpandOptions {
+        registers: target.registers,
+        ..ExpandOptions::default()
+    };
+    let (expanded, mut diags, origins) = expand_traced(ast, &opts);
+    let (assembled, errors) = assemble(&expanded, &origins, isa, &target.registers);
+    diags.extend(errors);
+    if diags.iter().any(|diag| diag.severity == Severity::Error) {
+        return Err(diags);
+    }
+    assembled
+        .image()
+        .map(|image| image.data)
+        .map_err(|diag| vec![diag])
+}
+
+struct Encoder<'a> {
+    isa: &'a Isa,
+    registers: &'a RegisterPattern,
+    table: &'a SymbolTable,
+    /// The section each label is in.
+    labels: HashMap<String, u32>,
+    externs: HashSet<String>,
+    levels: LintLevels,
+    layout: &'a Layout,
+    /// The section being encoded, and its address.
+    section: u32,
+    start: u64,
+    /// The section being encoded, from its start.
+    bytes: Vec<u8>,
+    relocations: Vec<Relocation>,
+    errors: Vec<Diagnostic>,
+}
+
+impl Encoder<'_> {
+    /// Encodes every section the layout has, in its order.
+    fn sections(&mut self) -> Vec<object::Section> {
+        phase!("encode");
+        let mut sections = Vec::with_capacity(self.layout.sections.len());
+        for (index, section) in self.layout.sections.iter().enumerate() {
+            self.section = index as u32;
+            self.start = section.start;
+            self.bytes = Vec::with_capacity(section.size() as usize);
+            for ((address, stmt), info) in section.items.iter().zip(&section.info) {
+                // Whatever an `@org` skipped.
+                self.bytes.resize((address - section.start) as usize, 0);
+                let reported = self.errors.len();
+                self.encode(stmt, *address, info.size);
+                for diag in &mut self.errors[reported..] {
+                    diag.expansion = info.origin.clone();
+                }
+                // Keep the layout's addresses whatever went wrong.
+                self.bytes
+                    .resize((address + info.size - section.start) as usize, 0);
+            }
+            self.bytes.resize(section.size() as usize, 0);
+            sections.push(object::Section {
+                name: section.name.clone(),
+                align: 1,
+                data: std::mem::take(&mut self.bytes),
+            });
+        }
+        sections
+    }
+
+    /// Appends the bytes of `stmt`, which is `size` bytes at `address`.
+    fn encode(&mut self, stmt: &Statement, address: u64, size: u64) {
+        let span = &stmt.span;
+        match &stmt.kind {
+            StatementKind::Instruction { name, args, spans } => {
+                self.instruction(name, args, spans, address + size, span)
+            }
+            StatementKind::Data {
+                width,
+                values,
+                spans,
+            } => {
+                let what = format!("`{}` value", width.as_str());
+                let kind = reloc_kind(width.bits(), Addressing::Absolute);
+                for (index, value) in values.iter().enumerate() {
+                    let span = spans.get(index).unwrap_or(span);
+                    if self.relocate(value, kind, address + size, span) {
+                        self.push(0, width.bits() / 8);
+                        continue;
+                    }
+                    let value = self.int(value, &what, span).unwrap_or(0);
+                    let (bytes, diag) =
+                        check_data(width.as_str(), width.bits(), value, span.clone());
+                    let diag = diag.and_then(|diag| self.levels.report(Lint::DataRange, diag));
+                    self.errors.extend(diag);
+                    self.push(bytes, width.bits() / 8);
+                }
+            }
+            StatementKind::StringData {
+                encoding,
+                nul,
+                literal,
+            } => {
+                // Layout already reported a string that can't be encoded.
+                if let Ok(bytes) = encode_string(*encoding, *nul, literal, span.clone()) {
+                    self.bytes.extend(bytes);
+                }
+            }
+            StatementKind::Align { fill, .. } => {
+                // Layout already checked the fill.
+                let fill = fill
+                    .as_ref()
+                    .and_then(|fill| self.int(fill, "the fill", span));
+                let len = self.bytes.len() + size as usize;
+                self.bytes.resize(len, fill.unwrap_or(0) as u8);
+            }
+            _ => {}
+        }
+    }
+
+    /// Appends instruction `name` with operands `args`, which ends at
+    /// `end`. A problem with one operand is reported at its span in
+    /// `spans`, else at `span`, the whole statement's.
+    fn instruction(&mut self, name: &str, args: &[Operand], spans: &[Span], end: u64, span: &Span) {
+        let Some(spec) = self.isa.instruction(name) else {
+            self.errors.push(
+                Diagnostic::error(format!("unknown instruction `{}`", name))
+                    .with_code(Code::E0304)
+                    .with_span(span.clone())
+                    .with_suggestions(name, self.isa.mnemonics()),
+            );
+            return;
+        };
+        if args.len() != spec.operands.len() {
+            self.errors.push(
+                Diagnostic::error(format!(
+                    "`{}` takes {} operand{}, not {}",
+                    spec.mnemonic,
+                    spec.operands.len(),
+                    if spec.operands.len() == 1 { "" } else { "s" },
+                    args.len()
+                ))
+                .with_code(Code::E0305)
+                .with_span(span.clone())
+                .with_note(format!("it's written `{}`", spec.usage())),
+            );
+            return;
+        }
+
+        self.bytes.push(spec.opcode);
+        for (index, (arg, operand)) in args.iter().zip(&spec.operands).enumerate() {
+            let position = index + 1;
+            let span = spans.get(index).unwrap_or(span);
+            let wrong = |what: String| {
+                Diagnostic::error(format!(
+                    "operand {} of `{}` {}",
+                    position, spec.mnemonic, what
+                ))
+                .with_code(Code::E0305)
+                .with_span(span.clone())
+                .with_note(format!("it's written `{}`", spec.usage()))
+            };
+            let field = match (operand, arg, operand_expr(arg)) {
+                (OperandSpec::Register, Operand::Register(reg), _) => {
+                    let number = self.registers.number(reg);
+                    if number.is_none() {
+                        let what = format!("is `{}`, which has no register number", reg);
+                        self.errors.push(wrong(what));
+                    }
+                    number
+                }
+                (OperandSpec::Register, _, _) => {
+                    self.errors
+                        .push(wrong(format!("has to be a register, not `{}`", arg)));
+                    None
+                }
+                (OperandSpec::Value(operand), _, Some(expr)) => {
+                    let what = format!("operand {} of `{}`", position, spec.mnemonic);
+                    let (forced, expr) = addressing(expr);
+                    let addressing = forced.unwrap_or(operand.addressing);
+                    let kind = reloc_kind(operand.field.bits, addressing);
+                    if self.relocate(&expr, kind, end, span) {
+                        self.push(0, operand.field.bits / 8);
+                        continue;
+                    }
+                    self.int(&expr, &what, span).and_then(|target| {
+                        let encoded = encode_target(
+                            &spec.mnemonic,
+                            position,
+                            *operand,
+                            forced,
+                            target as u64,
+                            end,
+                            span.clone(),
+                        );
+                        encoded.map_err(|diag| self.errors.push(diag)).ok()
+                    })
+                }
+                (OperandSpec::Value(_), _, None) => {
+                    self.errors
+                        .push(wrong(format!("has to be a value, not `{}`", arg)));
+                    None
+                }
+            };
+            self.push(field.unwrap_or(0), operand.size() as u32);
+        }
+    }
+
+    /// Records the relocation a field of `kind` holding `expr` needs, if
+    /// it refers to a label, for the field about to be appended to an item
+    /// ending at `end`. A branch to a label in the same section needs none.
+    /// Returns whether `expr` is external, which leaves the field's value to
+    /// the linker.
+    fn relocate(&mut self, expr: &Expr, kind: Option<RelocKind>, end: u64, span: &Span) -> bool {
+        let external = expr
+            .symbols()
+            .into_iter()
+            .find(|name| self.externs.contains(*name));
+        let (reference, kind) = match (self.reference(expr, span), kind) {
+            (Some(reference), Some(kind)) => (reference, kind),
+            (_, kind) => {
+                if let Some(name) = external {
+                    let diag = Diagnostic::error(format!("can't refer to `{}` here", name))
+                        .with_code(Code::E0308)
+                        .with_span(span.clone());
+                    self.errors.push(match kind {
+                        Some(_) => diag.with_note(format!(
+                            "the linker can fill in `{0}`, `{0} + n` or `{0} - n`",
+                            name
+                        )),
+                        None => diag.with_note("the linker fills in 8-, 16- or 32-bit fields"),
+                    });
+                }
+                return external.is_some();
+            }
+        };
+        let (symbol, mut addend) = reference;
+        if kind.pc_relative() && self.labels.get(symbol) == Some(&self.section) {
+            return false;
+        }
+        let offset = self.bytes.len() as u64;
+        if kind.pc_relative() {
+            // Measured from the end of the item, not the field.
+            addend -= (end - self.start - offset) as i64;
+        }
+        self.relocations.push(Relocation {
+            section: self.section,
+            offset,
+            kind,
+            symbol: symbol.to_string(),
+            addend,
+        });
+        external.is_some()
+    }
+
+    /// The label `expr` refers to and the number added to it, if it's
+    /// `label`, `label + n`, `n + label` or `label - n`.
+    fn reference<'e>(&self, expr: &'e Expr, span: &Span) -> Option<(&'e str, i64)> {
+        let relocatable =
+            |name: &str| self.labels.contains_key(name) || self.externs.contains(name);
+        let constant = |expr: &Expr| {
+            if expr.symbols().into_iter().any(relocatable) {
+                return None;
+            }
+            match eval(expr, &|name| self.table.value(name), span) {
+                Ok(Value::Int(n)) => Some(n),
+                _ => None,
+            }
+        };
+        match expr {
+            Expr::Symbol(name) if relocatable(name) => Some((name, 0)),
+            Expr::Binary {
+                op: BinaryOp::Add,
+                lhs,
+                rhs,
+            } => match (&**lhs, &**rhs) {
+                (Expr::Symbol(name), other) | (other, Expr::Symbol(name)) if relocatable(name) => {
+                    Some((name, constant(other)?))
+                }
+                _ => None,
+            },
+            Expr::Binary {
+                op: BinaryOp::Sub,
+                lhs,
+                rhs,
+            } => match &**lhs {
+                Expr::Symbol(name) if relocatable(name) => {
+                    Some((name, constant(rhs)?.checked_neg()?))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The integer value of `expr`, `what` in errors, its error reported
+    /// if it has none. Labels have their addresses.
+    fn int(&mut self, expr: &Expr, what: &str, span: &Span) -> Option<i64> {
+        let undefined = expr
+            .symbols()
+            .into_iter()
+            .find(|name| self.table.get(name).is_none());
+        if let Some(name) = undefined {
+            self.errors.push(self.undefined(name, span));
+            return None;
+        }
+        let address_of = |label: &str| self.layout.address_of(label);
+        let lookup = |name: &str| self.table.value_with(name, &address_of);
+        let value = match eval(expr, &lookup, span) {
+            Ok(Value::Int(n)) => return Some(n),
+            Ok(value) => value,
+            Err(diag) => {
+                self.errors.push(diag);
+                return None;
+            }
+        };
+        self.errors.push(
+            Diagnostic::error(format!("{} is {}, not an integer", what, value.kind()))
+                .with_code(Code::E0306)
+                .with_span(span.clone()),
+        );
+        None
+    }
+
+    /// The error for `name`, which nothing defines, with the names it might
+    /// have meant. A local label is shown, and matched, as `.local` among
+    /// the other locals of its scope.
+    fn undefined(&self, name: &str, span: &Span) -> Diagnostic {
+        let scope = name
+            .rsplit_once('.')
+            .map(|(scope, _)| scope)
+            .filter(|scope| {
+                let sym = self.table.get(scope);
+                sym.is_some_and(|sym| sym.kind == SymbolKind::Label)
+            });
+        let written = unscoped(name, scope);
+        let mut diag = Diagnostic::error(format!("undefined symbol `{}`", written))
+            .with_code(Code::E0307)
+            .with_span(span.clone());
+        let numeric = |label: &str| !label.is_empty() && label.bytes().all(|b| b.is_ascii_digit());
+        if let Some(label) = name.strip_suffix('f').filter(|label| numeric(label)) {
+            diag = diag.with_note(format!("there's no `{}:` after it", label));
+        } else if let Some(label) = name.strip_suffix('b').filter(|label| numeric(label)) {
+            diag = diag.with_note(format!("there's no `{}:` before it", label));
+        }
+        // Numeric labels are renamed `1:0`, `1:1` and so on.
+        let candidates = self
+            .table
+            .symbols
+            .iter()
+            .map(|sym| unscoped(&sym.name, scope))
+            .filter(|candidate| !candidate.contains(':'));
+        diag.with_suggestions(written, candidates)
+    }
+
+    /// Appends the low `bytes` bytes of `value`, little-endian.
+    fn push(&mut self, value: u64, bytes: u32) {
+        self.bytes
+            .extend_from_slice(&value.to_le_bytes()[..bytes as usize]);
+    }
+}
+
+/// The relocation that fills in a `bits`-wide field with `addressing`,
+/// if there is one.
+fn reloc_kind(bits: u32, addressing: Addressing) -> Option<RelocKind> {
+    match (addressing, bits) {
+        (Addressing::Absolute, 8) => Some(RelocKind::Abs8),
+        (Addressing::Absolute, 16) => Some(RelocKind::Abs16),
+        (Addressing::Absolute, 32) => Some(RelocKind::Abs32),
+        (Addressing::Relative, 8) => Some(RelocKind::PcRel8),
+        (Addressing::Relative, 16) => Some(RelocKind::PcRel16),
+        _ => None,
+    }
+}
+
+/// The addressing an `abs(...)` or `rel(...)` around a whole operand
+/// forces, and what it wraps. Anything else is left as it is.
+fn addressing(expr: Expr) -> (Option<Addressing>, Expr) {
+    if let Expr::Call { name, args } = &expr
+        && let Some(forced) = Addressing::from_wrapper(name)
+        && let [inner] = args.as_slice()
+    {
+        return (Some(forced), inner.clone());
+    }
+    (None, expr)
+}
+
+/// `name` as written under label `scope`: `.loop` for `main.loop`.
+fn unscoped<'a>(name: &'a str, scope: Option<&str>) -> &'a str {
+    scope
+        .and_then(|scope| name.strip_prefix(scope))
+        .filter(|local| local.starts_with('.'))
+        .unwrap_or(name)
+}
+
+/// The value an operand that isn't a register stands for.
+fn operand_expr(operand: &Operand) -> Option<Expr> {
+    match operand {
+        Operand::Immediate(expr) | Operand::Expr(expr) => Some(expr.clone()),
+        Operand::Symbol(name) => Some(Expr::Symbol(name.clone())),
+        Operand::Char(c) => Some(Expr::Number(Number::Int(i64::from(u32::from(*c))))),
+        Operand::Register(_) | Operand::Memory(_) | Operand::String(_) => None,
+    }
+}
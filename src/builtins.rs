@@ -0,0 +1,302 @@
+//! Functions callable from constant expressions, like `strlen(GREETING)`.
+//!
+//! The evaluator resolves the arguments (string literals arrive unescaped,
+//! constant names replaced by their values) and dispatches here by name.
+//!
+//! Strings are measured and indexed in bytes of their UTF-8 encoding,
+//! because that's what `.ascii` emits: `strlen("a\n")` is 2 and
+//! `strlen("é")` is 2 as well.
+//!
+//! Arrays count in items: `len([1, 2, 3])` is 3.
+//!
+//! Builtins only ever see finished values. An expression whose arguments
+//! mention labels, like `max(rx_end, tx_end)`, is evaluated once addresses
+//! are assigned, the same as any other label arithmetic.
+
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::macros::Arity;
+use crate::parser::{Expr, Number};
+use crate::source::Span;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Str(_) => "a string",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+impl Value {
+    /// An expression evaluating back to the value, a string as a literal.
+    pub fn to_expr(&self) -> Expr {
+        match self {
+            Value::Int(n) => Expr::Number(Number::Int(*n)),
+            Value::Float(x) => Expr::Number(Number::Float(*x)),
+            // Rust's escapes are all ones chasm reads too.
+            Value::Str(s) => Expr::Str(format!("{:?}", s)),
+            Value::Array(items) => Expr::Array(items.iter().map(Value::to_expr).collect()),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Str(s) => write!(f, "{:?}", s),
+            Value::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: Arity,
+    /// Gets exactly as many arguments as `arity` allows.
+    call: fn(&[Value]) -> Result<Value, String>,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "strlen",
+        arity: Arity {
+            min: 1,
+            max: Some(1),
+        },
+        call: strlen,
+    },
+    Builtin {
+        name: "char_at",
+        arity: Arity {
+            min: 2,
+            max: Some(2),
+        },
+        call: char_at,
+    },
+    Builtin {
+        name: "sizeof",
+        arity: Arity {
+            min: 1,
+            max: Some(2),
+        },
+        call: sizeof,
+    },
+    Builtin {
+        name: "len",
+        arity: Arity {
+            min: 1,
+            max: Some(1),
+        },
+        call: len,
+    },
+    Builtin {
+        name: "lo",
+        arity: Arity {
+            min: 1,
+            max: Some(1),
+        },
+        call: lo,
+    },
+    Builtin {
+        name: "hi",
+        arity: Arity {
+            min: 1,
+            max: Some(1),
+        },
+        call: hi,
+    },
+    Builtin {
+        name: "min",
+        arity: Arity { min: 2, max: None },
+        call: min,
+    },
+    Builtin {
+        name: "max",
+        arity: Arity { min: 2, max: None },
+        call: max,
+    },
+    Builtin {
+        name: "abs",
+        arity: Arity {
+            min: 1,
+            max: Some(1),
+        },
+        call: abs,
+    },
+    Builtin {
+        name: "clamp",
+        arity: Arity {
+            min: 3,
+            max: Some(3),
+        },
+        call: clamp,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Calls builtin `name`. `span` is the call's, for the diagnostic if the
+/// name is unknown, the argument count is wrong or the builtin fails.
+pub fn call(name: &str, args: &[Value], span: &Span) -> Result<Value, Diagnostic> {
+    let Some(builtin) = lookup(name) else {
+        return Err(Diagnostic::error(format!("unknown function `{}`", name))
+            .with_code(Code::E0501)
+            .with_span(span.clone())
+            .with_suggestions(name, BUILTINS.iter().map(|b| b.name)));
+    };
+    if !builtin.arity.accepts(args.len()) {
+        return Err(Diagnostic::error(format!(
+            "`{}` takes {}, got {}",
+            name,
+            builtin.arity.arguments(),
+            args.len()
+        ))
+        .with_code(Code::E0502)
+        .with_span(span.clone()));
+    }
+    (builtin.call)(args).map_err(|message| {
+        Diagnostic::error(format!("{}: {}", name, message))
+            .with_code(Code::E0503)
+            .with_span(span.clone())
+    })
+}
+
+fn string(value: &Value, position: usize) -> Result<&str, String> {
+    match value {
+        Value::Str(s) => Ok(s),
+        other => Err(format!(
+            "argument {} must be a string, got {}",
+            position,
+            other.kind()
+        )),
+    }
+}
+
+fn int(value: &Value, position: usize) -> Result<i64, String> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        other => Err(format!(
+            "argument {} must be an integer, got {}",
+            position,
+            other.kind()
+        )),
+    }
+}
+
+fn strlen(args: &[Value]) -> Result<Value, String> {
+    let s = string(&args[0], 1)?;
+    Ok(Value::Int(s.len() as i64))
+}
+
+fn char_at(args: &[Value]) -> Result<Value, String> {
+    let s = string(&args[0], 1)?;
+    let index = int(&args[1], 2)?;
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| s.as_bytes().get(i))
+        .map(|&b| Value::Int(i64::from(b)))
+        .ok_or_else(|| {
+            format!(
+                "index {} is out of range for {}, which is {} bytes long",
+                index,
+                args[0],
+                s.len()
+            )
+        })
+}
+
+fn len(args: &[Value]) -> Result<Value, String> {
+    match &args[0] {
+        Value::Array(items) => Ok(Value::Int(items.len() as i64)),
+        other => Err(format!("argument 1 must be an array, got {}", other.kind())),
+    }
+}
+
+/// The low byte of a value, like the address half an 8-bit load takes.
+fn lo(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Int(int(&args[0], 1)? & 0xff))
+}
+
+/// The byte above [`lo`]'s.
+fn hi(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Int((int(&args[0], 1)? >> 8) & 0xff))
+}
+
+/// `sizeof(start, end)` over two resolved label addresses, or
+/// `sizeof(Point)` for a struct, whose name already stands for its size.
+fn sizeof(args: &[Value]) -> Result<Value, String> {
+    let [start, end] = args else {
+        return int(&args[0], 1).map(Value::Int);
+    };
+    let (start, end) = (int(start, 1)?, int(end, 2)?);
+    if end < start {
+        return Err(format!("end {:#x} comes before start {:#x}", end, start));
+    }
+    Ok(Value::Int(end - start))
+}
+
+fn ints(args: &[Value]) -> Result<Vec<i64>, String> {
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| int(arg, i + 1))
+        .collect()
+}
+
+fn min(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Int(
+        ints(args)?.into_iter().min().unwrap_or_default(),
+    ))
+}
+
+fn max(args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Int(
+        ints(args)?.into_iter().max().unwrap_or_default(),
+    ))
+}
+
+/// Overflow is an error, as everywhere in constant evaluation, so
+/// `abs(i64::MIN)` is rejected rather than wrapping back to itself.
+fn abs(args: &[Value]) -> Result<Value, String> {
+    let x = int(&args[0], 1)?;
+    x.checked_abs()
+        .map(Value::Int)
+        .ok_or_else(|| format!("{} has no positive counterpart in 64 bits", x))
+}
+
+fn clamp(args: &[Value]) -> Result<Value, String> {
+    let [x, lo, hi] = ints(args)?[..] else {
+        unreachable!("arity is checked before the call")
+    };
+    if lo > hi {
+        return Err(format!(
+            "lower bound {} is greater than upper bound {}",
+            lo, hi
+        ));
+    }
+    Ok(Value::Int(x.clamp(lo, hi)))
+}
@@ -0,0 +1,242 @@
+use crate::assemble::assemble_file;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::isa::{self, Isa, Target};
+use crate::source::SourceManager;
+use std::ffi::{CStr, CString, c_char};
+use std::panic;
+use std::ptr;
+
+/// Status codes returned across the C boundary.
+pub const CHASM_OK: i32 = 0;
+pub const CHASM_DIAGNOSTICS: i32 = 1;
+pub const CHASM_INVALID_ARGUMENT: i32 = 2;
+pub const CHASM_INTERNAL_ERROR: i32 = 3;
+
+/// `ChasmDiagnostic::severity`.
+pub const CHASM_ERROR: i32 = 0;
+pub const CHASM_WARNING: i32 = 1;
+
+/// Owned output handed to C. Free with `chasm_result_free`.
+#[repr(C)]
+pub struct ChasmResult {
+    /// `CHASM_OK` or `CHASM_DIAGNOSTICS`.
+    pub status: i32,
+    /// From `chasm_parse`, JSON text: the AST on success,
+    /// `{"diagnostics": [...]}` otherwise. From `chasm_assemble`, the
+    /// binary on success and NULL otherwise.
+    pub data: *mut u8,
+    pub len: usize,
+    /// What went wrong in `chasm_assemble`, NULL otherwise.
+    pub diagnostics: *mut ChasmDiagnostic,
+    pub diagnostic_count: usize,
+}
+
+/// One diagnostic, its strings NUL-terminated.
+#[repr(C)]
+pub struct ChasmDiagnostic {
+    /// `CHASM_ERROR` or `CHASM_WARNING`.
+    pub severity: i32,
+    /// Like `E0304`, or NULL for a diagnostic without one.
+    pub code: *mut c_char,
+    pub message: *mut c_char,
+    /// Counting from 1, or 0 if it doesn't point into the source.
+    pub line: usize,
+    pub column: usize,
+}
+
+/// What to assemble for. Either field may be NULL, as may the whole
+/// struct, for the default.
+#[repr(C)]
+pub struct ChasmOptions {
+    /// A target name, like `generic`.
+    pub target: *const c_char,
+    /// An instruction table in `--isa`'s format, in place of the
+    /// target's.
+    pub isa: *const c_char,
+}
+
+/// Parses a NUL-terminated UTF-8 source string and writes a newly allocated
+/// result to `*out`.
+///
+/// # Safety
+/// `src` must be a valid NUL-terminated string and `out` a valid pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chasm_parse(src: *const c_char, out: *mut *mut ChasmResult) -> i32 {
+    if src.is_null() || out.is_null() {
+        return CHASM_INVALID_ARGUMENT;
+    }
+    unsafe { *out = ptr::null_mut() };
+
+    let source = match unsafe { CStr::from_ptr(src) }.to_str() {
+        Ok(s) => s.to_string(),
+        Err(_) => return CHASM_INVALID_ARGUMENT,
+    };
+
+    let json = match panic::catch_unwind(|| crate::json::parse_to_json(&source)) {
+        Ok(json) => json,
+        Err(_) => return CHASM_INTERNAL_ERROR,
+    };
+    let status = if json.starts_with("{\"diagnostics\"") {
+        CHASM_DIAGNOSTICS
+    } else {
+        CHASM_OK
+    };
+
+    let result = ChasmResult {
+        status,
+        ..ChasmResult::with_data(json.into_bytes())
+    };
+    unsafe { *out = Box::into_raw(Box::new(result)) };
+    status
+}
+
+/// Assembles a NUL-terminated UTF-8 source string into a flat binary and
+/// writes a newly allocated result to `*out`: the bytes, or every
+/// diagnostic if anything is an error. Included files aren't read.
+///
+/// # Safety
+/// `src` must be a valid NUL-terminated string, `options` NULL or a valid
+/// pointer whose strings are NULL or NUL-terminated, and `out` a valid
+/// pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chasm_assemble(
+    src: *const c_char,
+    options: *const ChasmOptions,
+    out: *mut *mut ChasmResult,
+) -> i32 {
+    if src.is_null() || out.is_null() {
+        return CHASM_INVALID_ARGUMENT;
+    }
+    unsafe { *out = ptr::null_mut() };
+
+    let text = |s: *const c_char| match s.is_null() {
+        true => Ok(None),
+        false => unsafe { CStr::from_ptr(s) }.to_str().map(Some),
+    };
+    let Ok(Some(source)) = text(src) else {
+        return CHASM_INVALID_ARGUMENT;
+    };
+    let (target, table) = match unsafe { options.as_ref() } {
+        Some(options) => match (text(options.target), text(options.isa)) {
+            (Ok(target), Ok(table)) => (target, table),
+            _ => return CHASM_INVALID_ARGUMENT,
+        },
+        None => (None, None),
+    };
+    let target = match target {
+        Some(name) => match isa::target(name) {
+            Some(target) => target,
+            None => return CHASM_INVALID_ARGUMENT,
+        },
+        None => &Target::DEFAULT,
+    };
+
+    let assembled = panic::catch_unwind(|| {
+        let mut sources = SourceManager::new();
+        let file = sources.add_virtual("input", source);
+        let isa = match table {
+            Some(table) => table
+                .parse::<Isa>()
+                .map_err(|e| vec![Diagnostic::error(format!("instruction table: {}", e))]),
+            None => Ok(target.isa()),
+        };
+        match isa.and_then(|isa| assemble_file(&sources, file, target, &isa)) {
+            Ok(bytes) => ChasmResult::with_data(bytes),
+            Err(diags) => ChasmResult::with_diagnostics(&diags, &sources),
+        }
+    });
+    let Ok(result) = assembled else {
+        return CHASM_INTERNAL_ERROR;
+    };
+    let status = result.status;
+    unsafe { *out = Box::into_raw(Box::new(result)) };
+    status
+}
+
+impl ChasmResult {
+    fn with_data(data: Vec<u8>) -> Self {
+        let len = data.len();
+        Self {
+            status: CHASM_OK,
+            data: Box::into_raw(data.into_boxed_slice()).cast(),
+            len,
+            diagnostics: ptr::null_mut(),
+            diagnostic_count: 0,
+        }
+    }
+
+    fn with_diagnostics(diags: &[Diagnostic], sources: &SourceManager) -> Self {
+        // A C string ends at its first NUL.
+        let string = |s: &str| {
+            CString::new(s.replace('\0', " "))
+                .unwrap_or_default()
+                .into_raw()
+        };
+        let diagnostics: Box<[ChasmDiagnostic]> = diags
+            .iter()
+            .map(|diag| {
+                let (line, column) = match &diag.span {
+                    Some(span) => {
+                        let location = sources.location(span);
+                        (location.line, location.col)
+                    }
+                    None => (0, 0),
+                };
+                ChasmDiagnostic {
+                    severity: match diag.severity {
+                        Severity::Error => CHASM_ERROR,
+                        Severity::Warning => CHASM_WARNING,
+                    },
+                    code: diag
+                        .code
+                        .map_or(ptr::null_mut(), |code| string(code.as_str())),
+                    message: string(&diag.message),
+                    line,
+                    column,
+                }
+            })
+            .collect();
+        Self {
+            status: CHASM_DIAGNOSTICS,
+            data: ptr::null_mut(),
+            len: 0,
+            diagnostic_count: diagnostics.len(),
+            diagnostics: Box::into_raw(diagnostics).cast(),
+        }
+    }
+}
+
+/// Frees a result returned by `chasm_parse` or `chasm_assemble`. Passing
+/// NULL is a no-op.
+///
+/// # Safety
+/// `result` must come from `chasm_parse` or `chasm_assemble` and not have
+/// been freed already.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chasm_result_free(result: *mut ChasmResult) {
+    if result.is_null() {
+        return;
+    }
+    let _ = panic::catch_unwind(|| unsafe {
+        let result = Box::from_raw(result);
+        if !result.data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                result.data,
+                result.len,
+            )));
+        }
+        if !result.diagnostics.is_null() {
+            let diagnostics = Box::from_raw(ptr::slice_from_raw_parts_mut(
+                result.diagnostics,
+                result.diagnostic_count,
+            ));
+            for diag in diagnostics.iter() {
+                for string in [diag.code, diag.message] {
+                    if !string.is_null() {
+                        drop(CString::from_raw(string));
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,1035 @@
+//! Stable codes for every kind of diagnostic, and `chasm explain` texts.
+//!
+//! Codes are grouped by the stage that reports them, a hundred to a block:
+//!
+//! | block   | stage                                   |
+//! |---------|-----------------------------------------|
+//! | `E00xx` | lexing and parsing                      |
+//! | `E01xx` | definitions: macros, symbols, exports   |
+//! | `W02xx` | lints (warnings unless `@deny`-ed)      |
+//! | `E03xx` | instruction encoding                    |
+//! | `E04xx` | linking                                 |
+//! | `E05xx` | constant-expression evaluation          |
+//! | `E06xx` | layout: sections and addresses          |
+//!
+//! A code names one kind of problem forever. New kinds take the next free
+//! number in their block; a kind that goes away keeps its entry (and its
+//! number) so old references still explain something. Renumbering is a
+//! breaking change.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    E0001,
+    E0002,
+    E0003,
+    E0004,
+    E0005,
+    E0006,
+    E0007,
+    E0008,
+    E0101,
+    E0102,
+    E0103,
+    E0104,
+    E0105,
+    E0106,
+    E0107,
+    E0108,
+    E0109,
+    E0110,
+    E0111,
+    E0112,
+    E0113,
+    E0114,
+    E0115,
+    E0116,
+    E0117,
+    E0118,
+    E0119,
+    E0120,
+    W0201,
+    W0202,
+    W0203,
+    W0204,
+    W0205,
+    E0301,
+    E0302,
+    E0303,
+    E0304,
+    E0305,
+    E0306,
+    E0307,
+    E0308,
+    E0401,
+    E0402,
+    E0403,
+    E0404,
+    E0501,
+    E0502,
+    E0503,
+    E0504,
+    E0505,
+    E0506,
+    E0507,
+    E0508,
+    E0509,
+    E0510,
+    E0511,
+    E0512,
+    E0513,
+    E0514,
+    E0515,
+    E0601,
+    E0602,
+    E0603,
+}
+
+impl Code {
+    pub const ALL: &'static [Code] = &[
+        Code::E0001,
+        Code::E0002,
+        Code::E0003,
+        Code::E0004,
+        Code::E0005,
+        Code::E0006,
+        Code::E0007,
+        Code::E0008,
+        Code::E0101,
+        Code::E0102,
+        Code::E0103,
+        Code::E0104,
+        Code::E0105,
+        Code::E0106,
+        Code::E0107,
+        Code::E0108,
+        Code::E0109,
+        Code::E0110,
+        Code::E0111,
+        Code::E0112,
+        Code::E0113,
+        Code::E0114,
+        Code::E0115,
+        Code::E0116,
+        Code::E0117,
+        Code::E0118,
+        Code::E0119,
+        Code::E0120,
+        Code::W0201,
+        Code::W0202,
+        Code::W0203,
+        Code::W0204,
+        Code::W0205,
+        Code::E0301,
+        Code::E0302,
+        Code::E0303,
+        Code::E0304,
+        Code::E0305,
+        Code::E0306,
+        Code::E0307,
+        Code::E0308,
+        Code::E0401,
+        Code::E0402,
+        Code::E0403,
+        Code::E0404,
+        Code::E0501,
+        Code::E0502,
+        Code::E0503,
+        Code::E0504,
+        Code::E0505,
+        Code::E0506,
+        Code::E0507,
+        Code::E0508,
+        Code::E0509,
+        Code::E0510,
+        Code::E0511,
+        Code::E0512,
+        Code::E0513,
+        Code::E0514,
+        Code::E0515,
+        Code::E0601,
+        Code::E0602,
+        Code::E0603,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::E0001 => "E0001",
+            Code::E0002 => "E0002",
+            Code::E0003 => "E0003",
+            Code::E0004 => "E0004",
+            Code::E0005 => "E0005",
+            Code::E0006 => "E0006",
+            Code::E0007 => "E0007",
+            Code::E0008 => "E0008",
+            Code::E0101 => "E0101",
+            Code::E0102 => "E0102",
+            Code::E0103 => "E0103",
+            Code::E0104 => "E0104",
+            Code::E0105 => "E0105",
+            Code::E0106 => "E0106",
+            Code::E0107 => "E0107",
+            Code::E0108 => "E0108",
+            Code::E0109 => "E0109",
+            Code::E0110 => "E0110",
+            Code::E0111 => "E0111",
+            Code::E0112 => "E0112",
+            Code::E0113 => "E0113",
+            Code::E0114 => "E0114",
+            Code::E0115 => "E0115",
+            Code::E0116 => "E0116",
+            Code::E0117 => "E0117",
+            Code::E0118 => "E0118",
+            Code::E0119 => "E0119",
+            Code::E0120 => "E0120",
+            Code::W0201 => "W0201",
+            Code::W0202 => "W0202",
+            Code::W0203 => "W0203",
+            Code::W0204 => "W0204",
+            Code::W0205 => "W0205",
+            Code::E0301 => "E0301",
+            Code::E0302 => "E0302",
+            Code::E0303 => "E0303",
+            Code::E0304 => "E0304",
+            Code::E0305 => "E0305",
+            Code::E0306 => "E0306",
+            Code::E0307 => "E0307",
+            Code::E0308 => "E0308",
+            Code::E0401 => "E0401",
+            Code::E0402 => "E0402",
+            Code::E0403 => "E0403",
+            Code::E0404 => "E0404",
+            Code::E0501 => "E0501",
+            Code::E0502 => "E0502",
+            Code::E0503 => "E0503",
+            Code::E0504 => "E0504",
+            Code::E0505 => "E0505",
+            Code::E0506 => "E0506",
+            Code::E0507 => "E0507",
+            Code::E0508 => "E0508",
+            Code::E0509 => "E0509",
+            Code::E0510 => "E0510",
+            Code::E0511 => "E0511",
+            Code::E0512 => "E0512",
+            Code::E0513 => "E0513",
+            Code::E0514 => "E0514",
+            Code::E0515 => "E0515",
+            Code::E0601 => "E0601",
+            Code::E0602 => "E0602",
+            Code::E0603 => "E0603",
+        }
+    }
+
+    /// Parses a code, ignoring ASCII case (`e0101` works too).
+    pub fn from_name(s: &str) -> Option<Code> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|code| code.as_str().eq_ignore_ascii_case(s))
+    }
+
+    /// One line saying what the code is about.
+    pub fn title(self) -> &'static str {
+        match self {
+            Code::E0001 => "unexpected token",
+            Code::E0002 => "unexpected end of input",
+            Code::E0003 => "blocks or expressions nested too deeply",
+            Code::E0004 => "unterminated block comment",
+            Code::E0005 => "unexpected character",
+            Code::E0006 => "invalid escape sequence",
+            Code::E0007 => "unknown `.` directive",
+            Code::E0008 => "`_` right after a radix prefix",
+            Code::E0101 => "macro defined twice",
+            Code::E0102 => "overlapping macro overloads",
+            Code::E0103 => "no macro overload takes this many arguments",
+            Code::E0104 => "malformed `@size`",
+            Code::E0105 => "`@size` refers to something that isn't a label",
+            Code::E0106 => "negative `@size`",
+            Code::E0107 => "name clash in generated output",
+            Code::E0108 => "value doesn't fit the output's integer type",
+            Code::E0109 => "unknown integer type",
+            Code::E0110 => "call of an undefined macro",
+            Code::E0111 => "`equ` defined in terms of itself",
+            Code::E0112 => "unknown directive",
+            Code::E0113 => "directive given the wrong number of arguments",
+            Code::E0114 => "directive argument of the wrong kind",
+            Code::E0115 => "macro argument that can't go where its parameter is used",
+            Code::E0116 => "macro calls nested too deep",
+            Code::E0117 => "`@foreach` over something that isn't a variadic parameter",
+            Code::E0118 => "name pasted together with `##` that doesn't make a name",
+            Code::E0119 => "`stringify!` of something that isn't a macro parameter",
+            Code::E0120 => "too many macro calls expanded",
+            Code::W0201 => "unused constant",
+            Code::W0202 => "unknown lint",
+            Code::W0203 => "skipped input",
+            Code::W0204 => "data value out of range",
+            Code::W0205 => "binary literal that spells a numeric label reference",
+            Code::E0301 => "operand out of range",
+            Code::E0302 => "branch target out of range",
+            Code::E0303 => "character the string encoding can't hold",
+            Code::E0304 => "unknown instruction",
+            Code::E0305 => "operands don't match the instruction",
+            Code::E0306 => "value to encode isn't an integer",
+            Code::E0307 => "undefined symbol",
+            Code::E0308 => "external symbol used where the linker can't fill it in",
+            Code::E0401 => "symbol defined in more than one object",
+            Code::E0402 => "undefined reference",
+            Code::E0403 => "relocated value out of range",
+            Code::E0404 => "malformed object",
+            Code::E0501 => "unknown function",
+            Code::E0502 => "wrong number of arguments to a function",
+            Code::E0503 => "invalid argument to a function",
+            Code::E0504 => "unknown name in a constant expression",
+            Code::E0505 => "division by zero",
+            Code::E0506 => "arithmetic overflow",
+            Code::E0507 => "operator applied to the wrong kind of value",
+            Code::E0508 => "loop runs too many times",
+            Code::E0509 => "condition isn't an integer",
+            Code::E0510 => "bad repeat count",
+            Code::E0511 => "`for!` bound isn't an integer",
+            Code::E0512 => "`break!` or `continue!` outside a loop",
+            Code::E0513 => "`@org` address isn't a non-negative integer",
+            Code::E0514 => "struct data doesn't match its struct",
+            Code::E0515 => "bad array index",
+            Code::E0601 => "`@org` moves back over laid-out code",
+            Code::E0602 => "bad `@align` boundary or fill",
+            Code::E0603 => "sections overlap in the image",
+        }
+    }
+
+    /// The longer text `chasm explain` prints, with an example.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Code::E0001 => {
+                "\
+The parser found a token that can't continue the statement it was reading.
+
+```
+macro_rules! push(reg {     // `{` where `)` or a parameter was expected
+    dec sp;
+}
+```
+
+Check the statement's syntax around the reported position."
+            }
+            Code::E0002 => {
+                "\
+The input ended in the middle of a statement.
+
+```
+const SIZE =
+```
+
+Finish the statement, or close whatever block or parameter list is still
+open."
+            }
+            Code::E0003 => {
+                "\
+Blocks, including macro and loop bodies, nest more than 256 deep, or an
+expression does: through parentheses, or as a chain of operators.
+
+```
+{ { { { ... } } } }
+```
+
+The limit keeps the parser's stack bounded. Flatten the nesting, usually
+by moving inner parts into macros or intermediate constants."
+            }
+            Code::E0004 => {
+                "\
+A `/*` block comment runs to the end of the file without its `*/`.
+
+```
+/* disabled for now
+    mov r0 r1;
+/* nested */            // closes the inner comment, not the outer one
+```
+
+Block comments nest, so every `/*` needs its own `*/`, including one
+inside a comment."
+            }
+            Code::E0005 => {
+                "\
+The file holds characters that aren't part of any token.
+
+```
+mov r0 ?10              // `?` means nothing in chasm
+```
+
+The lexer drops them and carries on, so the statement around them may
+also be parsed differently from what was meant."
+            }
+            Code::E0006 => {
+                "\
+A string or char literal has an escape that doesn't mean anything.
+
+```
+.ascii \"tab\\q\"          // `\\q` isn't an escape
+.byte '\\x80'             // `\\x` only goes up to `\\x7F`
+```
+
+The escapes are `\\n`, `\\r`, `\\t`, `\\0`, `\\'`, `\\\"`, `\\\\`, `\\xNN` for an
+ASCII character and `\\u{...}` for any Unicode character, as in `\\u{263A}`.
+Write a literal backslash as `\\\\`."
+            }
+            Code::E0007 => {
+                "\
+A statement starts with `.` and a name that isn't a directive chasm knows.
+
+```
+.half 0x1234            // there's no `.half`; `.word` is 16 bits
+```
+
+The data directives are `.byte`, `.word`, `.dword` and `.quad`, for 8,
+16, 32 and 64-bit items, and `.ascii`, `.utf8` and `.utf16` for strings,
+each with a `z` form like `.asciiz` that adds a NUL. `.text`, `.data`,
+`.bss`, `.rodata` and `.section NAME` pick the section what follows goes
+in. Names with `@`, like `@define`, are a different kind of directive."
+            }
+            Code::E0008 => {
+                "\
+An integer literal has a `_` right after its `0x`, `0b` or `0o`. A `_`
+separates digits, so it can only come after the first one.
+
+```
+const MASK = 0x_FF      // write 0xFF
+const WIDE = 0xFF_FF    // fine
+```"
+            }
+            Code::E0101 => {
+                "\
+A macro is defined twice with the same number of parameters.
+
+```
+macro_rules! push(reg) { dec sp; store sp reg; }
+macro_rules! push(value) { dec sp; }
+```
+
+Macros are overloaded by arity, so the second definition would make every
+one-argument `push` ambiguous. Rename one, or give it a different number
+of parameters."
+            }
+            Code::E0102 => {
+                "\
+Two overloads of a macro accept some of the same argument counts.
+
+A macro with optional or variadic parameters accepts a range of argument
+counts. Overloads of the same name have to accept disjoint ranges, or a
+call could match both. Narrow one of the ranges, or rename one macro."
+            }
+            Code::E0103 => {
+                "\
+A macro is called with a number of arguments none of its definitions
+accepts.
+
+```
+macro_rules! mov(dst, src) { add dst src; }
+mov r0;             // one argument, but `mov` takes two
+```
+
+The error lists the argument counts that are defined."
+            }
+            Code::E0104 => {
+                "\
+`@size` takes exactly three arguments: the constant to define and the
+labels at the start and end of the region.
+
+```
+@size BLOB_LEN, blob_start, blob_end
+```
+
+Reported as E0113 now, like any directive with the wrong number of
+arguments."
+            }
+            Code::E0105 => {
+                "\
+An `@size` names a start or end that isn't a label, so the region can't be
+measured.
+
+```
+@size BLOB_LEN, blob_start, blob_ned    // typo for `blob_end`
+```"
+            }
+            Code::E0106 => {
+                "\
+The end label of an `@size` comes before its start label.
+
+```
+@size BLOB_LEN, blob_end, blob_start    // arguments swapped
+blob_start:
+    ...
+blob_end:
+```
+
+The note lists both addresses. Usually the arguments are swapped."
+            }
+            Code::E0107 => {
+                "\
+Two symbols end up with the same name in generated output, such as a C
+header, because of sanitizing or because a name is defined twice.
+
+```
+const loop-count = 4
+const loop_count = 8    // both become `loop_count` in C
+```
+
+Rename one of them, or set a prefix that keeps them apart."
+            }
+            Code::E0108 => {
+                "\
+A constant doesn't fit the integer type chosen for generated output.
+
+```
+const BIG = 70000       // too big for `--int-type u16`
+```
+
+Pick a wider type or shrink the constant."
+            }
+            Code::E0109 => {
+                "\
+The integer type given for generated output isn't one chasm knows.
+
+```
+chasm main.asm --emit rust-consts --int-type usize
+```
+
+Use one of `u8`, `i8`, `u16`, `i16`, `u32`, `i32`, `u64` or `i64`."
+            }
+            Code::E0110 => {
+                "\
+A `name!(...)` call names a macro that no `macro_rules!` defines.
+
+```
+macro_rules! push(reg) { dec sp; st [sp], reg; }
+psuh!(r0);          // typo for `push`
+```
+
+Define the macro, or fix the name. Without the `!`, a name that isn't a
+macro is an instruction instead."
+            }
+            Code::E0111 => {
+                "\
+An `equ` symbol's expression uses the symbol itself, directly or through
+other `equ`s, so it never has a value.
+
+```
+LIMIT equ TOP + 1
+TOP equ LIMIT - 1
+```
+
+`equ`s are worked out when they're used, so they can refer to symbols
+defined later, but the chain has to end in numbers or labels."
+            }
+            Code::E0112 => {
+                "\
+A directive's name isn't one chasm knows, which is usually a typo.
+
+```
+@defin SIZE 32          // `@define`
+```
+
+Directives for other tools have to be registered to be accepted, with
+`--directive message(string)` on the command line."
+            }
+            Code::E0113 => {
+                "\
+A directive got more or fewer arguments than it takes. The note says what
+it expects.
+
+```
+@size BLOB_LEN, blob_start      // the end label is missing
+@define                         // the name is missing
+```"
+            }
+            Code::E0114 => {
+                "\
+A directive argument isn't the kind of thing the directive takes there: a
+number where a name goes, say, or a name where a string does.
+
+```
+@define 32 SIZE         // the name comes first
+@size LEN, 0, 16        // labels, not addresses
+```
+
+In a macro body, an argument naming one of the macro's parameters is
+accepted as any kind."
+            }
+            Code::E0115 => {
+                "\
+A macro's parameter is used somewhere its argument doesn't fit: a memory
+operand in an expression, or a register in a memory operand that already
+has a base and an index.
+
+```
+macro_rules! next(src) {
+    add r0, src + 1
+}
+next [r1]               // `[r1] + 1` isn't an expression
+```"
+            }
+            Code::E0116 => {
+                "\
+Expanding a macro call led to more calls, each inside the last, past the
+limit of 64 (`--macro-depth` changes it). That's most often a macro that
+calls itself with nothing to stop it:
+
+```
+macro_rules! spin(reg) {
+    dec reg
+    spin reg            // called again every time
+}
+```
+
+Put the recursive call in an `if!` that's false once the work is done, or
+use a `for!` or `repeat!` loop inside one expansion instead.
+
+Nothing more is expanded after this error: a macro that calls itself more
+than once would otherwise go on to hit the limit again for every call."
+            }
+            Code::E0117 => {
+                "\
+`@foreach` repeats its body once per argument of a variadic parameter, so
+it only works inside a macro, over that macro's last parameter written
+with `...`.
+
+```
+macro_rules! push_all(first, regs...) {
+    @foreach first {    // `first` takes one argument, not a list
+        push first
+    }
+}
+```"
+            }
+            Code::E0118 => {
+                "\
+`##` pastes the pieces of a name together once the loop variables, macro
+parameters and constants among them have values, like `handler_##i` into
+`handler_3`. Each piece has to end up a name or a whole number from 0 up,
+and the name can't start with a number.
+
+```
+for!(var i = 0; i < 4; i += 1) {
+    entry_##i:          // fine: `entry_0` to `entry_3`
+    i##_exit:           // `0_exit` isn't a name
+}
+```"
+            }
+            Code::E0119 => {
+                "\
+`stringify!(param)` is the argument a macro call passed for `param`, as a
+string literal, so it only works in a `macro_rules!` body and only on one
+of that macro's parameters.
+
+```
+macro_rules! check(reg) {
+    .asciiz stringify!(reg)      // fine: \"r1\" for `check r1`
+    .asciiz stringify!(value)    // `value` isn't a parameter
+}
+```"
+            }
+            Code::E0120 => {
+                "\
+Expanding the program took more than 1048576 macro calls in all. Macros
+that each call the next more than once multiply, so a short chain of them
+expands to far more code than it looks like:
+
+```
+macro_rules! twice(n) {
+    once!(n)
+    once!(n)
+}
+macro_rules! four(n) {
+    twice!(n)
+    twice!(n)
+}
+// ...and so on: twenty levels make a million calls
+```
+
+Nothing more is expanded after this error. Use a `repeat!` or `for!`
+loop inside one expansion to repeat code instead."
+            }
+            Code::W0201 => {
+                "\
+A constant is defined but nothing refers to it. Lint name: `unused_const`.
+
+```
+const SCRATCH = 4       // never used
+```
+
+Remove it, or silence the warning with `@allow(unused_const)` before it."
+            }
+            Code::W0202 => {
+                "\
+A lint directive names a lint that doesn't exist. Lint name:
+`unknown_lint`.
+
+```
+@allow(unused_cosnt)    // typo for `unused_const`
+```
+
+Directives accept a lint's name or its code."
+            }
+            Code::W0203 => {
+                "\
+A token was skipped because it doesn't start a statement. Lint name:
+`skipped_input`. Characters that aren't tokens at all are E0005.
+
+```
+mov r0 r1 )             // the stray `)` is skipped
+```"
+            }
+            Code::W0204 => {
+                "\
+A data value fits its directive's width neither as unsigned nor as
+two's-complement signed, so it would be truncated. Lint name:
+`data_range`.
+
+```
+.byte 300               // stored as 0x2c
+.byte -1                // fine: stored as 0xff
+```"
+            }
+            Code::W0205 => {
+                "\
+With `--suffix-radix`, a number with a `b` right after it is a binary
+literal, so `1b` doesn't refer to the numeric label `1:` before it. The
+lexer reports this, so lint directives don't change it.
+
+```
+1:  dec r0
+    bne 1b              // branches to address 1, not to `1:`
+```
+
+Write `0b1` for the number, or give the label a name."
+            }
+            Code::E0301 => {
+                "\
+An instruction operand doesn't fit its field in the encoding.
+
+```
+addi r1 100             // the immediate field is 6 bits unsigned
+```
+
+The note gives the field's range. Negative values can't go in unsigned
+fields at all."
+            }
+            Code::E0302 => {
+                "\
+A PC-relative branch can't reach its target: the distance doesn't fit the
+branch's offset field.
+
+```
+br far_away             // more than 127 bytes ahead
+```
+
+The notes list the reachable addresses. Use an absolute jump, or branch to
+a closer trampoline."
+            }
+            Code::E0303 => {
+                "\
+String data holds a character its encoding has no bytes for. `.ascii` and
+`.asciiz` only take ASCII.
+
+```
+.asciiz \"café\"          // `é` isn't ASCII
+```
+
+Use `.utf8` or `.utf16` (and `.utf8z` or `.utf16z` for a NUL-terminated
+string) to store any character."
+            }
+            Code::E0304 => {
+                "\
+An instruction names a mnemonic the target doesn't have.
+
+```
+jpm start               // `jmp` was meant
+```
+
+The error suggests close mnemonics. `--isa` reads another instruction
+table."
+            }
+            Code::E0305 => {
+                "\
+An instruction has the wrong number of operands, or one of the wrong kind:
+a value where its encoding wants a register, or the other way round.
+
+```
+mov r1 5                // `mov` takes two registers; `ldi` loads a value
+```
+
+The note shows the operands the instruction takes."
+            }
+            Code::E0306 => {
+                "\
+An instruction operand or a data value is a float, a string or an array,
+which have no bytes of their own.
+
+```
+.word 1.5               // a float
+ldi r1 \"A\"              // a string; `'A'` is the character's code
+```
+
+Use `.ascii` and friends to store strings."
+            }
+            Code::E0307 => {
+                "\
+An operand or data value names something that isn't defined anywhere: a
+label, constant, `equ` or `@define` with a typo, or a numeric label
+reference like `1f` with no `1:` in that direction.
+
+```
+start:
+    jmp strat           // typo for `start`
+```
+
+The error suggests close names; a misspelt `.local` label is matched
+against the locals under the same label."
+            }
+            Code::E0308 => {
+                "\
+An operand or data value uses a symbol `@extern` declares in a way a
+relocation can't express. The linker can only put the symbol's address,
+plus or minus a number, into an 8-, 16- or 32-bit field, or the distance
+to it into an 8- or 16-bit branch.
+
+```
+@extern table
+    .word table * 2     // only `table`, `table + n` or `table - n`
+    .quad table         // no 64-bit relocation
+```"
+            }
+            Code::E0401 => {
+                "\
+Two objects being linked both define the same global symbol.
+
+Make one of the definitions local to its object, or rename it."
+            }
+            Code::E0402 => {
+                "\
+An object refers to a symbol that no object being linked defines globally,
+or a program assembled straight to a binary uses a symbol it declares
+`@extern`, which only linking can provide.
+
+Add the object that defines it to the link, or check the name's spelling;
+the error suggests close matches."
+            }
+            Code::E0403 => {
+                "\
+A relocation's computed value doesn't fit the field it goes into, for
+example a PC-relative byte whose target ended up more than 127 bytes away
+after layout."
+            }
+            Code::E0404 => {
+                "\
+An object refers to a section or byte range it doesn't have, or has an
+invalid section alignment. It wasn't written by this version of chasm, or
+it was damaged."
+            }
+            Code::E0501 => {
+                "\
+An expression calls a function that isn't a builtin.
+
+```
+.byte strlne(GREETING)  // typo for `strlen`
+```"
+            }
+            Code::E0502 => {
+                "\
+A builtin function is called with the wrong number of arguments.
+
+```
+.space clamp(PAD, 64)   // clamp takes a value and two bounds
+```"
+            }
+            Code::E0503 => {
+                "\
+A builtin function was given an argument it can't work with: the wrong
+type, an index past the end of a string, bounds in the wrong order, or a
+result that overflows.
+
+```
+.byte char_at(\"hi\", 2)  // \"hi\" has bytes 0 and 1
+```"
+            }
+            Code::E0504 => {
+                "\
+A constant expression names something that has no value yet: a typo, or a
+constant defined further down. Labels are fine; expressions using them
+wait for addresses.
+
+```
+const SIZE = 16
+const TOTAL = SIZ * 2   // typo for `SIZE`
+```"
+            }
+            Code::E0505 => {
+                "\
+A constant expression divides by zero, or takes a remainder by it.
+
+```
+const STEP = SIZE / (COUNT - COUNT)
+```"
+            }
+            Code::E0506 => {
+                "\
+A constant expression's integer result doesn't fit in 64 bits, or it shifts
+by 64 bits or more, or by a negative amount. Integer arithmetic never
+wraps.
+
+```
+const BIG = 1 << 64
+```"
+            }
+            Code::E0507 => {
+                "\
+An operator got a value it doesn't work on: a string anywhere, or a float
+where only integers make sense (`%`, the shifts, `&`, `|`, `^`, `~`, `!`).
+
+```
+const HALF = 1.5 << 1
+```"
+            }
+            Code::E0508 => {
+                "\
+A `for!`, `while!` or `repeat!` loop ran its body 65536 times without
+finishing, so it was stopped there. A `while!` that never ends usually has
+a body that doesn't update what its condition tests.
+
+```
+var i = 0
+while!(i < 4) {
+    nop
+}
+```"
+            }
+            Code::E0509 => {
+                "\
+An `if!`, `elif!` or `while!` condition has to be an integer: zero is
+false and anything else true. A float or a string is neither.
+
+```
+while!(0.5) {
+    nop
+}
+```"
+            }
+            Code::E0510 => {
+                "\
+`repeat!` runs its body a number of times, so its count has to be an
+integer of at least 0.
+
+```
+repeat!(SIZE / 3.0) {
+    nop
+}
+```"
+            }
+            Code::E0511 => {
+                "\
+A `for!` loop counts in whole numbers, so its start, end and step have to
+be integers.
+
+```
+for!(var i = 0; i < 1; i += 0.25) {
+    nop
+}
+```"
+            }
+            Code::E0512 => {
+                "\
+`break!` and `continue!` act on the innermost `for!`, `while!` or
+`repeat!` loop around them. Outside any loop there's nothing for them to
+do.
+
+```
+if!(DONE) {
+    break!
+}
+```"
+            }
+            Code::E0513 => {
+                "\
+`@org` takes the address the section goes on from, which has to be a
+whole number and can't be negative.
+
+```
+@org BASE / 2           // a float if BASE is
+@org -0x100
+```"
+            }
+            Code::E0514 => {
+                "\
+Struct data like `Point { x: 1 }` names a struct no `struct` defines, a
+field the struct doesn't have, or the same field twice.
+
+```
+struct Point { x: word, y: word }
+Point { x: 1, z: 2 }    // `Point` has no `z`
+```
+
+Fields can come in any order, and ones left out are zero."
+            }
+            Code::E0515 => {
+                "\
+An array is indexed with something other than an integer, or with an index
+past its end. Items count from 0.
+
+```
+const TABLE = [1, 2, 4]
+.byte TABLE[3]          // TABLE has items 0 to 2
+```
+
+`len(TABLE)` gives the number of items."
+            }
+            Code::E0601 => {
+                "\
+An `@org` in the middle of a section can only move forward: the statements
+before it already have their addresses, and going back would put the next
+ones on top of them.
+
+```
+@org 0x8000
+.word 1, 2, 3, 4        // 0x8000..0x8008
+@org 0x8004             // inside the words above
+```
+
+The first `@org` in a section, before anything is laid out, can pick any
+start address. Use separate sections for code that lives elsewhere."
+            }
+            Code::E0602 => {
+                "\
+`@align` pads up to the next multiple of its boundary, which has to be a
+whole number above zero. The optional fill byte after it has to fit in a
+byte, 0 to 0xff.
+
+```
+@align 0                // nothing is a multiple of 0
+@align 4, 0x100         // too big for a byte
+@align 4, \"nop\"         // a string
+```"
+            }
+            Code::E0603 => {
+                "\
+Two sections take some of the same addresses, so they can't both go in a
+flat image. Sections start at 0 unless an `@org` moves them.
+
+```
+.text
+    nop
+.data
+    .byte 1             // also at 0
+```
+
+Give the later section an `@org` past the end of the earlier one, or
+assemble to an object and let the linker place them."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
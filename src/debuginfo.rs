@@ -0,0 +1,139 @@
+//! Debug info: the source line behind each address range of a layout, so
+//! a debugger or emulator can show what the PC is on.
+//!
+//! [`DebugInfo::to_text`] writes one range a line, in address order within
+//! each section:
+//!
+//! ```text
+//! text 0x0000..0x0002 main.asm:4:5
+//! text 0x0002..0x0004 main.asm:9:1 macro main.asm:2:5
+//! text 0x0004..0x0005 main.asm:12:9 iteration 3
+//! ```
+//!
+//! A statement a macro call produced points at the call, as written
+//! outside any macro, with the line in the macro's body after `macro`. One
+//! a loop produced points at its line in the loop, with the iteration it
+//! came from after `iteration`, counting from 1; nested loops give one
+//! number each, the outermost first, like `iteration 2,1`. Labels and
+//! anything else that takes no space have no range.
+
+use crate::diagnostic::ExpansionKind;
+use crate::layout::Layout;
+use crate::source::{SourceManager, Span};
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+/// A place in a source file, lines and columns counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    fn of(span: &Span, sources: &SourceManager) -> Self {
+        let location = sources.location(span);
+        Self {
+            file: location.name.to_string(),
+            line: location.line,
+            col: location.col,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+/// Where the bytes at `range` of `section` came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEntry {
+    pub section: String,
+    pub range: Range<u64>,
+    /// The statement, or the outermost macro call it came from.
+    pub position: Position,
+    /// The statement in the macro's body, if a macro call produced it.
+    pub in_macro: Option<Position>,
+    /// Which iteration of each loop around it produced it, outermost
+    /// first.
+    pub iterations: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// Section by section, in address order within each.
+    pub entries: Vec<LineEntry>,
+}
+
+impl DebugInfo {
+    /// The ranges of every item of `layout` that takes space. Spans are
+    /// looked up in `sources`.
+    pub fn new(layout: &Layout, sources: &SourceManager) -> Self {
+        let mut entries = Vec::new();
+        for section in &layout.sections {
+            for ((address, stmt), info) in section.items.iter().zip(&section.info) {
+                if info.size == 0 {
+                    continue;
+                }
+                let frames: Vec<_> = info.origin.iter().flat_map(|e| e.frames()).collect();
+                let call = frames
+                    .iter()
+                    .rev()
+                    .find(|frame| matches!(frame.kind, ExpansionKind::Macro { .. }));
+                let iterations = frames
+                    .iter()
+                    .rev()
+                    .filter_map(|frame| match frame.kind {
+                        ExpansionKind::Loop { iteration, .. } => Some(iteration),
+                        ExpansionKind::Macro { .. } => None,
+                    })
+                    .collect();
+                let own = Position::of(&stmt.span, sources);
+                let (position, in_macro) = match call {
+                    Some(call) => (Position::of(&call.call_site, sources), Some(own)),
+                    None => (own, None),
+                };
+                entries.push(LineEntry {
+                    section: section.name.clone(),
+                    range: *address..address + info.size,
+                    position,
+                    in_macro,
+                    iterations,
+                });
+            }
+        }
+        Self { entries }
+    }
+
+    /// The entry whose range holds `address`, the first if sections
+    /// overlap.
+    pub fn at(&self, address: u64) -> Option<&LineEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.range.contains(&address))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            let _ = write!(
+                out,
+                "{} {:#06x}..{:#06x} {}",
+                entry.section, entry.range.start, entry.range.end, entry.position
+            );
+            if let Some(in_macro) = &entry.in_macro {
+                let _ = write!(out, " macro {}", in_macro);
+            }
+            if !entry.iterations.is_empty() {
+                let iterations: Vec<String> =
+                    entry.iterations.iter().map(usize::to_string).collect();
+                let _ = write!(out, " iteration {}", iterations.join(","));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
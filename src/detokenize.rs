@@ -0,0 +1,66 @@
+use crate::parser::{LexOptions, Token, lex};
+use crate::source::FileId;
+use crate::tokens::TokenKind;
+
+/// How many tokens before the next one can lex differently once it's
+/// glued on: three, for `a##b`.
+const GLUED: usize = 3;
+
+/// Turns a token stream back into source text.
+///
+/// Adjacent tokens are separated by a single space only when gluing them
+/// together would lex differently (`0` + `xFF`, two idents, `:` + `:`,
+/// `%` + an ident, which makes a register), taking the tokens before as
+/// well, so `0` + `.` + `0` doesn't become a float, nor `a` + `#` + `#` +
+/// `b` a pasted name.
+/// Whitespace tokens are emitted verbatim and suppress the inserted spacing,
+/// and so is a token's [`leading`](Token::leading) trivia: tokens lexed with
+/// [`LexOptions::trivia`] come back as the source they were lexed from, up
+/// to the stream's trailing trivia and any unrecognised input.
+pub fn detokenize(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    // The last tokens written with nothing between them, at most
+    // `GLUED` of them.
+    let mut glued: Vec<&Token> = Vec::with_capacity(GLUED + 1);
+
+    for tok in tokens {
+        if tok.kind == TokenKind::Whitespace {
+            out.push_str(&tok.text);
+            glued.clear();
+            continue;
+        }
+
+        if !tok.leading.is_empty() {
+            tok.leading.iter().for_each(|t| out.push_str(&t.text));
+            glued.clear();
+        }
+
+        if !glued.is_empty() && would_merge(&glued, tok) {
+            out.push(' ');
+            glued.clear();
+        }
+
+        out.push_str(&tok.text);
+        glued.push(tok);
+        if glued.len() > GLUED {
+            glued.remove(0);
+        }
+    }
+
+    out
+}
+
+/// Whether `next` written right after `glued` lexes as anything but the
+/// same tokens.
+fn would_merge(glued: &[&Token], next: &Token) -> bool {
+    let joined: String = glued
+        .iter()
+        .chain([&next])
+        .map(|t| t.text.as_str())
+        .collect();
+    let mut lexed = lex(&joined, 0, FileId::default(), LexOptions::default());
+    let same = glued.iter().chain([&next]).all(
+        |want| matches!(lexed.next(), Some(Ok(t)) if t.kind == want.kind && t.text == want.text),
+    );
+    !same || lexed.next().is_some()
+}
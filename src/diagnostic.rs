@@ -0,0 +1,173 @@
+use crate::codes::Code;
+use crate::source::{SourceManager, Span};
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// How many expansion frames are rendered before the rest are summarised.
+pub const MAX_EXPANSION_NOTES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Why a statement exists: which macro call or loop iteration produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpansionKind {
+    Macro { name: String },
+    /// `keyword` is the loop's, `for!` or `while!`; `iteration` counts
+    /// from 1.
+    Loop {
+        keyword: &'static str,
+        iteration: usize,
+    },
+}
+
+/// One link in the chain from an expanded statement back to the source the
+/// user actually wrote. `parent` is the expansion that produced `call_site`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    pub kind: ExpansionKind,
+    pub call_site: Span,
+    pub parent: Option<Rc<Expansion>>,
+}
+
+impl Expansion {
+    pub fn new(kind: ExpansionKind, call_site: Span, parent: Option<Rc<Expansion>>) -> Rc<Self> {
+        Rc::new(Self {
+            kind,
+            call_site,
+            parent,
+        })
+    }
+
+    /// Innermost frame first.
+    pub fn frames(&self) -> impl Iterator<Item = &Expansion> {
+        std::iter::successors(Some(self), |e| e.parent.as_deref())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<Code>,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    pub expansion: Option<Rc<Expansion>>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            span: None,
+            notes: Vec::new(),
+            expansion: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Adds a "did you mean" note if any of `candidates` looks like what
+    /// `name` was meant to be.
+    pub fn with_suggestions<'a>(
+        self,
+        name: &str,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        match crate::suggest::did_you_mean(name, candidates) {
+            Some(note) => self.with_note(note),
+            None => self,
+        }
+    }
+
+    pub fn with_expansion(mut self, expansion: Option<Rc<Expansion>>) -> Self {
+        self.expansion = expansion;
+        self
+    }
+
+    /// Renders the diagnostic with file:line:col locations:
+    ///
+    /// ```text
+    /// error[E0307]: undefined symbol `SZIE`
+    ///   --> main.asm:14:9
+    ///   = note: did you mean `SIZE`?
+    ///   = note: expanded from macro `add2` called at main.asm:12:5
+    ///   = note: in iteration 3 of for! at main.asm:10:1
+    /// ```
+    pub fn render(&self, sources: &SourceManager) -> String {
+        let mut out = String::new();
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = match self.code {
+            Some(code) => writeln!(out, "{}[{}]: {}", level, code, self.message),
+            None => writeln!(out, "{}: {}", level, self.message),
+        };
+
+        if let Some(span) = &self.span {
+            let _ = writeln!(out, "  --> {}", sources.location(span));
+        }
+        for note in &self.notes {
+            let _ = writeln!(out, "  = note: {}", note);
+        }
+
+        if let Some(expansion) = &self.expansion {
+            let frames: Vec<&Expansion> = expansion.frames().collect();
+            for frame in frames.iter().take(MAX_EXPANSION_NOTES) {
+                let at = sources.location(&frame.call_site);
+                let _ = match &frame.kind {
+                    ExpansionKind::Macro { name } => {
+                        writeln!(
+                            out,
+                            "  = note: expanded from macro `{}` called at {}",
+                            name, at
+                        )
+                    }
+                    ExpansionKind::Loop { keyword, iteration } => {
+                        writeln!(
+                            out,
+                            "  = note: in iteration {} of {} at {}",
+                            iteration, keyword, at
+                        )
+                    }
+                };
+            }
+            if frames.len() > MAX_EXPANSION_NOTES {
+                let _ = writeln!(
+                    out,
+                    "  = note: ... and {} more",
+                    frames.len() - MAX_EXPANSION_NOTES
+                );
+            }
+        }
+
+        out
+    }
+}
@@ -0,0 +1,465 @@
+use crate::parser::{IfBranch, Statement, StatementKind};
+use std::fmt;
+
+/// One structural difference between two statement trees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Tree path, e.g. `stmt[3].ForLoop.body[1].Instruction.args[0]`.
+    pub path: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.path, self.left, self.right)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Also report statements, and operands and data values, whose spans
+    /// differ.
+    pub spans: bool,
+}
+
+/// Lists every structural difference between `a` and `b`, ignoring spans.
+pub fn diff(a: &[Statement], b: &[Statement]) -> Vec<Difference> {
+    diff_with(a, b, &DiffOptions::default())
+}
+
+pub fn diff_with(a: &[Statement], b: &[Statement], opts: &DiffOptions) -> Vec<Difference> {
+    let mut out = Vec::new();
+    diff_list(&mut out, opts, "stmt", a, b);
+    out
+}
+
+/// Asserts two statement trees are equal, printing the structural diff on failure.
+#[macro_export]
+macro_rules! assert_ast_eq {
+    ($left:expr, $right:expr $(,)?) => {{
+        let diffs = $crate::diff::diff(&$left, &$right);
+        if !diffs.is_empty() {
+            let lines: Vec<String> = diffs.iter().map(|d| format!("  {}", d)).collect();
+            panic!("ASTs differ:\n{}", lines.join("\n"));
+        }
+    }};
+}
+
+fn push(out: &mut Vec<Difference>, path: String, left: impl fmt::Debug, right: impl fmt::Debug) {
+    out.push(Difference {
+        path,
+        left: format!("{:?}", left),
+        right: format!("{:?}", right),
+    });
+}
+
+fn diff_list(
+    out: &mut Vec<Difference>,
+    opts: &DiffOptions,
+    path: &str,
+    a: &[Statement],
+    b: &[Statement],
+) {
+    for i in 0..a.len().max(b.len()) {
+        let here = format!("{}[{}]", path, i);
+        match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => diff_stmt(out, opts, &here, x, y),
+            (Some(x), None) => out.push(Difference {
+                path: here,
+                left: variant_name(x).to_string(),
+                right: "<missing>".to_string(),
+            }),
+            (None, Some(y)) => out.push(Difference {
+                path: here,
+                left: "<missing>".to_string(),
+                right: variant_name(y).to_string(),
+            }),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn diff_branches(
+    out: &mut Vec<Difference>,
+    opts: &DiffOptions,
+    path: &str,
+    a: &[IfBranch],
+    b: &[IfBranch],
+) {
+    for i in 0..a.len().max(b.len()) {
+        let here = format!("{}[{}]", path, i);
+        match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => {
+                diff_field(out, &here, "cond", &x.cond, &y.cond);
+                diff_list(out, opts, &format!("{}.body", here), &x.body, &y.body);
+            }
+            (x, y) => out.push(Difference {
+                path: here,
+                left: x.map_or("<missing>", |_| "IfBranch").to_string(),
+                right: y.map_or("<missing>", |_| "IfBranch").to_string(),
+            }),
+        }
+    }
+}
+
+fn diff_values<T: PartialEq + fmt::Debug>(
+    out: &mut Vec<Difference>,
+    path: &str,
+    a: &[T],
+    b: &[T],
+) {
+    for i in 0..a.len().max(b.len()) {
+        let here = format!("{}[{}]", path, i);
+        match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) if x == y => {}
+            (x, y) => out.push(Difference {
+                path: here,
+                left: x.map_or("<missing>".to_string(), |s| format!("{:?}", s)),
+                right: y.map_or("<missing>".to_string(), |s| format!("{:?}", s)),
+            }),
+        }
+    }
+}
+
+fn diff_field<T: PartialEq + fmt::Debug>(
+    out: &mut Vec<Difference>,
+    path: &str,
+    field: &str,
+    a: &T,
+    b: &T,
+) {
+    if a != b {
+        push(out, format!("{}.{}", path, field), a, b);
+    }
+}
+
+fn diff_stmt(
+    out: &mut Vec<Difference>,
+    opts: &DiffOptions,
+    path: &str,
+    a: &Statement,
+    b: &Statement,
+) {
+    let name = variant_name(a);
+    if name != variant_name(b) {
+        out.push(Difference {
+            path: path.to_string(),
+            left: name.to_string(),
+            right: variant_name(b).to_string(),
+        });
+        return;
+    }
+    if opts.spans && a.span != b.span {
+        push(out, format!("{}.span", path), &a.span, &b.span);
+    }
+    let path = format!("{}.{}", path, name);
+
+    match (&a.kind, &b.kind) {
+        (
+            StatementKind::VarAssign { name: n1, expr: e1 },
+            StatementKind::VarAssign { name: n2, expr: e2 },
+        )
+        | (
+            StatementKind::ConstAssign { name: n1, expr: e1 },
+            StatementKind::ConstAssign { name: n2, expr: e2 },
+        )
+        | (
+            StatementKind::Equ { name: n1, expr: e1 },
+            StatementKind::Equ { name: n2, expr: e2 },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_field(out, &path, "expr", e1, e2);
+        }
+        (
+            StatementKind::VarUpdate {
+                name: n1,
+                op: o1,
+                expr: e1,
+            },
+            StatementKind::VarUpdate {
+                name: n2,
+                op: o2,
+                expr: e2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_field(out, &path, "op", o1, o2);
+            diff_field(out, &path, "expr", e1, e2);
+        }
+        (
+            StatementKind::Label {
+                name: n1,
+                visibility: v1,
+            },
+            StatementKind::Label {
+                name: n2,
+                visibility: v2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_field(out, &path, "visibility", v1, v2);
+        }
+        (StatementKind::Include(x), StatementKind::Include(y))
+        | (StatementKind::Section(x), StatementKind::Section(y)) => {
+            if x != y {
+                push(out, path, x, y);
+            }
+        }
+        (StatementKind::Org(x), StatementKind::Org(y)) => {
+            if x != y {
+                push(out, path, x, y);
+            }
+        }
+        (
+            StatementKind::Align {
+                boundary: b1,
+                fill: f1,
+            },
+            StatementKind::Align {
+                boundary: b2,
+                fill: f2,
+            },
+        ) => {
+            diff_field(out, &path, "boundary", b1, b2);
+            diff_field(out, &path, "fill", f1, f2);
+        }
+        (
+            StatementKind::Instruction {
+                name: n1,
+                args: a1,
+                spans: s1,
+            },
+            StatementKind::Instruction {
+                name: n2,
+                args: a2,
+                spans: s2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.args", path), a1, a2);
+            if opts.spans {
+                diff_values(out, &format!("{}.spans", path), s1, s2);
+            }
+        }
+        (
+            StatementKind::Directive { name: n1, args: a1 },
+            StatementKind::Directive { name: n2, args: a2 },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.args", path), a1, a2);
+        }
+        (
+            StatementKind::Data {
+                width: w1,
+                values: v1,
+                spans: s1,
+            },
+            StatementKind::Data {
+                width: w2,
+                values: v2,
+                spans: s2,
+            },
+        ) => {
+            diff_field(out, &path, "width", w1, w2);
+            diff_values(out, &format!("{}.values", path), v1, v2);
+            if opts.spans {
+                diff_values(out, &format!("{}.spans", path), s1, s2);
+            }
+        }
+        (
+            StatementKind::StringData {
+                encoding: e1,
+                nul: z1,
+                literal: l1,
+            },
+            StatementKind::StringData {
+                encoding: e2,
+                nul: z2,
+                literal: l2,
+            },
+        ) => {
+            diff_field(out, &path, "encoding", e1, e2);
+            diff_field(out, &path, "nul", z1, z2);
+            diff_field(out, &path, "literal", l1, l2);
+        }
+        (
+            StatementKind::MacroCall { name: n1, args: a1 },
+            StatementKind::MacroCall { name: n2, args: a2 },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.args", path), a1, a2);
+        }
+        (
+            StatementKind::MacroDef {
+                name: n1,
+                params: p1,
+                defaults: d1,
+                body: b1,
+            },
+            StatementKind::MacroDef {
+                name: n2,
+                params: p2,
+                defaults: d2,
+                body: b2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.params", path), p1, p2);
+            diff_values(out, &format!("{}.defaults", path), d1, d2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (
+            StatementKind::ForLoop {
+                var: v1,
+                start: s1,
+                cmp: c1,
+                end: e1,
+                op: o1,
+                step: t1,
+                body: b1,
+            },
+            StatementKind::ForLoop {
+                var: v2,
+                start: s2,
+                cmp: c2,
+                end: e2,
+                op: o2,
+                step: t2,
+                body: b2,
+            },
+        ) => {
+            diff_field(out, &path, "var", v1, v2);
+            diff_field(out, &path, "start", s1, s2);
+            diff_field(out, &path, "cmp", c1, c2);
+            diff_field(out, &path, "end", e1, e2);
+            diff_field(out, &path, "op", o1, o2);
+            diff_field(out, &path, "step", t1, t2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (
+            StatementKind::WhileLoop { cond: c1, body: b1 },
+            StatementKind::WhileLoop { cond: c2, body: b2 },
+        ) => {
+            diff_field(out, &path, "cond", c1, c2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (
+            StatementKind::Repeat { count: c1, body: b1 },
+            StatementKind::Repeat { count: c2, body: b2 },
+        ) => {
+            diff_field(out, &path, "count", c1, c2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (StatementKind::Break, StatementKind::Break)
+        | (StatementKind::Continue, StatementKind::Continue) => {}
+        (
+            StatementKind::If {
+                branches: r1,
+                otherwise: o1,
+            },
+            StatementKind::If {
+                branches: r2,
+                otherwise: o2,
+            },
+        ) => {
+            diff_branches(out, opts, &format!("{}.branches", path), r1, r2);
+            diff_list(out, opts, &format!("{}.otherwise", path), o1, o2);
+        }
+        (
+            StatementKind::Module { name: n1, body: b1 },
+            StatementKind::Module { name: n2, body: b2 },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (
+            StatementKind::Foreach {
+                param: p1,
+                body: b1,
+            },
+            StatementKind::Foreach {
+                param: p2,
+                body: b2,
+            },
+        ) => {
+            diff_field(out, &path, "param", p1, p2);
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        (
+            StatementKind::Struct {
+                name: n1,
+                fields: f1,
+            },
+            StatementKind::Struct {
+                name: n2,
+                fields: f2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.fields", path), f1, f2);
+        }
+        (
+            StatementKind::StructData {
+                name: n1,
+                fields: f1,
+            },
+            StatementKind::StructData {
+                name: n2,
+                fields: f2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.fields", path), f1, f2);
+        }
+        (
+            StatementKind::Enum {
+                name: n1,
+                variants: v1,
+            },
+            StatementKind::Enum {
+                name: n2,
+                variants: v2,
+            },
+        ) => {
+            diff_field(out, &path, "name", n1, n2);
+            diff_values(out, &format!("{}.variants", path), v1, v2);
+        }
+        (StatementKind::Block(b1), StatementKind::Block(b2)) => {
+            diff_list(out, opts, &format!("{}.body", path), b1, b2);
+        }
+        _ => unreachable!("variant names already matched"),
+    }
+}
+
+fn variant_name(stmt: &Statement) -> &'static str {
+    match &stmt.kind {
+        StatementKind::VarAssign { .. } => "VarAssign",
+        StatementKind::VarUpdate { .. } => "VarUpdate",
+        StatementKind::ConstAssign { .. } => "ConstAssign",
+        StatementKind::Equ { .. } => "Equ",
+        StatementKind::Label { .. } => "Label",
+        StatementKind::Instruction { .. } => "Instruction",
+        StatementKind::Directive { .. } => "Directive",
+        StatementKind::Org(_) => "Org",
+        StatementKind::Align { .. } => "Align",
+        StatementKind::Include(_) => "Include",
+        StatementKind::Data { .. } => "Data",
+        StatementKind::Section(_) => "Section",
+        StatementKind::StringData { .. } => "StringData",
+        StatementKind::MacroDef { .. } => "MacroDef",
+        StatementKind::MacroCall { .. } => "MacroCall",
+        StatementKind::ForLoop { .. } => "ForLoop",
+        StatementKind::WhileLoop { .. } => "WhileLoop",
+        StatementKind::Repeat { .. } => "Repeat",
+        StatementKind::Foreach { .. } => "Foreach",
+        StatementKind::Break => "Break",
+        StatementKind::Continue => "Continue",
+        StatementKind::If { .. } => "If",
+        StatementKind::Module { .. } => "Module",
+        StatementKind::Struct { .. } => "Struct",
+        StatementKind::StructData { .. } => "StructData",
+        StatementKind::Enum { .. } => "Enum",
+        StatementKind::Block(_) => "Block",
+    }
+}
@@ -0,0 +1,360 @@
+//! The `@` directives chasm knows, and what arguments each one takes.
+//!
+//! Any `@word` parses, with whatever arguments follow it, so a typo like
+//! `@defin SIZE 32` would otherwise be dropped without a word. [`check`]
+//! looks every directive up in a [`Directives`] registry: one it doesn't
+//! list is an error, with a suggestion if a known name is close, and so
+//! are the wrong number of arguments or an argument of the wrong kind:
+//!
+//! ```text
+//! @define 32 SIZE         // a name has to come first
+//! @size LEN, start        // the end label is missing
+//! ```
+//!
+//! Directives meant for other tools are registered next to the built-in
+//! ones, in code with [`Directives::register`] or on the command line with
+//! `--directive`, written the way [`DirectiveSpec`]'s `FromStr` reads them:
+//! `message(string)` takes one string, `table(number...)` one number or
+//! more, and a bare `message` anything at all.
+//!
+//! Inside a `macro_rules!` body, an argument naming one of the macro's
+//! parameters could become anything once it's called, so it passes as
+//! every kind.
+
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::macros::{Arity, param_name};
+use crate::parser::{Statement, StatementKind};
+use crate::walk::bodies;
+use std::fmt;
+use std::str::FromStr;
+
+/// What a directive argument has to look like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// A symbol, label, lint or register name.
+    Name,
+    /// An integer or float literal, or `$`.
+    Number,
+    /// A string literal, raw ones included.
+    Str,
+    Any,
+}
+
+impl ArgKind {
+    pub const ALL: &'static [ArgKind] =
+        &[ArgKind::Name, ArgKind::Number, ArgKind::Str, ArgKind::Any];
+
+    /// The name a spec string uses: `name`, `number`, `string`, `any`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ArgKind::Name => "name",
+            ArgKind::Number => "number",
+            ArgKind::Str => "string",
+            ArgKind::Any => "any",
+        }
+    }
+
+    /// `a name`, `a number`, for messages.
+    fn described(self) -> &'static str {
+        match self {
+            ArgKind::Name => "a name",
+            ArgKind::Number => "a number",
+            ArgKind::Str => "a string",
+            ArgKind::Any => "anything",
+        }
+    }
+
+    /// Whether `arg`, as the parser keeps it, is of this kind.
+    pub fn accepts(self, arg: &str) -> bool {
+        let is_str = arg.starts_with('"') || arg.starts_with("r\"") || arg.starts_with("r#");
+        match self {
+            ArgKind::Name => {
+                !is_str && arg.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '%')
+            }
+            ArgKind::Number => {
+                arg == "$"
+                    || arg == "$$"
+                    || arg.parse::<i64>().is_ok()
+                    || arg.parse::<f64>().is_ok()
+            }
+            ArgKind::Str => is_str,
+            ArgKind::Any => true,
+        }
+    }
+}
+
+/// One directive: its name and the arguments it takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveSpec {
+    /// The name, without its `@`.
+    pub name: String,
+    pub arity: Arity,
+    /// The kind of each argument. Those past the end take the last kind,
+    /// and none at all means anything goes.
+    pub kinds: Vec<ArgKind>,
+    /// The arguments as a note on a mistake shows them, like
+    /// `NAME, start_label, end_label`.
+    pub usage: String,
+}
+
+impl DirectiveSpec {
+    fn new(name: &str, arity: Arity, kinds: &[ArgKind], usage: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            kinds: kinds.to_vec(),
+            usage: usage.to_string(),
+        }
+    }
+
+    /// The kind the argument at `index` has to be.
+    pub fn kind(&self, index: usize) -> ArgKind {
+        match self.kinds.get(index).or(self.kinds.last()) {
+            Some(&kind) => kind,
+            None => ArgKind::Any,
+        }
+    }
+}
+
+/// Reads `name`, `name(kind, kind)` or `name(kind, kind...)`, where each
+/// kind is one of [`ArgKind::as_str`] and `...` lets the last one repeat.
+impl FromStr for DirectiveSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (name, kinds) = match s.split_once('(') {
+            Some((name, rest)) => match rest.strip_suffix(')') {
+                Some(kinds) => (name.trim(), Some(kinds)),
+                None => return Err(format!("`{}` is missing its closing `)`", s)),
+            },
+            None => (s, None),
+        };
+        let name = name.strip_prefix('@').unwrap_or(name);
+        if !ArgKind::Name.accepts(name) || name.contains(|c: char| c.is_whitespace()) {
+            return Err(format!("`{}` isn't a directive name", name));
+        }
+        let Some(kinds) = kinds else {
+            return Ok(Self::new(name, Arity { min: 0, max: None }, &[], ""));
+        };
+
+        let mut parsed = Vec::new();
+        let mut variadic = false;
+        let list: Vec<&str> = kinds.split(',').map(str::trim).collect();
+        for (i, kind) in list.iter().enumerate() {
+            if kind.is_empty() && list.len() == 1 {
+                break;
+            }
+            let (kind, repeats) = match kind.strip_suffix("...") {
+                Some(kind) if i + 1 == list.len() => (kind.trim_end(), true),
+                Some(_) => return Err("only the last argument can repeat".to_string()),
+                None => (*kind, false),
+            };
+            let Some(&kind) = ArgKind::ALL.iter().find(|k| k.as_str() == kind) else {
+                return Err(format!(
+                    "unknown argument kind `{}`; expected `name`, `number`, `string` or `any`",
+                    kind
+                ));
+            };
+            parsed.push(kind);
+            variadic = repeats;
+        }
+        let arity = Arity {
+            min: parsed.len(),
+            max: (!variadic).then_some(parsed.len()),
+        };
+        let usage: Vec<&str> = parsed.iter().map(|kind| kind.as_str()).collect();
+        let usage = match variadic {
+            true => format!("{}...", usage.join(", ")),
+            false => usage.join(", "),
+        };
+        Ok(Self::new(name, arity, &parsed, &usage))
+    }
+}
+
+impl fmt::Display for DirectiveSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.usage.as_str() {
+            "" => write!(f, "@{}", self.name),
+            usage => write!(f, "@{} {}", self.name, usage),
+        }
+    }
+}
+
+/// The directives a program may use, by name.
+#[derive(Debug, Clone)]
+pub struct Directives {
+    specs: Vec<DirectiveSpec>,
+}
+
+impl Default for Directives {
+    /// The directives chasm itself gives a meaning to. `@org`, `@align`
+    /// and `@foreach` parse as statements of their own; they're listed so
+    /// a typo of any gets a suggestion.
+    fn default() -> Self {
+        use ArgKind::{Any, Name};
+        let lint =
+            |name| DirectiveSpec::new(name, Arity { min: 1, max: None }, &[Name], "lint, ...");
+        Self {
+            specs: vec![
+                DirectiveSpec::new(
+                    "define",
+                    Arity {
+                        min: 1,
+                        max: Some(2),
+                    },
+                    &[Name, Any],
+                    "NAME, value",
+                ),
+                DirectiveSpec::new(
+                    "size",
+                    Arity::exactly(3),
+                    &[Name],
+                    "NAME, start_label, end_label",
+                ),
+                lint("allow"),
+                lint("warn"),
+                lint("deny"),
+                DirectiveSpec::new("org", Arity::exactly(1), &[Any], "address"),
+                DirectiveSpec::new(
+                    "align",
+                    Arity {
+                        min: 1,
+                        max: Some(2),
+                    },
+                    &[Any],
+                    "boundary, fill",
+                ),
+                DirectiveSpec::new("foreach", Arity::exactly(1), &[Name], "param { ... }"),
+                DirectiveSpec::new(
+                    "extern",
+                    Arity { min: 1, max: None },
+                    &[Name],
+                    "NAME, ...",
+                ),
+            ],
+        }
+    }
+}
+
+impl Directives {
+    /// The built-in directives, to register more on.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `spec`, in place of any directive of the same name.
+    pub fn register(&mut self, spec: DirectiveSpec) {
+        match self.specs.iter_mut().find(|known| known.name == spec.name) {
+            Some(known) => *known = spec,
+            None => self.specs.push(spec),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DirectiveSpec> {
+        self.specs.iter().find(|spec| spec.name == name)
+    }
+
+    /// Every registered directive, built-in ones first.
+    pub fn iter(&self) -> impl Iterator<Item = &DirectiveSpec> {
+        self.specs.iter()
+    }
+
+    /// Reports directives in `ast` that aren't registered or don't get the
+    /// arguments they take, nested ones included.
+    pub fn check(&self, ast: &[Statement]) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+        self.check_list(ast, &[], &mut diags);
+        diags
+    }
+
+    /// `params` are those of the macro whose body `stmts` is in, if any.
+    fn check_list(&self, stmts: &[Statement], params: &[String], diags: &mut Vec<Diagnostic>) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StatementKind::Directive { name, args } => {
+                    diags.extend(self.check_directive(stmt, name, args, params));
+                }
+                StatementKind::MacroDef { params, body, .. } => {
+                    self.check_list(body, params, diags);
+                }
+                _ => {
+                    for body in bodies(stmt) {
+                        self.check_list(body, params, diags);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_directive(
+        &self,
+        stmt: &Statement,
+        name: &str,
+        args: &[String],
+        params: &[String],
+    ) -> Option<Diagnostic> {
+        match self.get(name) {
+            Some(spec) => spec.check(stmt, args, params),
+            None => Some(
+                Diagnostic::error(format!("unknown directive `@{}`", name))
+                    .with_code(Code::E0112)
+                    .with_span(stmt.span.clone())
+                    .with_suggestions(name, self.specs.iter().map(|spec| spec.name.as_str())),
+            ),
+        }
+    }
+
+    /// The arguments of `stmt` checked, if it's a directive this registry
+    /// knows. One it doesn't is [`check`](Self::check)'s to report, where
+    /// it's written.
+    pub fn check_arguments(&self, stmt: &Statement) -> Option<Diagnostic> {
+        let StatementKind::Directive { name, args } = &stmt.kind else {
+            return None;
+        };
+        self.get(name)?.check(stmt, args, &[])
+    }
+}
+
+impl DirectiveSpec {
+    /// An error if `args`, those of the directive `stmt`, are too many,
+    /// too few or the wrong kind. Any of `params` passes as every kind.
+    fn check(&self, stmt: &Statement, args: &[String], params: &[String]) -> Option<Diagnostic> {
+        let usage = format!("write `{}`", self);
+        if !self.arity.accepts(args.len()) {
+            return Some(
+                Diagnostic::error(format!(
+                    "`@{}` takes {}, got {}",
+                    self.name,
+                    self.arity.arguments(),
+                    args.len()
+                ))
+                .with_code(Code::E0113)
+                .with_span(stmt.span.clone())
+                .with_note(usage),
+            );
+        }
+        let (index, arg) = args.iter().enumerate().find(|(index, arg)| {
+            !params.iter().any(|param| param_name(param) == arg.as_str())
+                && !self.kind(*index).accepts(arg)
+        })?;
+        Some(
+            Diagnostic::error(format!(
+                "argument {} of `@{}` should be {}, not `{}`",
+                index + 1,
+                self.name,
+                self.kind(index).described(),
+                arg
+            ))
+            .with_code(Code::E0114)
+            .with_span(stmt.span.clone())
+            .with_note(usage),
+        )
+    }
+}
+
+/// [`Directives::check`] with only the built-in directives.
+pub fn check(ast: &[Statement]) -> Vec<Diagnostic> {
+    Directives::default().check(ast)
+}
@@ -0,0 +1,168 @@
+//! Computes constant expressions ([`Expr`]).
+//!
+//! Integers are 64-bit and never wrap: overflow is an error. An integer
+//! meeting a float in `+`, `-`, `*`, `/` or a comparison becomes a float;
+//! the other operators take integers only. Comparisons give 1 or 0.
+//! Strings are only ever builtin arguments, and arrays builtin arguments
+//! or indexed.
+
+use crate::builtins::{self, Value};
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::parser::{BinaryOp, Expr, Number, UnaryOp};
+use crate::source::Span;
+use crate::tokens::parse_string;
+use std::fmt;
+
+/// Evaluates `expr`, asking `lookup` for the value of each name. Errors
+/// point at `span`, the statement the expression is part of.
+pub fn eval(
+    expr: &Expr,
+    lookup: &dyn Fn(&str) -> Option<Value>,
+    span: &Span,
+) -> Result<Value, Diagnostic> {
+    let error = |(code, message): (Code, String)| {
+        Diagnostic::error(message)
+            .with_code(code)
+            .with_span(span.clone())
+    };
+    match expr {
+        Expr::Number(Number::Int(n)) => Ok(Value::Int(*n)),
+        Expr::Number(Number::Float(x)) => Ok(Value::Float(*x)),
+        Expr::Str(literal) => Ok(Value::Str(parse_string(literal))),
+        Expr::Symbol(name) => match lookup(name) {
+            Some(value) => Ok(value),
+            None => Err(error((Code::E0504, format!("`{}` has no value here", name)))),
+        },
+        Expr::Unary { op, expr } => unary(*op, eval(expr, lookup, span)?).map_err(error),
+        Expr::Binary { op, lhs, rhs } => {
+            let lhs = eval(lhs, lookup, span)?;
+            let rhs = eval(rhs, lookup, span)?;
+            binary(*op, lhs, rhs).map_err(error)
+        }
+        Expr::Call { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, lookup, span))
+                .collect::<Result<Vec<_>, _>>()?;
+            builtins::call(name, &args, span)
+        }
+        Expr::Array(items) => items
+            .iter()
+            .map(|item| eval(item, lookup, span))
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        Expr::Index { base, index } => {
+            let base = eval(base, lookup, span)?;
+            let index = eval(index, lookup, span)?;
+            item(base, index).map_err(error)
+        }
+    }
+}
+
+/// Item `index` of array `base`.
+fn item(base: Value, index: Value) -> Result<Value, Failure> {
+    let Value::Array(mut items) = base else {
+        return Err(wrong_kind("[]", &base));
+    };
+    let Value::Int(n) = index else {
+        return Err((Code::E0515, format!("an index must be an integer, not {}", index.kind())));
+    };
+    match usize::try_from(n).ok().filter(|&i| i < items.len()) {
+        Some(i) => Ok(items.swap_remove(i)),
+        None => Err((
+            Code::E0515,
+            format!("index {} is out of range for an array of {}", n, items.len()),
+        )),
+    }
+}
+
+type Failure = (Code, String);
+
+fn wrong_kind(op: impl fmt::Display, value: &Value) -> Failure {
+    (Code::E0507, format!("`{}` doesn't work on {}", op, value.kind()))
+}
+
+fn unary(op: UnaryOp, value: Value) -> Result<Value, Failure> {
+    match (op, &value) {
+        (UnaryOp::Neg, Value::Int(n)) => match n.checked_neg() {
+            Some(n) => Ok(Value::Int(n)),
+            None => Err((Code::E0506, format!("`-({})` overflows 64 bits", n))),
+        },
+        (UnaryOp::Neg, Value::Float(x)) => Ok(Value::Float(-x)),
+        (UnaryOp::BitNot, Value::Int(n)) => Ok(Value::Int(!n)),
+        (UnaryOp::Not, Value::Int(n)) => Ok(Value::Int(i64::from(*n == 0))),
+        _ => Err(wrong_kind(op, &value)),
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value) -> Result<Value, Failure> {
+    match (&lhs, &rhs) {
+        (Value::Int(x), Value::Int(y)) => int_binary(op, *x, *y).map(Value::Int),
+        (Value::Str(_) | Value::Array(_), _) => Err(wrong_kind(op, &lhs)),
+        (_, Value::Str(_) | Value::Array(_)) => Err(wrong_kind(op, &rhs)),
+        _ => {
+            let float = |v: &Value| match *v {
+                Value::Int(n) => n as f64,
+                Value::Float(x) => x,
+                Value::Str(_) | Value::Array(_) => {
+                    unreachable!("strings and arrays are rejected above")
+                }
+            };
+            let (x, y) = (float(&lhs), float(&rhs));
+            match op {
+                BinaryOp::Add => Ok(Value::Float(x + y)),
+                BinaryOp::Sub => Ok(Value::Float(x - y)),
+                BinaryOp::Mul => Ok(Value::Float(x * y)),
+                BinaryOp::Div if y == 0.0 => Err(divide_by_zero(&lhs, op, &rhs)),
+                BinaryOp::Div => Ok(Value::Float(x / y)),
+                BinaryOp::Lt => Ok(Value::Int(i64::from(x < y))),
+                BinaryOp::Le => Ok(Value::Int(i64::from(x <= y))),
+                BinaryOp::Gt => Ok(Value::Int(i64::from(x > y))),
+                BinaryOp::Ge => Ok(Value::Int(i64::from(x >= y))),
+                BinaryOp::Eq => Ok(Value::Int(i64::from(x == y))),
+                BinaryOp::Ne => Ok(Value::Int(i64::from(x != y))),
+                _ if matches!(lhs, Value::Float(_)) => Err(wrong_kind(op, &lhs)),
+                _ => Err(wrong_kind(op, &rhs)),
+            }
+        }
+    }
+}
+
+fn divide_by_zero(lhs: &Value, op: BinaryOp, rhs: &Value) -> Failure {
+    (Code::E0505, format!("`{} {} {}` divides by zero", lhs, op, rhs))
+}
+
+fn int_binary(op: BinaryOp, x: i64, y: i64) -> Result<i64, Failure> {
+    let result = match op {
+        BinaryOp::Add => x.checked_add(y),
+        BinaryOp::Sub => x.checked_sub(y),
+        BinaryOp::Mul => x.checked_mul(y),
+        BinaryOp::Div | BinaryOp::Rem if y == 0 => {
+            return Err(divide_by_zero(&Value::Int(x), op, &Value::Int(y)));
+        }
+        BinaryOp::Div => x.checked_div(y),
+        BinaryOp::Rem => x.checked_rem(y),
+        BinaryOp::Shl | BinaryOp::Shr => {
+            let Some(shift) = u32::try_from(y).ok().filter(|&s| s < 64) else {
+                return Err((Code::E0506, format!("can't shift {} by {} bits", x, y)));
+            };
+            // Bits shifted out are gone, as in any register.
+            Some(if op == BinaryOp::Shl {
+                x << shift
+            } else {
+                x >> shift
+            })
+        }
+        BinaryOp::And => Some(x & y),
+        BinaryOp::Or => Some(x | y),
+        BinaryOp::Xor => Some(x ^ y),
+        BinaryOp::Lt => Some(i64::from(x < y)),
+        BinaryOp::Le => Some(i64::from(x <= y)),
+        BinaryOp::Gt => Some(i64::from(x > y)),
+        BinaryOp::Ge => Some(i64::from(x >= y)),
+        BinaryOp::Eq => Some(i64::from(x == y)),
+        BinaryOp::Ne => Some(i64::from(x != y)),
+    };
+    result.ok_or_else(|| (Code::E0506, format!("`{} {} {}` overflows 64 bits", x, op, y)))
+}
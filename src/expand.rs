@@ -0,0 +1,1030 @@
+//! Assembly-time control flow: runs `for!`, `while!` and `repeat!` loops,
+//! picks the `if!` branch to keep, and puts the statements they produce in
+//! their place. Struct data like `Point { x: 1 }` comes out as one data
+//! statement per field.
+//!
+//! `var`s and `for!` counters are assembly-time variables. Each copy of a
+//! loop body has the values they held in that iteration written into its
+//! operands and data values, so a `while!` body doing `var i += 1` moves
+//! its condition and every later copy along.
+//!
+//! A macro call, `name!(args)` or written like an instruction, comes out
+//! as the macro's body with the arguments in place of its parameters (see
+//! [`instantiate`]), taken with the variables' values at the call. The
+//! body is expanded like any other statements, loops and calls inside it
+//! included, and each directive it ends up with is checked again with the
+//! arguments it got. Definitions stay where they are; their bodies only
+//! mean anything once called.
+//!
+//! A macro may call itself, directly or through others, as long as an
+//! `if!` stops it before [`ExpandOptions::macro_depth`] calls are nested:
+//!
+//! ```text
+//! macro_rules! countdown(n) {
+//!     .byte n
+//!     if!(n > 0) {
+//!         countdown!(n - 1)
+//!     }
+//! }
+//! ```
+//!
+//! Once a call nests deeper than that, or expanding takes more than
+//! [`ExpandOptions::max_expansions`] calls in all, nothing more is
+//! expanded.
+//!
+//! Errors found while expanding note the macro calls and loop iterations
+//! they're in, innermost first. [`expand_traced`] also says where each
+//! statement it returns came from, for passes that run after it.
+//!
+//! An item of a constant array, `TABLE[i]`, is written in as the item's
+//! own expression once `i` is known, and `.byte TABLE` stores every item,
+//! so arrays whose items use labels work in loops and data alike.
+//!
+//! `##` pastes a name together from pieces, each variable or constant
+//! among them written as its value, so a loop can define a label per
+//! iteration and a pasted `r##n` is a register:
+//!
+//! ```text
+//! for!(var i = 0; i < 8; i += 1) {
+//!     handler_##i:            // handler_0 to handler_7
+//!         jmp common
+//! }
+//! ```
+
+use crate::builtins::Value;
+use crate::codes::Code;
+use crate::diagnostic::{Diagnostic, Expansion, ExpansionKind};
+use crate::directives::Directives;
+use crate::eval::eval;
+use crate::isa::{RegisterPattern, Target};
+use crate::macros::{MacroTable, flatten_sum, forwards, instantiate};
+use crate::parser::{
+    BinaryOp, Expr, IfBranch, MemoryOperand, Number, Operand, Statement, StatementKind, StructField,
+};
+use crate::source::Span;
+use crate::symbols::{SymbolTable, SymbolValue, scope_modules};
+use crate::trace::{debug, phase};
+use crate::walk::{Visitor, walk_expr, walk_operand, walk_statement};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::rc::Rc;
+
+/// How many times one loop may run its body before it's taken to be
+/// endless.
+pub const MAX_ITERATIONS: usize = 65_536;
+
+/// How deep macro calls nest by default before the recursion is taken to
+/// be endless.
+pub const MAX_MACRO_DEPTH: usize = 64;
+
+/// How many macro calls expanding a program may take by default, nested
+/// or not.
+pub const MAX_EXPANSIONS: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandOptions {
+    /// How many macro calls may be expanding at once, each inside the
+    /// last.
+    pub macro_depth: usize,
+    /// How many macro calls may be expanded in all.
+    pub max_expansions: usize,
+    /// Which names pasted together with `##` are registers.
+    pub registers: RegisterPattern,
+}
+
+impl Default for ExpandOptions {
+    fn default() -> Self {
+        Self {
+            macro_depth: MAX_MACRO_DEPTH,
+            max_expansions: MAX_EXPANSIONS,
+            registers: Target::DEFAULT.registers,
+        }
+    }
+}
+
+/// Runs the loops and expands the macro calls in `ast`, returning the
+/// statements they expand to. Modules come out as blocks, their names in
+/// full (see [`scope_modules`]). Calls that match no definition are left
+/// out; [`macros::check`](crate::macros::check) reports them.
+///
+/// A loop stops after the first iteration that reports an error, rather
+/// than reporting the same error once per iteration. A `var` computed from
+/// a label has no value yet, so it's left as written.
+pub fn expand(ast: &[Statement]) -> (Vec<Statement>, Vec<Diagnostic>) {
+    expand_with(ast, &ExpandOptions::default())
+}
+
+pub fn expand_with(ast: &[Statement], opts: &ExpandOptions) -> (Vec<Statement>, Vec<Diagnostic>) {
+    let (out, errors, _) = expand_inner(ast, opts, false);
+    (out, errors)
+}
+
+/// [`expand_with`], also returning where each statement came from, so
+/// passes after expansion like
+/// [`layout_expanded`](crate::layout::layout_expanded) can say which
+/// macro call or loop iteration an error of theirs is in.
+pub fn expand_traced(
+    ast: &[Statement],
+    opts: &ExpandOptions,
+) -> (Vec<Statement>, Vec<Diagnostic>, Origins) {
+    let (out, errors, origins) = expand_inner(ast, opts, true);
+    (out, errors, origins.unwrap_or_default())
+}
+
+fn expand_inner(
+    ast: &[Statement],
+    opts: &ExpandOptions,
+    traced: bool,
+) -> (Vec<Statement>, Vec<Diagnostic>, Option<Origins>) {
+    phase!("expand");
+    let ast = &scope_modules(ast);
+    let table = SymbolTable::collect(ast);
+    let structs = crate::walk::iter_deep(ast)
+        .filter_map(|stmt| match &stmt.kind {
+            StatementKind::Struct { name, fields } => Some((name.as_str(), fields.as_slice())),
+            _ => None,
+        })
+        .collect();
+    let (macros, _) = MacroTable::collect(ast);
+    let mut expander = Expander {
+        table: &table,
+        structs: &structs,
+        macros: &macros,
+        directives: Directives::default(),
+        vars: HashMap::new(),
+        errors: Vec::new(),
+        frame: None,
+        loops: 0,
+        calls: 0,
+        max_calls: opts.macro_depth,
+        registers: opts.registers,
+        expansions: 0,
+        max_expansions: opts.max_expansions,
+        halted: false,
+        forwarding: HashSet::new(),
+        origins: traced.then(Origins::default),
+    };
+    let mut out = Vec::new();
+    expander.statements(ast, &mut out);
+    (out, expander.errors, expander.origins)
+}
+
+/// Which macro call or loop iteration, if any, each statement
+/// [`expand_traced`] returned came from.
+///
+/// Copies of a statement share its span, so they're told apart by order:
+/// they're recorded in the order [`iter_deep`](crate::walk::iter_deep)
+/// visits them, blocks aside, and an [`OriginCursor`] hands them out again
+/// as it's shown the statements in that order.
+#[derive(Debug, Clone, Default)]
+pub struct Origins {
+    frames: HashMap<Span, Vec<Option<Rc<Expansion>>>>,
+}
+
+impl Origins {
+    fn record(&mut self, stmt: &Statement, frame: Option<Rc<Expansion>>) {
+        self.frames.entry(stmt.span.clone()).or_default().push(frame);
+    }
+
+    pub fn cursor(&self) -> OriginCursor<'_> {
+        OriginCursor {
+            origins: self,
+            seen: HashMap::new(),
+        }
+    }
+}
+
+/// Walks [`Origins`] along with the statements they were recorded for.
+#[derive(Debug)]
+pub struct OriginCursor<'a> {
+    origins: &'a Origins,
+    /// How many statements with each span have been shown.
+    seen: HashMap<Span, usize>,
+}
+
+impl OriginCursor<'_> {
+    /// Where `stmt`, the statement after the last one shown, came from.
+    pub fn next(&mut self, stmt: &Statement) -> Option<Rc<Expansion>> {
+        let seen = self.seen.entry(stmt.span.clone()).or_default();
+        let frame = self.origins.frames.get(&stmt.span)?.get(*seen).cloned();
+        *seen += 1;
+        frame.flatten()
+    }
+}
+
+/// How a list of statements finished: at its end, or at a `break!` or
+/// `continue!` for the loop around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Done,
+    Break,
+    Continue,
+}
+
+struct Expander<'a> {
+    table: &'a SymbolTable,
+    /// Each struct's fields, by its full name.
+    structs: &'a HashMap<&'a str, &'a [StructField]>,
+    macros: &'a MacroTable<'a>,
+    /// What expanded directives are checked against.
+    directives: Directives,
+    vars: HashMap<String, Value>,
+    errors: Vec<Diagnostic>,
+    /// The loop iteration or macro call being expanded, if any.
+    frame: Option<Rc<Expansion>>,
+    /// How many loops the statements being expanded are in.
+    loops: usize,
+    /// How many macro calls the statements being expanded are in.
+    calls: usize,
+    max_calls: usize,
+    registers: RegisterPattern,
+    /// How many macro calls have been expanded, to number the next.
+    expansions: usize,
+    max_expansions: usize,
+    /// Set once a call nests too deep or goes over the budget. No call is
+    /// expanded after that: a macro calling itself twice would otherwise
+    /// hit the limit again for each of its 2^64 calls.
+    halted: bool,
+    /// Where each statement put out came from, if asked for.
+    origins: Option<Origins>,
+    /// Calls in macro bodies that pass a variadic parameter on, whose
+    /// argument count [`macros::check`](crate::macros::check) leaves to
+    /// be checked here.
+    forwarding: HashSet<Span>,
+}
+
+impl Expander<'_> {
+    fn statements(&mut self, ast: &[Statement], out: &mut Vec<Statement>) -> Flow {
+        for stmt in ast {
+            match &stmt.kind {
+                StatementKind::VarAssign { name, expr } => self.assign(name, expr, &stmt.span),
+                StatementKind::VarUpdate { name, op, expr } => {
+                    let expr = Expr::Binary {
+                        op: op.binary(),
+                        lhs: Box::new(Expr::Symbol(name.clone())),
+                        rhs: Box::new(expr.clone()),
+                    };
+                    self.assign(name, &expr, &stmt.span);
+                }
+                StatementKind::ForLoop { .. } => {
+                    self.for_loop(stmt, out);
+                    continue;
+                }
+                StatementKind::WhileLoop { cond, body } => {
+                    self.while_loop(stmt, cond, body, out);
+                    continue;
+                }
+                StatementKind::Repeat { count, body } => {
+                    self.repeat(stmt, count, body, out);
+                    continue;
+                }
+                StatementKind::StructData { name, fields } => {
+                    self.struct_data(stmt, name, fields, out);
+                    continue;
+                }
+                StatementKind::MacroCall { name, .. } => {
+                    let flow = self.call(stmt, name, out);
+                    if flow != Flow::Done {
+                        return flow;
+                    }
+                    continue;
+                }
+                StatementKind::Instruction { name, .. } if self.macros.is_macro(name) => {
+                    let flow = self.call(stmt, name, out);
+                    if flow != Flow::Done {
+                        return flow;
+                    }
+                    continue;
+                }
+                // A call has repeated those over its variadic parameter;
+                // `macros::check` reports any other.
+                StatementKind::Foreach { .. } => continue,
+                StatementKind::Break | StatementKind::Continue if self.loops == 0 => {
+                    let keyword = match stmt.kind {
+                        StatementKind::Break => "break!",
+                        _ => "continue!",
+                    };
+                    self.report(
+                        Diagnostic::error(format!("`{}` outside a loop", keyword))
+                            .with_code(Code::E0512)
+                            .with_span(stmt.span.clone()),
+                    );
+                    continue;
+                }
+                StatementKind::Break => return Flow::Break,
+                StatementKind::Continue => return Flow::Continue,
+                StatementKind::If {
+                    branches,
+                    otherwise,
+                } => {
+                    if let Some(body) = self.pick(stmt, branches, otherwise) {
+                        let flow = self.statements(body, out);
+                        if flow != Flow::Done {
+                            return flow;
+                        }
+                    }
+                    continue;
+                }
+                StatementKind::Block(body) => {
+                    let mut inner = Vec::new();
+                    let flow = self.statements(body, &mut inner);
+                    out.push(Statement {
+                        kind: StatementKind::Block(inner),
+                        span: stmt.span.clone(),
+                    });
+                    if flow != Flow::Done {
+                        return flow;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            let stmt = self.substitute(stmt);
+            self.check_pasted(&stmt);
+            // A directive from a macro body has its arguments only now.
+            if self.calls > 0
+                && let Some(diag) = self.directives.check_arguments(&stmt)
+            {
+                self.report(diag);
+            }
+            self.emit(stmt, out);
+        }
+        Flow::Done
+    }
+
+    fn assign(&mut self, name: &str, expr: &Expr, span: &Span) {
+        if self.waits(expr) {
+            self.vars.remove(name);
+            return;
+        }
+        match self.eval(expr, span) {
+            Ok(value) => {
+                self.vars.insert(name.to_string(), value);
+            }
+            Err(diag) => {
+                self.vars.remove(name);
+                self.report(diag);
+            }
+        }
+    }
+
+    /// Runs the `for!` loop `stmt`. Its start, end and step are computed
+    /// once, up front.
+    fn for_loop(&mut self, stmt: &Statement, out: &mut Vec<Statement>) {
+        let StatementKind::ForLoop {
+            var,
+            start,
+            cmp,
+            end,
+            op,
+            step,
+            body,
+        } = &stmt.kind
+        else {
+            unreachable!("only `for!` loops get here");
+        };
+        let bounds = [start, end, step].map(|expr| self.bound(expr, &stmt.span));
+        let [Some(start), Some(end), Some(step)] = bounds else {
+            return;
+        };
+
+        let outer = self.vars.remove(var);
+        let mut iteration = 0;
+        let mut i = start;
+        while cmp.holds(i, end) {
+            self.vars.insert(var.clone(), Value::Int(i));
+            if !self.iterate(stmt, "for!", &mut iteration, body, out) {
+                break;
+            }
+            let next = Expr::Binary {
+                op: op.binary(),
+                lhs: Box::new(Expr::Number(Number::Int(i))),
+                rhs: Box::new(Expr::Number(Number::Int(step))),
+            };
+            match self.eval(&next, &stmt.span) {
+                Ok(Value::Int(n)) => i = n,
+                Ok(_) => unreachable!("integers only combine into integers"),
+                Err(diag) => {
+                    self.report(diag);
+                    break;
+                }
+            }
+        }
+        match outer {
+            Some(value) => self.vars.insert(var.clone(), value),
+            None => self.vars.remove(var),
+        };
+    }
+
+    /// A `for!` start, end or step, which has to be an integer.
+    fn bound(&mut self, expr: &Expr, span: &Span) -> Option<i64> {
+        match self.eval(expr, span) {
+            Ok(Value::Int(n)) => Some(n),
+            Ok(value) => {
+                self.report(
+                    Diagnostic::error(format!(
+                        "the `for!` bound `{}` is {}, not an integer",
+                        expr,
+                        value.kind()
+                    ))
+                    .with_code(Code::E0511)
+                    .with_span(span.clone()),
+                );
+                None
+            }
+            Err(diag) => {
+                self.report(diag);
+                None
+            }
+        }
+    }
+
+    fn while_loop(
+        &mut self,
+        stmt: &Statement,
+        cond: &Expr,
+        body: &[Statement],
+        out: &mut Vec<Statement>,
+    ) {
+        let mut iteration = 0;
+        while self.condition("while!", cond, &stmt.span) == Some(true) {
+            if !self.iterate(stmt, "while!", &mut iteration, body, out) {
+                break;
+            }
+        }
+    }
+
+    fn repeat(
+        &mut self,
+        stmt: &Statement,
+        count: &Expr,
+        body: &[Statement],
+        out: &mut Vec<Statement>,
+    ) {
+        let times = match self.eval(count, &stmt.span) {
+            Ok(Value::Int(n)) if n >= 0 => n,
+            Ok(value) => {
+                self.report(
+                    Diagnostic::error(format!(
+                        "`repeat!` needs a count of 0 or more, not {}",
+                        value
+                    ))
+                    .with_code(Code::E0510)
+                    .with_span(stmt.span.clone()),
+                );
+                return;
+            }
+            Err(diag) => {
+                self.report(diag);
+                return;
+            }
+        };
+        let mut iteration = 0;
+        for _ in 0..times {
+            if !self.iterate(stmt, "repeat!", &mut iteration, body, out) {
+                break;
+            }
+        }
+    }
+
+    /// The body of the first branch of `stmt` whose condition holds, else
+    /// `otherwise`. None if a condition fails to evaluate: a branch taken
+    /// after that would be a guess.
+    fn pick<'s>(
+        &mut self,
+        stmt: &Statement,
+        branches: &'s [IfBranch],
+        otherwise: &'s [Statement],
+    ) -> Option<&'s [Statement]> {
+        for (i, branch) in branches.iter().enumerate() {
+            let keyword = if i == 0 { "if!" } else { "elif!" };
+            if self.condition(keyword, &branch.cond, &stmt.span)? {
+                return Some(branch.body.as_slice());
+            }
+        }
+        Some(otherwise)
+    }
+
+    /// Whether `cond`, from a `keyword`, holds: it's a nonzero integer.
+    /// None, with the error reported, if it isn't an integer at all.
+    fn condition(&mut self, keyword: &str, cond: &Expr, span: &Span) -> Option<bool> {
+        match self.eval(cond, span) {
+            Ok(Value::Int(n)) => Some(n != 0),
+            Ok(value) => {
+                self.report(
+                    Diagnostic::error(format!(
+                        "the `{}` condition is {}, not an integer",
+                        keyword,
+                        value.kind()
+                    ))
+                    .with_code(Code::E0509)
+                    .with_span(span.clone()),
+                );
+                None
+            }
+            Err(diag) => {
+                self.report(diag);
+                None
+            }
+        }
+    }
+
+    /// Expands one more iteration of the loop `stmt`. False once the loop
+    /// has to stop: it has run [`MAX_ITERATIONS`] times, the body failed, or
+    /// it reached a `break!`.
+    fn iterate(
+        &mut self,
+        stmt: &Statement,
+        keyword: &'static str,
+        iteration: &mut usize,
+        body: &[Statement],
+        out: &mut Vec<Statement>,
+    ) -> bool {
+        if *iteration == MAX_ITERATIONS {
+            self.report(
+                Diagnostic::error(format!(
+                    "`{}` runs its body more than {} times",
+                    keyword, MAX_ITERATIONS
+                ))
+                .with_code(Code::E0508)
+                .with_span(stmt.span.clone()),
+            );
+            return false;
+        }
+        *iteration += 1;
+
+        let kind = ExpansionKind::Loop {
+            keyword,
+            iteration: *iteration,
+        };
+        let frame = Expansion::new(kind, stmt.span.clone(), self.frame.clone());
+        let outer = self.frame.replace(frame);
+        let errors = self.errors.len();
+        self.loops += 1;
+        let flow = self.statements(body, out);
+        self.loops -= 1;
+        self.frame = outer;
+        self.errors.len() == errors && flow != Flow::Break
+    }
+
+    /// Expands `stmt`, a call of macro `name`, in its place. A `break!` or
+    /// `continue!` in the body leaves the loop around the call.
+    fn call(&mut self, stmt: &Statement, name: &str, out: &mut Vec<Statement>) -> Flow {
+        let call = self.substitute(stmt);
+        self.check_pasted(&call);
+        let (StatementKind::MacroCall { args, .. } | StatementKind::Instruction { args, .. }) =
+            &call.kind
+        else {
+            unreachable!("only macro calls get here");
+        };
+        let macros = self.macros;
+        let def = match macros.resolve(name, args.len()) {
+            Ok(Some(def)) => def,
+            Ok(None) => return Flow::Done,
+            Err(diag) => {
+                if self.forwarding.contains(&stmt.span) {
+                    self.report(diag.with_span(stmt.span.clone()));
+                }
+                return Flow::Done;
+            }
+        };
+        if self.halted {
+            return Flow::Done;
+        }
+        if self.expansions == self.max_expansions {
+            self.halted = true;
+            self.report(
+                Diagnostic::error(format!(
+                    "expanding `{}` goes past {} macro calls in all",
+                    name, self.max_expansions
+                ))
+                .with_code(Code::E0120)
+                .with_span(stmt.span.clone())
+                .with_note("nothing more is expanded"),
+            );
+            return Flow::Done;
+        }
+        if self.calls == self.max_calls {
+            self.halted = true;
+            self.report(
+                Diagnostic::error(format!(
+                    "macro calls nest more than {} deep expanding `{}`",
+                    self.max_calls, name
+                ))
+                .with_code(Code::E0116)
+                .with_span(stmt.span.clone())
+                .with_note(format!("the chain of calls is {}", self.chain(name)))
+                .with_note("stop the recursion with an `if!`, or raise the limit"),
+            );
+            return Flow::Done;
+        }
+
+        let kind = ExpansionKind::Macro {
+            name: name.to_string(),
+        };
+        let frame = Expansion::new(kind, stmt.span.clone(), self.frame.clone());
+        let outer = self.frame.replace(frame);
+        if let Some(rest) = def.rest() {
+            let calls = crate::walk::iter_deep(def.body).filter(|stmt| forwards(stmt, rest));
+            self.forwarding.extend(calls.map(|stmt| stmt.span.clone()));
+        }
+        self.expansions += 1;
+        debug!(name = %name, args = args.len(), depth = self.calls, "macro expansion");
+        let (body, errors) = instantiate(def, args, self.expansions);
+        for diag in errors {
+            self.report(diag);
+        }
+        self.calls += 1;
+        let flow = self.statements(&body, out);
+        self.calls -= 1;
+        self.frame = outer;
+        flow
+    }
+
+    /// The macros being expanded, outermost first, then `next`, as in
+    /// `` `outer` -> `countdown` (64 times) ``.
+    fn chain(&self, next: &str) -> String {
+        let mut names: Vec<&str> = (self.frame.iter())
+            .flat_map(|frame| frame.frames())
+            .filter_map(|frame| match &frame.kind {
+                ExpansionKind::Macro { name } => Some(name.as_str()),
+                ExpansionKind::Loop { .. } => None,
+            })
+            .collect();
+        names.reverse();
+        names.push(next);
+
+        let mut runs: Vec<(&str, usize)> = Vec::new();
+        for name in names {
+            match runs.last_mut() {
+                Some((last, count)) if *last == name => *count += 1,
+                _ => runs.push((name, 1)),
+            }
+        }
+        let runs: Vec<String> = runs
+            .into_iter()
+            .map(|(name, count)| match count {
+                1 => format!("`{}`", name),
+                count => format!("`{}` ({} times)", name, count),
+            })
+            .collect();
+        runs.join(" -> ")
+    }
+
+    /// A data statement per field of struct `name`, in the struct's order,
+    /// holding the value `values` gives it or else zero.
+    fn struct_data(
+        &mut self,
+        stmt: &Statement,
+        name: &str,
+        values: &[(String, Expr)],
+        out: &mut Vec<Statement>,
+    ) {
+        let error = |message: String| {
+            Diagnostic::error(message)
+                .with_code(Code::E0514)
+                .with_span(stmt.span.clone())
+        };
+        let structs = self.structs;
+        let Some(fields) = structs.get(name) else {
+            let diag = error(format!("no struct named `{}`", name));
+            self.report(diag.with_suggestions(name, structs.keys().copied()));
+            return;
+        };
+        for (i, (field, _)) in values.iter().enumerate() {
+            if !fields.iter().any(|f| f.name == *field) {
+                let diag = error(format!("`{}` has no field `{}`", name, field));
+                let names = fields.iter().map(|f| f.name.as_str());
+                self.report(diag.with_suggestions(field, names));
+            } else if values[..i].iter().any(|(earlier, _)| earlier == field) {
+                self.report(error(format!("field `{}` is given twice", field)));
+            }
+        }
+        for field in fields.iter() {
+            let value = values
+                .iter()
+                .find(|(name, _)| *name == field.name)
+                .map_or(Expr::Number(Number::Int(0)), |(_, value)| value.clone());
+            let data = self.substitute(&Statement {
+                kind: StatementKind::Data {
+                    width: field.width,
+                    values: vec![value],
+                    spans: Vec::new(),
+                },
+                span: stmt.span.clone(),
+            });
+            self.emit(data, out);
+        }
+    }
+
+    /// Reports a name in `stmt` that still has a `##` after
+    /// [`paste`](Self::paste).
+    fn check_pasted(&mut self, stmt: &Statement) {
+        let Some(name) = unpasted(stmt) else {
+            return;
+        };
+        let pieces: Vec<&str> = name.split("##").collect();
+        self.report(
+            Diagnostic::error(format!("`{}` doesn't paste into a name", name))
+                .with_code(Code::E0118)
+                .with_span(stmt.span.clone())
+                .with_note(format!(
+                    "each of `{}` has to be a name, or a variable or constant that's a whole \
+                     number from 0 up, and the first can't be a number",
+                    pieces.join("`, `")
+                )),
+        );
+    }
+
+    /// Puts `stmt`, which isn't a block, out.
+    fn emit(&mut self, stmt: Statement, out: &mut Vec<Statement>) {
+        if let Some(origins) = &mut self.origins {
+            origins.record(&stmt, self.frame.clone());
+        }
+        out.push(stmt);
+    }
+
+    fn report(&mut self, diag: Diagnostic) {
+        self.errors.push(diag.with_expansion(self.frame.clone()));
+    }
+
+    fn eval(&self, expr: &Expr, span: &Span) -> Result<Value, Diagnostic> {
+        let mut expr = expr.clone();
+        self.substitute_expr(&mut expr);
+        let lookup = |name: &str| match self.vars.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.table.value(name),
+        };
+        eval(&expr, &lookup, span)
+    }
+
+    /// Whether `expr` needs a label's address, which isn't known yet.
+    fn waits(&self, expr: &Expr) -> bool {
+        let mut expr = expr.clone();
+        self.substitute_expr(&mut expr);
+        expr.symbols()
+            .into_iter()
+            .any(|name| !self.vars.contains_key(name) && self.table.unresolved(name))
+    }
+
+    /// `stmt` with the current value of each variable its operands or
+    /// data values use written in.
+    fn substitute(&self, stmt: &Statement) -> Statement {
+        let mut stmt = stmt.clone();
+        match &mut stmt.kind {
+            StatementKind::Instruction { args, .. } | StatementKind::MacroCall { args, .. } => {
+                for arg in args {
+                    self.substitute_operand(arg);
+                }
+            }
+            StatementKind::Data { values, spans, .. } => {
+                // `.byte TABLE` stores each of its items, all where `TABLE`
+                // is.
+                let items: Vec<Vec<Expr>> = values
+                    .iter()
+                    .map(|value| {
+                        self.with_items(value, <[Expr]>::to_vec)
+                            .unwrap_or_else(|| vec![value.clone()])
+                    })
+                    .collect();
+                *spans = spans
+                    .iter()
+                    .zip(&items)
+                    .flat_map(|(span, items)| vec![span.clone(); items.len()])
+                    .collect();
+                *values = items.into_iter().flatten().collect();
+                for value in values {
+                    self.substitute_expr(value);
+                }
+            }
+            StatementKind::Org(address) => self.substitute_expr(address),
+            StatementKind::Align { boundary, fill } => {
+                self.substitute_expr(boundary);
+                if let Some(fill) = fill {
+                    self.substitute_expr(fill);
+                }
+            }
+            StatementKind::Label { name, .. } => *name = self.paste(name),
+            StatementKind::Directive { args, .. } => {
+                for arg in args.iter_mut() {
+                    *arg = self.paste(arg);
+                }
+            }
+            _ => {}
+        }
+        stmt
+    }
+
+    /// `name` with the pieces between its `##`s that are variables or
+    /// constants written as their values, and the pieces pasted together:
+    /// `handler_##i` is `handler_3` while `i` is 3. A piece whose value
+    /// isn't a whole number from 0 up, or a name that doesn't come out as
+    /// one, is left as it is, for [`unpasted`] to find.
+    fn paste(&self, name: &str) -> String {
+        if !name.contains("##") {
+            return name.to_string();
+        }
+        let mut pasted = String::new();
+        for piece in name.split("##") {
+            let value = match self.vars.get(piece) {
+                Some(value) => Some(value.clone()),
+                None => self.table.value(piece),
+            };
+            match value {
+                Some(Value::Int(n)) if n >= 0 => pasted.push_str(&n.to_string()),
+                Some(_) => return name.to_string(),
+                None => pasted.push_str(piece),
+            }
+        }
+        match pasted.starts_with(|c: char| c.is_alphabetic() || "_.%".contains(c)) {
+            true => pasted,
+            false => name.to_string(),
+        }
+    }
+
+    fn substitute_operand(&self, arg: &mut Operand) {
+        match arg {
+            Operand::Symbol(name) => {
+                *name = self.paste(name);
+                if self.registers.matches(name) {
+                    *arg = Operand::Register(mem::take(name));
+                } else if let Some(n) = self.number(name) {
+                    *arg = Operand::Expr(Expr::Number(n));
+                }
+            }
+            Operand::Immediate(expr) | Operand::Expr(expr) => self.substitute_expr(expr),
+            Operand::Memory(memory) => {
+                if let Some(disp) = &mut memory.displacement {
+                    self.substitute_expr(disp);
+                }
+                for register in memory.base.iter_mut().chain(&mut memory.index) {
+                    *register = self.paste(register);
+                }
+                self.pasted_registers(memory);
+            }
+            Operand::Register(name) => *name = self.paste(name),
+            Operand::String(_) | Operand::Char(_) => {}
+        }
+    }
+
+    /// Moves registers pasted together in `memory`'s displacement, like
+    /// `r##n`, into its base and index, while either is free.
+    fn pasted_registers(&self, memory: &mut MemoryOperand) {
+        let Some(displacement) = memory.displacement.take() else {
+            return;
+        };
+        let mut terms = Vec::new();
+        flatten_sum(displacement, &mut terms);
+        let mut rest = Vec::new();
+        for term in terms {
+            match term {
+                Expr::Symbol(name) if self.registers.matches(&name) && memory.base.is_none() => {
+                    memory.base = Some(name);
+                }
+                Expr::Symbol(name) if self.registers.matches(&name) && memory.index.is_none() => {
+                    memory.index = Some(name);
+                }
+                term => rest.push(term),
+            }
+        }
+        memory.displacement = rest.into_iter().reduce(|lhs, rhs| Expr::Binary {
+            op: BinaryOp::Add,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        });
+    }
+
+    fn substitute_expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::Symbol(name) => {
+                *name = self.paste(name);
+                if let Some(n) = self.number(name) {
+                    *expr = Expr::Number(n);
+                }
+            }
+            Expr::Unary { expr, .. } => self.substitute_expr(expr),
+            Expr::Binary { lhs, rhs, .. } => {
+                self.substitute_expr(lhs);
+                self.substitute_expr(rhs);
+            }
+            Expr::Call { name, args } => {
+                for arg in args.iter_mut() {
+                    self.substitute_expr(arg);
+                }
+                if let [array] = &args[..]
+                    && name == "len"
+                    && let Some(len) = self.with_items(array, <[Expr]>::len)
+                {
+                    *expr = Expr::Number(Number::Int(len as i64));
+                }
+            }
+            Expr::Array(items) => {
+                for item in items {
+                    self.substitute_expr(item);
+                }
+            }
+            Expr::Index { base, index } => {
+                self.substitute_expr(base);
+                self.substitute_expr(index);
+                if let Some(mut item) = self.item(base, index) {
+                    self.substitute_expr(&mut item);
+                    *expr = item;
+                }
+            }
+            Expr::Number(_) | Expr::Str(_) => {}
+        }
+    }
+
+    /// `base[index]` as the item's own expression, if `base` is an array
+    /// and `index` is known and in range.
+    fn item(&self, base: &Expr, index: &Expr) -> Option<Expr> {
+        if self.waits(index) {
+            return None;
+        }
+        let Ok(Value::Int(index)) = self.eval(index, &Span::default()) else {
+            return None;
+        };
+        let index = usize::try_from(index).ok()?;
+        self.with_items(base, |items| items.get(index).cloned())?
+    }
+
+    /// `f` of the items of `array`: an array literal, or a variable or
+    /// constant holding an array. `None` if it's none of those.
+    fn with_items<T>(&self, array: &Expr, f: impl FnOnce(&[Expr]) -> T) -> Option<T> {
+        match array {
+            Expr::Array(items) => Some(f(items)),
+            Expr::Symbol(name) => match self.vars.get(name) {
+                Some(Value::Array(values)) => {
+                    let items: Vec<Expr> = values.iter().map(Value::to_expr).collect();
+                    Some(f(&items))
+                }
+                Some(_) => None,
+                None => match &self.table.get(name)?.value {
+                    SymbolValue::Array(items) => Some(f(items)),
+                    _ => None,
+                },
+            },
+            _ => None,
+        }
+    }
+
+    /// A variable's value as a literal. A string or array variable stays
+    /// a name.
+    fn number(&self, name: &str) -> Option<Number> {
+        match self.vars.get(name)? {
+            Value::Int(n) => Some(Number::Int(*n)),
+            Value::Float(x) => Some(Number::Float(*x)),
+            Value::Str(_) | Value::Array(_) => None,
+        }
+    }
+}
+
+/// The first name in `stmt` with a `##` in it, macro bodies aside.
+fn unpasted(stmt: &Statement) -> Option<String> {
+    #[derive(Default)]
+    struct Unpasted(Option<String>);
+
+    impl<'ast> Visitor<'ast> for Unpasted {
+        fn visit_statement(&mut self, stmt: &'ast Statement) {
+            let names: Vec<&String> = match &stmt.kind {
+                StatementKind::MacroDef { .. } => return,
+                StatementKind::Label { name, .. } => vec![name],
+                StatementKind::Directive { args, .. } => args.iter().collect(),
+                _ => Vec::new(),
+            };
+            if let Some(name) = names.into_iter().find(|name| name.contains("##")) {
+                self.0.get_or_insert_with(|| name.clone());
+            }
+            walk_statement(self, stmt);
+        }
+
+        fn visit_operand(&mut self, operand: &'ast Operand) {
+            match operand {
+                Operand::Register(name) | Operand::Symbol(name) if name.contains("##") => {
+                    self.0.get_or_insert_with(|| name.clone());
+                }
+                Operand::Memory(memory) => {
+                    let registers = memory.base.iter().chain(&memory.index);
+                    if let Some(name) = registers.into_iter().find(|name| name.contains("##")) {
+                        self.0.get_or_insert_with(|| name.clone());
+                    }
+                }
+                _ => {}
+            }
+            walk_operand(self, operand);
+        }
+
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let Expr::Symbol(name) = expr
+                && name.contains("##")
+            {
+                self.0.get_or_insert_with(|| name.clone());
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut visitor = Unpasted::default();
+    visitor.visit_statement(stmt);
+    visitor.0
+}
@@ -0,0 +1,239 @@
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::isa::Isa;
+use crate::source::SourceManager;
+use crate::symbols::{SymbolKind, SymbolTable, SymbolValue};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Default)]
+pub struct CHeaderOptions {
+    /// Prepended to every macro name, e.g. `ASM_`.
+    pub prefix: String,
+    /// Include guard name; derived from the prefix when empty.
+    pub guard: String,
+}
+
+/// Renders a C header with one `#define` per integer symbol, taking
+/// [`Assembled::exports`](crate::assemble::Assembled::exports) so labels
+/// have their addresses. Anything else is a comment saying it's skipped.
+///
+/// Names are sanitized to C identifiers and sorted. Two symbols that
+/// sanitize to the same name are an error rather than a silent overwrite.
+pub fn c_header(symbols: &SymbolTable, opts: &CHeaderOptions) -> Result<String, Diagnostic> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut lines = Vec::new();
+
+    for sym in &symbols.symbols {
+        let ident = format!("{}{}", opts.prefix, c_identifier(&sym.name));
+        if let Some(first) = seen.insert(ident.clone(), &sym.name) {
+            let message = if first == sym.name {
+                format!("`{}` is defined more than once", sym.name)
+            } else {
+                format!(
+                    "`{}` and `{}` both become `{}` in the C header",
+                    first, sym.name, ident
+                )
+            };
+            return Err(Diagnostic::error(message)
+                .with_code(Code::E0107)
+                .with_span(sym.span.clone()));
+        }
+
+        let line = match (&symbols.settled(sym), sym.kind) {
+            (SymbolValue::Int(n), SymbolKind::Label) => format!("#define {} 0x{:X}", ident, n),
+            (SymbolValue::Int(n), _) if *n < 0 => format!("#define {} ({})", ident, n),
+            (SymbolValue::Int(n), _) => format!("#define {} {}", ident, n),
+            (SymbolValue::Float(_), _) => format!("/* {}: float constant skipped */", ident),
+            (SymbolValue::Str(_), _) => format!("/* {}: string constant skipped */", ident),
+            (SymbolValue::Array(_), _) => format!("/* {}: array constant skipped */", ident),
+            (SymbolValue::Unresolved | SymbolValue::Equ(_), _) => {
+                format!("/* {}: address not resolved */", ident)
+            }
+            (SymbolValue::Size { .. }, _) => format!("/* {}: size not resolved */", ident),
+        };
+        lines.push((ident, line));
+    }
+
+    lines.sort();
+
+    let guard = if opts.guard.is_empty() {
+        format!("{}CHASM_SYMBOLS_H", opts.prefix)
+    } else {
+        opts.guard.clone()
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Generated by chasm. Do not edit. */");
+    let _ = writeln!(out, "#ifndef {}", guard);
+    let _ = writeln!(out, "#define {}", guard);
+    out.push('\n');
+    for (_, line) in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+    out.push('\n');
+    let _ = writeln!(out, "#endif /* {} */", guard);
+    Ok(out)
+}
+
+fn c_identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+#[derive(Debug, Clone)]
+pub struct RustOptions {
+    /// Integer type for every constant, e.g. `u16` for a 16-bit address space.
+    pub int_type: String,
+}
+
+impl Default for RustOptions {
+    fn default() -> Self {
+        Self {
+            int_type: "i64".to_string(),
+        }
+    }
+}
+
+impl RustOptions {
+    /// Unsigned, as wide as `isa`'s addresses: `u16` for the generic
+    /// target.
+    pub fn for_isa(isa: &Isa) -> Self {
+        Self {
+            int_type: format!("u{}", isa.address_bits()),
+        }
+    }
+}
+
+/// Renders a `pub mod symbols { ... }` Rust module with one `pub const` per
+/// integer symbol, named in upper snake case and sorted. Takes the same
+/// symbols as [`c_header`].
+pub fn rust_consts(
+    symbols: &SymbolTable,
+    sources: &SourceManager,
+    opts: &RustOptions,
+) -> Result<String, Diagnostic> {
+    let Some(range) = int_range(&opts.int_type) else {
+        return Err(
+            Diagnostic::error(format!("`{}` is not a Rust integer type", opts.int_type))
+                .with_code(Code::E0109),
+        );
+    };
+
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut items = Vec::new();
+
+    for sym in &symbols.symbols {
+        let ident = upper_snake(&sym.name);
+        if let Some(first) = seen.insert(ident.clone(), &sym.name) {
+            let message = if first == sym.name {
+                format!("`{}` is defined more than once", sym.name)
+            } else {
+                format!(
+                    "`{}` and `{}` both become `{}` in the Rust module",
+                    first, sym.name, ident
+                )
+            };
+            return Err(Diagnostic::error(message)
+                .with_code(Code::E0107)
+                .with_span(sym.span.clone()));
+        }
+
+        let mut item = format!(
+            "    /// `{}` at {}\n",
+            sym.name,
+            sources.location(&sym.span)
+        );
+        match (&symbols.settled(sym), sym.kind) {
+            (SymbolValue::Int(n), _) if !range.contains(&i128::from(*n)) => {
+                return Err(Diagnostic::error(format!(
+                    "`{}` = {} does not fit in `{}`",
+                    sym.name, n, opts.int_type
+                ))
+                .with_code(Code::E0108)
+                .with_span(sym.span.clone()));
+            }
+            (SymbolValue::Int(n), SymbolKind::Label) => {
+                let _ = writeln!(
+                    item,
+                    "    pub const {}: {} = 0x{:X};",
+                    ident, opts.int_type, n
+                );
+            }
+            (SymbolValue::Int(n), _) => {
+                let _ = writeln!(item, "    pub const {}: {} = {};", ident, opts.int_type, n);
+            }
+            (SymbolValue::Float(_), _) => {
+                item = format!("    // {}: float constant skipped\n", ident);
+            }
+            (SymbolValue::Str(_), _) => {
+                item = format!("    // {}: string constant skipped\n", ident);
+            }
+            (SymbolValue::Array(_), _) => {
+                item = format!("    // {}: array constant skipped\n", ident);
+            }
+            (SymbolValue::Unresolved | SymbolValue::Equ(_), _) => {
+                item = format!("    // {}: address not resolved\n", ident);
+            }
+            (SymbolValue::Size { .. }, _) => {
+                item = format!("    // {}: size not resolved\n", ident);
+            }
+        }
+        items.push((ident, item));
+    }
+
+    items.sort();
+
+    let mut out = String::from("// Generated by chasm. Do not edit.\n\npub mod symbols {\n");
+    for (_, item) in items {
+        out.push_str(&item);
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+fn int_range(ty: &str) -> Option<RangeInclusive<i128>> {
+    let bits = match ty {
+        "u8" | "i8" => 8,
+        "u16" | "i16" => 16,
+        "u32" | "i32" => 32,
+        "u64" | "i64" => 64,
+        _ => return None,
+    };
+    Some(if ty.starts_with('u') {
+        0..=(1i128 << bits) - 1
+    } else {
+        -(1i128 << (bits - 1))..=(1i128 << (bits - 1)) - 1
+    })
+}
+
+/// `loopStart` / `loop-start` / `loop_start` -> `LOOP_START`.
+fn upper_snake(name: &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && prev_lower {
+                out.push('_');
+            }
+            prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+            out.push(c.to_ascii_uppercase());
+        } else {
+            if !out.ends_with('_') {
+                out.push('_');
+            }
+            prev_lower = false;
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
@@ -0,0 +1,108 @@
+//! `--emit hexdump`: an annotated dump of a linked image.
+//!
+//! ```text
+//! 00008000  10 06 80 20 fe 00 30 09  80 68 69                 |... ..0..hi     |  start ext=8006
+//! 0000800b
+//! ```
+//!
+//! Rows cover 16-byte aligned target addresses, so an image that doesn't
+//! start on a boundary has blank cells before its first byte. A run of
+//! all-zero rows is shown as its first row and a `*`, unless a symbol points
+//! into it. Symbols are listed after the row holding their address: bare if
+//! they're at the row's first address, `name=addr` otherwise. The last line
+//! is the address just past the image, with any symbols that point there.
+
+use crate::link::Image;
+use std::fmt::Write;
+
+const ROW: u64 = 16;
+
+pub fn hexdump(image: &Image) -> String {
+    let mut out = String::new();
+    let start = image.base;
+    let end = image.base + image.data.len() as u64;
+    let width = address_width(end);
+
+    let mut row = start - start % ROW;
+    let mut symbols = image.symbols.iter().peekable();
+    let mut prev_zero = false;
+    let mut collapsed = false;
+
+    while row < end {
+        let next = row + ROW;
+        let byte = |addr: u64| {
+            (start..end)
+                .contains(&addr)
+                .then(|| image.data[(addr - start) as usize])
+        };
+
+        let mut labels = Vec::new();
+        while let Some((name, addr)) = symbols.next_if(|(_, addr)| *addr < next) {
+            if *addr == row {
+                labels.push(name.clone());
+            } else if *addr > row {
+                labels.push(format!("{}={:x}", name, addr));
+            }
+        }
+
+        let zero = (row..next).all(|addr| byte(addr).is_none_or(|b| b == 0));
+        if zero && prev_zero && labels.is_empty() && next < end {
+            if !collapsed {
+                out.push_str("*\n");
+                collapsed = true;
+            }
+            row = next;
+            continue;
+        }
+        prev_zero = zero;
+        collapsed = false;
+
+        let _ = write!(out, "{:0width$x}  ", row, width = width);
+        let mut ascii = String::new();
+        for (i, addr) in (row..next).enumerate() {
+            if i == 8 {
+                out.push(' ');
+            }
+            match byte(addr) {
+                Some(b) => {
+                    let _ = write!(out, "{:02x} ", b);
+                    let printable = (0x20..0x7f).contains(&b);
+                    ascii.push(if printable { b as char } else { '.' });
+                }
+                None => {
+                    out.push_str("   ");
+                    ascii.push(' ');
+                }
+            }
+        }
+        let _ = write!(out, " |{}|", ascii);
+        if !labels.is_empty() {
+            let _ = write!(out, "  {}", labels.join(" "));
+        }
+        out.push('\n');
+        row = next;
+    }
+
+    // Symbols just past the image, like the end label of a blob.
+    let _ = write!(out, "{:0width$x}", end, width = width);
+    let rest: Vec<String> = symbols
+        .map(|(name, addr)| {
+            if *addr == end {
+                name.clone()
+            } else {
+                format!("{}={:x}", name, addr)
+            }
+        })
+        .collect();
+    if !rest.is_empty() {
+        let _ = write!(out, "  {}", rest.join(" "));
+    }
+    out.push('\n');
+    out
+}
+
+/// At least 8 digits, more for addresses that need them.
+fn address_width(end: u64) -> usize {
+    let digits = (u64::BITS - end.leading_zeros()).div_ceil(4) as usize;
+    digits.max(8)
+}
@@ -0,0 +1,198 @@
+use crate::analysis::{self, SymbolKind};
+use crate::isa::Target;
+use crate::parser::{self, LexOptions, Token, is_dot_directive};
+use crate::source::FileId;
+use crate::tokens::{TokenKind, breaks_line, split_trivia};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Mnemonic,
+    Register,
+    LabelDef,
+    LabelRef,
+    Const,
+    MacroName,
+    Directive,
+    Number,
+    String,
+    Comment,
+}
+
+/// Classifies every interesting token in `source` for syntax highlighting,
+/// lexing it the way the parser does for `target`: `%name` and the
+/// target's register names are registers, `1f` is a label reference, and
+/// comments are kept.
+///
+/// Punctuation is left out. The result is sorted by position and spans
+/// never overlap. Unparseable input is skipped rather than aborting.
+pub fn classify(source: &str, target: &Target) -> Vec<(Range<usize>, TokenClass)> {
+    let index = analysis::index(source);
+
+    let defs: HashMap<usize, SymbolKind> = index
+        .definitions
+        .iter()
+        .map(|d| (d.span.start, d.kind))
+        .collect();
+    let refs: HashMap<usize, Option<SymbolKind>> = index
+        .references
+        .iter()
+        .map(|r| {
+            (
+                r.span.start,
+                r.definition.map(|d| index.definitions[d].kind),
+            )
+        })
+        .collect();
+
+    let opts = LexOptions {
+        trivia: true,
+        ..LexOptions::for_target(target)
+    };
+    let tokens: Vec<Token> = parser::lex(source, 0, FileId::default(), opts)
+        .filter_map(Result::ok)
+        .collect();
+    // `%name:` defines a label even though `%name` lexes as a register.
+    let sigil_labels: HashSet<&str> = tokens
+        .windows(2)
+        .filter(|pair| sigil_label(&pair[0], Some(&pair[1])))
+        .map(|pair| pair[0].text.as_str())
+        .collect();
+
+    let mut out = Vec::new();
+    let mut prev: Option<(TokenKind, usize)> = None;
+
+    for (i, tok) in tokens.iter().enumerate() {
+        out.extend(tok.leading.iter().filter_map(comment));
+
+        let span = tok.span.range.clone();
+        let next = tokens.get(i + 1);
+        let defines = matches!(next, Some(t) if t.kind == TokenKind::Colon)
+            && starts_statement(source, prev.as_ref(), span.start);
+
+        let class = match &tok.kind {
+            TokenKind::Var
+            | TokenKind::Const
+            | TokenKind::Include
+            | TokenKind::MacroRules
+            | TokenKind::ForBang
+            | TokenKind::WhileBang
+            | TokenKind::RepeatBang
+            | TokenKind::BreakBang
+            | TokenKind::ContinueBang
+            | TokenKind::IfBang
+            | TokenKind::ElifBang
+            | TokenKind::ElseBang => Some(TokenClass::Keyword),
+
+            TokenKind::Ident(name)
+                if name.eq_ignore_ascii_case("equ")
+                    && matches!(prev, Some((TokenKind::Ident(_), _))) =>
+            {
+                Some(TokenClass::Keyword)
+            }
+            TokenKind::Ident(name)
+                if ["module", "struct", "enum"]
+                    .iter()
+                    .any(|word| name.eq_ignore_ascii_case(word))
+                    && starts_statement(source, prev.as_ref(), span.start) =>
+            {
+                Some(TokenClass::Keyword)
+            }
+
+            TokenKind::AtDirective => Some(TokenClass::Directive),
+            TokenKind::Ident(name) if dot_directive(prev.as_ref(), span.start, name) => {
+                Some(TokenClass::Directive)
+            }
+
+            TokenKind::Register(_) if defines && sigil_label(tok, next) => {
+                Some(TokenClass::LabelDef)
+            }
+            TokenKind::Register(name) if sigil_labels.contains(name.as_str()) => {
+                Some(TokenClass::LabelRef)
+            }
+            TokenKind::Register(_) => Some(TokenClass::Register),
+
+            // `1:` defines a numeric label, which `1f` and `1b` refer to.
+            TokenKind::IntLit(_) if defines => Some(TokenClass::LabelDef),
+            TokenKind::NumericLabelRef(_) => Some(TokenClass::LabelRef),
+
+            TokenKind::HexLit(_)
+            | TokenKind::BinLit(_)
+            | TokenKind::OctLit(_)
+            | TokenKind::IntLit(_)
+            | TokenKind::FloatLit(_) => Some(TokenClass::Number),
+
+            TokenKind::StrLit(_) | TokenKind::CharLit(_) => Some(TokenClass::String),
+
+            TokenKind::UnterminatedComment => Some(TokenClass::Comment),
+
+            TokenKind::Ident(_) => Some(match defs.get(&span.start) {
+                Some(SymbolKind::Label) => TokenClass::LabelDef,
+                Some(SymbolKind::Macro) => TokenClass::MacroName,
+                Some(_) => TokenClass::Const,
+                None => match refs.get(&span.start).copied().flatten() {
+                    Some(SymbolKind::Label) => TokenClass::LabelRef,
+                    Some(SymbolKind::Macro) => TokenClass::MacroName,
+                    Some(_) => TokenClass::Const,
+                    None if starts_statement(source, prev.as_ref(), span.start) => {
+                        TokenClass::Mnemonic
+                    }
+                    None => TokenClass::LabelRef,
+                },
+            }),
+
+            _ => None,
+        };
+
+        if let Some(class) = class {
+            out.push((span.clone(), class));
+        }
+        prev = Some((tok.kind.clone(), span.end));
+    }
+
+    // Comments after the last token belong to no token's leading trivia.
+    let end = prev.map_or(0, |(_, end)| end);
+    out.extend(
+        split_trivia(&source[end..])
+            .into_iter()
+            .filter(|(kind, _)| is_comment(kind))
+            .map(|(_, piece)| (end + piece.start..end + piece.end, TokenClass::Comment)),
+    );
+
+    out
+}
+
+/// Whether `tok` is a `%name` label's name, with `next` its `:`.
+fn sigil_label(tok: &Token, next: Option<&Token>) -> bool {
+    matches!(&tok.kind, TokenKind::Register(name) if name.starts_with('%'))
+        && matches!(next, Some(t) if t.kind == TokenKind::Colon)
+}
+
+fn is_comment(kind: &TokenKind) -> bool {
+    matches!(kind, TokenKind::LineComment | TokenKind::BlockComment)
+}
+
+/// A piece of kept trivia, if it's a comment.
+fn comment(trivia: &Token) -> Option<(Range<usize>, TokenClass)> {
+    is_comment(&trivia.kind).then(|| (trivia.span.range.clone(), TokenClass::Comment))
+}
+/// Whether `name` is the name of a `.` directive like `.word`, right
+/// after its dot.
+fn dot_directive(prev: Option<&(TokenKind, usize)>, start: usize, name: &str) -> bool {
+    matches!(prev, Some((TokenKind::Dot, end)) if *end == start)
+        && is_dot_directive(&name.to_ascii_lowercase())
+}
+
+/// Whether the token after `prev` begins a new statement: first on its
+/// line, or right after a label's `:`, a `;` or a block's `{`.
+fn starts_statement(source: &str, prev: Option<&(TokenKind, usize)>, start: usize) -> bool {
+    match prev {
+        None => true,
+        Some((
+            TokenKind::Colon | TokenKind::Semicolon | TokenKind::LeftBrace | TokenKind::RightBrace,
+            _,
+        )) => true,
+        Some((_, end)) => breaks_line(&source[*end..start]),
+    }
+}
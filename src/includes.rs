@@ -0,0 +1,139 @@
+use crate::parser::{Parser, StatementKind};
+use crate::source::SourceManager;
+use crate::tokens::parse_string;
+use crate::walk::iter_deep;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IncludeOptions {
+    /// Searched, in order, when an include isn't found next to the including file.
+    pub include_dirs: Vec<PathBuf>,
+}
+
+/// One `include "..."` site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeEdge {
+    pub from: usize,
+    pub to: usize,
+    /// The path as written in the source.
+    pub written: String,
+}
+
+/// Files reachable from an entry point through `include`, keyed by canonical path.
+/// Node 0 is the entry file. Cycles show up as edges back to an earlier node.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeGraph {
+    pub nodes: Vec<PathBuf>,
+    pub edges: Vec<IncludeEdge>,
+}
+
+/// Walks the include tree starting at `entry`.
+pub fn graph(entry: &Path, opts: &IncludeOptions) -> io::Result<IncludeGraph> {
+    let mut builder = Builder {
+        graph: IncludeGraph::default(),
+        ids: HashMap::new(),
+        sources: SourceManager::new(),
+        opts,
+    };
+    let entry = fs::canonicalize(entry)?;
+    builder.visit(entry)?;
+    Ok(builder.graph)
+}
+
+struct Builder<'a> {
+    graph: IncludeGraph,
+    ids: HashMap<PathBuf, usize>,
+    sources: SourceManager,
+    opts: &'a IncludeOptions,
+}
+
+impl Builder<'_> {
+    fn visit(&mut self, path: PathBuf) -> io::Result<usize> {
+        let id = self.graph.nodes.len();
+        self.ids.insert(path.clone(), id);
+        self.graph.nodes.push(path.clone());
+
+        let file = self.sources.load(&path)?;
+        let ast = Parser::for_file(&self.sources, file).parse();
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        for stmt in iter_deep(&ast) {
+            let StatementKind::Include(raw) = &stmt.kind else {
+                continue;
+            };
+            let written = parse_string(raw);
+            let target = resolve(dir, &written, self.opts)?;
+
+            // Record the edge before descending so edges stay in source order.
+            let seen = self.ids.get(&target).copied();
+            self.graph.edges.push(IncludeEdge {
+                from: id,
+                to: seen.unwrap_or(self.graph.nodes.len()),
+                written,
+            });
+            if seen.is_none() {
+                self.visit(target)?;
+            }
+        }
+
+        Ok(id)
+    }
+}
+
+/// Finds `written` next to the including file (in `dir`) or in one of the
+/// include dirs, returning its canonical path.
+pub(crate) fn resolve(dir: &Path, written: &str, opts: &IncludeOptions) -> io::Result<PathBuf> {
+    let candidates = std::iter::once(dir).chain(opts.include_dirs.iter().map(PathBuf::as_path));
+    for base in candidates {
+        let candidate = base.join(written);
+        if candidate.is_file() {
+            return fs::canonicalize(candidate);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("include \"{}\" not found from {}", written, dir.display()),
+    ))
+}
+
+impl IncludeGraph {
+    /// Graphviz DOT rendering, one edge per include site.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph includes {\n");
+        for node in &self.nodes {
+            let _ = writeln!(out, "    {:?};", node.display().to_string());
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    {:?} -> {:?};",
+                self.nodes[edge.from].display().to_string(),
+                self.nodes[edge.to].display().to_string()
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Makefile-style depfile: `target: every file the build read`.
+    pub fn to_depfile(&self, target: &str) -> String {
+        let mut out = escape_make(target);
+        out.push(':');
+        for node in &self.nodes {
+            out.push_str(" \\\n ");
+            out.push_str(&escape_make(&node.display().to_string()));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+fn escape_make(path: &str) -> String {
+    path.replace('$', "$$")
+        .replace(' ', "\\ ")
+        .replace('#', "\\#")
+}
@@ -0,0 +1,341 @@
+//! Incremental reparsing for editors.
+//!
+//! [`ParseResult::update`] applies one text edit to an earlier parse. It
+//! relexes from the last token the edit can't have influenced until the new
+//! tokens line up with the old ones again, then reparses top-level
+//! statements from the last one the changed tokens can't have influenced
+//! until the parser reaches the start of an old statement in the unchanged
+//! tail. Everything after that is reused with its spans shifted.
+
+use crate::parser::{
+    self, LexOptions, ParseError, Parser, Statement, StatementKind, Token, TokenStream,
+};
+use crate::source::{FileId, SourceFile};
+use crate::trace::debug;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Bytes past its end the lexer may have read to settle a valid token
+/// (`0x` followed by a non-digit, `for` followed by `!`).
+const LEX_LOOKAHEAD: usize = 3;
+
+/// Tokens past its last one the parser may have peeked at to end a
+/// statement (the operand loop stops on the first non-operand).
+const PARSE_LOOKAHEAD: usize = 2;
+
+/// Replace the bytes in `range` with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+}
+
+/// A parse that can be brought up to date after an edit without starting
+/// over.
+pub struct ParseResult {
+    source: Rc<SourceFile>,
+    file: FileId,
+    tokens: Vec<Token>,
+    lex_errors: Vec<Range<usize>>,
+    statements: Vec<Statement>,
+    /// Token indices each top-level statement was parsed from.
+    extents: Vec<Range<usize>>,
+    errors: Vec<ParseError>,
+}
+
+impl ParseResult {
+    /// Parses `text` from scratch. Like [`Parser::parse`], a syntax error
+    /// is recorded and the statements around it still parse.
+    pub fn new(text: &str) -> Self {
+        let file = FileId::default();
+        let source = Rc::new(SourceFile::new("<input>", text));
+
+        let mut tokens = Vec::with_capacity(text.len() / 4);
+        let mut lex_errors = Vec::new();
+        let start = source.start();
+        for item in parser::lex(&text[start..], start, file, LexOptions::default()) {
+            match item {
+                Ok(tok) => tokens.push(tok),
+                Err(span) => lex_errors.push(span),
+            }
+        }
+
+        let parsed = parse_from(tokens, 0, file, &source, |_| None);
+        Self {
+            source,
+            file,
+            tokens: parsed.tokens,
+            lex_errors,
+            statements: parsed.statements,
+            extents: parsed.extents,
+            errors: parsed.errors,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source.text
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    pub fn into_statements(self) -> Vec<Statement> {
+        self.statements
+    }
+
+    /// Syntax errors, in source order.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Applies `edit` and returns the parse of the new text, equal to what
+    /// [`ParseResult::new`] would produce for it. Consumes `self` so the
+    /// unchanged parts move over instead of being cloned.
+    ///
+    /// Panics if the edit's range is out of bounds or not on char
+    /// boundaries.
+    pub fn update(self, edit: TextEdit) -> ParseResult {
+        let TextEdit { range, text } = edit;
+        let mut new_text = self.source.text.to_string();
+        new_text.replace_range(range.clone(), &text);
+        let source = Rc::new(SourceFile::new(self.source.name.clone(), new_text));
+
+        let new_end = range.start + text.len();
+        let shift = |offset: usize| offset - range.end + new_end;
+
+        // Relex from the end of the last token that can't have seen the edit.
+        // A lexer error's lookahead is unbounded (an unterminated string
+        // reads to the end of input), so never restart after one.
+        let mut kept = self
+            .tokens
+            .partition_point(|t| t.span.range.end + LEX_LOOKAHEAD <= range.start);
+        let mut restart = kept
+            .checked_sub(1)
+            .map_or(0, |i| self.tokens[i].span.range.end);
+        if let Some(err) = self.lex_errors.iter().find(|e| e.start < range.start)
+            && err.start < restart
+        {
+            restart = err.start;
+            kept = self
+                .tokens
+                .partition_point(|t| t.span.range.start < restart);
+        }
+        // The edit may have added or removed a byte order mark.
+        restart = restart.max(source.start());
+        let kept_errors = self.lex_errors.partition_point(|e| e.start < restart);
+
+        // Stop at the first item that starts where an old one did in the
+        // unchanged tail: from there on the lexer repeats itself.
+        let mut relexed = Vec::new();
+        let mut new_errors = Vec::new();
+        let mut resync = (self.tokens.len(), self.lex_errors.len());
+        let relex = parser::lex(&source.text[restart..], restart, self.file, LexOptions::default());
+        for item in relex {
+            let start = match &item {
+                Ok(tok) => tok.span.range.start,
+                Err(span) => span.start,
+            };
+            if start >= new_end {
+                let old = start - new_end + range.end;
+                let tok = self.tokens.partition_point(|t| t.span.range.start < old);
+                let err = self.lex_errors.partition_point(|e| e.start < old);
+                let at_token = self
+                    .tokens
+                    .get(tok)
+                    .is_some_and(|t| t.span.range.start == old);
+                let at_error = self.lex_errors.get(err).is_some_and(|e| e.start == old);
+                if at_token || at_error {
+                    resync = (tok, err);
+                    break;
+                }
+            }
+            match item {
+                Ok(tok) => relexed.push(tok),
+                Err(span) => new_errors.push(span),
+            }
+        }
+
+        let mut tokens = self.tokens;
+        let mut tail = tokens.split_off(resync.0);
+        tokens.truncate(kept);
+        tokens.append(&mut relexed);
+        for tok in &mut tail {
+            tok.span.range = shift(tok.span.range.start)..shift(tok.span.range.end);
+        }
+        let new_resync = tokens.len();
+        tokens.append(&mut tail);
+
+        let mut lex_errors = self.lex_errors;
+        let error_tail = lex_errors.split_off(resync.1);
+        lex_errors.truncate(kept_errors);
+        lex_errors.append(&mut new_errors);
+        lex_errors.extend(error_tail.into_iter().map(|e| shift(e.start)..shift(e.end)));
+
+        // Reparse from the end of the last statement that can't have seen
+        // a changed token, until the parser lands on the start of an old
+        // statement in the unchanged tail.
+        let first = self
+            .extents
+            .partition_point(|e| e.end + PARSE_LOOKAHEAD <= kept);
+        let from = first.checked_sub(1).map_or(0, |i| self.extents[i].end);
+        let old_resync = resync.0;
+        let old_extents = &self.extents;
+        let mut reparsed = parse_from(tokens, from, self.file, &source, |pos| {
+            let old = (pos >= new_resync).then(|| pos - new_resync + old_resync)?;
+            old_extents.binary_search_by_key(&old, |e| e.start).ok()
+        });
+
+        debug!(
+            relexed = new_resync - kept,
+            reparsed = reparsed.statements.len(),
+            "incremental update"
+        );
+
+        // A syntax error lies between the statements around it, so the
+        // errors before the reparsed range and in the reused tail carry
+        // over like the statements there.
+        let errors_before = first
+            .checked_sub(1)
+            .map_or(0, |i| self.statements[i].span.range.end);
+        let errors_after = reparsed
+            .reuse
+            .map_or(usize::MAX, |i| self.statements[i].span.range.start);
+        let mut errors = self.errors;
+        let tail = errors.partition_point(|e| e.span.range.start < errors_after);
+        let error_tail = errors.split_off(tail);
+        errors.truncate(errors.partition_point(|e| e.span.range.start < errors_before));
+        errors.append(&mut reparsed.errors);
+        errors.extend(error_tail.into_iter().map(|mut e| {
+            e.span.range = shift(e.span.range.start)..shift(e.span.range.end);
+            e
+        }));
+
+        let reuse = reparsed.reuse.unwrap_or(self.statements.len());
+        let mut statements = self.statements;
+        let mut stmt_tail = statements.split_off(reuse);
+        statements.truncate(first);
+        statements.append(&mut reparsed.statements);
+        for stmt in &mut stmt_tail {
+            shift_statement(stmt, &shift);
+        }
+        statements.append(&mut stmt_tail);
+
+        let mut extents = self.extents;
+        let extent_tail = extents.split_off(reuse);
+        extents.truncate(first);
+        extents.append(&mut reparsed.extents);
+        extents.extend(
+            extent_tail
+                .into_iter()
+                .map(|e| e.start - old_resync + new_resync..e.end - old_resync + new_resync),
+        );
+
+        ParseResult {
+            source,
+            file: self.file,
+            tokens: reparsed.tokens,
+            lex_errors,
+            statements,
+            extents,
+            errors,
+        }
+    }
+}
+
+/// What [`parse_from`] parsed.
+struct Reparsed {
+    /// All the tokens, with any payloads the parser moved out put back.
+    tokens: Vec<Token>,
+    statements: Vec<Statement>,
+    extents: Vec<Range<usize>>,
+    errors: Vec<ParseError>,
+    /// The old statement to reuse from, if the parser reached one.
+    reuse: Option<usize>,
+}
+
+/// Runs the top-level parse loop from token `from` until `stop` names the
+/// old statement to resume from (or the input ends).
+fn parse_from(
+    tokens: Vec<Token>,
+    from: usize,
+    file: FileId,
+    source: &Rc<SourceFile>,
+    mut stop: impl FnMut(usize) -> Option<usize>,
+) -> Reparsed {
+    let mut parser =
+        Parser::from_stream(TokenStream::from_tokens(tokens, from, file, source.clone()));
+    let mut statements = Vec::new();
+    let mut extents = Vec::new();
+
+    let reuse = loop {
+        let pos = parser.stream().position();
+        if let Some(old) = stop(pos) {
+            break Some(old);
+        }
+        if parser.stream().eof() {
+            break None;
+        }
+        if let Some(stmt) = parser.step() {
+            extents.push(pos..parser.stream().position());
+            statements.push(stmt);
+        }
+    };
+
+    let end = parser.stream().position();
+    let errors = parser.errors().to_vec();
+    let mut tokens = parser.into_stream().into_tokens();
+    tokens[from..end].iter_mut().for_each(Token::restore);
+    Reparsed {
+        tokens,
+        statements,
+        extents,
+        errors,
+        reuse,
+    }
+}
+
+fn shift_statement(stmt: &mut Statement, shift: &impl Fn(usize) -> usize) {
+    stmt.span.range = shift(stmt.span.range.start)..shift(stmt.span.range.end);
+    match &mut stmt.kind {
+        StatementKind::Instruction { spans, .. } | StatementKind::Data { spans, .. } => {
+            for span in spans {
+                span.range = shift(span.range.start)..shift(span.range.end);
+            }
+        }
+        StatementKind::MacroDef { body, .. }
+        | StatementKind::Module { body, .. }
+        | StatementKind::ForLoop { body, .. }
+        | StatementKind::WhileLoop { body, .. }
+        | StatementKind::Repeat { body, .. }
+        | StatementKind::Foreach { body, .. }
+        | StatementKind::Block(body) => {
+            for inner in body {
+                shift_statement(inner, shift);
+            }
+        }
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            let bodies = branches.iter_mut().map(|branch| &mut branch.body);
+            for inner in bodies.chain([otherwise]).flatten() {
+                shift_statement(inner, shift);
+            }
+        }
+        _ => {}
+    }
+}
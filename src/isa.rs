@@ -0,0 +1,566 @@
+//! Instruction sets, and operand field ranges for instruction encoding.
+//!
+//! An [`Isa`] is a table of what each mnemonic assembles to, one
+//! instruction a line:
+//!
+//! ```text
+//! # mnemonic  opcode  operands
+//! nop         0x00
+//! mov         0x10    reg reg
+//! addi        0x13    reg s8
+//! jmp         0x20    u16
+//! beq         0x23    rel8
+//! ```
+//!
+//! An instruction is its opcode byte, then each operand in order,
+//! little-endian: `reg` is a register's number in a byte, `u8`, `u16` and
+//! `u32` unsigned values, `s8`, `s16` and `s32` signed ones, and `rel8` and
+//! `rel16` signed distances from the end of the instruction to a target.
+//! A label in a `u` or `s` field stands for its address, and in a `rel`
+//! field for the distance to it.
+//!
+//! The encoder, [`assemble`](crate::assemble), calls [`check_operand`] for
+//! every immediate it's about to pack, and [`encode_target`] for label
+//! operands once addresses are known, so out-of-range values are reported
+//! instead of truncated.
+//!
+//! Each [`Target`] also says what its register names look like, so the
+//! lexer can tell registers from symbols, and which instructions it has.
+
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::parser::Encoding;
+use crate::source::Span;
+use crate::tokens::parse_string;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// An operand field of an instruction encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub bits: u32,
+    /// Two's-complement signed rather than unsigned.
+    pub signed: bool,
+}
+
+impl Field {
+    pub const fn unsigned(bits: u32) -> Self {
+        Self {
+            bits,
+            signed: false,
+        }
+    }
+
+    pub const fn signed(bits: u32) -> Self {
+        Self { bits, signed: true }
+    }
+
+    /// The values the field can hold. Fields wider than 64 bits hold any
+    /// `i64` the same as a 64-bit field would.
+    pub fn range(self) -> RangeInclusive<i64> {
+        let bits = self.bits.min(64);
+        match (self.signed, bits) {
+            (_, 0) => 0..=0,
+            (true, bits) => {
+                let half = 1i128 << (bits - 1);
+                (-half) as i64..=(half - 1) as i64
+            }
+            (false, bits) => 0..=((1i128 << bits) - 1).min(i64::MAX as i128) as i64,
+        }
+    }
+
+    pub fn fits(self, value: i64) -> bool {
+        self.range().contains(&value)
+    }
+
+    /// `value` as the field's bit pattern, or `None` if it doesn't fit.
+    pub fn encode(self, value: i64) -> Option<u64> {
+        if !self.fits(value) {
+            return None;
+        }
+        Some(value as u64 & mask(self.bits))
+    }
+
+    /// `8-bit signed field holds -128..=127`, for diagnostics.
+    pub fn describe(self) -> String {
+        let range = self.range();
+        format!(
+            "{}-bit {} field holds {}..={}",
+            self.bits,
+            if self.signed { "signed" } else { "unsigned" },
+            range.start(),
+            range.end()
+        )
+    }
+}
+
+/// Checks operand `position` (counting from 1) of `instruction` against
+/// its field.
+pub fn check_operand(
+    instruction: &str,
+    position: usize,
+    value: i64,
+    field: Field,
+    span: Span,
+) -> Result<u64, Diagnostic> {
+    field.encode(value).ok_or_else(|| {
+        let mut diag = Diagnostic::error(format!(
+            "operand {} of `{}` is out of range: {}",
+            position, instruction, value
+        ))
+        .with_code(Code::E0301)
+        .with_span(span)
+        .with_note(format!("the {}", field.describe()));
+        if value < 0 && !field.signed {
+            diag = diag.with_note("negative values can't go in an unsigned field");
+        }
+        diag
+    })
+}
+
+/// Checks a PC-relative branch once addresses are known: the offset from
+/// `from` (the address the ISA measures from, usually the end of the
+/// branch) to `target` has to fit the branch's field.
+pub fn check_branch(
+    instruction: &str,
+    position: usize,
+    from: u64,
+    target: u64,
+    field: Field,
+    span: Span,
+) -> Result<u64, Diagnostic> {
+    let offset = target.wrapping_sub(from) as i64;
+    field.encode(offset).ok_or_else(|| {
+        let range = field.range();
+        Diagnostic::error(format!(
+            "branch target of `{}` is out of range: offset {} from {:#x} to {:#x}",
+            instruction, offset, from, target
+        ))
+        .with_code(Code::E0302)
+        .with_span(span)
+        .with_note(format!("operand {}: the {}", position, field.describe()))
+        .with_note(format!(
+            "reachable targets are {:#x}..={:#x}",
+            from.wrapping_add(*range.start() as u64),
+            from.wrapping_add(*range.end() as u64)
+        ))
+    })
+}
+
+/// How a label operand becomes a field value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    /// The label's address.
+    Absolute,
+    /// The signed distance from the end of the instruction to the label.
+    Relative,
+}
+
+impl Addressing {
+    /// The mode an operand wrapper forces: `abs(target)` or `rel(target)`.
+    ///
+    /// These wrap a whole operand and are handled here, not by the
+    /// expression evaluator; `abs` inside an expression is the arithmetic
+    /// builtin.
+    pub fn from_wrapper(name: &str) -> Option<Self> {
+        match name {
+            "abs" => Some(Addressing::Absolute),
+            "rel" => Some(Addressing::Relative),
+            _ => None,
+        }
+    }
+}
+
+/// One operand of an instruction's encoding: its field, and how a bare
+/// label in that position is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operand {
+    pub field: Field,
+    pub addressing: Addressing,
+}
+
+/// How one operand of an instruction is written, and encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub enum OperandSpec {
+    /// A register, its number in a byte.
+    Register,
+    /// A value: an immediate, an address or a branch target.
+    Value(Operand),
+}
+
+impl OperandSpec {
+    /// Bytes the operand takes.
+    pub fn size(self) -> u64 {
+        match self {
+            OperandSpec::Register => 1,
+            OperandSpec::Value(operand) => u64::from(operand.field.bits / 8),
+        }
+    }
+}
+
+impl FromStr for OperandSpec {
+    type Err = String;
+
+    /// `reg`, `u8`, `s16`, `rel8` and so on.
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s == "reg" {
+            return Ok(OperandSpec::Register);
+        }
+        let unknown = || format!("`{}` is not an operand; try `reg`, `u16` or `rel8`", s);
+        let (kind, bits) = s.split_at(s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len()));
+        let bits = match bits {
+            "8" => 8,
+            "16" => 16,
+            "32" => 32,
+            _ => return Err(unknown()),
+        };
+        let (field, addressing) = match kind {
+            "u" => (Field::unsigned(bits), Addressing::Absolute),
+            "s" => (Field::signed(bits), Addressing::Absolute),
+            "rel" if bits <= 16 => (Field::signed(bits), Addressing::Relative),
+            _ => return Err(unknown()),
+        };
+        Ok(OperandSpec::Value(Operand { field, addressing }))
+    }
+}
+
+impl TryFrom<String> for OperandSpec {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<OperandSpec> for String {
+    fn from(spec: OperandSpec) -> Self {
+        spec.to_string()
+    }
+}
+
+impl fmt::Display for OperandSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperandSpec::Register => f.write_str("reg"),
+            OperandSpec::Value(Operand { field, addressing }) => {
+                let kind = match (addressing, field.signed) {
+                    (Addressing::Relative, _) => "rel",
+                    (Addressing::Absolute, true) => "s",
+                    (Addressing::Absolute, false) => "u",
+                };
+                write!(f, "{}{}", kind, field.bits)
+            }
+        }
+    }
+}
+
+/// What one mnemonic assembles to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstructionSpec {
+    pub mnemonic: String,
+    pub opcode: u8,
+    pub operands: Vec<OperandSpec>,
+}
+
+impl InstructionSpec {
+    /// Bytes the instruction takes: its opcode and its operands.
+    pub fn size(&self) -> u64 {
+        1 + self.operands.iter().map(|op| op.size()).sum::<u64>()
+    }
+
+    /// `mov reg, reg`, for diagnostics.
+    pub fn usage(&self) -> String {
+        let operands: Vec<String> = self.operands.iter().map(|op| op.to_string()).collect();
+        match operands.as_slice() {
+            [] => self.mnemonic.clone(),
+            _ => format!("{} {}", self.mnemonic, operands.join(", ")),
+        }
+    }
+}
+
+/// An instruction set, read from a table like the one at the top of this
+/// module.
+///
+/// With the `serde` feature it's also JSON, each operand written as in the
+/// table: `{"instructions":[{"mnemonic":"ldi","opcode":17,"operands":["reg","u16"]}]}`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Isa {
+    pub instructions: Vec<InstructionSpec>,
+}
+
+impl Isa {
+    /// The instruction `mnemonic` names, ignoring ASCII case.
+    pub fn instruction(&self, mnemonic: &str) -> Option<&InstructionSpec> {
+        self.instructions
+            .iter()
+            .find(|spec| spec.mnemonic.eq_ignore_ascii_case(mnemonic))
+    }
+
+    pub fn mnemonics(&self) -> impl Iterator<Item = &str> {
+        self.instructions.iter().map(|spec| spec.mnemonic.as_str())
+    }
+
+    /// How wide an address is: the widest unsigned field an instruction
+    /// has, since that's what an absolute address goes in. 16 for the
+    /// generic target, and 64 for a table without one.
+    pub fn address_bits(&self) -> u32 {
+        self.instructions
+            .iter()
+            .flat_map(|spec| &spec.operands)
+            .filter_map(|operand| match operand {
+                OperandSpec::Value(Operand { field, addressing })
+                    if *addressing == Addressing::Absolute && !field.signed =>
+                {
+                    Some(field.bits)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(64)
+    }
+}
+
+impl FromStr for Isa {
+    type Err = String;
+
+    /// Reads a table, `#` starting a comment. Errors say which line.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut isa = Isa::default();
+        for (index, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(mnemonic) = words.next() else {
+                continue;
+            };
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            let opcode = words
+                .next()
+                .ok_or_else(|| error(format!("`{}` has no opcode", mnemonic)))?;
+            let opcode = match opcode.strip_prefix("0x") {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => opcode.parse(),
+            }
+            .map_err(|_| error(format!("`{}` is not an opcode byte", opcode)))?;
+            let operands = words
+                .map(|word| word.trim_end_matches(',').parse())
+                .collect::<Result<_, _>>()
+                .map_err(error)?;
+            if isa.instruction(mnemonic).is_some() {
+                return Err(error(format!("`{}` is listed twice", mnemonic)));
+            }
+            isa.instructions.push(InstructionSpec {
+                mnemonic: mnemonic.to_string(),
+                opcode,
+                operands,
+            });
+        }
+        Ok(isa)
+    }
+}
+
+/// Encodes label operand `position` of `instruction` in the second pass.
+///
+/// `target` is the label's address and `end` the address just past the
+/// instruction. `forced` is the mode from an `abs()`/`rel()` wrapper;
+/// without one the operand's declared addressing applies.
+pub fn encode_target(
+    instruction: &str,
+    position: usize,
+    operand: Operand,
+    forced: Option<Addressing>,
+    target: u64,
+    end: u64,
+    span: Span,
+) -> Result<u64, Diagnostic> {
+    match forced.unwrap_or(operand.addressing) {
+        Addressing::Absolute => {
+            check_operand(instruction, position, target as i64, operand.field, span)
+        }
+        Addressing::Relative => {
+            check_branch(instruction, position, end, target, operand.field, span)
+        }
+    }
+}
+
+/// Packs `value` into a `bits`-wide data item (`.byte` is 8, `.word` 16).
+///
+/// Data has no signedness, so anything that fits as either unsigned or
+/// two's-complement signed is accepted: `-1` in a byte is `0xff`. Anything
+/// else is truncated, with a warning to be passed through
+/// [`LintLevels::report`](crate::lint::LintLevels::report) as
+/// [`Lint::DataRange`](crate::lint::Lint::DataRange).
+pub fn check_data(directive: &str, bits: u32, value: i64, span: Span) -> (u64, Option<Diagnostic>) {
+    let unsigned = Field::unsigned(bits);
+    let signed = Field::signed(bits);
+    if let Some(bytes) = unsigned.encode(value).or_else(|| signed.encode(value)) {
+        return (bytes, None);
+    }
+
+    let truncated = value as u64 & mask(bits);
+    let diag = Diagnostic::warning(format!(
+        "`{}` value {} doesn't fit in {} bits",
+        directive, value, bits
+    ))
+    .with_code(Code::W0204)
+    .with_span(span)
+    .with_note(format!(
+        "{}-bit data holds {}..={} or {}..={}; this would be stored as {:#x}",
+        bits,
+        signed.range().start(),
+        signed.range().end(),
+        unsigned.range().start(),
+        unsigned.range().end(),
+        truncated
+    ));
+    (truncated, Some(diag))
+}
+
+/// The bytes string data like `.asciiz "hi"` stores: the unescaped
+/// `literal` in `encoding`, then a NUL as wide as one of its code units if
+/// `nul`.
+pub fn encode_string(
+    encoding: Encoding,
+    nul: bool,
+    literal: &str,
+    span: Span,
+) -> Result<Vec<u8>, Diagnostic> {
+    let mut bytes = encoding.encode(&parse_string(literal)).map_err(|c| {
+        Diagnostic::error(format!(
+            "`{}` can't hold {:?} (U+{:04X})",
+            encoding.directive(nul),
+            c,
+            u32::from(c)
+        ))
+        .with_code(Code::E0303)
+        .with_span(span)
+        .with_note(format!(
+            "`{}` and `{}` hold any character",
+            Encoding::Utf8.directive(nul),
+            Encoding::Utf16.directive(nul)
+        ))
+    })?;
+    if nul {
+        bytes.resize(bytes.len() + encoding.unit(), 0);
+    }
+    Ok(bytes)
+}
+
+/// An architecture chasm assembles for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub name: &'static str,
+    pub registers: RegisterPattern,
+    /// Its instructions, as a table [`Isa`] reads.
+    pub instructions: &'static str,
+}
+
+impl Target {
+    /// `r0`, `R15` and so on, plus any name written with a `%`.
+    pub const GENERIC: Target = Target {
+        name: "generic",
+        registers: RegisterPattern {
+            sigil: Some('%'),
+            numbered: &["r"],
+            named: &[],
+        },
+        instructions: GENERIC_INSTRUCTIONS,
+    };
+
+    pub const DEFAULT: Target = Target::GENERIC;
+}
+
+pub const TARGETS: &[Target] = &[Target::GENERIC];
+
+pub fn target(name: &str) -> Option<&'static Target> {
+    TARGETS.iter().find(|t| t.name == name)
+}
+
+impl Target {
+    pub fn isa(&self) -> Isa {
+        self.instructions
+            .parse()
+            .unwrap_or_else(|e| panic!("the `{}` instruction table: {}", self.name, e))
+    }
+}
+
+/// A small load/store machine with 16-bit addresses.
+const GENERIC_INSTRUCTIONS: &str = "
+nop   0x00
+halt  0x01
+ret   0x02
+mov   0x10 reg reg
+ldi   0x11 reg u16
+add   0x12 reg reg
+addi  0x13 reg s8
+sub   0x14 reg reg
+subi  0x15 reg s8
+ld    0x16 reg u16
+st    0x17 u16 reg
+push  0x18 reg
+pop   0x19 reg
+nand  0x1a reg reg
+jmp   0x20 u16
+call  0x21 u16
+br    0x22 rel8
+beq   0x23 rel8
+bne   0x24 rel8
+";
+
+/// Which identifiers a target lexes as registers. Names are matched
+/// ignoring ASCII case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterPattern {
+    /// Makes the identifier right after it a register, as in `%tmp`.
+    pub sigil: Option<char>,
+    /// Prefixes that make a register when followed by a decimal number:
+    /// `r` covers `r0` and `R15`.
+    pub numbered: &'static [&'static str],
+    /// Registers named outright, like `sp`.
+    pub named: &'static [&'static str],
+}
+
+impl RegisterPattern {
+    /// Whether identifier `name` is a register without a sigil.
+    pub fn matches(&self, name: &str) -> bool {
+        let numbered = self.numbered.iter().any(|prefix| {
+            name.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix))
+                && name.len() > prefix.len()
+                && name[prefix.len()..].bytes().all(|b| b.is_ascii_digit())
+        });
+        numbered || self.named.iter().any(|n| n.eq_ignore_ascii_case(name))
+    }
+
+    /// The number register `name` is encoded as: `r3` is 3, and a named
+    /// register its place in `named`. `None` for one written with the
+    /// sigil, which only the programmer knows the meaning of.
+    pub fn number(&self, name: &str) -> Option<u64> {
+        if let Some(index) = self.named.iter().position(|n| n.eq_ignore_ascii_case(name)) {
+            return Some(index as u64);
+        }
+        self.numbered.iter().find_map(|prefix| {
+            let digits = name.get(prefix.len()..)?;
+            let matches = name[..prefix.len()].eq_ignore_ascii_case(prefix);
+            matches.then(|| digits.parse().ok()).flatten()
+        })
+    }
+
+    /// Whether `text` is the sigil on its own.
+    pub fn is_sigil(&self, text: &str) -> bool {
+        self.sigil.is_some_and(|c| text.len() == c.len_utf8() && text.starts_with(c))
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    match bits {
+        bits if bits >= 64 => u64::MAX,
+        bits => (1u64 << bits) - 1,
+    }
+}
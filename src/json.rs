@@ -0,0 +1,383 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parser::{Expr, LexError, Operand, Parser, Statement, StatementKind};
+use crate::source::SourceManager;
+use std::fmt::Write;
+
+/// Parses `source` and returns `{"ast": [...]}`, or `{"diagnostics": [...]}`
+/// with every lexing and syntax error if there were any.
+pub fn parse_to_json(source: &str) -> String {
+    let mut sources = SourceManager::new();
+    let file = sources.add_virtual("input", source);
+    let mut parser = Parser::for_file(&sources, file);
+
+    let mut ast = Vec::new();
+    let mut diags = Vec::new();
+    for item in parser.statements() {
+        match item {
+            Ok(stmt) => ast.push(stmt),
+            Err(err) => diags.push(err.to_diagnostic()),
+        }
+    }
+    diags.extend(parser.lex_errors().iter().map(LexError::to_diagnostic));
+    diags.sort_by_key(|diag| diag.span.as_ref().map(|span| span.range.start));
+
+    if diags.is_empty() {
+        format!("{{\"ast\":{}}}", statements_to_json(&ast))
+    } else {
+        diagnostics_to_json(&diags, &sources)
+    }
+}
+
+/// `{"diagnostics": [{"severity": ..., "code": ..., "message": ...,
+/// "file": ..., "line": ..., "column": ...}, ...]}`. `code` is `null` for
+/// diagnostics without one; `file`, `line` and `column` are left out if
+/// there's no span.
+pub fn diagnostics_to_json(diags: &[Diagnostic], sources: &SourceManager) -> String {
+    let items: Vec<String> = diags
+        .iter()
+        .map(|diag| diagnostic_to_json(diag, sources))
+        .collect();
+    format!("{{\"diagnostics\":[{}]}}", items.join(","))
+}
+
+pub fn diagnostic_to_json(diag: &Diagnostic, sources: &SourceManager) -> String {
+    let severity = match diag.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    let code = match diag.code {
+        Some(code) => string(code.as_str()),
+        None => "null".to_string(),
+    };
+    let mut out = format!(
+        "{{\"severity\":{},\"code\":{},\"message\":{}",
+        string(severity),
+        code,
+        string(&diag.message)
+    );
+    if let Some(span) = &diag.span {
+        let (line, col) = sources.line_col(span.file, span.range.start);
+        let _ = write!(
+            out,
+            ",\"file\":{},\"line\":{},\"column\":{}",
+            string(sources.name(span.file)),
+            line,
+            col
+        );
+    }
+    out.push('}');
+    out
+}
+
+pub fn statements_to_json(stmts: &[Statement]) -> String {
+    let items: Vec<String> = stmts.iter().map(statement_to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
+pub fn statement_to_json(stmt: &Statement) -> String {
+    match &stmt.kind {
+        StatementKind::VarAssign { name, expr } => format!(
+            "{{\"kind\":\"VarAssign\",\"name\":{},\"expr\":{}}}",
+            string(name),
+            expr_to_json(expr)
+        ),
+        StatementKind::VarUpdate { name, op, expr } => format!(
+            "{{\"kind\":\"VarUpdate\",\"name\":{},\"op\":{},\"expr\":{}}}",
+            string(name),
+            string(op.as_str()),
+            expr_to_json(expr)
+        ),
+        StatementKind::ConstAssign { name, expr } => format!(
+            "{{\"kind\":\"ConstAssign\",\"name\":{},\"expr\":{}}}",
+            string(name),
+            expr_to_json(expr)
+        ),
+        StatementKind::Equ { name, expr } => format!(
+            "{{\"kind\":\"Equ\",\"name\":{},\"expr\":{}}}",
+            string(name),
+            expr_to_json(expr)
+        ),
+        StatementKind::Label { name, visibility } => format!(
+            "{{\"kind\":\"Label\",\"name\":{},\"visibility\":{}}}",
+            string(name),
+            string(visibility.as_str())
+        ),
+        StatementKind::Instruction { name, args, .. } => {
+            let args: Vec<String> = args.iter().map(operand_to_json).collect();
+            format!(
+                "{{\"kind\":\"Instruction\",\"name\":{},\"args\":[{}]}}",
+                string(name),
+                args.join(",")
+            )
+        }
+        StatementKind::Directive { name, args } => format!(
+            "{{\"kind\":\"Directive\",\"name\":{},\"args\":{}}}",
+            string(name),
+            strings(args)
+        ),
+        StatementKind::Org(address) => {
+            format!("{{\"kind\":\"Org\",\"address\":{}}}", expr_to_json(address))
+        }
+        StatementKind::Align { boundary, fill } => format!(
+            "{{\"kind\":\"Align\",\"boundary\":{},\"fill\":{}}}",
+            expr_to_json(boundary),
+            fill.as_ref().map_or("null".to_string(), expr_to_json)
+        ),
+        StatementKind::Include(path) => {
+            format!("{{\"kind\":\"Include\",\"path\":{}}}", string(path))
+        }
+        StatementKind::Data { width, values, .. } => {
+            let values: Vec<String> = values.iter().map(expr_to_json).collect();
+            format!(
+                "{{\"kind\":\"Data\",\"width\":{},\"values\":[{}]}}",
+                string(width.as_str()),
+                values.join(",")
+            )
+        }
+        StatementKind::Section(name) => {
+            format!("{{\"kind\":\"Section\",\"name\":{}}}", string(name))
+        }
+        StatementKind::StringData {
+            encoding,
+            nul,
+            literal,
+        } => format!(
+            "{{\"kind\":\"StringData\",\"encoding\":{},\"nul\":{},\"literal\":{}}}",
+            string(encoding.as_str()),
+            nul,
+            string(literal)
+        ),
+        StatementKind::MacroDef {
+            name,
+            params,
+            defaults,
+            body,
+        } => {
+            let defaults: Vec<String> = defaults.iter().map(operand_to_json).collect();
+            format!(
+                "{{\"kind\":\"MacroDef\",\"name\":{},\"params\":{},\"defaults\":[{}],\"body\":{}}}",
+                string(name),
+                strings(params),
+                defaults.join(","),
+                statements_to_json(body)
+            )
+        }
+        StatementKind::MacroCall { name, args } => {
+            let args: Vec<String> = args.iter().map(operand_to_json).collect();
+            format!(
+                "{{\"kind\":\"MacroCall\",\"name\":{},\"args\":[{}]}}",
+                string(name),
+                args.join(",")
+            )
+        }
+        StatementKind::ForLoop {
+            var,
+            start,
+            cmp,
+            end,
+            op,
+            step,
+            body,
+        } => format!(
+            "{{\"kind\":\"ForLoop\",\"var\":{},\"start\":{},\"cmp\":{},\"end\":{},\
+             \"op\":{},\"step\":{},\"body\":{}}}",
+            string(var),
+            expr_to_json(start),
+            string(cmp.as_str()),
+            expr_to_json(end),
+            string(op.as_str()),
+            expr_to_json(step),
+            statements_to_json(body)
+        ),
+        StatementKind::WhileLoop { cond, body } => format!(
+            "{{\"kind\":\"WhileLoop\",\"cond\":{},\"body\":{}}}",
+            expr_to_json(cond),
+            statements_to_json(body)
+        ),
+        StatementKind::Repeat { count, body } => format!(
+            "{{\"kind\":\"Repeat\",\"count\":{},\"body\":{}}}",
+            expr_to_json(count),
+            statements_to_json(body)
+        ),
+        StatementKind::Break => "{\"kind\":\"Break\"}".to_string(),
+        StatementKind::Continue => "{\"kind\":\"Continue\"}".to_string(),
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            let branches: Vec<String> = branches
+                .iter()
+                .map(|branch| {
+                    format!(
+                        "{{\"cond\":{},\"body\":{}}}",
+                        expr_to_json(&branch.cond),
+                        statements_to_json(&branch.body)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"kind\":\"If\",\"branches\":[{}],\"otherwise\":{}}}",
+                branches.join(","),
+                statements_to_json(otherwise)
+            )
+        }
+        StatementKind::Foreach { param, body } => format!(
+            "{{\"kind\":\"Foreach\",\"param\":{},\"body\":{}}}",
+            string(param),
+            statements_to_json(body)
+        ),
+        StatementKind::Module { name, body } => format!(
+            "{{\"kind\":\"Module\",\"name\":{},\"body\":{}}}",
+            string(name),
+            statements_to_json(body)
+        ),
+        StatementKind::Struct { name, fields } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|field| {
+                    format!(
+                        "{{\"name\":{},\"width\":{}}}",
+                        string(&field.name),
+                        string(field.width.as_str())
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"kind\":\"Struct\",\"name\":{},\"fields\":[{}]}}",
+                string(name),
+                fields.join(",")
+            )
+        }
+        StatementKind::StructData { name, fields } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, value)| {
+                    format!(
+                        "{{\"name\":{},\"value\":{}}}",
+                        string(field),
+                        expr_to_json(value)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"kind\":\"StructData\",\"name\":{},\"fields\":[{}]}}",
+                string(name),
+                fields.join(",")
+            )
+        }
+        StatementKind::Enum { name, variants } => {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|(variant, value)| {
+                    format!(
+                        "{{\"name\":{},\"value\":{}}}",
+                        string(variant),
+                        value.as_ref().map_or("null".to_string(), expr_to_json)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"kind\":\"Enum\",\"name\":{},\"variants\":[{}]}}",
+                string(name),
+                variants.join(",")
+            )
+        }
+        StatementKind::Block(body) => {
+            format!(
+                "{{\"kind\":\"Block\",\"body\":{}}}",
+                statements_to_json(body)
+            )
+        }
+    }
+}
+
+/// Numbers are bare JSON numbers; everything else is an object tagged with
+/// its `kind`, as statements are.
+pub fn expr_to_json(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(n) => n.to_string(),
+        Expr::Str(literal) => format!("{{\"kind\":\"Str\",\"text\":{}}}", string(literal)),
+        Expr::Symbol(name) => format!("{{\"kind\":\"Symbol\",\"name\":{}}}", string(name)),
+        Expr::Unary { op, expr } => format!(
+            "{{\"kind\":\"Unary\",\"op\":{},\"expr\":{}}}",
+            string(op.as_str()),
+            expr_to_json(expr)
+        ),
+        Expr::Binary { op, lhs, rhs } => format!(
+            "{{\"kind\":\"Binary\",\"op\":{},\"lhs\":{},\"rhs\":{}}}",
+            string(op.as_str()),
+            expr_to_json(lhs),
+            expr_to_json(rhs)
+        ),
+        Expr::Call { name, args } => {
+            let args: Vec<String> = args.iter().map(expr_to_json).collect();
+            format!(
+                "{{\"kind\":\"Call\",\"name\":{},\"args\":[{}]}}",
+                string(name),
+                args.join(",")
+            )
+        }
+        Expr::Array(items) => {
+            let items: Vec<String> = items.iter().map(expr_to_json).collect();
+            format!("{{\"kind\":\"Array\",\"items\":[{}]}}", items.join(","))
+        }
+        Expr::Index { base, index } => format!(
+            "{{\"kind\":\"Index\",\"base\":{},\"index\":{}}}",
+            expr_to_json(base),
+            expr_to_json(index)
+        ),
+    }
+}
+
+/// `{"kind": ..., ...}`, with the operand's name, text or expression.
+pub fn operand_to_json(operand: &Operand) -> String {
+    match operand {
+        Operand::Register(name) => format!("{{\"kind\":\"Register\",\"name\":{}}}", string(name)),
+        Operand::Immediate(expr) => {
+            format!("{{\"kind\":\"Immediate\",\"expr\":{}}}", expr_to_json(expr))
+        }
+        Operand::Symbol(name) => format!("{{\"kind\":\"Symbol\",\"name\":{}}}", string(name)),
+        Operand::Expr(expr) => format!("{{\"kind\":\"Expr\",\"expr\":{}}}", expr_to_json(expr)),
+        Operand::Memory(memory) => {
+            let name = |name: &Option<String>| name.as_deref().map_or("null".to_string(), string);
+            format!(
+                "{{\"kind\":\"Memory\",\"base\":{},\"index\":{},\"scale\":{},\"displacement\":{}}}",
+                name(&memory.base),
+                name(&memory.index),
+                memory.scale,
+                memory
+                    .displacement
+                    .as_ref()
+                    .map_or("null".to_string(), expr_to_json)
+            )
+        }
+        Operand::String(literal) => format!("{{\"kind\":\"String\",\"text\":{}}}", string(literal)),
+        Operand::Char(c) => format!("{{\"kind\":\"Char\",\"char\":{}}}", string(&c.to_string())),
+    }
+}
+
+fn strings(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|s| string(s)).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// `s` as a JSON string, quoted and escaped.
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
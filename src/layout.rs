@@ -0,0 +1,339 @@
+//! Places statements in sections and gives labels their addresses.
+//!
+//! Each statement goes in the section the last `.text`, `.data`, `.bss`,
+//! `.rodata` or `.section NAME` before it picked, `.text` until one does.
+//! Every section keeps its own location counter, so switching away and
+//! back carries on where the section left off:
+//!
+//! ```text
+//! .text
+//! start:  nop             // text + 0
+//! .data
+//! table:  .word 1, 2      // data + 0
+//! .text
+//! next:   nop             // text + 1, with 1-byte instructions
+//! ```
+//!
+//! Sections start at address 0 unless an `@org` before anything else in
+//! them says otherwise. A later `@org` skips ahead to its address, and
+//! `@align 4, 0xff` to the next multiple of 4, the gap filled with `0xff`
+//! bytes (zeros without a fill). The `@align` is an item of its own, as big
+//! as the gap, so whatever writes the bytes out knows what goes there.
+//!
+//! Labels are renamed apart first: those in a `module io` become `io::name`
+//! (see [`scope_modules`]), `.local` labels go under the plain label before
+//! them (see [`scope_local_labels`]), so `.loop` after `main:` is laid out
+//! as `main.loop`, and the second `1:` as `1:1` (see
+//! [`name_numeric_labels`]).
+//!
+//! Layout runs on [`expand`](crate::expand::expand)ed statements; loops
+//! and conditionals still in the input take no space, and neither do macro
+//! calls. There's no encoder yet, so the caller says how big each
+//! instruction is. Given the [`Origins`] of the statements,
+//! [`layout_expanded`] notes which macro call an error is in.
+
+use crate::builtins::Value;
+use crate::codes::Code;
+use crate::diagnostic::{Diagnostic, Expansion};
+use crate::eval::eval;
+use crate::expand::{OriginCursor, Origins};
+use crate::isa::encode_string;
+use crate::object::ObjectSymbol;
+use crate::parser::{Expr, Operand, Statement, StatementKind, Visibility};
+use crate::source::Span;
+use crate::symbols::{SymbolTable, name_numeric_labels, scope_local_labels, scope_modules};
+use std::rc::Rc;
+
+/// Where statements go before any section directive.
+pub const DEFAULT_SECTION: &str = "text";
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The name, without its dot.
+    pub name: String,
+    /// The address of the section's first byte.
+    pub start: u64,
+    /// The location counter: the address the next statement goes at.
+    pub pc: u64,
+    /// Labels and the statements that take space, each at its address.
+    pub items: Vec<(u64, Statement)>,
+    /// One for each of `items`, in the same order.
+    pub info: Vec<ItemInfo>,
+}
+
+/// What's known about an item besides its address.
+#[derive(Debug, Clone, Default)]
+pub struct ItemInfo {
+    /// How many bytes it takes; none for a label.
+    pub size: u64,
+    /// The macro call or loop iteration that produced it, if any.
+    pub origin: Option<Rc<Expansion>>,
+}
+
+impl Section {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            start: 0,
+            pc: 0,
+            items: Vec::new(),
+            info: Vec::new(),
+        }
+    }
+
+    /// Bytes from the start to the location counter, gaps left by `@org`
+    /// included.
+    pub fn size(&self) -> u64 {
+        self.pc - self.start
+    }
+}
+
+/// Sections in the order they were first used.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub sections: Vec<Section>,
+    /// Each label with the index of its section and its address, in
+    /// definition order.
+    pub labels: Vec<(String, usize, u64)>,
+}
+
+impl Layout {
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// The section `label` is in and its address.
+    pub fn label(&self, label: &str) -> Option<(&Section, u64)> {
+        self.labels
+            .iter()
+            .find(|(name, ..)| name == label)
+            .map(|&(_, section, address)| (&self.sections[section], address))
+    }
+
+    /// `label`'s address, as [`SymbolTable::resolve`] takes it.
+    pub fn address_of(&self, label: &str) -> Option<u64> {
+        self.label(label).map(|(_, address)| address)
+    }
+
+    /// The labels an object file lists, each at its offset into its
+    /// section, so relocations can refer to any of them: `::` labels
+    /// global, the rest only for this object's own.
+    pub fn object_symbols(&self) -> Vec<ObjectSymbol> {
+        let mut symbols = Vec::new();
+        for (index, section) in self.sections.iter().enumerate() {
+            for (address, stmt) in &section.items {
+                if let StatementKind::Label { name, visibility } = &stmt.kind {
+                    symbols.push(ObjectSymbol {
+                        name: name.clone(),
+                        global: *visibility == Visibility::Global,
+                        definition: Some((index as u32, address - section.start)),
+                    });
+                }
+            }
+        }
+        symbols
+    }
+
+    /// Index into `sections` of the section called `name`, added if new.
+    fn open(&mut self, name: &str) -> usize {
+        match self.sections.iter().position(|section| section.name == name) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        }
+    }
+}
+
+/// Lays out `ast`, sizing instructions with `instruction_size`. String
+/// data its encoding can't hold is reported and takes no space; so is an
+/// `@org` or `@align` that can't be followed.
+pub fn layout(
+    ast: &[Statement],
+    instruction_size: impl Fn(&str, &[Operand]) -> u64,
+) -> (Layout, Vec<Diagnostic>) {
+    layout_expanded(ast, &Origins::default(), instruction_size)
+}
+
+/// [`layout`] for statements [`expand_traced`](crate::expand::expand_traced)
+/// returned with `origins`: an error about one that a macro call or loop
+/// produced says which, the way expansion's own errors do.
+pub fn layout_expanded(
+    ast: &[Statement],
+    origins: &Origins,
+    instruction_size: impl Fn(&str, &[Operand]) -> u64,
+) -> (Layout, Vec<Diagnostic>) {
+    let ast = name_numeric_labels(&scope_local_labels(&scope_modules(ast)));
+    let table = SymbolTable::collect(&ast);
+    let mut placer = Placer {
+        layout: Layout::default(),
+        current: 0,
+        table: &table,
+        instruction_size: &instruction_size,
+        origins: origins.cursor(),
+        errors: Vec::new(),
+    };
+    placer.current = placer.layout.open(DEFAULT_SECTION);
+    placer.place(&ast);
+    (placer.layout, placer.errors)
+}
+
+struct Placer<'a> {
+    layout: Layout,
+    /// Index of the section statements go in.
+    current: usize,
+    table: &'a SymbolTable,
+    instruction_size: &'a dyn Fn(&str, &[Operand]) -> u64,
+    origins: OriginCursor<'a>,
+    errors: Vec<Diagnostic>,
+}
+
+impl Placer<'_> {
+    fn place(&mut self, ast: &[Statement]) {
+        for stmt in ast {
+            if let StatementKind::Block(body) = &stmt.kind {
+                self.place(body);
+                continue;
+            }
+            let origin = self.origins.next(stmt);
+            let reported = self.errors.len();
+            let size = self.size(stmt);
+            for diag in &mut self.errors[reported..] {
+                diag.expansion = origin.clone();
+            }
+            let Some(size) = size else {
+                continue;
+            };
+
+            let section = &mut self.layout.sections[self.current];
+            section.items.push((section.pc, stmt.clone()));
+            section.info.push(ItemInfo { size, origin });
+            section.pc += size;
+        }
+    }
+
+    /// How many bytes `stmt` takes where it goes, or `None` if it isn't
+    /// an item at all.
+    fn size(&mut self, stmt: &Statement) -> Option<u64> {
+        let size = match &stmt.kind {
+            StatementKind::Section(name) => {
+                self.current = self.layout.open(name);
+                return None;
+            }
+            StatementKind::Org(address) => {
+                if let Some(address) = self.address(address, &stmt.span) {
+                    self.org(address, &stmt.span);
+                }
+                return None;
+            }
+            StatementKind::Align { boundary, fill } => {
+                self.padding(boundary, fill.as_ref(), &stmt.span)?
+            }
+            StatementKind::Label { name, .. } => {
+                let pc = self.layout.sections[self.current].pc;
+                self.layout.labels.push((name.clone(), self.current, pc));
+                0
+            }
+            StatementKind::Instruction { name, args, .. } => (self.instruction_size)(name, args),
+            StatementKind::Data { width, values, .. } => {
+                values.len() as u64 * u64::from(width.bits() / 8)
+            }
+            StatementKind::StringData {
+                encoding,
+                nul,
+                literal,
+            } => match encode_string(*encoding, *nul, literal, stmt.span.clone()) {
+                Ok(bytes) => bytes.len() as u64,
+                Err(diag) => {
+                    self.errors.push(diag);
+                    0
+                }
+            },
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Moves the current section's location counter to `address`: the
+    /// whole section if nothing is in it yet, otherwise only forward.
+    fn org(&mut self, address: u64, span: &Span) {
+        let section = &mut self.layout.sections[self.current];
+        if section.items.is_empty() {
+            section.start = address;
+            section.pc = address;
+        } else if address >= section.pc {
+            section.pc = address;
+        } else {
+            let diag = Diagnostic::error(format!(
+                "`@org {:#x}` would move `.{}` back from {:#x}",
+                address, section.name, section.pc
+            ))
+            .with_code(Code::E0601)
+            .with_span(span.clone())
+            .with_note(format!(
+                "`.{}` already holds {:#x}..{:#x}",
+                section.name, section.start, section.pc
+            ));
+            self.errors.push(diag);
+        }
+    }
+
+    /// The value of an `@org` address.
+    fn address(&mut self, expr: &Expr, span: &Span) -> Option<u64> {
+        let message = match self.eval(expr, span)? {
+            Value::Int(n) if n >= 0 => return Some(n as u64),
+            Value::Int(n) => format!("the `@org` address {} is negative", n),
+            value => format!("the `@org` address is {}, not an integer", value.kind()),
+        };
+        self.errors.push(
+            Diagnostic::error(message)
+                .with_code(Code::E0513)
+                .with_span(span.clone()),
+        );
+        None
+    }
+
+    /// How many bytes `@align boundary, fill` pads with where the current
+    /// section is up to. `None` if the boundary or the fill byte is bad.
+    fn padding(&mut self, boundary: &Expr, fill: Option<&Expr>, span: &Span) -> Option<u64> {
+        let message = match self.eval(boundary, span)? {
+            Value::Int(n) if n > 0 => {
+                let pc = self.layout.sections[self.current].pc;
+                let padding = pc.next_multiple_of(n as u64) - pc;
+                match fill.map(|fill| self.eval(fill, span)) {
+                    None | Some(Some(Value::Int(0..=0xff))) => return Some(padding),
+                    Some(None) => return None,
+                    Some(Some(Value::Int(n))) => {
+                        format!("the `@align` fill {} doesn't fit in a byte", n)
+                    }
+                    Some(Some(value)) => {
+                        format!("the `@align` fill is {}, not an integer", value.kind())
+                    }
+                }
+            }
+            Value::Int(n) => format!("can't align to {} bytes", n),
+            value => format!("the `@align` boundary is {}, not an integer", value.kind()),
+        };
+        self.errors.push(
+            Diagnostic::error(message)
+                .with_code(Code::E0602)
+                .with_span(span.clone()),
+        );
+        None
+    }
+
+    /// The value of `expr`, its error reported if it has none. Labels laid
+    /// out so far have their addresses, as do `equ`s built on them.
+    fn eval(&mut self, expr: &Expr, span: &Span) -> Option<Value> {
+        let address_of = |label: &str| self.layout.address_of(label);
+        let lookup = |name: &str| self.table.value_with(name, &address_of);
+        match eval(expr, &lookup, span) {
+            Ok(value) => Some(value),
+            Err(diag) => {
+                self.errors.push(diag);
+                None
+            }
+        }
+    }
+}
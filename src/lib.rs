@@ -1,2 +1,47 @@
+mod trace;
+
 pub mod tokens;
+pub mod source;
+pub mod codes;
+pub mod diagnostic;
+pub mod suggest;
+pub mod builtins;
 pub mod parser;
+pub mod eval;
+pub mod detokenize;
+pub mod print;
+pub mod walk;
+pub mod diff;
+pub mod json;
+pub mod analysis;
+pub mod highlight;
+pub mod includes;
+pub mod incremental;
+pub mod lint;
+pub mod directives;
+pub mod macros;
+pub mod expand;
+pub mod layout;
+pub mod debuginfo;
+pub mod sourcemap;
+pub mod session;
+pub mod program;
+pub mod symbols;
+pub mod export;
+pub mod isa;
+pub mod assemble;
+pub mod link;
+pub mod hexdump;
+pub mod readmem;
+pub mod object;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "arena")]
+pub mod arena;
+
+pub use detokenize::detokenize;
+pub use print::to_source;
+pub use walk::{Visitor, VisitorMut, instructions, iter_deep, iter_deep_with_depth};
@@ -0,0 +1,198 @@
+//! Combines relocatable [`Object`]s into one image.
+//!
+//! Sections with the same name are concatenated in the order the objects
+//! were added, each piece aligned to its own section's alignment. The
+//! combined sections follow each other from the base address in the order
+//! their names first appear. Global symbols are shared between objects;
+//! everything else only resolves relocations in the object defining it.
+
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::object::{Object, RelocKind};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The linked program, ready to be written out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// Address of `data[0]`.
+    pub base: u64,
+    pub data: Vec<u8>,
+    /// Every defined global symbol and its address, sorted by address.
+    pub symbols: Vec<(String, u64)>,
+    /// The addresses nothing was put at, filled with zeros, in order:
+    /// those between section pieces, and in an assembled program those an
+    /// `@org` skipped.
+    pub gaps: Vec<Range<u64>>,
+}
+
+pub struct Linker<'a> {
+    base: u64,
+    objects: Vec<(&'a str, &'a Object)>,
+}
+
+impl<'a> Linker<'a> {
+    pub fn new(base: u64) -> Self {
+        Self {
+            base,
+            objects: Vec::new(),
+        }
+    }
+
+    /// Adds an object. `name` is only used in diagnostics.
+    pub fn add(&mut self, name: &'a str, object: &'a Object) -> &mut Self {
+        self.objects.push((name, object));
+        self
+    }
+
+    /// Lays out, resolves and relocates everything added so far. Every
+    /// duplicate definition, undefined reference and out-of-range field is
+    /// reported, not just the first.
+    pub fn link(&self) -> Result<Image, Vec<Diagnostic>> {
+        let mut diags = Vec::new();
+        for (name, obj) in &self.objects {
+            if let Err(message) = obj.check() {
+                diags.push(
+                    Diagnostic::error(format!("{}: {}", name, message)).with_code(Code::E0404),
+                );
+            }
+        }
+        if !diags.is_empty() {
+            return Err(diags);
+        }
+
+        // Place each section piece: first group by name, then assign
+        // addresses group by group.
+        let mut groups: Vec<(&str, Vec<(usize, usize)>)> = Vec::new();
+        for (obj_index, (_, obj)) in self.objects.iter().enumerate() {
+            for (sec_index, section) in obj.sections.iter().enumerate() {
+                match groups.iter_mut().find(|(name, _)| *name == section.name) {
+                    Some((_, pieces)) => pieces.push((obj_index, sec_index)),
+                    None => groups.push((&section.name, vec![(obj_index, sec_index)])),
+                }
+            }
+        }
+
+        // `addresses[obj][sec]` is where that section piece starts.
+        let mut addresses: Vec<Vec<u64>> = self
+            .objects
+            .iter()
+            .map(|(_, obj)| vec![0; obj.sections.len()])
+            .collect();
+        let mut data = Vec::new();
+        let mut gaps = Vec::new();
+        for (_, pieces) in &groups {
+            for &(obj_index, sec_index) in pieces {
+                let section = &self.objects[obj_index].1.sections[sec_index];
+                let end = self.base + data.len() as u64;
+                let addr = align_up(end, section.align);
+                if addr > end {
+                    gaps.push(end..addr);
+                }
+                data.resize((addr - self.base) as usize, 0);
+                addresses[obj_index][sec_index] = addr;
+                data.extend_from_slice(&section.data);
+            }
+        }
+
+        let address_of = |obj_index: usize, (section, offset): (u32, u64)| {
+            addresses[obj_index][section as usize] + offset
+        };
+
+        let mut globals: HashMap<&str, (u64, &str)> = HashMap::new();
+        for (obj_index, (obj_name, obj)) in self.objects.iter().enumerate() {
+            for sym in &obj.symbols {
+                let Some(definition) = sym.definition.filter(|_| sym.global) else {
+                    continue;
+                };
+                let addr = address_of(obj_index, definition);
+                if let Some((_, first)) = globals.insert(&sym.name, (addr, obj_name)) {
+                    diags.push(
+                        Diagnostic::error(format!("`{}` is defined more than once", sym.name))
+                            .with_code(Code::E0401)
+                            .with_note(format!("first defined in {}", first))
+                            .with_note(format!("defined again in {}", obj_name)),
+                    );
+                }
+            }
+        }
+
+        for (obj_index, (obj_name, obj)) in self.objects.iter().enumerate() {
+            for reloc in &obj.relocations {
+                let local = obj
+                    .symbols
+                    .iter()
+                    .find(|sym| sym.name == reloc.symbol)
+                    .and_then(|sym| sym.definition);
+                let target = match local {
+                    Some(definition) => address_of(obj_index, definition),
+                    None => match globals.get(reloc.symbol.as_str()) {
+                        Some(&(addr, _)) => addr,
+                        None => {
+                            diags.push(
+                                Diagnostic::error(format!(
+                                    "undefined reference to `{}`",
+                                    reloc.symbol
+                                ))
+                                .with_code(Code::E0402)
+                                .with_note(format!("referenced from {}", obj_name))
+                                .with_suggestions(&reloc.symbol, globals.keys().copied()),
+                            );
+                            continue;
+                        }
+                    },
+                };
+
+                let place = addresses[obj_index][reloc.section as usize] + reloc.offset;
+                let mut value = (target as i64).wrapping_add(reloc.addend);
+                if reloc.kind.pc_relative() {
+                    value = value.wrapping_sub(place as i64);
+                }
+
+                let field = reloc.kind.field();
+                let Some(bits) = field.encode(value) else {
+                    diags.push(
+                        Diagnostic::error(format!(
+                            "relocation against `{}` at {:#x} is out of range: {}",
+                            reloc.symbol, place, value
+                        ))
+                        .with_code(Code::E0403)
+                        .with_note(format!("referenced from {}", obj_name))
+                        .with_note(format!("the {}", field.describe())),
+                    );
+                    continue;
+                };
+                let bits = match reloc.kind {
+                    RelocKind::Hi8 => bits >> 8,
+                    _ => bits,
+                };
+
+                let start = (place - self.base) as usize;
+                let size = reloc.kind.size();
+                data[start..start + size].copy_from_slice(&bits.to_le_bytes()[..size]);
+            }
+        }
+
+        if !diags.is_empty() {
+            return Err(diags);
+        }
+
+        let mut symbols: Vec<(String, u64)> = globals
+            .into_iter()
+            .map(|(name, (addr, _))| (name.to_string(), addr))
+            .collect();
+        symbols.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+        Ok(Image {
+            base: self.base,
+            data,
+            symbols,
+            gaps,
+        })
+    }
+}
+
+fn align_up(addr: u64, align: u32) -> u64 {
+    let align = u64::from(align.max(1));
+    addr.div_ceil(align) * align
+}
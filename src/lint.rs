@@ -0,0 +1,272 @@
+//! Warnings with stable names, and the `@allow`/`@warn`/`@deny` directives
+//! that change how they're reported.
+//!
+//! A lint directive before the first other statement of a file sets the
+//! level for the whole file, later ones overriding earlier ones. Anywhere
+//! else it applies to the statement right after it, including everything
+//! nested inside that statement:
+//!
+//! ```text
+//! @deny(skipped_input)
+//!
+//! @allow(unused_const)
+//! const SCRATCH = 4
+//! ```
+//!
+//! Directives take a lint's code as well as its name: `@allow(W0201)` is
+//! the same as `@allow(unused_const)`.
+
+use crate::codes::Code;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parser::{Expr, Operand, Statement, StatementKind};
+use crate::source::Span;
+use crate::symbols::scope_modules;
+use crate::walk::{
+    Visitor, bodies, iter_deep, walk_expr, walk_operand, walk_statement, walk_statements,
+};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A lint directive names a lint that doesn't exist.
+    UnknownLint,
+    /// A `const` that nothing refers to.
+    UnusedConst,
+    /// A token that was dropped because it doesn't start a statement.
+    SkippedInput,
+    /// A data value that fits its directive's width neither as unsigned
+    /// nor as signed, and gets truncated.
+    DataRange,
+}
+
+impl Lint {
+    pub const ALL: &'static [Lint] = &[
+        Lint::UnknownLint,
+        Lint::UnusedConst,
+        Lint::SkippedInput,
+        Lint::DataRange,
+    ];
+
+    /// The name used in directives. Never changes once released.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnknownLint => "unknown_lint",
+            Lint::UnusedConst => "unused_const",
+            Lint::SkippedInput => "skipped_input",
+            Lint::DataRange => "data_range",
+        }
+    }
+
+    /// The diagnostic code its warnings carry.
+    pub fn code(self) -> Code {
+        match self {
+            Lint::UnusedConst => Code::W0201,
+            Lint::UnknownLint => Code::W0202,
+            Lint::SkippedInput => Code::W0203,
+            Lint::DataRange => Code::W0204,
+        }
+    }
+
+    /// The lint a directive argument names, by name or by code.
+    pub fn from_name(name: &str) -> Option<Lint> {
+        let code = Code::from_name(name);
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|lint| lint.name() == name || code == Some(lint.code()))
+    }
+
+    pub fn default_level(self) -> Level {
+        Level::Warn
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    /// The level a directive (`allow`, `warn`, `deny`) sets.
+    pub fn from_directive(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// The lint levels one file sets with directives.
+#[derive(Debug, Default)]
+pub struct LintLevels {
+    file: HashMap<Lint, Level>,
+    /// Levels attached to a statement, by that statement's span.
+    scoped: Vec<(Span, Lint, Level)>,
+    /// Names that aren't lints, with the span of their directive.
+    unknown: Vec<(String, Span)>,
+}
+
+impl LintLevels {
+    pub fn collect(ast: &[Statement]) -> Self {
+        let mut levels = Self::default();
+        levels.collect_list(ast, true);
+        levels
+    }
+
+    fn collect_list(&mut self, stmts: &[Statement], file_scope: bool) {
+        let mut leading = file_scope;
+        let mut pending = Vec::new();
+
+        for stmt in stmts {
+            if let StatementKind::Directive { name, args } = &stmt.kind
+                && let Some(level) = Level::from_directive(name)
+            {
+                for arg in args {
+                    match Lint::from_name(arg) {
+                        Some(lint) if leading => {
+                            self.file.insert(lint, level);
+                        }
+                        Some(lint) => pending.push((lint, level)),
+                        None => self.unknown.push((arg.clone(), stmt.span.clone())),
+                    }
+                }
+                continue;
+            }
+
+            leading = false;
+            for (lint, level) in pending.drain(..) {
+                self.scoped.push((stmt.span.clone(), lint, level));
+            }
+            for inner in bodies(stmt) {
+                self.collect_list(inner, false);
+            }
+        }
+    }
+
+    /// The level of `lint` at `span`: the innermost statement-level
+    /// directive covering it, else the file level, else the default.
+    pub fn level(&self, lint: Lint, span: Option<&Span>) -> Level {
+        let scoped = span.and_then(|span| {
+            self.scoped
+                .iter()
+                .rev()
+                .filter(|(s, l, _)| {
+                    *l == lint
+                        && s.file == span.file
+                        && s.range.start <= span.range.start
+                        && span.range.end <= s.range.end
+                })
+                .min_by_key(|(s, _, _)| s.range.len())
+        });
+
+        match scoped {
+            Some(&(_, _, level)) => level,
+            None => self
+                .file
+                .get(&lint)
+                .copied()
+                .unwrap_or_else(|| lint.default_level()),
+        }
+    }
+
+    /// Applies the level in effect where `diag` points: `None` if the lint
+    /// is allowed there, otherwise `diag` as a warning or error.
+    pub fn report(&self, lint: Lint, diag: Diagnostic) -> Option<Diagnostic> {
+        let (severity, note) = match self.level(lint, diag.span.as_ref()) {
+            Level::Allow => return None,
+            Level::Warn => (
+                Severity::Warning,
+                format!("`@allow({})` silences this", lint.name()),
+            ),
+            Level::Deny => (Severity::Error, format!("`{}` is denied here", lint.name())),
+        };
+        Some(
+            Diagnostic { severity, ..diag }
+                .with_code(lint.code())
+                .with_note(note),
+        )
+    }
+}
+
+/// Runs every lint over a parsed file. `skipped` is what the parser
+/// dropped (see [`Parser::skipped`](crate::parser::Parser::skipped)).
+pub fn check(ast: &[Statement], skipped: &[Span]) -> Vec<Diagnostic> {
+    let levels = LintLevels::collect(ast);
+    let mut out = Vec::new();
+
+    for (name, span) in &levels.unknown {
+        let diag = Diagnostic::warning(format!("unknown lint `{}`", name))
+            .with_span(span.clone())
+            .with_suggestions(name, Lint::ALL.iter().map(|lint| lint.name()));
+        out.extend(levels.report(Lint::UnknownLint, diag));
+    }
+
+    for span in skipped {
+        let diag = Diagnostic::warning("skipped input that doesn't form a statement")
+            .with_span(span.clone());
+        out.extend(levels.report(Lint::SkippedInput, diag));
+    }
+
+    // Constants in modules are used by their full names.
+    let scoped = scope_modules(ast);
+    let mut referenced = References::default();
+    walk_statements(&mut referenced, &scoped);
+    for stmt in iter_deep(&scoped) {
+        if let StatementKind::ConstAssign { name, .. } = &stmt.kind
+            && !referenced.names.contains(name.as_str())
+        {
+            let diag = Diagnostic::warning(format!("constant `{}` is never used", name))
+                .with_span(stmt.span.clone());
+            out.extend(levels.report(Lint::UnusedConst, diag));
+        }
+    }
+
+    out.sort_by_key(|diag| diag.span.as_ref().map(|span| span.range.start));
+    out
+}
+
+/// Every name used anywhere: in operands, expressions and directive
+/// arguments.
+#[derive(Default)]
+struct References<'ast> {
+    names: HashSet<&'ast str>,
+}
+
+impl<'ast> References<'ast> {
+    /// `name`, and each piece of it if it's pasted together with `##`.
+    fn add(&mut self, name: &'ast str) {
+        self.names.insert(name);
+        if name.contains("##") {
+            self.names.extend(name.split("##"));
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for References<'ast> {
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        if let StatementKind::Directive { args, .. } = &stmt.kind {
+            for arg in args {
+                self.add(arg);
+            }
+        }
+        walk_statement(self, stmt);
+    }
+
+    fn visit_operand(&mut self, operand: &'ast Operand) {
+        if let Operand::Symbol(name) = operand {
+            self.add(name);
+        }
+        walk_operand(self, operand);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Expr::Symbol(name) = expr {
+            self.add(name);
+        }
+        walk_expr(self, expr);
+    }
+}
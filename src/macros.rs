@@ -0,0 +1,880 @@
+//! The macro registry: which `macro_rules!` definition a call refers to.
+//!
+//! Macros are keyed on name and arity, so `mov(dst, src)` and
+//! `mov(dst, src, shift)` can coexist and a call picks one by its number of
+//! arguments. Each definition accepts a range of arities. Two definitions of
+//! the same name whose ranges overlap would make some calls ambiguous, so
+//! the second one is rejected; an exact duplicate is the degenerate case of
+//! that.
+//!
+//! A call expands to the body of the definition it picks, each parameter
+//! replaced by its argument (see [`instantiate`]). Parameters stand for
+//! whole operands, names in expressions, labels and directive arguments:
+//!
+//! ```text
+//! macro_rules! load(dst, base, off) {
+//!     ld dst, [base + off]
+//! }
+//! load r0, r1, 8          // ld r0, [r1 + 8]
+//! ```
+//!
+//! A register passed for a name in a memory operand's displacement
+//! becomes the operand's base, or its index if it already has a base.
+//!
+//! A parameter between `##`s in a name is pasted in as its argument, a
+//! name or a number, and expansion finishes the paste: `vec_##n:` with
+//! `n` passed `3` defines `vec_3`.
+//!
+//! `stringify!(param)` is the argument as written, as a string literal,
+//! for messages about what the macro was called with:
+//!
+//! ```text
+//! macro_rules! check(reg) {
+//!     .asciiz stringify!(reg)  // .asciiz "r1" for `check r1`
+//! }
+//! ```
+//!
+//! Labels the body defines with a `%` or `.`, like `%top:`, are renamed
+//! in each expansion, so a macro that branches can be called twice.
+//!
+//! A parameter written `cycles = 10` has a default, which a call that
+//! leaves it out gets. Those come last, so `delay` and `delay 20` both
+//! call `macro_rules! delay(cycles = 10)`, which counts as taking 0 or 1
+//! arguments when it's checked against other definitions of `delay`.
+//!
+//! A last parameter written `regs...` is variadic: it takes every argument
+//! past the others, none included. In an operand or directive argument
+//! list it stands for all of them, and in an expression for an array of
+//! them, so `len(regs)` counts them and `regs[i]` picks one:
+//!
+//! ```text
+//! macro_rules! push_all(regs...) {
+//!     push regs               // push r1, r2, r3
+//! }
+//! push_all r1, r2, r3
+//! ```
+//!
+//! `@foreach regs { ... }` repeats its body once per argument instead,
+//! `regs` standing for that one argument in each copy:
+//!
+//! ```text
+//! macro_rules! save(regs...) {
+//!     @foreach regs {
+//!         st regs, [sp]
+//!         sub sp, sp, 4
+//!     }
+//! }
+//! ```
+
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::parser::{
+    Args, BinaryOp, Expr, MemoryOperand, Number, Operand, Operands, Statement, StatementKind,
+};
+use crate::source::Span;
+use crate::walk::{
+    Visitor, VisitorMut, bodies, bodies_mut, iter_deep, walk_expr, walk_expr_mut, walk_operand_mut,
+    walk_statement, walk_statement_mut, walk_statements,
+};
+use std::collections::HashMap;
+use std::{fmt, mem};
+
+/// The argument counts a macro accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    /// `None` for no upper bound.
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exactly(n: usize) -> Self {
+        Self {
+            min: n,
+            max: Some(n),
+        }
+    }
+
+    pub fn accepts(self, n: usize) -> bool {
+        n >= self.min && self.max.is_none_or(|max| n <= max)
+    }
+
+    /// `1 argument`, `2..=3 arguments`, for messages.
+    pub fn arguments(self) -> String {
+        let plural = self != Arity::exactly(1);
+        format!("{} argument{}", self, if plural { "s" } else { "" })
+    }
+
+    pub fn overlaps(self, other: Arity) -> bool {
+        let below = |a: Arity, b: Arity| a.max.is_some_and(|max| max < b.min);
+        !below(self, other) && !below(other, self)
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{}..={}", self.min, max),
+            None => write!(f, "{} or more", self.min),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MacroDef<'a> {
+    pub name: &'a str,
+    pub params: &'a [String],
+    /// The values of the last parameters before any variadic one, for a
+    /// call that leaves them out.
+    pub defaults: &'a [Operand],
+    pub body: &'a [Statement],
+    pub arity: Arity,
+    pub span: &'a Span,
+}
+
+impl<'a> MacroDef<'a> {
+    /// The name of the variadic parameter, if the last one is.
+    pub fn rest(&self) -> Option<&'a str> {
+        let params: &'a [String] = self.params;
+        params.last()?.strip_suffix("...")
+    }
+}
+
+/// The name of parameter `param`, without the `...` of a variadic one.
+pub fn param_name(param: &str) -> &str {
+    param.strip_suffix("...").unwrap_or(param)
+}
+
+/// Whether `stmt` passes variadic parameter `rest` on as operands, so its
+/// number of arguments isn't known until it's expanded.
+pub fn forwards(stmt: &Statement, rest: &str) -> bool {
+    match &stmt.kind {
+        StatementKind::Instruction { args, .. } | StatementKind::MacroCall { args, .. } => args
+            .iter()
+            .any(|arg| matches!(arg, Operand::Symbol(name) if name == rest)),
+        _ => false,
+    }
+}
+
+/// Every macro defined in a file, by name.
+#[derive(Debug, Default)]
+pub struct MacroTable<'a> {
+    macros: HashMap<&'a str, Vec<MacroDef<'a>>>,
+}
+
+impl<'a> MacroTable<'a> {
+    /// Registers every `macro_rules!` in `ast`, including nested ones, in
+    /// source order. Definitions that clash with an earlier one are left
+    /// out and reported.
+    pub fn collect(ast: &'a [Statement]) -> (Self, Vec<Diagnostic>) {
+        let mut table = Self::default();
+        let mut diags = Vec::new();
+        for stmt in iter_deep(ast) {
+            if let StatementKind::MacroDef {
+                name,
+                params,
+                defaults,
+                body,
+            } = &stmt.kind
+            {
+                let variadic = params.last().is_some_and(|p| p.ends_with("..."));
+                let fixed = params.len() - usize::from(variadic);
+                let arity = Arity {
+                    min: fixed - defaults.len(),
+                    max: (!variadic).then_some(fixed),
+                };
+                let def = MacroDef {
+                    name,
+                    params,
+                    defaults,
+                    body,
+                    arity,
+                    span: &stmt.span,
+                };
+                if let Err(diag) = table.define(def) {
+                    diags.push(diag);
+                }
+            }
+        }
+        (table, diags)
+    }
+
+    pub fn define(&mut self, def: MacroDef<'a>) -> Result<(), Diagnostic> {
+        let defs = self.macros.entry(def.name).or_default();
+        if let Some(prev) = defs.iter().find(|prev| prev.arity.overlaps(def.arity)) {
+            let (code, message) = if prev.arity == def.arity {
+                (
+                    Code::E0101,
+                    format!(
+                        "macro `{}` taking {} is already defined",
+                        def.name,
+                        def.arity.arguments()
+                    ),
+                )
+            } else {
+                (
+                    Code::E0102,
+                    format!(
+                        "macro `{}` taking {} overlaps an earlier definition taking {}",
+                        def.name,
+                        def.arity.arguments(),
+                        prev.arity.arguments()
+                    ),
+                )
+            };
+            return Err(Diagnostic::error(message)
+                .with_code(code)
+                .with_span(def.span.clone()));
+        }
+        defs.push(def);
+        defs.sort_by_key(|d| d.arity.min);
+        Ok(())
+    }
+
+    pub fn is_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    /// Every definition named `name`, by arity.
+    pub fn overloads(&self, name: &str) -> &[MacroDef<'a>] {
+        self.macros.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The definition a call of `name` with `argc` arguments expands.
+    /// `Ok(None)` means `name` isn't a macro at all.
+    pub fn resolve(&self, name: &str, argc: usize) -> Result<Option<&MacroDef<'a>>, Diagnostic> {
+        let Some(defs) = self.macros.get(name) else {
+            return Ok(None);
+        };
+        if let Some(def) = defs.iter().find(|def| def.arity.accepts(argc)) {
+            return Ok(Some(def));
+        }
+
+        let available = match defs.as_slice() {
+            [only] => only.arity.arguments(),
+            [rest @ .., last] => {
+                let rest: Vec<String> = rest.iter().map(|def| def.arity.to_string()).collect();
+                format!("{} or {} arguments", rest.join(", "), last.arity)
+            }
+            [] => unreachable!("macro names are only registered with a definition"),
+        };
+        Err(Diagnostic::error(format!(
+            "no definition of macro `{}` takes {}",
+            name,
+            Arity::exactly(argc).arguments()
+        ))
+        .with_code(Code::E0103)
+        .with_note(format!("`{}` takes {}", name, available)))
+    }
+}
+
+/// Builds the macro table for `ast` and checks every call against it:
+/// clashing definitions, calls whose argument count matches no definition
+/// and `name!(...)` calls of a name that isn't a macro are errors. A call
+/// that passes a variadic parameter on has its count checked once it's
+/// expanded.
+pub fn check(ast: &[Statement]) -> Vec<Diagnostic> {
+    let (table, mut diags) = MacroTable::collect(ast);
+    check_calls(&table, ast, None, &mut diags);
+    let mut stringify = Stringify {
+        params: &[],
+        span: None,
+        diags: &mut diags,
+    };
+    walk_statements(&mut stringify, ast);
+    diags.sort_by_key(|diag| diag.span.as_ref().map(|span| span.range.start));
+    diags
+}
+
+/// `rest` is the variadic parameter of the macro whose body `stmts` is in.
+fn check_calls(
+    table: &MacroTable,
+    stmts: &[Statement],
+    rest: Option<&str>,
+    diags: &mut Vec<Diagnostic>,
+) {
+    for stmt in stmts {
+        let diag = match &stmt.kind {
+            _ if rest.is_some_and(|rest| forwards(stmt, rest)) => None,
+            StatementKind::Instruction { name, args, .. } => table.resolve(name, args.len()).err(),
+            StatementKind::MacroCall { name, args } => match table.resolve(name, args.len()) {
+                Ok(Some(_)) => None,
+                Ok(None) => Some(
+                    Diagnostic::error(format!("macro `{}` is not defined", name))
+                        .with_code(Code::E0110)
+                        .with_suggestions(name, table.macros.keys().copied()),
+                ),
+                Err(diag) => Some(diag),
+            },
+            StatementKind::MacroDef { params, body, .. } => {
+                let rest = params.last().and_then(|p| p.strip_suffix("..."));
+                check_calls(table, body, rest, diags);
+                continue;
+            }
+            StatementKind::Foreach { param, .. } if rest != Some(param.as_str()) => Some(
+                Diagnostic::error(format!(
+                    "`@foreach` over `{}`, which isn't a variadic parameter",
+                    param
+                ))
+                .with_code(Code::E0117)
+                .with_note(match rest {
+                    Some(rest) => format!("the macro's variadic parameter is `{}`", rest),
+                    None => "only a macro's last parameter, written `name...`, is".to_string(),
+                }),
+            ),
+            _ => None,
+        };
+        if let Some(diag) = diag {
+            diags.push(diag.with_span(stmt.span.clone()));
+        }
+        for body in bodies(stmt) {
+            check_calls(table, body, rest, diags);
+        }
+    }
+}
+
+/// Reports each `stringify!` of something other than a parameter of the
+/// macro it's in.
+struct Stringify<'ast, 'd> {
+    params: &'ast [String],
+    /// The statement being walked.
+    span: Option<&'ast Span>,
+    diags: &'d mut Vec<Diagnostic>,
+}
+
+impl Stringify<'_, '_> {
+    fn check(&mut self, param: &str) {
+        if self.params.iter().any(|p| param_name(p) == param) {
+            return;
+        }
+        let note = match self.params.is_empty() {
+            true => "`stringify!` only works in a `macro_rules!` body".to_string(),
+            false => {
+                let params: Vec<&str> = self.params.iter().map(|p| param_name(p)).collect();
+                format!("the macro's parameters are `{}`", params.join("`, `"))
+            }
+        };
+        let mut diag = Diagnostic::error(format!(
+            "`stringify!({})`, of something that isn't a macro parameter",
+            param
+        ))
+        .with_code(Code::E0119)
+        .with_note(note);
+        if let Some(span) = self.span {
+            diag = diag.with_span(span.clone());
+        }
+        self.diags.push(diag);
+    }
+}
+
+impl<'ast> Visitor<'ast> for Stringify<'ast, '_> {
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        let outer = (self.params, self.span.replace(&stmt.span));
+        match &stmt.kind {
+            StatementKind::MacroDef { params, .. } => self.params = params,
+            StatementKind::StringData { literal, .. } => {
+                if let Some(param) = stringified(literal) {
+                    self.check(param);
+                }
+            }
+            _ => {}
+        }
+        walk_statement(self, stmt);
+        (self.params, self.span) = outer;
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match stringify_call(expr) {
+            Some(param) => self.check(param),
+            None => walk_expr(self, expr),
+        }
+    }
+}
+
+/// `def`'s body with each parameter replaced by the argument in its place
+/// in `args`, along with errors for arguments that can't go where their
+/// parameter is used, like a memory operand in an expression.
+///
+/// `expansion` numbers the call, so the `%` and `.` labels the body
+/// defines get names no other call's do.
+pub fn instantiate(
+    def: &MacroDef,
+    args: &[Operand],
+    expansion: usize,
+) -> (Vec<Statement>, Vec<Diagnostic>) {
+    let mut body = def.body.to_vec();
+    let mut hygiene = Hygiene::default();
+    hygiene.collect(&body, def.name, expansion);
+    for stmt in &mut body {
+        hygiene.visit_statement_mut(stmt);
+    }
+
+    let fixed = match def.rest() {
+        Some(_) => &def.params[..def.params.len() - 1],
+        None => def.params,
+    };
+    let (args, rest) = args.split_at(fixed.len().min(args.len()));
+    // The parameters the call leaves out take their defaults.
+    let missing = fixed.len() - args.len();
+    let defaults = &def.defaults[def.defaults.len().saturating_sub(missing)..];
+    let mut bind = Bind {
+        args: fixed
+            .iter()
+            .map(String::as_str)
+            .zip(args.iter().chain(defaults))
+            .collect(),
+        rest: def.rest().map(|name| (name, rest)),
+        repeated: false,
+        span: def.span.clone(),
+        errors: Vec::new(),
+    };
+    for stmt in &mut body {
+        bind.visit_statement_mut(stmt);
+    }
+    if let Some(name) = def.rest() {
+        repeat(&mut body, name, rest, &mut bind.errors);
+    }
+    (body, bind.errors)
+}
+
+/// Replaces each `@foreach` over variadic parameter `rest` in `stmts` with
+/// a copy of its body per argument in `args`, `rest` bound to that one.
+fn repeat(stmts: &mut Vec<Statement>, rest: &str, args: &[Operand], errors: &mut Vec<Diagnostic>) {
+    for mut stmt in mem::take(stmts) {
+        match stmt.kind {
+            StatementKind::Foreach { param, body } if param == rest => {
+                for arg in args {
+                    let mut bind = Bind {
+                        args: HashMap::from([(rest, arg)]),
+                        rest: None,
+                        repeated: false,
+                        span: stmt.span.clone(),
+                        errors: Vec::new(),
+                    };
+                    for mut copy in body.iter().cloned() {
+                        bind.visit_statement_mut(&mut copy);
+                        stmts.push(copy);
+                    }
+                    errors.append(&mut bind.errors);
+                }
+            }
+            StatementKind::MacroDef { .. } => stmts.push(stmt),
+            _ => {
+                for body in bodies_mut(&mut stmt) {
+                    repeat(body, rest, args, errors);
+                }
+                stmts.push(stmt);
+            }
+        }
+    }
+}
+
+/// Renames the `%name` and `.name` labels a macro body defines, and every
+/// use of them in it, so the body can be expanded more than once:
+///
+/// ```text
+/// macro_rules! wait(n) {
+///     ld r0, n
+/// %top:
+///     sub r0, r0, 1
+///     bnz r0, %top        // `.top__macro_wait_3` in the third expansion
+/// }
+/// ```
+///
+/// The new names start with a `.`, so they stay local to the label before
+/// the call. Arguments are bound afterwards, so a caller's `.name` passed
+/// in is left alone.
+#[derive(Debug, Default)]
+struct Hygiene {
+    /// The name each local label gets, by the name it's written with.
+    renamed: HashMap<String, String>,
+}
+
+impl Hygiene {
+    /// Finds the local labels `body` defines, outside macros defined in it.
+    fn collect(&mut self, body: &[Statement], name: &str, expansion: usize) {
+        for stmt in body {
+            match &stmt.kind {
+                StatementKind::MacroDef { .. } => {}
+                StatementKind::Label { name: label, .. } if label.starts_with(['%', '.']) => {
+                    let renamed = format!(".{}__macro_{}_{}", &label[1..], name, expansion);
+                    self.renamed.insert(label.clone(), renamed);
+                }
+                _ => {
+                    for body in bodies(stmt) {
+                        self.collect(body, name, expansion);
+                    }
+                }
+            }
+        }
+    }
+
+    fn rename(&self, name: &mut String) -> bool {
+        match self.renamed.get(name.as_str()) {
+            Some(renamed) => {
+                name.clone_from(renamed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl VisitorMut for Hygiene {
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        match &mut stmt.kind {
+            StatementKind::MacroDef { .. } => return,
+            StatementKind::Label { name, .. } => {
+                self.rename(name);
+            }
+            StatementKind::Directive { args, .. } => {
+                for arg in args.iter_mut() {
+                    self.rename(arg);
+                }
+            }
+            _ => {}
+        }
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_operand_mut(&mut self, operand: &mut Operand) {
+        match operand {
+            // `%top` lexes as a register.
+            Operand::Register(name) | Operand::Symbol(name) => {
+                if self.rename(name) {
+                    *operand = Operand::Symbol(mem::take(name));
+                }
+            }
+            Operand::Memory(memory) => {
+                // `[%top]`: the label is an address, not a base register.
+                if let Some(mut base) = memory.base.take_if(|base| self.renamed.contains_key(base))
+                {
+                    self.rename(&mut base);
+                    let label = Expr::Symbol(base);
+                    memory.displacement = Some(match memory.displacement.take() {
+                        Some(displacement) => Expr::Binary {
+                            op: BinaryOp::Add,
+                            lhs: Box::new(label),
+                            rhs: Box::new(displacement),
+                        },
+                        None => label,
+                    });
+                }
+                walk_operand_mut(self, operand);
+            }
+            _ => walk_operand_mut(self, operand),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Symbol(name) => {
+                self.rename(name);
+            }
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+}
+
+/// Replaces parameters with the arguments they're bound to.
+struct Bind<'a> {
+    args: HashMap<&'a str, &'a Operand>,
+    /// The variadic parameter and the arguments it takes.
+    rest: Option<(&'a str, &'a [Operand])>,
+    /// Whether the walk is in a `@foreach` over the variadic parameter,
+    /// where it's left for [`repeat`] to bind one argument at a time.
+    repeated: bool,
+    /// The statement being bound, for errors.
+    span: Span,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> Bind<'a> {
+    /// The arguments `name` stands for, if it's the variadic parameter.
+    fn rest(&self, name: &str) -> Option<&'a [Operand]> {
+        if self.repeated {
+            return None;
+        }
+        self.rest
+            .filter(|(rest, _)| *rest == name)
+            .map(|(_, args)| args)
+    }
+
+    /// `arg` as part of an expression, or an error for one that can't be.
+    fn expr(&mut self, arg: &Operand, param: &str) -> Option<Expr> {
+        let expr = operand_expr(arg);
+        if expr.is_none() {
+            self.errors.push(
+                Diagnostic::error(format!(
+                    "`{}`, passed as `{}`, can't be part of an expression",
+                    arg, param
+                ))
+                .with_code(Code::E0115)
+                .with_span(self.span.clone()),
+            );
+        }
+        expr
+    }
+
+    /// What `param` was passed as a string literal, as written, if it's a
+    /// parameter. The variadic one is all its arguments with commas.
+    fn stringify(&self, param: &str) -> Option<String> {
+        let text = match (self.rest(param), self.args.get(param)) {
+            (Some(rest), _) => {
+                let args: Vec<String> = rest.iter().map(Operand::to_string).collect();
+                args.join(", ")
+            }
+            (None, Some(arg)) => arg.to_string(),
+            (None, None) => return None,
+        };
+        Some(format!("{:?}", text))
+    }
+
+    /// Writes the arguments of the parameters among the pieces of `name`
+    /// in their place, keeping the `##`s for expansion to paste them
+    /// together once variables have values too.
+    fn paste(&mut self, name: &mut String) {
+        if !name.contains("##") {
+            return;
+        }
+        let pieces: Vec<String> = name
+            .split("##")
+            .map(|piece| match self.args.get(piece) {
+                Some(&arg) => self.piece(arg, piece),
+                None => piece.to_string(),
+            })
+            .collect();
+        *name = pieces.join("##");
+    }
+
+    /// `arg`, passed as `param`, as a piece of a name.
+    fn piece(&mut self, arg: &Operand, param: &str) -> String {
+        match arg {
+            Operand::Symbol(name) | Operand::Register(name) | Operand::Expr(Expr::Symbol(name)) => {
+                name.clone()
+            }
+            Operand::Immediate(Expr::Number(Number::Int(n)))
+            | Operand::Expr(Expr::Number(Number::Int(n))) => n.to_string(),
+            _ => {
+                let message = format!(
+                    "`{}`, passed as `{}`, can't be pasted into a name",
+                    arg, param
+                );
+                // Once per statement, however often it's pasted there.
+                let span = Some(&self.span);
+                if !self
+                    .errors
+                    .iter()
+                    .any(|e| e.message == message && e.span.as_ref() == span)
+                {
+                    self.errors.push(
+                        Diagnostic::error(message)
+                            .with_code(Code::E0115)
+                            .with_span(self.span.clone()),
+                    );
+                }
+                param.to_string()
+            }
+        }
+    }
+
+    /// Moves names in `memory`'s displacement that are bound to registers
+    /// into its base and index, while either is free.
+    fn registers(&mut self, memory: &mut MemoryOperand) {
+        let Some(displacement) = memory.displacement.take() else {
+            return;
+        };
+        let mut terms = Vec::new();
+        flatten_sum(displacement, &mut terms);
+        let mut rest = Vec::new();
+        for term in terms {
+            let register = match &term {
+                Expr::Symbol(name) => match self.args.get(name.as_str()) {
+                    Some(Operand::Register(register)) => Some(register),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match register {
+                Some(register) if memory.base.is_none() => memory.base = Some(register.clone()),
+                Some(register) if memory.index.is_none() => memory.index = Some(register.clone()),
+                Some(register) => self.errors.push(
+                    Diagnostic::error(format!(
+                        "no room for `{}` in a memory operand that has a base and an index",
+                        register
+                    ))
+                    .with_code(Code::E0115)
+                    .with_span(self.span.clone()),
+                ),
+                None => rest.push(term),
+            }
+        }
+        memory.displacement = rest.into_iter().reduce(|lhs, rhs| Expr::Binary {
+            op: BinaryOp::Add,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        });
+    }
+}
+
+/// The terms of `expr` if it's a sum, else `expr` itself.
+pub(crate) fn flatten_sum(expr: Expr, terms: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary {
+            op: BinaryOp::Add,
+            lhs,
+            rhs,
+        } => {
+            flatten_sum(*lhs, terms);
+            flatten_sum(*rhs, terms);
+        }
+        expr => terms.push(expr),
+    }
+}
+
+/// The parameter of `literal`, string data written `stringify!(param)`.
+fn stringified(literal: &str) -> Option<&str> {
+    literal.strip_prefix("stringify!(")?.strip_suffix(')')
+}
+
+/// The parameter of `expr`, if it's `stringify!(param)`.
+fn stringify_call(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Call { name, args } if name == "stringify!" => match args.as_slice() {
+            [Expr::Symbol(param)] => Some(param),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// An argument as part of an expression. A register stays a name; a
+/// memory operand can't be one.
+fn operand_expr(arg: &Operand) -> Option<Expr> {
+    match arg {
+        Operand::Immediate(expr) | Operand::Expr(expr) => Some(expr.clone()),
+        Operand::Symbol(name) | Operand::Register(name) => Some(Expr::Symbol(name.clone())),
+        Operand::String(literal) => Some(Expr::Str(literal.clone())),
+        Operand::Char(c) => Some(Expr::Number(Number::Int(i64::from(u32::from(*c))))),
+        Operand::Memory(_) => None,
+    }
+}
+
+impl VisitorMut for Bind<'_> {
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        // A macro defined in the body binds its own parameters.
+        if let StatementKind::MacroDef { .. } = stmt.kind {
+            return;
+        }
+        let outer = mem::replace(&mut self.span, stmt.span.clone());
+        match &mut stmt.kind {
+            StatementKind::Label { name, .. } => {
+                if let Some(Operand::Symbol(arg)) = self.args.get(name.as_str()) {
+                    *name = arg.clone();
+                }
+                self.paste(name);
+            }
+            StatementKind::Directive { args, .. } => {
+                let mut bound = Args::new();
+                for mut arg in args.drain(..) {
+                    match (self.rest(&arg), self.args.get(arg.as_str())) {
+                        (Some(rest), _) => bound.extend(rest.iter().map(Operand::to_string)),
+                        (None, Some(value)) => bound.push(value.to_string()),
+                        (None, None) => {
+                            self.paste(&mut arg);
+                            bound.push(arg);
+                        }
+                    }
+                }
+                *args = bound;
+            }
+            StatementKind::StringData { literal, .. } => {
+                if let Some(text) = stringified(literal).and_then(|param| self.stringify(param)) {
+                    *literal = text;
+                }
+            }
+            _ => {}
+        }
+        let repeated = self.repeated;
+        if let StatementKind::Foreach { param, .. } = &stmt.kind {
+            self.repeated |= self.rest.is_some_and(|(rest, _)| rest == param);
+        }
+        walk_statement_mut(self, stmt);
+        self.repeated = repeated;
+        self.span = outer;
+
+        // Spliced in after the walk, so arguments aren't bound again.
+        let (args, mut spans) = match &mut stmt.kind {
+            StatementKind::Instruction { args, spans, .. } => (args, Some(spans)),
+            StatementKind::MacroCall { args, .. } => (args, None),
+            _ => return,
+        };
+        if let Some((name, rest)) = self.rest.filter(|_| !self.repeated) {
+            let mut bound = Operands::new();
+            // Each argument spliced in is where the parameter was.
+            let mut bound_spans = Vec::new();
+            for (index, arg) in args.drain(..).enumerate() {
+                let span = spans.as_ref().and_then(|spans| spans.get(index).cloned());
+                let count = match arg {
+                    Operand::Symbol(param) if param == name => {
+                        bound.extend(rest.iter().cloned());
+                        rest.len()
+                    }
+                    arg => {
+                        bound.push(arg);
+                        1
+                    }
+                };
+                bound_spans.extend(span.into_iter().flat_map(|span| vec![span; count]));
+            }
+            *args = bound;
+            if let Some(spans) = &mut spans {
+                **spans = bound_spans;
+            }
+        }
+    }
+
+    fn visit_operand_mut(&mut self, operand: &mut Operand) {
+        match operand {
+            Operand::Symbol(name) => match self.args.get(name.as_str()) {
+                Some(arg) => *operand = (*arg).clone(),
+                None => self.paste(name),
+            },
+            Operand::Register(name) => self.paste(name),
+            Operand::Expr(expr) => {
+                self.visit_expr_mut(expr);
+                // A `stringify!` on its own is a string operand.
+                if let Expr::Str(literal) = expr {
+                    *operand = Operand::String(mem::take(literal));
+                }
+            }
+            Operand::Memory(memory) => {
+                self.registers(memory);
+                walk_operand_mut(self, operand);
+            }
+            _ => walk_operand_mut(self, operand),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Some(text) = stringify_call(expr).and_then(|param| self.stringify(param)) {
+            *expr = Expr::Str(text);
+            return;
+        }
+        let Expr::Symbol(name) = expr else {
+            return walk_expr_mut(self, expr);
+        };
+        let name = name.clone();
+        if let Some(rest) = self.rest(&name) {
+            let items = rest.iter().filter_map(|arg| self.expr(arg, &name));
+            *expr = Expr::Array(items.collect());
+        } else if let Some(&arg) = self.args.get(name.as_str()) {
+            if let Some(value) = self.expr(arg, &name) {
+                *expr = value;
+            }
+        } else if let Expr::Symbol(name) = expr {
+            self.paste(name);
+        }
+    }
+}
@@ -1,8 +1,205 @@
-use chasm::tokens::TokenKind;
-use chasm::parser::Parser;
-use logos::Logos;
+use chasm::assemble::assemble;
+use chasm::codes::Code;
+use chasm::debuginfo::DebugInfo;
+use chasm::diagnostic::{Diagnostic, Severity};
+use chasm::directives::{DirectiveSpec, Directives};
+use chasm::expand::{ExpandOptions, expand_traced};
+use chasm::export::{self, CHeaderOptions, RustOptions};
+use chasm::hexdump::hexdump;
+use chasm::includes::{self, IncludeOptions};
+use chasm::isa::{self, Isa, Target};
+use chasm::link::{Image, Linker};
+use chasm::lint;
+use chasm::macros;
+use chasm::object::Object;
+use chasm::parser::{LexError, LexOptions, ParseError, Parser, Statement};
+use chasm::program::Program;
+use chasm::readmem::{Endian, Radix, ReadmemOptions, readmem};
+use chasm::session::{self, CacheStats, Session, Timings};
+use chasm::source::SourceManager;
+use chasm::sourcemap::SourceMap;
+use chasm::symbols;
+use clap::builder::PossibleValuesParser;
+use clap::{Arg, ArgAction, Command};
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+use std::{fs, process};
 
 fn main() {
+    let matches = Command::new("chasm")
+        .arg(
+            Arg::new("input")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Files to assemble together, in order"),
+        )
+        .arg(
+            Arg::new("include-dir")
+                .short('I')
+                .action(ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Extra directory to search for included files"),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .value_parser(PossibleValuesParser::new(isa::TARGETS.iter().map(|t| t.name)))
+                .default_value(Target::DEFAULT.name)
+                .help("Architecture to assemble for, which decides what is a register"),
+        )
+        .arg(
+            Arg::new("isa")
+                .long("isa")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Read the instruction table from this file instead of the target's"),
+        )
+        .arg(
+            Arg::new("suffix-radix")
+                .long("suffix-radix")
+                .action(ArgAction::SetTrue)
+                .help("Read 0FFh, 1010b and 777o as hex, binary and octal literals"),
+        )
+        .arg(
+            Arg::new("ignore-case")
+                .long("ignore-case")
+                .action(ArgAction::SetTrue)
+                .help("Accept keywords, directives and mnemonics in any case"),
+        )
+        .arg(
+            Arg::new("directive")
+                .long("directive")
+                .action(ArgAction::Append)
+                .value_parser(|s: &str| s.parse::<DirectiveSpec>())
+                .help("Accept a directive of another tool, e.g. 'message(string)'"),
+        )
+        .arg(
+            Arg::new("macro-depth")
+                .long("macro-depth")
+                .value_parser(clap::value_parser!(usize))
+                .help("How deep macro calls may nest in --emit expanded (default 64)"),
+        )
+        .arg(
+            Arg::new("emit")
+                .long("emit")
+                .value_parser([
+                    "ast",
+                    "source",
+                    "expanded",
+                    "bin",
+                    "obj",
+                    "include-graph",
+                    "c-header",
+                    "rust-consts",
+                ])
+                .default_value("ast"),
+        )
+        .arg(format_arg())
+        .args(readmem_args())
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write output here instead of stdout"),
+        )
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .default_value("")
+                .help("Prefix for names in generated headers"),
+        )
+        .arg(
+            Arg::new("int-type")
+                .long("int-type")
+                .help("Integer type for --emit rust-consts [default: unsigned, address-wide]"),
+        )
+        .arg(
+            Arg::new("source-map")
+                .long("source-map")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("With --emit expanded, write where each line came from here as JSON"),
+        )
+        .arg(
+            Arg::new("debug-info")
+                .long("debug-info")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("With --emit bin or obj, write the source line of each address range here"),
+        )
+        .arg(
+            Arg::new("depfile")
+                .long("depfile")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Write a Makefile-style list of every file read"),
+        )
+        .arg(
+            Arg::new("timings")
+                .long("timings")
+                .action(ArgAction::SetTrue)
+                .help("Print per-phase times and files parsed to stderr"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .help("Log pipeline activity (-v for info, -vv for debug, -vvv for trace)"),
+        )
+        .subcommand(
+            Command::new("link")
+                .about("Link chasm objects into one image")
+                .arg(
+                    Arg::new("objects")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("base")
+                        .long("base")
+                        .default_value("0")
+                        .value_parser(parse_address)
+                        .help("Address the image is loaded at"),
+                )
+                .arg(format_arg().alias("emit"))
+                .args(readmem_args())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Write output here instead of stdout"),
+                ),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Explain a diagnostic code, e.g. E0102")
+                .arg(Arg::new("code").required(true)),
+        )
+        .args_conflicts_with_subcommands(true)
+        .get_matches();
+
+    #[cfg(feature = "tracing")]
+    init_tracing(matches.get_count("verbose"));
+
+    match matches.subcommand() {
+        Some(("link", link)) => {
+            run_link(link);
+            return;
+        }
+        Some(("explain", explain)) => {
+            run_explain(explain.get_one::<String>("code").unwrap());
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(paths) = matches.get_many::<PathBuf>("input") {
+        run_files(&paths.collect::<Vec<_>>(), &matches);
+        return;
+    }
+
     // Test input demonstrating many features
     let input = r#"
 @define SIZE 32
@@ -43,3 +240,355 @@ label:
         println!("{:?}", stmt);
     }
 }
+
+fn run_files(paths: &[&PathBuf], matches: &clap::ArgMatches) {
+    let opts = IncludeOptions {
+        include_dirs: matches
+            .get_many::<PathBuf>("include-dir")
+            .map(|dirs| dirs.cloned().collect())
+            .unwrap_or_default(),
+    };
+
+    let emit = matches.get_one::<String>("emit").map(String::as_str);
+    let depfile = matches.get_one::<PathBuf>("depfile");
+    if emit != Some("expanded") && matches.contains_id("source-map") {
+        fail("--source-map needs --emit expanded");
+    }
+    if !matches!(emit, Some("bin" | "obj")) && matches.contains_id("debug-info") {
+        fail("--debug-info needs --emit bin or obj");
+    }
+    if emit != Some("bin") && matches.contains_id("format") {
+        fail("--format needs --emit bin");
+    }
+
+    if emit == Some("include-graph") || depfile.is_some() {
+        let [path] = paths else {
+            fail("--emit include-graph and --depfile take a single input");
+        };
+        let graph = includes::graph(path, &opts).unwrap_or_else(|e| fail(e));
+
+        if let Some(depfile) = depfile {
+            let target = path.with_extension("bin").display().to_string();
+            fs::write(depfile, graph.to_depfile(&target)).unwrap_or_else(|e| fail(e));
+        }
+        if emit == Some("include-graph") {
+            print!("{}", graph.to_dot());
+            return;
+        }
+    }
+
+    let target = matches
+        .get_one::<String>("target")
+        .and_then(|name| isa::target(name))
+        .unwrap_or(&Target::DEFAULT);
+    let lex_opts = LexOptions {
+        suffix_radix: matches.get_flag("suffix-radix"),
+        case_insensitive: matches.get_flag("ignore-case"),
+        ..LexOptions::for_target(target)
+    };
+    let start = Instant::now();
+    let mut program = Program::with_options(lex_opts);
+    for path in paths {
+        program.load(path).unwrap_or_else(|e| fail(e));
+    }
+    // Each run parses every file; only a session kept between builds
+    // has a cache to hit.
+    let timings = Cell::new(Timings {
+        parse: start.elapsed(),
+        cache: CacheStats {
+            misses: program.files().len(),
+            hits: 0,
+        },
+        ..Timings::default()
+    });
+    let sources = program.sources();
+    let ast = program.ast();
+
+    let mut directives = Directives::new();
+    for spec in matches
+        .get_many::<DirectiveSpec>("directive")
+        .into_iter()
+        .flatten()
+    {
+        directives.register(spec.clone());
+    }
+
+    let mut diags = program.diagnostics();
+    diags.extend(directives.check(&ast));
+    diags.extend(macros::check(&ast));
+    diags.extend(symbols::check(&ast));
+    diags.extend(lint::check(&ast, &program.skipped()));
+    report(&diags, sources);
+
+    let mut expand_opts = ExpandOptions::default();
+    if let Some(&depth) = matches.get_one::<usize>("macro-depth") {
+        expand_opts.macro_depth = depth;
+    }
+    expand_opts.registers = target.registers;
+    // Expanded and encoded, so labels have their addresses; stops at any
+    // error.
+    let isa = || match matches.get_one::<PathBuf>("isa") {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| text.parse::<Isa>())
+            .unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e))),
+        None => target.isa(),
+    };
+    let assembled = |isa: &Isa| {
+        let start = Instant::now();
+        let (expanded, errors, origins) = expand_traced(&ast, &expand_opts);
+        let expand = start.elapsed();
+        report(&errors, sources);
+        let start = Instant::now();
+        let (assembled, errors) = assemble(&expanded, &origins, isa, &target.registers);
+        timings.set(Timings {
+            expand,
+            assemble: start.elapsed(),
+            ..timings.get()
+        });
+        report(&errors, sources);
+        assembled
+    };
+
+    let output = match emit {
+        Some("c-header") => {
+            let opts = CHeaderOptions {
+                prefix: matches.get_one::<String>("prefix").cloned().unwrap_or_default(),
+                ..Default::default()
+            };
+            export::c_header(&assembled(&isa()).exports(), &opts)
+                .unwrap_or_else(|d| fail(d.render(sources).trim_end()))
+                .into_bytes()
+        }
+        Some("rust-consts") => {
+            let isa = isa();
+            let opts = match matches.get_one::<String>("int-type") {
+                Some(int_type) => RustOptions {
+                    int_type: int_type.clone(),
+                },
+                None => RustOptions::for_isa(&isa),
+            };
+            export::rust_consts(&assembled(&isa).exports(), sources, &opts)
+                .unwrap_or_else(|d| fail(d.render(sources).trim_end()))
+                .into_bytes()
+        }
+        Some("source") => chasm::to_source(&ast).into_bytes(),
+        Some("expanded") => {
+            let start = Instant::now();
+            let (session, ast) = with_includes(paths, &opts, lex_opts);
+            let parse = start.elapsed();
+            let sources = session.sources();
+            let start = Instant::now();
+            let (expanded, errors, origins) = expand_traced(&ast, &expand_opts);
+            timings.set(Timings {
+                parse,
+                expand: start.elapsed(),
+                cache: session.stats(),
+                ..Timings::default()
+            });
+            report(&errors, sources);
+            if let Some(path) = matches.get_one::<PathBuf>("source-map") {
+                let map = SourceMap::new(&expanded, &origins, sources);
+                fs::write(path, map.to_json()).unwrap_or_else(|e| fail(e));
+            }
+            chasm::to_source(&expanded).into_bytes()
+        }
+        Some(emit @ ("bin" | "obj")) => {
+            let assembled = assembled(&isa());
+            if let Some(path) = matches.get_one::<PathBuf>("debug-info") {
+                let info = DebugInfo::new(&assembled.layout, sources);
+                fs::write(path, info.to_text()).unwrap_or_else(|e| fail(e));
+            }
+            if emit == "obj" {
+                assembled.object.to_bytes()
+            } else {
+                let image = assembled
+                    .image()
+                    .unwrap_or_else(|d| fail(d.render(sources).trim_end()));
+                write_image(image, matches)
+            }
+        }
+        _ => ast
+            .iter()
+            .map(|stmt| format!("{:?}\n", stmt))
+            .collect::<String>()
+            .into_bytes(),
+    };
+
+    match matches.get_one::<PathBuf>("output") {
+        Some(out) => fs::write(out, output).unwrap_or_else(|e| fail(e)),
+        None => io::stdout().write_all(&output).unwrap_or_else(|e| fail(e)),
+    }
+    if matches.get_flag("timings") {
+        eprint!("{}", timings.get());
+    }
+}
+
+/// `paths` one after the other, each with the files it includes spliced
+/// in, and the session holding their text. Reports what's wrong in the
+/// included files; the inputs themselves have been checked already.
+fn with_includes(
+    paths: &[&PathBuf],
+    opts: &IncludeOptions,
+    lex_opts: LexOptions,
+) -> (Session, Vec<Statement>) {
+    let mut session = Session::new(opts.clone());
+    session.set_lex_options(lex_opts);
+    let mut ast = Vec::new();
+    for path in paths {
+        let files = session.build(path).unwrap_or_else(|e| fail(e));
+        let diags: Vec<Diagnostic> = files[1..]
+            .iter()
+            .flat_map(|file| {
+                let lex = file.lex_errors.iter().map(LexError::to_diagnostic);
+                lex.chain(file.parse_errors.iter().map(ParseError::to_diagnostic))
+            })
+            .collect();
+        report(&diags, session.sources());
+        ast.extend(session::splice(&files));
+    }
+    (session, ast)
+}
+
+/// Prints `diags`, and stops if any is an error.
+fn report(diags: &[Diagnostic], sources: &SourceManager) {
+    for diag in diags {
+        eprint!("{}", diag.render(sources));
+    }
+    if diags.iter().any(|d| d.severity == Severity::Error) {
+        process::exit(1);
+    }
+}
+
+fn run_link(matches: &clap::ArgMatches) {
+    let paths: Vec<&PathBuf> = matches.get_many::<PathBuf>("objects").unwrap().collect();
+    let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+    let objects: Vec<Object> = paths
+        .iter()
+        .zip(&names)
+        .map(|(path, name)| {
+            let bytes = fs::read(path).unwrap_or_else(|e| fail(format!("{}: {}", name, e)));
+            Object::from_bytes(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", name, e)))
+        })
+        .collect();
+
+    let mut linker = Linker::new(*matches.get_one::<u64>("base").unwrap());
+    for (name, obj) in names.iter().zip(&objects) {
+        linker.add(name, obj);
+    }
+    let image = linker.link().unwrap_or_else(|diags| {
+        let sources = SourceManager::new();
+        for diag in &diags {
+            eprint!("{}", diag.render(&sources));
+        }
+        process::exit(1);
+    });
+
+    let output = write_image(image, matches);
+    match matches.get_one::<PathBuf>("output") {
+        Some(out) => fs::write(out, output).unwrap_or_else(|e| fail(e)),
+        None => io::stdout().write_all(&output).unwrap_or_else(|e| fail(e)),
+    }
+}
+
+/// `--format`, how an image is written.
+fn format_arg() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .value_parser(["bin", "hexdump", "readmemh", "readmemb"])
+        .help("How to write the image (default bin)")
+}
+
+/// The options of `--format readmemh` and `readmemb`.
+fn readmem_args() -> [Arg; 3] {
+    [
+        Arg::new("word-width")
+            .long("word-width")
+            .value_parser(["8", "16", "32"])
+            .default_value("8")
+            .help("Bits per line of --format readmemh and readmemb"),
+        Arg::new("endian")
+            .long("endian")
+            .value_parser(["little", "big"])
+            .default_value("little")
+            .help("Byte order within a word of --format readmemh and readmemb"),
+        Arg::new("pad-gaps")
+            .long("pad-gaps")
+            .action(ArgAction::SetTrue)
+            .help("Write gaps in the image as zeros instead of @address markers"),
+    ]
+}
+
+/// `image` the way `--format` says.
+fn write_image(image: Image, matches: &clap::ArgMatches) -> Vec<u8> {
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("hexdump") => hexdump(&image).into_bytes(),
+        Some(format @ ("readmemh" | "readmemb")) => {
+            let opts = ReadmemOptions {
+                radix: if format == "readmemh" {
+                    Radix::Hex
+                } else {
+                    Radix::Binary
+                },
+                word_bytes: match matches.get_one::<String>("word-width").map(String::as_str) {
+                    Some("16") => 2,
+                    Some("32") => 4,
+                    _ => 1,
+                },
+                endian: match matches.get_one::<String>("endian").map(String::as_str) {
+                    Some("big") => Endian::Big,
+                    _ => Endian::Little,
+                },
+                markers: !matches.get_flag("pad-gaps"),
+            };
+            readmem(&image, &opts).into_bytes()
+        }
+        _ => image.data,
+    }
+}
+
+fn run_explain(code: &str) {
+    let Some(code) = Code::from_name(code) else {
+        fail(format!("`{}` is not a diagnostic code", code));
+    };
+    println!("{}: {}\n", code, code.title());
+    println!("{}", code.explanation());
+}
+
+/// `0x8000`, `0o100000`, `0b1...` or plain decimal.
+fn parse_address(s: &str) -> Result<u64, String> {
+    let (digits, radix) = match s.get(..2) {
+        Some("0x" | "0X") => (&s[2..], 16),
+        Some("0o" | "0O") => (&s[2..], 8),
+        Some("0b" | "0B") => (&s[2..], 2),
+        _ => (s, 10),
+    };
+    u64::from_str_radix(digits, radix).map_err(|e| format!("`{}` is not an address: {}", s, e))
+}
+
+fn fail(err: impl std::fmt::Display) -> ! {
+    let err = err.to_string();
+    if err.starts_with("error:") || err.starts_with("error[") {
+        eprintln!("{}", err);
+    } else {
+        eprintln!("error: {}", err);
+    }
+    process::exit(1);
+}
+
+#[cfg(feature = "tracing")]
+fn init_tracing(verbose: u8) {
+    use tracing::level_filters::LevelFilter;
+
+    let level = match verbose {
+        0 => return,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .init();
+}
@@ -0,0 +1,345 @@
+//! Relocatable objects: assembled sections plus the references into them
+//! that can only be filled in once everything is linked.
+//!
+//! On disk an object is a small little-endian container:
+//!
+//! ```text
+//! magic       b"CHOB"
+//! version     u16 (1)
+//! sections    u32 count, then per section:
+//!               name str, align u32, data (u32 length, bytes)
+//! symbols     u32 count, then per symbol:
+//!               name str, flags u8 (1 = global, 2 = defined),
+//!               if defined: section u32, offset u64
+//! relocations u32 count, then per relocation:
+//!               section u32, offset u64, kind u8, symbol str, addend i64
+//! ```
+//!
+//! A `str` is a u32 byte length followed by UTF-8. A symbol without the
+//! defined flag is external: some other object has to define it.
+
+use crate::isa::Field;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"CHOB";
+const VERSION: u16 = 1;
+
+const GLOBAL: u8 = 1;
+const DEFINED: u8 = 2;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<ObjectSymbol>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    /// Required alignment of the section's start, in bytes.
+    pub align: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    /// Visible to other objects, rather than only to relocations in this
+    /// one.
+    pub global: bool,
+    /// `(section index, offset)`, or `None` for an external symbol.
+    pub definition: Option<(u32, u64)>,
+}
+
+/// What to write where once `symbol`'s address is known.
+///
+/// The value is computed as in ELF: `S + A` for absolute kinds and
+/// `S + A - P` for PC-relative ones, where `S` is the symbol's address, `A`
+/// the addend and `P` the address of the field itself. A branch measured
+/// from the end of its instruction uses the distance from the field to
+/// that end as a negative addend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub section: u32,
+    /// Byte offset of the field within the section.
+    pub offset: u64,
+    pub kind: RelocKind,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocKind {
+    Abs8,
+    Abs16,
+    Abs32,
+    PcRel8,
+    PcRel16,
+    /// Low byte of a 16-bit address.
+    Lo8,
+    /// High byte of a 16-bit address.
+    Hi8,
+}
+
+impl RelocKind {
+    const ALL: [RelocKind; 7] = [
+        RelocKind::Abs8,
+        RelocKind::Abs16,
+        RelocKind::Abs32,
+        RelocKind::PcRel8,
+        RelocKind::PcRel16,
+        RelocKind::Lo8,
+        RelocKind::Hi8,
+    ];
+
+    /// The name used in listings: `abs16`, `pcrel8`, `lo8`, ...
+    pub fn name(self) -> &'static str {
+        match self {
+            RelocKind::Abs8 => "abs8",
+            RelocKind::Abs16 => "abs16",
+            RelocKind::Abs32 => "abs32",
+            RelocKind::PcRel8 => "pcrel8",
+            RelocKind::PcRel16 => "pcrel16",
+            RelocKind::Lo8 => "lo8",
+            RelocKind::Hi8 => "hi8",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Bytes the field occupies.
+    pub fn size(self) -> usize {
+        match self {
+            RelocKind::Abs8 | RelocKind::PcRel8 | RelocKind::Lo8 | RelocKind::Hi8 => 1,
+            RelocKind::Abs16 | RelocKind::PcRel16 => 2,
+            RelocKind::Abs32 => 4,
+        }
+    }
+
+    pub fn pc_relative(self) -> bool {
+        matches!(self, RelocKind::PcRel8 | RelocKind::PcRel16)
+    }
+
+    /// The range the computed value has to fit, checked before the
+    /// high/low byte is taken for [`Lo8`](RelocKind::Lo8) and
+    /// [`Hi8`](RelocKind::Hi8).
+    pub fn field(self) -> Field {
+        match self {
+            RelocKind::Abs8 => Field::unsigned(8),
+            RelocKind::Abs16 | RelocKind::Lo8 | RelocKind::Hi8 => Field::unsigned(16),
+            RelocKind::Abs32 => Field::unsigned(32),
+            RelocKind::PcRel8 => Field::signed(8),
+            RelocKind::PcRel16 => Field::signed(16),
+        }
+    }
+
+    fn code(self) -> u8 {
+        self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(code)).copied()
+    }
+}
+
+impl Object {
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+
+        write_len(w, self.sections.len())?;
+        for section in &self.sections {
+            write_str(w, &section.name)?;
+            w.write_all(&section.align.to_le_bytes())?;
+            write_len(w, section.data.len())?;
+            w.write_all(&section.data)?;
+        }
+
+        write_len(w, self.symbols.len())?;
+        for sym in &self.symbols {
+            write_str(w, &sym.name)?;
+            let mut flags = 0;
+            if sym.global {
+                flags |= GLOBAL;
+            }
+            if sym.definition.is_some() {
+                flags |= DEFINED;
+            }
+            w.write_all(&[flags])?;
+            if let Some((section, offset)) = sym.definition {
+                w.write_all(&section.to_le_bytes())?;
+                w.write_all(&offset.to_le_bytes())?;
+            }
+        }
+
+        write_len(w, self.relocations.len())?;
+        for reloc in &self.relocations {
+            w.write_all(&reloc.section.to_le_bytes())?;
+            w.write_all(&reloc.offset.to_le_bytes())?;
+            w.write_all(&[reloc.kind.code()])?;
+            write_str(w, &reloc.symbol)?;
+            w.write_all(&reloc.addend.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let _ = self.write_to(&mut out);
+        out
+    }
+
+    /// Reads an object back, rejecting anything [`Object::check`] would.
+    pub fn read_from(r: &mut impl Read) -> io::Result<Object> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a chasm object"));
+        }
+        let version = u16::from_le_bytes(read_array(r)?);
+        if version != VERSION {
+            return Err(invalid(format!("unsupported object version {}", version)));
+        }
+
+        let mut obj = Object::default();
+
+        for _ in 0..read_u32(r)? {
+            let name = read_str(r)?;
+            let align = read_u32(r)?;
+            let data = read_bytes(r)?;
+            obj.sections.push(Section { name, align, data });
+        }
+
+        for _ in 0..read_u32(r)? {
+            let name = read_str(r)?;
+            let [flags] = read_array(r)?;
+            if flags & !(GLOBAL | DEFINED) != 0 {
+                return Err(invalid(format!("symbol `{}` has unknown flags", name)));
+            }
+            let definition = if flags & DEFINED != 0 {
+                let section = read_u32(r)?;
+                let offset = u64::from_le_bytes(read_array(r)?);
+                Some((section, offset))
+            } else {
+                None
+            };
+            obj.symbols.push(ObjectSymbol {
+                name,
+                global: flags & GLOBAL != 0,
+                definition,
+            });
+        }
+
+        for _ in 0..read_u32(r)? {
+            let section = read_u32(r)?;
+            let offset = u64::from_le_bytes(read_array(r)?);
+            let [code] = read_array(r)?;
+            let kind = RelocKind::from_code(code)
+                .ok_or_else(|| invalid(format!("unknown relocation kind {}", code)))?;
+            let symbol = read_str(r)?;
+            let addend = i64::from_le_bytes(read_array(r)?);
+            obj.relocations.push(Relocation {
+                section,
+                offset,
+                kind,
+                symbol,
+                addend,
+            });
+        }
+
+        obj.check().map_err(invalid)?;
+        Ok(obj)
+    }
+
+    /// Checks that alignments are powers of two and that every symbol and
+    /// relocation lies inside a section the object has.
+    pub fn check(&self) -> Result<(), String> {
+        for section in &self.sections {
+            if !section.align.is_power_of_two() {
+                return Err(format!(
+                    "section `{}` has alignment {}, which isn't a power of two",
+                    section.name, section.align
+                ));
+            }
+        }
+        for sym in &self.symbols {
+            if let Some((section, offset)) = sym.definition
+                && !self.contains(section, offset, 0)
+            {
+                return Err(format!("symbol `{}` is outside its section", sym.name));
+            }
+        }
+        for reloc in &self.relocations {
+            if !self.contains(reloc.section, reloc.offset, reloc.kind.size()) {
+                return Err(format!(
+                    "relocation against `{}` is outside its section",
+                    reloc.symbol
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn contains(&self, section: u32, offset: u64, len: usize) -> bool {
+        self.sections.get(section as usize).is_some_and(|s| {
+            offset
+                .checked_add(len as u64)
+                .is_some_and(|end| end <= s.data.len() as u64)
+        })
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> io::Result<Object> {
+        let obj = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(invalid("trailing bytes after object"));
+        }
+        Ok(obj)
+    }
+
+    /// The symbol named `name`, if this object mentions it.
+    pub fn symbol(&self, name: &str) -> Option<&ObjectSymbol> {
+        self.symbols.iter().find(|sym| sym.name == name)
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn write_len(w: &mut impl Write, len: usize) -> io::Result<()> {
+    let len = u32::try_from(len).map_err(|_| invalid("object too large"))?;
+    w.write_all(&len.to_le_bytes())
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_len(w, s.len())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_bytes(r: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(r)? as u64;
+    let mut data = Vec::new();
+    // `take` so a corrupt length can't make us allocate gigabytes up front.
+    r.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(r)?).map_err(|_| invalid("name is not valid UTF-8"))
+}
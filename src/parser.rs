@@ -1,339 +1,2709 @@
-use crate::tokens::TokenKind;
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::isa::{RegisterPattern, Target};
+use crate::source::{FileId, SourceFile, SourceManager, Span};
+use crate::tokens::{
+    TokenKind, breaks_line, check_escapes, separator_after_prefix, split_trivia, str_lit,
+    suffix_lit,
+};
+use crate::trace::{Phase, debug, phase, piecewise_phase, resume, warning};
 use logos::Logos;
-#[derive(Debug)]
-
+use smallvec::SmallVec;
+use std::collections::HashSet;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
     pub text: String,
+    pub span: Span,
+    /// The whitespace, comments and line continuations right before the
+    /// token, as tokens of their own. Empty unless lexed with
+    /// [`LexOptions::trivia`].
+    pub leading: Vec<Token>,
+}
+
+impl Token {
+    /// Puts back a payload moved out by [`TokenStream::take_ident`],
+    /// [`TokenStream::take_register`], [`TokenStream::take_str`] or
+    /// [`TokenStream::take_with`], rebuilding it from the token's text.
+    pub(crate) fn restore(&mut self) {
+        match &mut self.kind {
+            TokenKind::Ident(s) | TokenKind::Register(s) if s.is_empty() => {
+                s.clone_from(&self.text)
+            }
+            TokenKind::StrLit(s) if s.is_empty() => *s = str_lit(&self.text),
+            // As `lex` spells it: the number without `_`s, then `f` or `b`.
+            TokenKind::NumericLabelRef(s) if s.is_empty() => {
+                let (digits, direction) = self.text.split_at(self.text.len() - 1);
+                let digits = digits.replace('_', "");
+                *s = match digits.parse::<i64>() {
+                    Ok(n) => format!("{}{}", n, direction),
+                    Err(_) => format!("{}{}", digits, direction),
+                };
+            }
+            _ => {}
+        }
+    }
 }
 
+/// Tokens the parser may look at past the next one.
+const LOOKAHEAD: usize = 4;
+
+/// Tokens a streaming [`TokenStream`] lexes at a time.
+const CHUNK: usize = 256;
+
 pub struct TokenStream {
+    /// The tokens held: all of them, or for a streaming stream, a window
+    /// from just before the current position.
     tokens: Vec<Token>,
+    /// Absolute index of `tokens[0]`.
+    base: usize,
     pos: usize,
+    file: FileId,
+    source: Rc<SourceFile>,
+    /// Where lexing picks up again, while some input is still unlexed.
+    pending: Option<(usize, LexOptions)>,
+    /// Lexes on demand and lets go of consumed tokens between statements.
+    streaming: bool,
+    /// Input the lexer couldn't turn into tokens.
+    lex_errors: Vec<LexError>,
+    /// Trivia after the last token, with [`LexOptions::trivia`].
+    trailing: Vec<Token>,
+    /// Lexed with [`LexOptions::case_insensitive`]: mnemonic, directive and
+    /// macro names are lowercased as they're parsed.
+    pub(crate) fold_case: bool,
+    /// The numbers of the `1:` labels lexed so far.
+    numeric_labels: HashSet<i64>,
 }
 
 impl TokenStream {
     pub fn new(input: &str) -> Self {
-        let lex = TokenKind::lexer(input);
+        Self::with_file(FileId::default(), Rc::new(SourceFile::new("<input>", input)))
+    }
+
+    pub fn with_file(file: FileId, source: Rc<SourceFile>) -> Self {
+        Self::with_options(file, source, LexOptions::default())
+    }
+
+    pub fn with_options(file: FileId, source: Rc<SourceFile>, opts: LexOptions) -> Self {
+        phase!("lex");
+        let start = source.start();
+        // Typical source averages a little over 4 bytes per token.
+        let capacity = source.text.len() / 4;
+
+        let mut stream = Self::from_tokens(Vec::with_capacity(capacity), 0, file, source);
+        stream.pending = Some((start, opts));
+        stream.fold_case = opts.case_insensitive;
+        stream.lex_more(usize::MAX);
+
+        debug!(count = stream.tokens.len(), "lexed tokens");
+        stream
+    }
 
-        let tokens = lex
-            .spanned()
-            .filter_map(|(tok, span)| match tok {
-                Ok(kind) => Some(Token {
-                    kind,
-                    text: input[span.clone()].to_string(),
-                }),
-                Err(_) => None,
-            })
-            .collect();
+    /// Like [`with_options`](Self::with_options), but lexes only as far as
+    /// the parser has read, a little ahead of the current position, and
+    /// drops the tokens of each statement once the next one starts. The
+    /// whole token list never exists at once, which matters for generated
+    /// sources of many megabytes.
+    ///
+    /// Seeking back before the tokens still held panics, and
+    /// [`len`](Self::len), [`slice`](Self::slice) and the lex errors only
+    /// cover what has been lexed so far.
+    pub fn streaming(file: FileId, source: Rc<SourceFile>, opts: LexOptions) -> Self {
+        let start = source.start();
+        let mut stream = Self::from_tokens(Vec::new(), 0, file, source);
+        stream.pending = Some((start, opts));
+        stream.streaming = true;
+        stream.fold_case = opts.case_insensitive;
+        stream.refill();
+        stream
+    }
+
+    /// Lexes up to `limit` more tokens, or unrecognised runs, from where
+    /// lexing stopped.
+    fn lex_more(&mut self, limit: usize) {
+        let Some((mut at, opts)) = self.pending.take() else {
+            return;
+        };
+        let input: &str = &self.source.text;
+        let file = self.file;
+
+        let mut lexed = lex(&input[at..], at, file, opts);
+        for _ in 0..limit {
+            let Some(item) = lexed.next() else {
+                if opts.trivia {
+                    self.trailing = trivia(input, at..input.len(), 0, file);
+                }
+                return;
+            };
+            match item {
+                Ok(tok) => {
+                    if matches!(tok.kind, TokenKind::StrLit(_) | TokenKind::CharLit(_)) {
+                        let lit = tok.span.range.start;
+                        self.lex_errors.extend(check_escapes(&tok.text).into_iter().map(
+                            |bad| LexError {
+                                text: tok.text[bad.range.clone()].to_string(),
+                                span: Span::new(file, lit + bad.range.start..lit + bad.range.end),
+                                kind: LexErrorKind::BadEscape(bad.message),
+                            },
+                        ));
+                    }
+                    if tok.kind.int_value().is_some() && separator_after_prefix(&tok.text) {
+                        self.lex_errors.push(LexError {
+                            text: tok.text.clone(),
+                            span: tok.span.clone(),
+                            kind: LexErrorKind::SeparatorAfterPrefix,
+                        });
+                    }
+                    // `1b` reads as a number here, but after a label `1:`
+                    // it was likely meant to refer to it.
+                    if tok.kind == TokenKind::Colon
+                        && let Some(TokenKind::IntLit(n)) = self.tokens.last().map(|t| &t.kind)
+                    {
+                        self.numeric_labels.insert(*n);
+                    }
+                    if opts.suffix_radix
+                        && matches!(tok.kind, TokenKind::BinLit(_))
+                        && let Some(digits) = tok.text.strip_suffix('b')
+                        && let Ok(n) = digits.replace('_', "").parse()
+                        && self.numeric_labels.contains(&n)
+                    {
+                        self.lex_errors.push(LexError {
+                            text: tok.text.clone(),
+                            span: tok.span.clone(),
+                            kind: LexErrorKind::SuffixLabelRef,
+                        });
+                    }
+                    at = tok.span.range.end;
+                    self.tokens.push(tok);
+                }
+                Err(span) => {
+                    warning!(?span, text = &input[span.clone()], "skipping unrecognised input");
+                    at = span.end;
+                    match self.lex_errors.last_mut() {
+                        Some(LexError {
+                            span: last,
+                            text,
+                            kind: LexErrorKind::Unrecognised,
+                        }) if last.range.end == span.start => {
+                            last.range.end = span.end;
+                            text.push_str(&input[span]);
+                        }
+                        _ => self.lex_errors.push(LexError {
+                            text: input[span.clone()].to_string(),
+                            span: Span::new(file, span),
+                            kind: LexErrorKind::Unrecognised,
+                        }),
+                    }
+                }
+            }
+        }
+        // Stopping between two tokens loses nothing: lexing is the same
+        // from any token boundary.
+        self.pending = Some((at, opts));
+    }
+
+    /// Keeps more than [`LOOKAHEAD`] tokens past the current position
+    /// lexed, while there is input left.
+    fn refill(&mut self) {
+        while self.pending.is_some() && self.remaining() <= LOOKAHEAD {
+            self.lex_more(CHUNK);
+        }
+    }
+
+    /// Drops the tokens a streaming stream has no more use for: those
+    /// before the last consumed one, which [`last_span`](Self::last_span)
+    /// still reads.
+    pub(crate) fn release(&mut self) {
+        if !self.streaming || self.pos <= self.base + 1 {
+            return;
+        }
+        let done = self.pos - 1;
+        self.tokens.drain(..self.index(done));
+        self.base = done;
+    }
 
-        Self { tokens, pos: 0 }
+    /// Where token `pos` sits in `tokens`.
+    fn index(&self, pos: usize) -> usize {
+        pos - self.base
+    }
+
+    /// Resumes over already-lexed tokens, starting at index `pos`.
+    pub(crate) fn from_tokens(
+        tokens: Vec<Token>,
+        pos: usize,
+        file: FileId,
+        source: Rc<SourceFile>,
+    ) -> Self {
+        Self {
+            tokens,
+            base: 0,
+            pos,
+            file,
+            source,
+            pending: None,
+            streaming: false,
+            lex_errors: Vec::new(),
+            trailing: Vec::new(),
+            fold_case: false,
+            numeric_labels: HashSet::new(),
+        }
+    }
+
+    /// Index of the next token [`next`](Self::next) would return; equal to
+    /// [`len`](Self::len) at the end.
+    ///
+    /// Tokens before the position may have had their identifier or string
+    /// payload moved out by the `take_*` methods; `text` is always intact.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Moves to token index `pos`. Seeking backward puts back any payloads
+    /// taken from the tokens in between, so they can be parsed again.
+    ///
+    /// Panics if `pos` is past the end, or before the tokens a streaming
+    /// stream still holds.
+    pub fn set_position(&mut self, pos: usize) {
+        assert!(
+            (self.base..=self.len()).contains(&pos),
+            "position {} out of range for tokens {}..{}",
+            pos,
+            self.base,
+            self.len()
+        );
+        if pos < self.pos {
+            let (from, to) = (self.index(pos), self.index(self.pos));
+            self.tokens[from..to].iter_mut().for_each(Token::restore);
+        }
+        self.pos = pos;
+        self.refill();
+    }
+
+    /// Tokens left from the current position.
+    pub fn remaining(&self) -> usize {
+        self.tokens.len() - self.index(self.pos)
+    }
+
+    /// Tokens by absolute index, independent of the position.
+    pub fn slice(&self, range: Range<usize>) -> &[Token] {
+        &self.tokens[self.index(range.start)..self.index(range.end)]
+    }
+
+    /// Total number of tokens, consumed or not.
+    pub fn len(&self) -> usize {
+        self.base + self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The remaining tokens, without consuming them.
+    pub fn iter(&self) -> std::slice::Iter<'_, Token> {
+        self.tokens[self.index(self.pos)..].iter()
+    }
+
+    /// Input the lexer couldn't turn into tokens, so far.
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    /// Whitespace and comments after the last token, once the input is all
+    /// lexed. Empty unless lexed with [`LexOptions::trivia`].
+    pub fn trailing_trivia(&self) -> &[Token] {
+        &self.trailing
+    }
+
+    /// The lexed tokens, for tools that work below the statement level.
+    pub fn into_tokens(mut self) -> Vec<Token> {
+        self.lex_more(usize::MAX);
+        self.tokens
     }
 
     pub fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.pos)
+        self.tokens.get(self.index(self.pos))
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&Token> {
-        let tok = self.tokens.get(self.pos);
-        if tok.is_some() {
-            self.pos += 1;
+        if self.eof() {
+            return None;
+        }
+        self.pos += 1;
+        self.refill();
+        self.tokens.get(self.index(self.pos - 1))
+    }
+
+    /// Consumes the next token, moving its payload out with `take` if it
+    /// matches. On a mismatch the token is still consumed and a copy of its
+    /// kind is handed back for the error message. `None` at end of input.
+    pub fn take_with<T>(
+        &mut self,
+        take: impl FnOnce(&mut TokenKind) -> Option<T>,
+    ) -> Option<Result<T, TokenKind>> {
+        let index = self.index(self.pos);
+        let tok = self.tokens.get_mut(index)?;
+        let taken = take(&mut tok.kind).ok_or_else(|| tok.kind.clone());
+        self.pos += 1;
+        self.refill();
+        Some(taken)
+    }
+
+    pub fn take_ident(&mut self) -> Option<Result<String, TokenKind>> {
+        self.take_with(|kind| match kind {
+            TokenKind::Ident(name) => Some(mem::take(name)),
+            _ => None,
+        })
+    }
+
+    pub fn take_register(&mut self) -> Option<Result<String, TokenKind>> {
+        self.take_with(|kind| match kind {
+            TokenKind::Register(name) => Some(mem::take(name)),
+            _ => None,
+        })
+    }
+
+    pub fn take_str(&mut self) -> Option<Result<String, TokenKind>> {
+        self.take_with(|kind| match kind {
+            TokenKind::StrLit(s) => Some(mem::take(s)),
+            _ => None,
+        })
+    }
+
+    pub fn take_int(&mut self) -> Option<Result<i64, TokenKind>> {
+        self.take_with(|kind| kind.int_value())
+    }
+
+    /// Like [`take_int`](Self::take_int), also accepting a float literal.
+    pub fn take_number(&mut self) -> Option<Result<Number, TokenKind>> {
+        self.take_with(|kind| match kind {
+            TokenKind::FloatLit(v) => Some(Number::Float(*v)),
+            kind => kind.int_value().map(Number::Int),
+        })
+    }
+
+    /// Like `expect(TokenKind::Ident(name.to_string()))` without building
+    /// the expected token.
+    pub fn expect_ident(&mut self, name: &str) -> Result<(), ParseError> {
+        let Some(next) = self.next() else {
+            return self.unexpected_eof();
+        };
+        if !matches!(&next.kind, TokenKind::Ident(n) if n == name) {
+            let found = next.kind.clone();
+            return self.fail(format_args!(
+                "Expected {:?} but found {:?}",
+                TokenKind::Ident(name.to_string()),
+                found
+            ));
         }
-        tok
+        Ok(())
     }
 
-    pub fn expect(&mut self, expected: TokenKind) {
-        let next = self.next().expect("Unexpected EOF");
+    pub fn expect(&mut self, expected: TokenKind) -> Result<(), ParseError> {
+        let Some(next) = self.next() else {
+            return self.unexpected_eof();
+        };
         if next.kind != expected {
-            panic!("Expected {:?} but found {:?}", expected, next.kind);
+            let found = next.kind.clone();
+            return self.fail(format_args!("Expected {:?} but found {:?}", expected, found));
         }
+        Ok(())
+    }
+
+    /// Whether a line break separates the last consumed token from the
+    /// next one. A line ending in `\` runs on into the next.
+    pub fn newline_before_next(&self) -> bool {
+        let (Some(prev), Some(next)) = (self.last(), self.peek()) else {
+            return false;
+        };
+        breaks_line(&self.source.text[prev.span.range.end..next.span.range.start])
     }
 
     pub fn eof(&self) -> bool {
-        self.pos >= self.tokens.len()
+        self.index(self.pos) >= self.tokens.len()
+    }
+
+    /// The most recently consumed token.
+    fn last(&self) -> Option<&Token> {
+        self.index(self.pos).checked_sub(1).and_then(|i| self.tokens.get(i))
+    }
+
+    /// Span of the most recently consumed token, or an empty span at the
+    /// end of input if nothing has been consumed yet.
+    pub fn last_span(&self) -> Span {
+        match self.last() {
+            Some(tok) => tok.span.clone(),
+            None => self.eof_span(),
+        }
+    }
+
+    fn eof_span(&self) -> Span {
+        let end = self.source.text.len();
+        Span::new(self.file, end..end)
+    }
+
+    /// An error with `msg`, located at the most recently consumed token.
+    pub fn fail<T>(&self, msg: impl fmt::Display) -> Result<T, ParseError> {
+        self.fail_with(Code::E0001, msg)
+    }
+
+    /// Like [`fail`](Self::fail), for errors that aren't a plain unexpected
+    /// token.
+    pub fn fail_with<T>(&self, code: Code, msg: impl fmt::Display) -> Result<T, ParseError> {
+        Err(ParseError {
+            code,
+            message: msg.to_string(),
+            span: self.last_span(),
+        })
+    }
+
+    /// The error for input that ends in the middle of a statement.
+    pub fn unexpected_eof<T>(&self) -> Result<T, ParseError> {
+        self.fail_with(Code::E0002, "Unexpected EOF")
+    }
+}
+
+/// Yields the remaining tokens by value.
+impl IntoIterator for TokenStream {
+    type Item = Token;
+    type IntoIter = std::vec::IntoIter<Token>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.lex_more(usize::MAX);
+        self.tokens.drain(..self.index(self.pos));
+        self.tokens.into_iter()
+    }
+}
+
+/// Same as [`TokenStream::iter`].
+impl<'a> IntoIterator for &'a TokenStream {
+    type Item = &'a Token;
+    type IntoIter = std::slice::Iter<'a, Token>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A syntax error. The parser records it and carries on with the next
+/// statement; see [`Parser::errors`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub code: Code,
+    pub message: String,
+    pub span: Span,
+}
+
+impl ParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.message.clone())
+            .with_code(self.code)
+            .with_span(self.span.clone())
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Input the lexer couldn't make sense of, or could read more than one way.
+/// Unrecognised characters next to each other make up one error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub span: Span,
+    pub text: String,
+    pub kind: LexErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    /// Characters that don't start any token.
+    Unrecognised,
+    /// An escape in a string or char literal, which still lexes as a token.
+    /// Holds what's wrong with it.
+    BadEscape(String),
+    /// A `_` right after an integer literal's radix prefix: `0x_FF`. The
+    /// literal still lexes as a token.
+    SeparatorAfterPrefix,
+    /// `1b` lexed with [`LexOptions::suffix_radix`] after a label `1:`:
+    /// binary one, though it spells a reference to the label too. Only a
+    /// warning.
+    SuffixLabelRef,
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = match &self.kind {
+            LexErrorKind::Unrecognised => {
+                let what = if self.text.chars().count() == 1 {
+                    "character"
+                } else {
+                    "characters"
+                };
+                Diagnostic::error(format!("unexpected {} `{}`", what, self.text.escape_debug()))
+                    .with_code(Code::E0005)
+            }
+            LexErrorKind::BadEscape(message) => {
+                Diagnostic::error(message.clone()).with_code(Code::E0006)
+            }
+            LexErrorKind::SeparatorAfterPrefix => {
+                let (prefix, digits) = self.text.split_at(2);
+                Diagnostic::error(format!("`_` right after `{}` in `{}`", prefix, self.text))
+                    .with_code(Code::E0008)
+                    .with_note(format!(
+                        "`_` only goes between digits: write `{}{}`",
+                        prefix,
+                        digits.trim_start_matches('_')
+                    ))
+            }
+            LexErrorKind::SuffixLabelRef => {
+                let digits = self.text.trim_end_matches('b');
+                let label = digits.replace('_', "");
+                let value = i64::from_str_radix(&label, 2).unwrap_or_default();
+                Diagnostic::warning(format!(
+                    "`{}` is binary {} here, not a reference to label `{}:`",
+                    self.text, value, label
+                ))
+                .with_code(Code::W0205)
+                .with_note(format!("write `0b{}` to make the number clear", digits))
+            }
+        };
+        diag.with_span(self.span.clone())
+    }
+}
+
+/// Lexer settings that the token definitions alone can't express.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LexOptions {
+    /// Which identifiers are registers.
+    pub registers: RegisterPattern,
+    /// Reads `0FFh`, `1010b` and `777o` as hex, binary and octal literals,
+    /// for sources written for older assemblers. `1b` is then binary one,
+    /// not a numeric label reference.
+    pub suffix_radix: bool,
+    /// Accepts keywords, directives and mnemonics in any case (`VAR`,
+    /// `@Define`, `MOV`), for sources written in upper case.
+    pub case_insensitive: bool,
+    /// Keeps the whitespace and comments the parser never sees, as each
+    /// token's [`leading`](Token::leading) trivia and the stream's
+    /// [`trailing_trivia`](TokenStream::trailing_trivia), so formatters and
+    /// listings can rebuild the source exactly.
+    pub trivia: bool,
+}
+
+impl LexOptions {
+    pub fn for_target(target: &Target) -> Self {
+        Self {
+            registers: target.registers,
+            suffix_radix: false,
+            case_insensitive: false,
+            trivia: false,
+        }
+    }
+}
+
+impl Default for LexOptions {
+    fn default() -> Self {
+        Self::for_target(&Target::DEFAULT)
+    }
+}
+
+/// Lexes `input`, which starts at byte `base` of `file`. Unrecognised input
+/// comes out as the `Err` span it covers.
+///
+/// Identifiers the target's register pattern matches come out as
+/// [`TokenKind::Register`], and so does its sigil together with the
+/// identifier right after it. With `suffix_radix`, a number and the
+/// identifier right after it make one literal if together they read as one.
+/// Otherwise a decimal literal and an `f` or `b` right after it make a
+/// [`TokenKind::NumericLabelRef`].
+/// With `case_insensitive`, identifiers that spell a keyword in another case
+/// come out as that keyword. With `trivia`, each token carries what was
+/// skipped since the token or unrecognised run before it.
+pub(crate) fn lex(
+    input: &str,
+    base: usize,
+    file: FileId,
+    opts: LexOptions,
+) -> impl Iterator<Item = Result<Token, Range<usize>>> + '_ {
+    let registers = opts.registers;
+    let mut lexer = TokenKind::lexer(input).spanned().peekable();
+    let mut end = 0;
+    let mut leading = Vec::new();
+    std::iter::from_fn(move || {
+        let (mut tok, mut span) = lexer.next()?;
+        if opts.suffix_radix
+            && matches!(tok, Ok(TokenKind::IntLit(_) | TokenKind::FloatLit(_)))
+            && let Some((Ok(TokenKind::Ident(_)), next)) = lexer.peek()
+            && next.start == span.end
+            && let Some(lit) = suffix_lit(&input[span.start..next.end])
+        {
+            span.end = next.end;
+            lexer.next();
+            tok = lit;
+        } else if let Ok(TokenKind::IntLit(n)) = tok
+            && let Some((Ok(TokenKind::Ident(direction)), next)) = lexer.peek()
+            && next.start == span.end
+            && matches!(direction.as_str(), "f" | "b")
+        {
+            tok = Ok(TokenKind::NumericLabelRef(format!("{}{}", n, direction)));
+            span.end = next.end;
+            lexer.next();
+        } else if registers.is_sigil(&input[span.clone()])
+            && let Some((
+                Ok(TokenKind::Ident(_) | TokenKind::Var | TokenKind::Const | TokenKind::Include),
+                next,
+            )) = lexer.peek()
+            && next.start == span.end
+        {
+            span.end = next.end;
+            lexer.next();
+            tok = Ok(TokenKind::Register(input[span.clone()].to_string()));
+        } else if let Ok(TokenKind::Ident(name)) = &mut tok
+            && registers.matches(name)
+        {
+            tok = Ok(TokenKind::Register(mem::take(name)));
+        } else if let Ok(TokenKind::Ident(name)) = &tok
+            && matches!(name.as_str(), "var" | "const" | "include")
+        {
+            // Followed by `#`, which might have started a `##` paste, a
+            // keyword comes out of logos as an identifier.
+            tok = Ok(keyword(name, false).expect("`name` is a keyword"));
+        } else if opts.case_insensitive
+            && let Ok(TokenKind::Ident(name)) = &tok
+        {
+            let bang = match lexer.peek() {
+                Some((Ok(TokenKind::Bang), next)) if next.start == span.end => Some(next.end),
+                _ => None,
+            };
+            if let Some(keyword) = keyword(name, bang.is_some()) {
+                if matches!(
+                    keyword,
+                    TokenKind::MacroRules
+                        | TokenKind::ForBang
+                        | TokenKind::WhileBang
+                        | TokenKind::RepeatBang
+                        | TokenKind::BreakBang
+                        | TokenKind::ContinueBang
+                        | TokenKind::IfBang
+                        | TokenKind::ElifBang
+                        | TokenKind::ElseBang
+                ) && let Some(end) = bang
+                {
+                    span.end = end;
+                    lexer.next();
+                }
+                tok = Ok(keyword);
+            }
+        }
+
+        if opts.trivia {
+            leading.extend(trivia(input, end..span.start, base, file));
+        }
+        end = span.end;
+
+        let abs = span.start + base..span.end + base;
+        Some(match tok {
+            Ok(kind) => Ok(Token {
+                kind,
+                text: input[span].to_string(),
+                span: Span::new(file, abs),
+                leading: mem::take(&mut leading),
+            }),
+            Err(_) => Err(abs),
+        })
+    })
+}
+
+/// The skipped input in `input[range]` as trivia tokens, for `input` starting
+/// at byte `base` of `file`.
+fn trivia(input: &str, range: Range<usize>, base: usize, file: FileId) -> Vec<Token> {
+    split_trivia(&input[range.clone()])
+        .into_iter()
+        .map(|(kind, piece)| {
+            let at = range.start + piece.start..range.start + piece.end;
+            Token {
+                kind,
+                text: input[at.clone()].to_string(),
+                span: Span::new(file, at.start + base..at.end + base),
+                leading: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// The keyword `name` spells in some case, if any. `macro_rules`, `for`,
+/// `while`, `repeat`, `break`, `continue`, `if`, `elif` and `else` only
+/// count with the `!` right after them.
+fn keyword(name: &str, bang: bool) -> Option<TokenKind> {
+    match name.to_ascii_lowercase().as_str() {
+        "var" => Some(TokenKind::Var),
+        "const" => Some(TokenKind::Const),
+        "include" => Some(TokenKind::Include),
+        "macro_rules" if bang => Some(TokenKind::MacroRules),
+        "for" if bang => Some(TokenKind::ForBang),
+        "while" if bang => Some(TokenKind::WhileBang),
+        "repeat" if bang => Some(TokenKind::RepeatBang),
+        "break" if bang => Some(TokenKind::BreakBang),
+        "continue" if bang => Some(TokenKind::ContinueBang),
+        "if" if bang => Some(TokenKind::IfBang),
+        "elif" if bang => Some(TokenKind::ElifBang),
+        "else" if bang => Some(TokenKind::ElseBang),
+        _ => None,
+    }
+}
+
+/// Directive argument list. Almost always 0-3 entries, so they live inline.
+pub type Args = SmallVec<[String; 4]>;
+
+/// Instruction operand list, inline like [`Args`].
+pub type Operands = SmallVec<[Operand; 4]>;
+
+/// A number literal, or a negated one.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+/// Just the value, as chasm would write it: `4`, `1.5`, `2.0`.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            // `{:?}` keeps the `.0` that marks a whole float.
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl fmt::Debug for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// What a `var` or `const` is set to: `4`, `SIZE`, `(SIZE * 2) + 1`,
+/// `strlen(GREETING)`. [`crate::eval`] computes its value.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Number(Number),
+    /// A string literal as written, quotes and escapes included.
+    Str(String),
+    /// A `const`, `@define` or label, by name.
+    Symbol(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// A builtin from [`crate::builtins`], or `stringify!(param)` in a
+    /// macro body, named `stringify!`.
+    Call {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `[1, 2, lo(addr)]`: a constant array.
+    Array(Vec<Expr>),
+    /// `TABLE[i]`: an item of an array, counting from 0.
+    Index {
+        base: Box<Expr>,
+        index: Box<Expr>,
+    },
+}
+
+impl Expr {
+    /// The names the expression refers to, in order, repeats included.
+    /// Called functions aren't among them.
+    pub fn symbols(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut todo = vec![self];
+        while let Some(expr) = todo.pop() {
+            match expr {
+                Expr::Number(_) | Expr::Str(_) => {}
+                Expr::Symbol(name) => names.push(name.as_str()),
+                Expr::Unary { expr, .. } => todo.push(expr),
+                Expr::Binary { lhs, rhs, .. } => todo.extend([rhs, lhs].map(|e| &**e)),
+                Expr::Call { args, .. } | Expr::Array(args) => todo.extend(args.iter().rev()),
+                Expr::Index { base, index } => todo.extend([index, base].map(|e| &**e)),
+            }
+        }
+        names
+    }
+}
+
+/// As chasm would write it. Nested operations get parentheses, so the text
+/// parses back to the same tree: `(SIZE * 2) + 1`, `-(4)`, `~MASK`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(n) => write!(f, "{}", n),
+            Expr::Str(literal) => f.write_str(literal),
+            Expr::Symbol(name) => f.write_str(name),
+            Expr::Unary { op, expr } => {
+                // `-4` would read back as the number -4, `--x` as `--`.
+                let bare = match **expr {
+                    Expr::Unary { .. } | Expr::Binary { .. } => false,
+                    Expr::Number(_) => *op != UnaryOp::Neg,
+                    _ => true,
+                };
+                if bare {
+                    write!(f, "{}{}", op, expr)
+                } else {
+                    write!(f, "{}({})", op, expr)
+                }
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                for (i, side) in [lhs, rhs].into_iter().enumerate() {
+                    if i == 1 {
+                        write!(f, " {} ", op)?;
+                    }
+                    match **side {
+                        Expr::Binary { .. } => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                write_exprs(f, args)?;
+                f.write_str(")")
+            }
+            Expr::Array(items) => {
+                f.write_str("[")?;
+                write_exprs(f, items)?;
+                f.write_str("]")
+            }
+            Expr::Index { base, index } => match **base {
+                Expr::Symbol(_) | Expr::Call { .. } | Expr::Array(_) | Expr::Index { .. } => {
+                    write!(f, "{}[{}]", base, index)
+                }
+                _ => write!(f, "({})[{}]", base, index),
+            },
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum Statement {
+fn write_exprs(f: &mut fmt::Formatter<'_>, exprs: &[Expr]) -> fmt::Result {
+    for (i, expr) in exprs.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", expr)?;
+    }
+    Ok(())
+}
+
+impl fmt::Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnaryOp {
+    /// `-`
+    Neg,
+    /// `~`, bitwise.
+    BitNot,
+    /// `!`: 1 for 0, else 0.
+    Not,
+}
+
+impl UnaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::BitNot => "~",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    And,
+    Or,
+    Xor,
+    /// `<`, `<=`, `>`, `>=`, `==` and `!=`: 1 if true, else 0.
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl BinaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+        }
+    }
+
+    /// How tightly the operator binds, as in C: `*` over `+` over `<<`
+    /// over `<` over `==` over `&` over `^` over `|`.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 8,
+            BinaryOp::Add | BinaryOp::Sub => 7,
+            BinaryOp::Shl | BinaryOp::Shr => 6,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 5,
+            BinaryOp::Eq | BinaryOp::Ne => 4,
+            BinaryOp::And => 3,
+            BinaryOp::Xor => 2,
+            BinaryOp::Or => 1,
+        }
+    }
+
+    fn from_token(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::Plus => BinaryOp::Add,
+            TokenKind::Minus => BinaryOp::Sub,
+            TokenKind::Star => BinaryOp::Mul,
+            TokenKind::Slash => BinaryOp::Div,
+            TokenKind::Mod => BinaryOp::Rem,
+            TokenKind::LessLess => BinaryOp::Shl,
+            TokenKind::GreaterGreater => BinaryOp::Shr,
+            TokenKind::Amp => BinaryOp::And,
+            TokenKind::Pipe => BinaryOp::Or,
+            TokenKind::Xor => BinaryOp::Xor,
+            TokenKind::Less => BinaryOp::Lt,
+            TokenKind::LessEqual => BinaryOp::Le,
+            TokenKind::Greater => BinaryOp::Gt,
+            TokenKind::GreaterEqual => BinaryOp::Ge,
+            TokenKind::EqualEqual => BinaryOp::Eq,
+            TokenKind::BangEqual => BinaryOp::Ne,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// One instruction operand, sorted by what it is so encoders needn't
+/// re-read the text.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operand {
+    /// `r0`.
+    Register(String),
+    /// `#123`, `#label + 1`: the value itself. Targets like the 6502 tell
+    /// `lda #10` (load 10) from `lda 10` (load what's at 10).
+    Immediate(Expr),
+    /// `loop`: a label, constant or macro parameter, by name.
+    Symbol(String),
+    /// `10`, `$ - start`: any other value.
+    Expr(Expr),
+    /// `[r2 + 4 * i]`: what's at an address.
+    Memory(Box<MemoryOperand>),
+    /// `"text"`, the literal as written.
+    String(String),
+    /// `'A'`.
+    Char(char),
+}
+
+impl Operand {
+    /// The names the operand refers to, in source order.
+    pub fn symbols(&self) -> Vec<&str> {
+        match self {
+            Operand::Symbol(name) => vec![name.as_str()],
+            Operand::Immediate(expr) | Operand::Expr(expr) => expr.symbols(),
+            Operand::Memory(memory) => memory.symbols(),
+            Operand::Register(_) | Operand::String(_) | Operand::Char(_) => Vec::new(),
+        }
+    }
+}
+
+/// As chasm would write it.
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(name) | Operand::Symbol(name) => f.write_str(name),
+            Operand::Immediate(expr) => write!(f, "#{}", expr),
+            // One starting with `[` would read back as a memory operand.
+            Operand::Expr(expr) => {
+                let text = expr.to_string();
+                if text.starts_with('[') {
+                    write!(f, "({})", text)
+                } else {
+                    f.write_str(&text)
+                }
+            }
+            Operand::Memory(memory) => write!(f, "{}", memory),
+            Operand::String(literal) => f.write_str(literal),
+            Operand::Char(c) => write!(f, "'{}'", c.escape_default()),
+        }
+    }
+}
+
+/// The address `base + index * scale + displacement`, from an operand
+/// like `[r1 + r2 * 4 + 8]`. Registers can only be added, and only the
+/// index scaled; every other term sums into the displacement.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryOperand {
+    pub base: Option<String>,
+    pub index: Option<String>,
+    /// What `index` is multiplied by: 1 unless written.
+    pub scale: i64,
+    pub displacement: Option<Expr>,
+}
+
+impl MemoryOperand {
+    /// The names the displacement refers to. Registers aren't symbols.
+    pub fn symbols(&self) -> Vec<&str> {
+        self.displacement.as_ref().map_or_else(Vec::new, Expr::symbols)
+    }
+}
+
+/// As chasm would write it: base, index, then displacement.
+impl fmt::Display for MemoryOperand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        if let Some(base) = &self.base {
+            f.write_str(base)?;
+        }
+        if let Some(index) = &self.index {
+            if self.base.is_some() {
+                f.write_str(" + ")?;
+            }
+            f.write_str(index)?;
+            if self.scale != 1 {
+                write!(f, " * {}", self.scale)?;
+            }
+        }
+        if let Some(disp) = &self.displacement {
+            // Anything looser than `+` has to stay one term.
+            let loose = |e: &Expr| match e {
+                Expr::Binary { op, .. } => op.precedence() < BinaryOp::Add.precedence(),
+                _ => false,
+            };
+            let registers = self.base.is_some() || self.index.is_some();
+            let text = disp.to_string();
+            match disp {
+                Expr::Number(_) if registers && text.starts_with('-') => {
+                    write!(f, " - {}", &text[1..])?
+                }
+                // `-(4)` would read back as the number -4.
+                Expr::Unary {
+                    op: UnaryOp::Neg,
+                    expr,
+                } if registers && !matches!(**expr, Expr::Number(_)) => match **expr {
+                    Expr::Binary { .. } => write!(f, " - ({})", expr)?,
+                    _ => write!(f, " - {}", expr)?,
+                },
+                _ if registers && loose(disp) => write!(f, " + ({})", text)?,
+                _ if registers => write!(f, " + {}", text)?,
+                _ if loose(disp) => write!(f, "({})", text)?,
+                _ => f.write_str(&text)?,
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+/// How a `for!` condition compares the loop variable with its bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+        }
+    }
+
+    /// Whether `lhs` compared with `rhs` this way is true.
+    pub fn holds(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+            Comparison::Eq => lhs == rhs,
+            Comparison::Ne => lhs != rhs,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The item size of a data directive like `.word`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataWidth {
+    Byte,
+    Word,
+    Dword,
+    Quad,
+}
+
+impl DataWidth {
+    pub const ALL: &'static [DataWidth] =
+        &[DataWidth::Byte, DataWidth::Word, DataWidth::Dword, DataWidth::Quad];
+
+    /// The directive, dot included.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataWidth::Byte => ".byte",
+            DataWidth::Word => ".word",
+            DataWidth::Dword => ".dword",
+            DataWidth::Quad => ".quad",
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            DataWidth::Byte => 8,
+            DataWidth::Word => 16,
+            DataWidth::Dword => 32,
+            DataWidth::Quad => 64,
+        }
+    }
+
+    /// The width a directive name, without its dot, stands for.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|width| &width.as_str()[1..] == name)
+    }
+}
+
+impl fmt::Display for DataWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How string data like `.asciiz "hi"` stores its text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+    /// Little-endian, like everything else chasm writes.
+    Utf16,
+}
+
+impl Encoding {
+    pub const ALL: &'static [Encoding] = &[Encoding::Ascii, Encoding::Utf8, Encoding::Utf16];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ascii",
+            Encoding::Utf8 => "utf8",
+            Encoding::Utf16 => "utf16",
+        }
+    }
+
+    /// The directive storing text this way, dot included: `.ascii`, or
+    /// `.asciiz` with a NUL after the text.
+    pub fn directive(self, nul: bool) -> String {
+        format!(".{}{}", self.as_str(), if nul { "z" } else { "" })
+    }
+
+    /// The encoding and NUL flag a directive name, without its dot,
+    /// stands for.
+    pub fn from_name(name: &str) -> Option<(Self, bool)> {
+        let (name, nul) = match name.strip_suffix('z') {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let encoding = Self::ALL.iter().copied().find(|e| e.as_str() == name)?;
+        Some((encoding, nul))
+    }
+
+    /// `text` in this encoding, or the first character it can't hold.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        match self {
+            Encoding::Ascii => match text.chars().find(|c| !c.is_ascii()) {
+                Some(c) => Err(c),
+                None => Ok(text.as_bytes().to_vec()),
+            },
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Utf16 => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        }
+    }
+
+    /// Bytes per code unit, which is also how long a NUL is.
+    pub fn unit(self) -> usize {
+        match self {
+            Encoding::Ascii | Encoding::Utf8 => 1,
+            Encoding::Utf16 => 2,
+        }
+    }
+}
+
+/// Sections with a directive of their own, like `.text`. `.section NAME`
+/// switches to any other.
+pub const SECTIONS: &[&str] = &["text", "data", "bss", "rodata"];
+
+/// Whether `name`, without its dot, is a `.` directive chasm knows.
+pub fn is_dot_directive(name: &str) -> bool {
+    DataWidth::from_name(name).is_some()
+        || Encoding::from_name(name).is_some()
+        || SECTIONS.contains(&name)
+        || name == "section"
+}
+
+/// The operator of a compound `var` assignment like `var offset += 4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssignOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Shl,
+    Shr,
+    And,
+    Or,
+}
+
+impl AssignOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AssignOp::Add => "+=",
+            AssignOp::Sub => "-=",
+            AssignOp::Mul => "*=",
+            AssignOp::Div => "/=",
+            AssignOp::Shl => "<<=",
+            AssignOp::Shr => ">>=",
+            AssignOp::And => "&=",
+            AssignOp::Or => "|=",
+        }
+    }
+
+    /// The operation `var x op= e` applies: `x = x op e`.
+    pub fn binary(self) -> BinaryOp {
+        match self {
+            AssignOp::Add => BinaryOp::Add,
+            AssignOp::Sub => BinaryOp::Sub,
+            AssignOp::Mul => BinaryOp::Mul,
+            AssignOp::Div => BinaryOp::Div,
+            AssignOp::Shl => BinaryOp::Shl,
+            AssignOp::Shr => BinaryOp::Shr,
+            AssignOp::And => BinaryOp::And,
+            AssignOp::Or => BinaryOp::Or,
+        }
+    }
+
+    pub(crate) fn from_token(kind: &TokenKind) -> Option<Self> {
+        Some(match kind {
+            TokenKind::PlusEqual => AssignOp::Add,
+            TokenKind::MinusEqual => AssignOp::Sub,
+            TokenKind::StarEqual => AssignOp::Mul,
+            TokenKind::SlashEqual => AssignOp::Div,
+            TokenKind::LessLessEqual => AssignOp::Shl,
+            TokenKind::GreaterGreaterEqual => AssignOp::Shr,
+            TokenKind::AmpEqual => AssignOp::And,
+            TokenKind::PipeEqual => AssignOp::Or,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for AssignOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Who can refer to a label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Visibility {
+    /// `.loop:` and `1:`: only the code around it.
+    Local,
+    /// `start:`: anything in the same file.
+    File,
+    /// `::start:`: other objects too, once linked.
+    Global,
+}
+
+impl Visibility {
+    pub const ALL: &'static [Visibility] =
+        &[Visibility::Local, Visibility::File, Visibility::Global];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Local => "local",
+            Visibility::File => "file",
+            Visibility::Global => "global",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StatementKind {
     VarAssign {
         name: String,
-        expr: i64,
+        expr: Expr,
+    },
+    /// `var offset += 4`: updates a `var` in place.
+    VarUpdate {
+        name: String,
+        op: AssignOp,
+        expr: Expr,
     },
     ConstAssign {
         name: String,
-        expr: i64,
+        expr: Expr,
+    },
+    /// `LIMIT equ TOP - 4`: a symbol standing for `expr`, worked out where
+    /// it's used rather than where it's defined.
+    Equ {
+        name: String,
+        expr: Expr,
+    },
+    /// `name:`, `.name:` (its dot kept), `1:` or `::name:`.
+    Label {
+        name: String,
+        visibility: Visibility,
     },
-    Label(String),
     Instruction {
         name: String,
-        args: Vec<String>,
+        args: Operands,
+        /// Where each of `args` is, in the same order, for diagnostics
+        /// about one operand.
+        spans: Vec<Span>,
     },
 
     Directive {
         name: String,
-        args: Vec<String>,
+        args: Args,
+    },
+    /// `@org 0x8000`: the address the section goes on from.
+    Org(Expr),
+    /// `@align 4, 0xff`: pads with `fill`, zero if there's none, up to the
+    /// next address that's a multiple of `boundary`.
+    Align {
+        boundary: Expr,
+        fill: Option<Expr>,
     },
     Include(String),
+    /// `.word start, end, 0x1234`: one `width`-sized item per value.
+    Data {
+        width: DataWidth,
+        values: Vec<Expr>,
+        /// Where each of `values` is, in the same order.
+        spans: Vec<Span>,
+    },
+    /// `.data` or `.section NAME`: later statements go in the named
+    /// section, without its dot.
+    Section(String),
+    /// `.asciiz "hi\n"`: a string literal, as written, stored as
+    /// `encoding` and followed by a NUL if `nul`. In a macro body the
+    /// literal may be `stringify!(param)` instead.
+    StringData {
+        encoding: Encoding,
+        nul: bool,
+        literal: String,
+    },
 
     MacroDef {
         name: String,
+        /// The last one ends in `...` if it's variadic, like `regs...`.
         params: Vec<String>,
+        /// The values of the parameters a call may leave out, which come
+        /// last but for a variadic one: `delay(cycles = 10)` has `[10]`.
+        defaults: Vec<Operand>,
         body: Vec<Statement>,
     },
+    /// `add2!(R1, R2)`: a call of a `macro_rules!` macro.
+    MacroCall {
+        name: String,
+        args: Operands,
+    },
 
+    /// `for!(var i = 0; i < N; i += 2) { ... }`. `i++` and `i--` are
+    /// `i += 1` and `i -= 1`.
     ForLoop {
         var: String,
-        start: i64,
-        end: i64,
+        start: Expr,
+        cmp: Comparison,
+        end: Expr,
+        op: AssignOp,
+        step: Expr,
+        body: Vec<Statement>,
+    },
+    /// `while!(n < 8) { ... }`: repeats its body while `cond` is nonzero.
+    WhileLoop {
+        cond: Expr,
+        body: Vec<Statement>,
+    },
+    /// `repeat!(8) { ... }`: its body `count` times over, with no loop
+    /// variable.
+    Repeat {
+        count: Expr,
         body: Vec<Statement>,
     },
+    /// `@foreach regs { ... }`, in a macro body: its body once per
+    /// argument of variadic parameter `param`, which stands for that one
+    /// argument in it.
+    Foreach {
+        param: String,
+        body: Vec<Statement>,
+    },
+    /// `break!`: leaves the innermost `for!`, `while!` or `repeat!`.
+    Break,
+    /// `continue!`: goes on to the next iteration of the innermost loop.
+    Continue,
+    /// `if!(A) { ... } elif!(B) { ... } else { ... }`: only the first
+    /// branch whose condition is nonzero is assembled, else `otherwise`.
+    If {
+        branches: Vec<IfBranch>,
+        otherwise: Vec<Statement>,
+    },
+    /// `module io { ... }`: the labels, constants and `equ`s defined in
+    /// `body` are `io::name` outside it. Modules nest.
+    Module {
+        name: String,
+        body: Vec<Statement>,
+    },
+    /// `struct Point { x: word, y: word }`: a layout for data. `Point.x`
+    /// and `Point.y` are the fields' offsets in bytes, and `Point`, or
+    /// `sizeof(Point)`, its size. Fields are packed, with no padding.
+    Struct {
+        name: String,
+        fields: Vec<StructField>,
+    },
+    /// `Point { x: 1, y: 2 }`: one `Point`'s worth of data, each field
+    /// stored at its width. Fields left out are zero.
+    StructData {
+        name: String,
+        fields: Vec<(String, Expr)>,
+    },
+    /// `enum State { Idle, Run = 4, Stop }`: constants `State.Idle`,
+    /// `State.Run` and `State.Stop`. A variant without a value is one more
+    /// than the variant before it, the first zero.
+    Enum {
+        name: String,
+        variants: Vec<(String, Option<Expr>)>,
+    },
+
+    Block(Vec<Statement>),
+}
+
+/// One field of a [`StatementKind::Struct`]: `x: word`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructField {
+    pub name: String,
+    pub width: DataWidth,
+}
+
+/// One `if!` or `elif!` of an [`StatementKind::If`].
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IfBranch {
+    pub cond: Expr,
+    pub body: Vec<Statement>,
+}
+
+/// How deep blocks (including macro and loop bodies) may nest. The parser
+/// recurses per level, so this is what keeps hostile input from
+/// overflowing the stack.
+pub const MAX_NESTING: usize = 256;
+
+pub struct Parser {
+    pub(crate) stream: TokenStream,
+    pub(crate) depth: usize,
+    skipped: Vec<Span>,
+    pub(crate) errors: Vec<ParseError>,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Self {
+        Self {
+            stream: TokenStream::new(input),
+            depth: 0,
+            skipped: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Parses a file registered with `sources`; spans and error locations
+    /// refer to that file.
+    pub fn for_file(sources: &SourceManager, file: FileId) -> Self {
+        Self::with_options(sources, file, LexOptions::default())
+    }
+
+    /// Like [`for_file`](Self::for_file), lexing the way `opts` says.
+    pub fn with_options(sources: &SourceManager, file: FileId, opts: LexOptions) -> Self {
+        Self {
+            stream: TokenStream::with_options(file, sources.file(file).clone(), opts),
+            depth: 0,
+            skipped: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Like [`with_options`](Self::with_options), over a
+    /// [`TokenStream::streaming`] stream: tokens are lexed as they're parsed
+    /// and dropped statement by statement.
+    pub fn streaming(sources: &SourceManager, file: FileId, opts: LexOptions) -> Self {
+        Self::from_stream(TokenStream::streaming(file, sources.file(file).clone(), opts))
+    }
+
+    pub(crate) fn from_stream(stream: TokenStream) -> Self {
+        Self {
+            stream,
+            depth: 0,
+            skipped: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn stream(&self) -> &TokenStream {
+        &self.stream
+    }
+
+    pub(crate) fn into_stream(self) -> TokenStream {
+        self.stream
+    }
+
+    /// Tokens dropped so far because they couldn't start a statement, in
+    /// source order.
+    pub fn skipped(&self) -> Vec<Span> {
+        self.skipped.clone()
+    }
+
+    /// Text the lexer didn't recognise, in source order. It never reaches
+    /// the parser, so each of these is an error on its own.
+    pub fn lex_errors(&self) -> &[LexError] {
+        self.stream.lex_errors()
+    }
+
+    /// Parses everything. A syntax error doesn't stop the parse: it's
+    /// recorded in [`errors`](Self::errors), the rest of its statement is
+    /// skipped and parsing picks up with the next one.
+    pub fn parse(&mut self) -> Vec<Statement> {
+        phase!("parse");
+        let mut stmts = vec![];
+
+        while !self.stream.eof() {
+            if let Some(stmt) = self.step() {
+                stmts.push(stmt);
+            }
+        }
+
+        stmts
+    }
+
+    /// Syntax errors found so far, in source order.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Parses lazily: each `next` parses just enough tokens for one more
+    /// top-level statement, so a caller can stop early without the rest
+    /// being parsed. Nested bodies arrive whole inside their statement.
+    ///
+    /// A syntax error is yielded as `Err` as soon as it's found, so one in
+    /// a block comes out ahead of the statement holding the block. Parsing
+    /// carries on with the next statement, so errors come out in source
+    /// order.
+    pub fn statements(&mut self) -> Statements<'_> {
+        let reported = self.errors.len();
+        Statements {
+            parser: self,
+            reported,
+            pending: None,
+            phase: piecewise_phase!("parse"),
+        }
+    }
+
+    /// One iteration of the top-level loop: a statement, or `None` if the
+    /// tokens consumed didn't form one (unknown tokens are dropped here,
+    /// and statements with a syntax error).
+    pub(crate) fn step(&mut self) -> Option<Statement> {
+        self.stream.release();
+        self.parse_recovering()
+    }
+
+    /// The next statement. If it has a syntax error, the error is recorded
+    /// and the rest of the statement skipped instead.
+    fn parse_recovering(&mut self) -> Option<Statement> {
+        let depth = self.depth;
+        match self.parse_spanned() {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                debug!(error = %err.message, "recovering from syntax error");
+                self.errors.push(err);
+                // The error may have come from inside a nested block or
+                // expression.
+                self.depth = depth;
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Skips what's left of a statement that failed to parse: the rest of
+    /// its line, through a `;`, or up to the `}` closing the block it's in.
+    pub(crate) fn synchronize(&mut self) {
+        while let Some(tok) = self.stream.peek() {
+            if self.stream.newline_before_next()
+                || (tok.kind == TokenKind::RightBrace && self.depth > 0)
+            {
+                break;
+            }
+            let separator = tok.kind == TokenKind::Semicolon;
+            self.stream.next();
+            if separator {
+                break;
+            }
+        }
+    }
+
+    fn parse_spanned(&mut self) -> Result<Option<Statement>, ParseError> {
+        let Some(start) = self.stream.peek().map(|t| t.span.clone()) else {
+            return Ok(None);
+        };
+        let Some(kind) = self.parse_statement()? else {
+            return Ok(None);
+        };
+        let span = start.to(&self.stream.last_span());
+        Ok(Some(Statement { kind, span }))
+    }
+
+    pub(crate) fn parse_statement(&mut self) -> Result<Option<StatementKind>, ParseError> {
+        let Some(tok) = self.stream.peek() else {
+            return Ok(None);
+        };
+        let kind = match &tok.kind {
+            TokenKind::Var => self.parse_var(),
+            TokenKind::Const => self.parse_const(),
+
+            TokenKind::Ident(_)
+            | TokenKind::IntLit(_)
+            | TokenKind::Dot
+            | TokenKind::DoubleColon
+            | TokenKind::Register(_)
+                if self.lookahead_is_label() =>
+            {
+                self.parse_label()
+            }
+
+            TokenKind::AtDirective => self.parse_directive(),
+
+            TokenKind::Include => self.parse_include(),
+
+            TokenKind::Dot => self.parse_dot_directive(),
+
+            TokenKind::MacroRules => self.parse_macro(),
+
+            TokenKind::ForBang => self.parse_for_loop(),
+
+            TokenKind::WhileBang => self.parse_while_loop(),
+
+            TokenKind::RepeatBang => self.parse_repeat(),
+
+            TokenKind::BreakBang => {
+                self.stream.next();
+                Ok(StatementKind::Break)
+            }
+            TokenKind::ContinueBang => {
+                self.stream.next();
+                Ok(StatementKind::Continue)
+            }
+
+            TokenKind::IfBang => self.parse_if(),
+            TokenKind::ElifBang | TokenKind::ElseBang => {
+                self.stream.next();
+                self.stream.fail("`elif!` and `else!` only follow an `if!` body")
+            }
+
+            TokenKind::LeftBrace => self.parse_block().map(StatementKind::Block),
+
+            TokenKind::Ident(_) if self.lookahead_is_module() => self.parse_module(),
+            TokenKind::Ident(_) if self.lookahead_is_struct() => self.parse_struct(),
+            TokenKind::Ident(_) if self.lookahead_is_enum() => self.parse_enum(),
+            TokenKind::Ident(_) if self.lookahead_is_struct_data() => self.parse_struct_data(),
+            TokenKind::Ident(_) if self.lookahead_is_equ() => self.parse_equ(),
+            TokenKind::Ident(_) if self.lookahead_is_macro_call() => self.parse_macro_call(),
+            TokenKind::Ident(_) => self.parse_instruction(),
+
+            TokenKind::UnterminatedComment => {
+                self.stream.next();
+                self.stream.fail_with(Code::E0004, "block comment is never closed")
+            }
+
+            // `;` is tolerated as a separator; anything else is reported.
+            TokenKind::Semicolon => {
+                self.stream.next();
+                return Ok(None);
+            }
+            _ => {
+                let span = tok.span.clone();
+                self.stream.next();
+                self.skipped.push(span);
+                return Ok(None);
+            }
+        };
+        kind.map(Some)
+    }
+
+    pub(crate) fn lookahead_is_label(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+
+        match (ahead.next(), ahead.next(), ahead.next(), ahead.next()) {
+            (Some(TokenKind::Register(name)), Some(TokenKind::Colon), _, _) => {
+                name.starts_with('%')
+            }
+            (Some(TokenKind::Ident(_) | TokenKind::IntLit(_)), Some(TokenKind::Colon), _, _)
+            | (Some(TokenKind::Dot), Some(TokenKind::Ident(_)), Some(TokenKind::Colon), _)
+            | (
+                Some(TokenKind::DoubleColon),
+                Some(TokenKind::Ident(_)),
+                Some(TokenKind::Colon),
+                _,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the statement being parsed ends here: at the end of the
+    /// line or input, a `;` before the next statement on the line, or the
+    /// `}` closing a block.
+    pub(crate) fn at_statement_end(&self) -> bool {
+        self.stream.newline_before_next()
+            || self
+                .stream
+                .peek()
+                .is_none_or(|t| matches!(t.kind, TokenKind::Semicolon | TokenKind::RightBrace))
+    }
+
+    /// `name equ`, in any case.
+    pub(crate) fn lookahead_is_equ(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+        match (ahead.next(), ahead.next()) {
+            (Some(TokenKind::Ident(_)), Some(TokenKind::Ident(word))) => {
+                word.eq_ignore_ascii_case("equ")
+            }
+            _ => false,
+        }
+    }
+
+    /// `module name {`, `module` in any case.
+    pub(crate) fn lookahead_is_module(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+        match (ahead.next(), ahead.next(), ahead.next()) {
+            (Some(TokenKind::Ident(word)), Some(TokenKind::Ident(_)), Some(TokenKind::LeftBrace)) => {
+                word.eq_ignore_ascii_case("module")
+            }
+            _ => false,
+        }
+    }
+
+    /// `struct name {`, `struct` in any case.
+    pub(crate) fn lookahead_is_struct(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+        match (ahead.next(), ahead.next(), ahead.next()) {
+            (Some(TokenKind::Ident(word)), Some(TokenKind::Ident(_)), Some(TokenKind::LeftBrace)) => {
+                word.eq_ignore_ascii_case("struct")
+            }
+            _ => false,
+        }
+    }
+
+    /// `enum name {`, `enum` in any case.
+    pub(crate) fn lookahead_is_enum(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+        match (ahead.next(), ahead.next(), ahead.next()) {
+            (Some(TokenKind::Ident(word)), Some(TokenKind::Ident(_)), Some(TokenKind::LeftBrace)) => {
+                word.eq_ignore_ascii_case("enum")
+            }
+            _ => false,
+        }
+    }
+
+    /// `Point { x:` or `Point { }`, the `{` on the name's line; the name
+    /// may be a path like `io::Point`. A `{` on the next line is a block
+    /// after an instruction.
+    pub(crate) fn lookahead_is_struct_data(&self) -> bool {
+        let mut ahead = self.stream.iter();
+        let Some(mut name) = ahead.next() else {
+            return false;
+        };
+        let mut next = ahead.next();
+        while next.is_some_and(|t| t.kind == TokenKind::DoubleColon) {
+            match ahead.next() {
+                Some(part) if matches!(part.kind, TokenKind::Ident(_)) => name = part,
+                _ => return false,
+            }
+            next = ahead.next();
+        }
+        let (Some(brace), Some(first), second) = (next, ahead.next(), ahead.next()) else {
+            return false;
+        };
+        let gap = &self.stream.source.text[name.span.range.end..brace.span.range.start];
+        brace.kind == TokenKind::LeftBrace
+            && !breaks_line(gap)
+            && matches!(
+                (&first.kind, second.map(|t| &t.kind)),
+                (TokenKind::RightBrace, _) | (TokenKind::Ident(_), Some(TokenKind::Colon))
+            )
+    }
+
+    /// `::name` or `.name` right against the name just taken, with no
+    /// space either side: the next part of a name like `io::putc` or
+    /// `Point.x`. Gives back the separator.
+    pub(crate) fn lookahead_is_path(&self) -> Option<&'static str> {
+        let end = self.stream.last_span().range.end;
+        let mut ahead = self.stream.iter();
+        let (Some(separator), Some(name)) = (ahead.next(), ahead.next()) else {
+            return None;
+        };
+        let text = match separator.kind {
+            TokenKind::DoubleColon => "::",
+            TokenKind::Dot => ".",
+            _ => return None,
+        };
+        (matches!(name.kind, TokenKind::Ident(_))
+            && separator.span.range.start == end
+            && separator.span.range.end == name.span.range.start)
+            .then_some(text)
+    }
+
+    /// `name!(`, with the `!` right against the name: `name !(x)` is an
+    /// instruction with a negated operand.
+    pub(crate) fn lookahead_is_macro_call(&self) -> bool {
+        let mut ahead = self.stream.iter();
+        let (Some(name), Some(bang), Some(paren)) = (ahead.next(), ahead.next(), ahead.next())
+        else {
+            return false;
+        };
+        matches!(name.kind, TokenKind::Ident(_))
+            && bang.kind == TokenKind::Bang
+            && name.span.range.end == bang.span.range.start
+            && paren.kind == TokenKind::LeftParen
+    }
 
-    Block(Vec<Statement>),
-}
+    /// `name:`; `.name:`, a local label, kept with its dot; `%name:`, one
+    /// a macro expansion renames, kept with its `%`; `1:`, a numeric label,
+    /// which can be defined any number of times; or `::name:`, a global
+    /// one.
+    fn parse_label(&mut self) -> Result<StatementKind, ParseError> {
+        let (name, visibility) = match self.stream.peek().map(|t| &t.kind) {
+            Some(TokenKind::IntLit(n)) => {
+                let name = n.to_string();
+                self.stream.next();
+                (name, Visibility::Local)
+            }
+            Some(TokenKind::Register(name)) if name.starts_with('%') => {
+                let name = name.clone();
+                self.stream.next();
+                (name, Visibility::Local)
+            }
+            Some(TokenKind::Dot) => {
+                self.stream.next();
+                let name = format!(".{}", self.ident("a local label")?);
+                (name, Visibility::Local)
+            }
+            Some(TokenKind::DoubleColon) => {
+                self.stream.next();
+                (self.ident("a label")?, Visibility::Global)
+            }
+            _ => (self.ident("a label")?, Visibility::File),
+        };
+        self.stream.expect(TokenKind::Colon)?;
+        Ok(StatementKind::Label { name, visibility })
+    }
 
-pub struct Parser {
-    stream: TokenStream,
-}
+    /// `module name { ... }`.
+    fn parse_module(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.next(); // eat 'module'
+        let name = self.ident("a module name")?;
+        let body = self.parse_block()?;
+        Ok(StatementKind::Module { name, body })
+    }
 
-impl Parser {
-    pub fn new(input: &str) -> Self {
-        Self {
-            stream: TokenStream::new(input),
+    /// `struct name { field: width, ... }`, the commas optional.
+    fn parse_struct(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.next(); // eat 'struct'
+        let name = self.ident("a struct name")?;
+        self.stream.expect(TokenKind::LeftBrace)?;
+        let mut fields: Vec<StructField> = Vec::new();
+        while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightBrace) {
+            let field = self.ident("a field name")?;
+            if fields.iter().any(|f| f.name == field) {
+                return self
+                    .stream
+                    .fail(format_args!("`{}` has two fields named `{}`", name, field));
+            }
+            self.stream.expect(TokenKind::Colon)?;
+            let width = self.ident("a field width")?;
+            let Some(width) = DataWidth::from_name(&width) else {
+                return self.stream.fail(format_args!(
+                    "`{}` isn't a field width; use `byte`, `word`, `dword` or `quad`",
+                    width
+                ));
+            };
+            fields.push(StructField { name: field, width });
+            if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                self.stream.next();
+            }
         }
+        self.stream.expect(TokenKind::RightBrace)?;
+        Ok(StatementKind::Struct { name, fields })
     }
 
-    pub fn parse(&mut self) -> Vec<Statement> {
-        let mut stmts = vec![];
-
-        while !self.stream.eof() {
-            if let Some(stmt) = self.parse_statement() {
-                stmts.push(stmt);
+    /// `enum name { Variant, Variant = value, ... }`, the commas optional.
+    fn parse_enum(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.next(); // eat 'enum'
+        let name = self.ident("an enum name")?;
+        self.stream.expect(TokenKind::LeftBrace)?;
+        let mut variants: Vec<(String, Option<Expr>)> = Vec::new();
+        while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightBrace) {
+            let variant = self.ident("a variant name")?;
+            if variants.iter().any(|(v, _)| *v == variant) {
+                return self
+                    .stream
+                    .fail(format_args!("`{}` has two variants named `{}`", name, variant));
+            }
+            let value = if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Equal) {
+                self.stream.next();
+                Some(self.parse_expr()?)
             } else {
-                self.stream.next(); // skip unknown
+                None
+            };
+            variants.push((variant, value));
+            if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                self.stream.next();
             }
         }
-
-        stmts
+        self.stream.expect(TokenKind::RightBrace)?;
+        Ok(StatementKind::Enum { name, variants })
     }
 
-    fn parse_statement(&mut self) -> Option<Statement> {
-        let tok = self.stream.peek()?.kind.clone();
-
-        match tok {
-            TokenKind::Var => return self.parse_var(),
-            TokenKind::Const => return self.parse_const(),
-
-            TokenKind::Ident(_) if self.lookahead_is_label() => {
-                return self.parse_label();
+    /// `Point { x: value, ... }`, the commas optional.
+    fn parse_struct_data(&mut self) -> Result<StatementKind, ParseError> {
+        let mut name = self.ident("a struct name")?;
+        while self.lookahead_is_path() == Some("::") {
+            self.stream.next();
+            name.push_str("::");
+            name.push_str(&self.ident("a name")?);
+        }
+        self.stream.expect(TokenKind::LeftBrace)?;
+        let mut fields = Vec::new();
+        while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightBrace) {
+            let field = self.ident("a field name")?;
+            self.stream.expect(TokenKind::Colon)?;
+            fields.push((field, self.parse_expr()?));
+            if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                self.stream.next();
             }
+        }
+        self.stream.expect(TokenKind::RightBrace)?;
+        Ok(StatementKind::StructData { name, fields })
+    }
 
-            TokenKind::AtDirective => return self.parse_directive(),
-
-            TokenKind::Include => return self.parse_include(),
-
-            TokenKind::MacroRules => return self.parse_macro(),
+    fn parse_instruction(&mut self) -> Result<StatementKind, ParseError> {
+        // eat the name
+        let mut name = self.ident("a mnemonic")?;
+        if self.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
 
-            TokenKind::ForBang => return self.parse_for_loop(),
+        // parse zero or more operands until the statement ends or a token
+        // that can't be one; commas between them are optional
+        let mut args = Operands::new();
+        let mut spans = Vec::new();
 
-            TokenKind::LeftBrace => return self.parse_block(),
+        while !self.at_statement_end() {
+            let start = self.stream.peek().map(|t| t.span.clone());
+            let Some(arg) = self.operand()? else {
+                break;
+            };
+            args.push(arg);
+            spans.extend(start.map(|start| start.to(&self.stream.last_span())));
+            if !self.stream.newline_before_next()
+                && self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma)
+            {
+                self.stream.next();
+            }
+        }
 
-            TokenKind::Ident(_) => return self.parse_instruction(),
+        Ok(StatementKind::Instruction { name, args, spans })
+    }
 
-            _ => {
+    /// `name!(arg, ...)`. Arguments are operands, commas required.
+    fn parse_macro_call(&mut self) -> Result<StatementKind, ParseError> {
+        let mut name = self.ident("a macro name")?;
+        if self.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+        self.stream.expect(TokenKind::Bang)?;
+        self.stream.expect(TokenKind::LeftParen)?;
+
+        let mut args = Operands::new();
+        while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightParen) {
+            let Some(arg) = self.operand()? else {
+                return self.unexpected("a macro argument");
+            };
+            args.push(arg);
+            if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
                 self.stream.next();
-                None
+            } else {
+                break;
             }
         }
-    }
+        self.stream.expect(TokenKind::RightParen)?;
 
-    fn lookahead_is_label(&self) -> bool {
-        let a = self.stream.tokens.get(self.stream.pos);
-        let b = self.stream.tokens.get(self.stream.pos + 1);
+        Ok(StatementKind::MacroCall { name, args })
+    }
 
-        match (a.map(|t| &t.kind), b.map(|t| &t.kind)) {
-            (Some(TokenKind::Ident(_)), Some(TokenKind::Colon)) => true,
-            (Some(TokenKind::Dot), Some(TokenKind::Ident(_))) => {
-                matches!(
-                    self.stream.tokens.get(self.stream.pos + 2).map(|t| &t.kind),
-                    Some(TokenKind::Colon)
-                )
+    /// The next operand, or `None`, consuming nothing, if the next token
+    /// can't start one. Values run as far as the expression does, so
+    /// `$ - start` is one operand.
+    fn operand(&mut self) -> Result<Option<Operand>, ParseError> {
+        let Some(tok) = self.stream.peek() else {
+            return Ok(None);
+        };
+        let operand = match tok.kind {
+            TokenKind::Register(_) => Operand::Register(self.register()?),
+            // A literal on its own stays as written; in arithmetic it's a
+            // value like any other.
+            TokenKind::StrLit(_) => {
+                let literal = self.string()?;
+                match self.binary_op() {
+                    Some(_) => Operand::Expr(self.parse_binary_from(Expr::Str(literal), 1)?),
+                    None => Operand::String(literal),
+                }
+            }
+            TokenKind::CharLit(c) => {
+                self.stream.next();
+                match self.binary_op() {
+                    Some(_) => {
+                        let code = Expr::Number(Number::Int(i64::from(u32::from(c))));
+                        Operand::Expr(self.parse_binary_from(code, 1)?)
+                    }
+                    None => Operand::Char(c),
+                }
             }
-            (Some(TokenKind::Colon), Some(TokenKind::Colon)) => {
-                matches!(
-                    self.stream.tokens.get(self.stream.pos + 2).map(|t| &t.kind),
-                    Some(TokenKind::Ident(_))
-                ) && matches!(
-                    self.stream.tokens.get(self.stream.pos + 3).map(|t| &t.kind),
-                    Some(TokenKind::Colon)
-                )
+            TokenKind::Pound => {
+                self.stream.next();
+                Operand::Immediate(self.parse_expr()?)
+            }
+            TokenKind::LeftBracket => Operand::Memory(Box::new(self.parse_memory()?)),
+            TokenKind::IntLit(_)
+            | TokenKind::HexLit(_)
+            | TokenKind::BinLit(_)
+            | TokenKind::OctLit(_)
+            | TokenKind::FloatLit(_)
+            | TokenKind::Ident(_)
+            | TokenKind::NumericLabelRef(_)
+            | TokenKind::Dot
+            | TokenKind::Dollar
+            | TokenKind::DollarDollar
+            | TokenKind::LeftParen
+            | TokenKind::Minus
+            | TokenKind::Tilde
+            | TokenKind::Bang => match self.parse_expr()? {
+                Expr::Symbol(name) => Operand::Symbol(name),
+                expr => Operand::Expr(expr),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(operand))
+    }
+
+    /// `[base + index * scale + displacement]`, the parts in any order.
+    /// Terms are registers, scaled or not, or values as tight as `*`;
+    /// anything looser goes in parentheses.
+    fn parse_memory(&mut self) -> Result<MemoryOperand, ParseError> {
+        self.stream.expect(TokenKind::LeftBracket)?;
+        let mut memory = MemoryOperand {
+            base: None,
+            index: None,
+            scale: 1,
+            displacement: None,
+        };
+        let mut op = BinaryOp::Add;
+        loop {
+            if self.register_term() {
+                if op == BinaryOp::Sub {
+                    return self.stream.fail("registers can only be added in a memory operand");
+                }
+                self.memory_register(&mut memory)?;
+            } else {
+                let term = self.parse_binary(BinaryOp::Mul.precedence())?;
+                memory.displacement = Some(match memory.displacement.take() {
+                    Some(lhs) => Expr::Binary {
+                        op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(term),
+                    },
+                    None if op == BinaryOp::Sub => match term {
+                        Expr::Number(Number::Int(n)) => Expr::Number(Number::Int(-n)),
+                        Expr::Number(Number::Float(x)) => Expr::Number(Number::Float(-x)),
+                        term => Expr::Unary {
+                            op: UnaryOp::Neg,
+                            expr: Box::new(term),
+                        },
+                    },
+                    None => term,
+                });
             }
+            op = match self.stream.peek().map(|t| &t.kind) {
+                Some(TokenKind::Plus) => BinaryOp::Add,
+                Some(TokenKind::Minus) => BinaryOp::Sub,
+                _ => break,
+            };
+            self.stream.next();
+        }
+        self.stream.expect(TokenKind::RightBracket)?;
+        Ok(memory)
+    }
+
+    /// Whether a memory operand's next term is a register: `r2`, `r2 * 4`
+    /// or `4 * r2`.
+    pub(crate) fn register_term(&self) -> bool {
+        let mut ahead = self.stream.iter().map(|t| &t.kind);
+        match ahead.next() {
+            Some(TokenKind::Register(_)) => true,
+            Some(kind) if kind.int_value().is_some() => matches!(
+                (ahead.next(), ahead.next()),
+                (Some(TokenKind::Star), Some(TokenKind::Register(_)))
+            ),
             _ => false,
         }
     }
 
-    fn parse_label(&mut self) -> Option<Statement> {
-        if let TokenKind::Ident(name) = self.stream.next()?.kind.clone() {
-            self.stream.expect(TokenKind::Colon);
-            Some(Statement::Label(name))
-        } else {
-            None
+    /// Takes a register term into `memory`: the base if it's unscaled and
+    /// the first, the index otherwise.
+    pub(crate) fn memory_register(&mut self, memory: &mut MemoryOperand) -> Result<(), ParseError> {
+        let mut scale = None;
+        if self.stream.peek().is_some_and(|t| t.kind.int_value().is_some()) {
+            scale = self.stream.take_int().and_then(Result::ok);
+            self.stream.expect(TokenKind::Star)?;
+        }
+        let register = self.register()?;
+        if scale.is_none() && self.stream.peek().is_some_and(|t| t.kind == TokenKind::Star) {
+            self.stream.next();
+            let taken = self.stream.take_int();
+            scale = Some(self.taken("a scale", taken)?);
+        }
+
+        match scale {
+            None if memory.base.is_none() => memory.base = Some(register),
+            _ if memory.index.is_some() => {
+                return self.stream.fail(format_args!(
+                    "`{}` is a register too many; a memory operand takes a base and an index",
+                    register
+                ));
+            }
+            scale => {
+                memory.index = Some(register);
+                memory.scale = scale.unwrap_or(1);
+            }
         }
+        Ok(())
     }
-    fn parse_instruction(&mut self) -> Option<Statement> {
-        // eat the name
-        let name = match self.stream.next()?.kind.clone() {
-            TokenKind::Ident(n) => n,
-            _ => return None,
-        };
 
-        // parse zero or more arguments until newline or symbol
+    fn parse_directive(&mut self) -> Result<StatementKind, ParseError> {
+        // read @something
+        let Some(tok) = self.stream.next() else {
+            return self.stream.unexpected_eof();
+        };
+        let mut name = tok.text.trim_start_matches('@').to_string();
+        if self.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+        if name == "org" {
+            return self.parse_expr().map(StatementKind::Org);
+        }
+        if name == "align" {
+            return self.parse_align();
+        }
+        if name == "foreach" {
+            let param = self.ident("a variadic parameter")?;
+            let body = self.parse_block()?;
+            return Ok(StatementKind::Foreach { param, body });
+        }
 
-        let mut args = Vec::new();
+        // now parse args
+        let mut args = Args::new();
+
+        // @name(a, b, c)
+        if let Some(TokenKind::LeftParen) = self.stream.peek().map(|t| &t.kind) {
+            self.stream.next();
+            loop {
+                let Some(tok) = self.stream.peek() else {
+                    return self.stream.unexpected_eof();
+                };
+                match &tok.kind {
+                    TokenKind::Ident(_) => args.push(self.ident("an argument")?),
+                    TokenKind::Register(_) => args.push(self.register()?),
+                    TokenKind::StrLit(_) => args.push(self.string()?),
+                    TokenKind::IntLit(n)
+                    | TokenKind::HexLit(n)
+                    | TokenKind::BinLit(n)
+                    | TokenKind::OctLit(n) => {
+                        args.push(n.to_string());
+                        self.stream.next();
+                    }
+                    TokenKind::FloatLit(x) => {
+                        args.push(Number::Float(*x).to_string());
+                        self.stream.next();
+                    }
+                    TokenKind::Dollar | TokenKind::DollarDollar => {
+                        args.push(tok.text.clone());
+                        self.stream.next();
+                    }
+                    TokenKind::Comma => {
+                        self.stream.next();
+                    }
+                    TokenKind::RightParen => {
+                        self.stream.next();
+                        break;
+                    }
+                    t => {
+                        let t = t.clone();
+                        self.stream.next();
+                        return self.stream.fail(format_args!(
+                            "Unexpected token in directive arguments: {:?}",
+                            t
+                        ));
+                    }
+                }
+            }
+            return Ok(StatementKind::Directive { name, args });
+        }
 
+        // @name a, b, c up to the end of the line
         while let Some(tok) = self.stream.peek() {
+            if self.stream.newline_before_next() {
+                break;
+            }
             match tok.kind {
-                TokenKind::Ident(ref s) => {
-                    args.push(s.clone());
+                TokenKind::Ident(_) => args.push(self.ident("an argument")?),
+                TokenKind::Register(_) => args.push(self.register()?),
+                TokenKind::StrLit(_) => args.push(self.string()?),
+                TokenKind::IntLit(n)
+                | TokenKind::HexLit(n)
+                | TokenKind::BinLit(n)
+                | TokenKind::OctLit(n) => {
+                    args.push(n.to_string());
                     self.stream.next();
                 }
-                TokenKind::IntLit(n) => {
-                    args.push(n.to_string());
-
+                TokenKind::FloatLit(x) => {
+                    args.push(Number::Float(x).to_string());
                     self.stream.next();
                 }
-                TokenKind::StrLit(ref s) => {
-                    args.push(s.clone());
+                TokenKind::Dollar | TokenKind::DollarDollar => {
+                    args.push(tok.text.clone());
                     self.stream.next();
                 }
-                TokenKind::CharLit(c) => {
-                    args.push(format!("{}", c));
+                TokenKind::Comma if !args.is_empty() => {
                     self.stream.next();
                 }
                 _ => break,
             }
         }
 
-        Some(Statement::Instruction { name, args })
+        Ok(StatementKind::Directive { name, args })
     }
 
-    fn parse_directive(&mut self) -> Option<Statement> {
-        // read @something
-        let at_tok = self.stream.next()?.text.clone();
-
-        let name = at_tok.trim_start_matches('@').to_string();
-
-        // now parse args
-        let mut args = vec![];
-
-        while let Some(tok) = self.stream.peek() {
-            match tok.kind {
-                TokenKind::Ident(ref s) | TokenKind::StrLit(ref s) => {
-                    args.push(s.clone());
-                    self.stream.next();
-                }
-                TokenKind::IntLit(n) => {
-                    args.push(n.to_string());
+    /// `.word a, b, c`: a `.` directive and its comma-separated values, up
+    /// to the end of the line. String data takes one string literal.
+    fn parse_dot_directive(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::Dot)?;
+        let mut name = self.ident("a directive name")?;
+        if self.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
+        if SECTIONS.contains(&name.as_str()) {
+            return Ok(StatementKind::Section(name));
+        }
+        if name == "section" {
+            return self.ident("a section name").map(StatementKind::Section);
+        }
+        if let Some((encoding, nul)) = Encoding::from_name(&name) {
+            // `stringify!(param)` stays as written until a call binds it.
+            let literal = match self.stream.peek().map(|t| &t.kind) {
+                Some(TokenKind::Ident(name)) if name == "stringify" => {
                     self.stream.next();
+                    format!("stringify!({})", self.stringify()?)
                 }
-                _ => break,
+                _ => self.string()?,
+            };
+            return Ok(StatementKind::StringData {
+                encoding,
+                nul,
+                literal,
+            });
+        }
+        let Some(width) = DataWidth::from_name(&name) else {
+            return self.stream.fail_with(
+                Code::E0007,
+                format_args!("unknown directive `.{}`", name),
+            );
+        };
+
+        let mut values = Vec::new();
+        let mut spans = Vec::new();
+        while !self.at_statement_end() {
+            let start = self.stream.peek().map(|t| t.span.clone());
+            values.push(self.parse_expr()?);
+            spans.extend(start.map(|start| start.to(&self.stream.last_span())));
+            if self.stream.newline_before_next()
+                || self.stream.peek().is_none_or(|t| t.kind != TokenKind::Comma)
+            {
+                break;
             }
+            self.stream.next();
         }
 
-        Some(Statement::Directive { name, args })
+        Ok(StatementKind::Data {
+            width,
+            values,
+            spans,
+        })
+    }
+    fn parse_align(&mut self) -> Result<StatementKind, ParseError> {
+        let boundary = self.parse_expr()?;
+        let mut fill = None;
+        if !self.stream.newline_before_next()
+            && self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma)
+        {
+            self.stream.next();
+            fill = Some(self.parse_expr()?);
+        }
+        Ok(StatementKind::Align { boundary, fill })
     }
-    fn parse_include(&mut self) -> Option<Statement> {
-        self.stream.expect(TokenKind::Include);
 
-        let file = match self.stream.next()?.kind.clone() {
-            TokenKind::StrLit(s) => s,
-            t => panic!("Expected string literal after include, got {:?}", t),
+    fn parse_include(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::Include)?;
+
+        let file = match self.stream.take_str() {
+            Some(Ok(s)) => s,
+            Some(Err(t)) => {
+                return self.stream.fail(format_args!(
+                    "Expected string literal after include, got {:?}",
+                    t
+                ));
+            }
+            None => return self.stream.unexpected_eof(),
         };
 
-        Some(Statement::Include(file))
+        debug!(file = %file, "include");
+        Ok(StatementKind::Include(file))
     }
-    fn parse_macro(&mut self) -> Option<Statement> {
-        self.stream.expect(TokenKind::MacroRules);
+    fn parse_macro(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::MacroRules)?;
 
-        let name = match self.stream.next()?.kind.clone() {
-            TokenKind::Ident(n) => n,
-
-            t => panic!("Expected macro name, got {:?}", t),
+        let mut name = match self.stream.take_ident() {
+            Some(Ok(n)) => n,
+            Some(Err(t)) => {
+                return self.stream.fail(format_args!("Expected macro name, got {:?}", t));
+            }
+            None => return self.stream.unexpected_eof(),
         };
+        // invoked like a mnemonic, so folded like one
+        if self.stream.fold_case {
+            name.make_ascii_lowercase();
+        }
 
         // parse param list: (a, b, c)
-        self.stream.expect(TokenKind::LeftParen);
+        self.stream.expect(TokenKind::LeftParen)?;
 
         let mut params = Vec::new();
+        let mut defaults = Vec::new();
 
         loop {
-            match self.stream.next()?.kind.clone() {
-                TokenKind::Ident(p) => params.push(p),
-                TokenKind::RightParen => break,
-                TokenKind::Comma => continue,
-                t => panic!("Unexpected token in macro param list: {:?}", t),
+            let Some(tok) = self.stream.peek() else {
+                return self.stream.unexpected_eof();
+            };
+            match &tok.kind {
+                TokenKind::Ident(_)
+                    if params.last().is_some_and(|p: &String| p.ends_with("...")) =>
+                {
+                    return self.stream.fail("only the last parameter can be variadic");
+                }
+                TokenKind::Ident(_) => {
+                    let mut param = self.ident("a parameter")?;
+                    // `regs...`, taking the rest of the arguments.
+                    let mut ahead = self.stream.iter().map(|t| &t.kind);
+                    if (0..3).all(|_| ahead.next() == Some(&TokenKind::Dot)) {
+                        for _ in 0..3 {
+                            self.stream.next();
+                        }
+                        param.push_str("...");
+                    }
+                    // `cycles = 10`, which a call may leave out.
+                    let variadic = param.ends_with("...");
+                    if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Equal) {
+                        if variadic {
+                            return self
+                                .stream
+                                .fail("a variadic parameter can't have a default");
+                        }
+                        self.stream.next();
+                        match self.operand()? {
+                            Some(default) => defaults.push(default),
+                            None => return self.unexpected("a default value"),
+                        }
+                    } else if !variadic && !defaults.is_empty() {
+                        return self.stream.fail(format_args!(
+                            "`{}` needs a default, like the parameters before it",
+                            param
+                        ));
+                    }
+                    params.push(param);
+                }
+                TokenKind::RightParen => {
+                    self.stream.next();
+                    break;
+                }
+                TokenKind::Comma => {
+                    self.stream.next();
+                }
+                t => {
+                    let t = t.clone();
+                    self.stream.next();
+                    return self.stream.fail(format_args!(
+                        "Unexpected token in macro param list: {:?}",
+                        t
+                    ));
+                }
             }
         }
 
         // body is a block
-        let body = match self.parse_block()? {
-            Statement::Block(stmts) => stmts,
-            _ => panic!("Expected a block in macro definition"),
-        };
+        let body = self.parse_block()?;
 
-        Some(Statement::MacroDef { name, params, body })
+        debug!(name = %name, params = params.len(), "macro definition");
+        Ok(StatementKind::MacroDef {
+            name,
+            params,
+            defaults,
+            body,
+        })
     }
-    fn parse_for_loop(&mut self) -> Option<Statement> {
-        self.stream.expect(TokenKind::ForBang);
+    fn parse_for_loop(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::ForBang)?;
 
-        self.stream.expect(TokenKind::LeftParen);
+        self.stream.expect(TokenKind::LeftParen)?;
 
         // initializer: var i = 0
-        self.stream.expect(TokenKind::Var);
-        let var = match self.stream.next()?.kind.clone() {
-            TokenKind::Ident(n) => n,
-
-            _ => panic!("expected loop variable name"),
-        };
-        self.stream.expect(TokenKind::Equal);
-        let start = match self.stream.next()?.kind.clone() {
-            TokenKind::IntLit(v) => v,
-            _ => panic!("expected integer literal in for loop start"),
+        self.stream.expect(TokenKind::Var)?;
+        let var = match self.stream.take_ident() {
+            Some(Ok(n)) => n,
+            Some(Err(_)) => return self.stream.fail("expected loop variable name"),
+            None => return self.stream.unexpected_eof(),
         };
+        self.stream.expect(TokenKind::Equal)?;
+        let start = self.parse_expr()?;
 
-        self.stream.expect(TokenKind::Semicolon);
+        self.stream.expect(TokenKind::Semicolon)?;
 
-        // condition: i < limit
-        self.stream.expect(TokenKind::Ident(var.clone()));
-        self.stream.expect(TokenKind::Less);
-        let end = match self.stream.next()?.kind.clone() {
-            TokenKind::IntLit(v) => v,
-            _ => panic!("expected integer literal in for loop end"),
-        };
+        // condition: i < limit, or any other comparison
+        self.stream.expect_ident(&var)?;
+        let cmp = self.parse_comparison()?;
+        let end = self.parse_expr()?;
 
-        self.stream.expect(TokenKind::Semicolon);
+        self.stream.expect(TokenKind::Semicolon)?;
 
-        // increment: i++
-        self.stream.expect(TokenKind::Ident(var.clone()));
-        self.stream.expect(TokenKind::PlusPlus);
+        // step: i++, i--, or a compound assignment like i += 2
+        self.stream.expect_ident(&var)?;
+        let Some(next) = self.stream.next() else {
+            return self.stream.unexpected_eof();
+        };
+        let one = Expr::Number(Number::Int(1));
+        let (op, step) = match &next.kind {
+            TokenKind::PlusPlus => (AssignOp::Add, one),
+            TokenKind::MinusMinus => (AssignOp::Sub, one),
+            kind => match AssignOp::from_token(kind) {
+                Some(op) => (op, self.parse_expr()?),
+                None => {
+                    let found = kind.clone();
+                    return self.stream.fail(format_args!(
+                        "expected `++`, `--` or a compound assignment like `+=`, got {:?}",
+                        found
+                    ));
+                }
+            },
+        };
 
-        self.stream.expect(TokenKind::RightParen);
+        self.stream.expect(TokenKind::RightParen)?;
 
         // parse body block {...}
-        let body = match self.parse_block()? {
-            Statement::Block(stmts) => stmts,
-            _ => panic!("Expected a block in for loop"),
-        };
+        let body = self.parse_block()?;
 
-        Some(Statement::ForLoop {
+        Ok(StatementKind::ForLoop {
             var,
             start,
+            cmp,
             end,
+            op,
+            step,
             body,
         })
     }
-    fn parse_block(&mut self) -> Option<Statement> {
-        self.stream.expect(TokenKind::LeftBrace);
+
+    fn parse_while_loop(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::WhileBang)?;
+        let (cond, body) = self.parse_guarded()?;
+        Ok(StatementKind::WhileLoop { cond, body })
+    }
+
+    fn parse_repeat(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::RepeatBang)?;
+        let (count, body) = self.parse_guarded()?;
+        Ok(StatementKind::Repeat { count, body })
+    }
+
+    /// `if!(A) { ... }`, any number of `elif!(B) { ... }`, then an optional
+    /// `else { ... }` (or `else!`).
+    fn parse_if(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.expect(TokenKind::IfBang)?;
+        let (cond, body) = self.parse_guarded()?;
+        let mut branches = vec![IfBranch { cond, body }];
+        let mut otherwise = Vec::new();
+        while let Some(tok) = self.stream.peek() {
+            match &tok.kind {
+                TokenKind::ElifBang => {
+                    self.stream.next();
+                    let (cond, body) = self.parse_guarded()?;
+                    branches.push(IfBranch { cond, body });
+                }
+                TokenKind::ElseBang => {
+                    self.stream.next();
+                    otherwise = self.parse_block()?;
+                    break;
+                }
+                TokenKind::Ident(name) if name == "else" => {
+                    self.stream.next();
+                    otherwise = self.parse_block()?;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        Ok(StatementKind::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    /// `(cond) { ... }`: the rest of a `while!`, `repeat!`, `if!` or
+    /// `elif!`.
+    fn parse_guarded(&mut self) -> Result<(Expr, Vec<Statement>), ParseError> {
+        self.stream.expect(TokenKind::LeftParen)?;
+        let cond = self.parse_expr()?;
+        self.stream.expect(TokenKind::RightParen)?;
+        let body = self.parse_block()?;
+        Ok((cond, body))
+    }
+
+    /// `<`, `<=`, `>`, `>=`, `==` or `!=`.
+    pub(crate) fn parse_comparison(&mut self) -> Result<Comparison, ParseError> {
+        let Some(next) = self.stream.next() else {
+            return self.stream.unexpected_eof();
+        };
+        Ok(match next.kind {
+            TokenKind::Less => Comparison::Lt,
+            TokenKind::LessEqual => Comparison::Le,
+            TokenKind::Greater => Comparison::Gt,
+            TokenKind::GreaterEqual => Comparison::Ge,
+            TokenKind::EqualEqual => Comparison::Eq,
+            TokenKind::BangEqual => Comparison::Ne,
+            ref other => {
+                let found = other.clone();
+                return self.stream.fail(format_args!("expected a comparison, found {:?}", found));
+            }
+        })
+    }
+
+    /// `{ ... }`. A statement in it with a syntax error is recorded and
+    /// skipped as at the top level, so the rest of the block still parses.
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.stream.expect(TokenKind::LeftBrace)?;
+
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return self.stream.fail_with(
+                Code::E0003,
+                format_args!("blocks nested more than {} deep", MAX_NESTING),
+            );
+        }
 
         let mut body = Vec::new();
 
@@ -342,51 +2712,366 @@ impl Parser {
                 break;
             }
 
-            if let Some(stmt) = self.parse_statement() {
+            if let Some(stmt) = self.parse_recovering() {
                 body.push(stmt);
-            } else {
-                self.stream.next();
             }
         }
 
-        self.stream.expect(TokenKind::RightBrace);
+        self.stream.expect(TokenKind::RightBrace)?;
+        self.depth -= 1;
 
-        Some(Statement::Block(body))
+        Ok(body)
     }
 
-    fn parse_var(&mut self) -> Option<Statement> {
+    fn parse_var(&mut self) -> Result<StatementKind, ParseError> {
         self.stream.next(); // eat 'var'
 
-        let name = match self.stream.next()?.kind.clone() {
-            TokenKind::Ident(n) => n,
-            t => panic!("expected identifier, got {:?}", t),
+        let name = self.ident("identifier")?;
+
+        let Some(next) = self.stream.next() else {
+            return self.stream.unexpected_eof();
+        };
+        let op = match &next.kind {
+            TokenKind::Equal => None,
+            kind => match AssignOp::from_token(kind) {
+                Some(op) => Some(op),
+                None => {
+                    let found = kind.clone();
+                    return self.stream.fail(format_args!(
+                        "expected `=` or a compound assignment like `+=`, got {:?}",
+                        found
+                    ));
+                }
+            },
         };
 
-        self.stream.expect(TokenKind::Equal);
+        let expr = self.parse_expr()?;
+
+        Ok(match op {
+            None => StatementKind::VarAssign { name, expr },
+            Some(op) => StatementKind::VarUpdate { name, op, expr },
+        })
+    }
+
+    fn parse_const(&mut self) -> Result<StatementKind, ParseError> {
+        self.stream.next(); // eat 'const'
+
+        let name = self.ident("identifier")?;
+
+        self.stream.expect(TokenKind::Equal)?;
+
+        let expr = self.parse_expr()?;
+
+        Ok(StatementKind::ConstAssign { name, expr })
+    }
+
+    fn parse_equ(&mut self) -> Result<StatementKind, ParseError> {
+        let name = self.ident("identifier")?;
+        self.stream.next(); // eat 'equ'
+
+        let expr = self.parse_expr()?;
+
+        Ok(StatementKind::Equ { name, expr })
+    }
+
+    /// A constant expression, by precedence climbing. An operator has to
+    /// be on the line its left operand ends, as instruction arguments do.
+    fn parse_expr(&mut self) -> Result<Expr, ParseError> {
+        self.parse_binary(1)
+    }
+
+    /// Operations binding at least as tightly as `min`, left to right.
+    fn parse_binary(&mut self, min: u8) -> Result<Expr, ParseError> {
+        let lhs = self.parse_unary()?;
+        self.parse_binary_from(lhs, min)
+    }
+
+    /// Like [`parse_binary`](Self::parse_binary), with `lhs` already parsed.
+    fn parse_binary_from(&mut self, mut lhs: Expr, min: u8) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        while let Some(op) = self.binary_op()
+            && op.precedence() >= min
+        {
+            self.stream.next();
+            // A long chain nests as deep as it is long.
+            self.nest_expr()?;
+            let rhs = self.parse_binary(op.precedence() + 1)?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    pub(crate) fn binary_op(&self) -> Option<BinaryOp> {
+        if self.stream.newline_before_next() {
+            return None;
+        }
+        BinaryOp::from_token(&self.stream.peek()?.kind)
+    }
 
-        let expr = match self.stream.next()?.kind.clone() {
-            TokenKind::IntLit(v) => v,
-            t => panic!("expected integer literal, got {:?}", t),
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let Some(tok) = self.stream.peek() else {
+            return self.stream.unexpected_eof();
         };
+        let op = match tok.kind {
+            TokenKind::Minus => UnaryOp::Neg,
+            TokenKind::Tilde => UnaryOp::BitNot,
+            TokenKind::Bang => UnaryOp::Not,
+            _ => return self.parse_primary(),
+        };
+        self.stream.next();
+
+        // `-4` is a number of its own; `-(4)` and `-SIZE` are negations.
+        if op == UnaryOp::Neg
+            && let Some(n) = self.number_literal()
+        {
+            return Ok(Expr::Number(match n {
+                Number::Int(n) => Number::Int(-n),
+                Number::Float(x) => Number::Float(-x),
+            }));
+        }
 
-        Some(Statement::VarAssign { name, expr })
+        self.nest_expr()?;
+        let expr = self.parse_unary()?;
+        self.depth -= 1;
+        Ok(Expr::Unary {
+            op,
+            expr: Box::new(expr),
+        })
     }
 
-    fn parse_const(&mut self) -> Option<Statement> {
-        self.stream.next(); // eat 'const'
+    /// A primary expression, then any `[index]`s right against it.
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.parse_atom()?;
+        while self.lookahead_is_index() {
+            self.stream.next();
+            self.nest_expr()?;
+            let index = self.parse_expr()?;
+            self.stream.expect(TokenKind::RightBracket)?;
+            self.depth -= 1;
+            expr = Expr::Index {
+                base: Box::new(expr),
+                index: Box::new(index),
+            };
+        }
+        Ok(expr)
+    }
+
+    /// `[` right against what was just taken: `TABLE[i]` indexes, while
+    /// `mov r0 [r1]` has a memory operand.
+    pub(crate) fn lookahead_is_index(&self) -> bool {
+        let end = self.stream.last_span().range.end;
+        self.stream
+            .peek()
+            .is_some_and(|t| t.kind == TokenKind::LeftBracket && t.span.range.start == end)
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr, ParseError> {
+        if let Some(n) = self.number_literal() {
+            return Ok(Expr::Number(n));
+        }
+        if self.stream.peek().is_some_and(|t| t.kind == TokenKind::LeftParen) {
+            self.stream.next();
+            self.nest_expr()?;
+            let expr = self.parse_expr()?;
+            self.stream.expect(TokenKind::RightParen)?;
+            self.depth -= 1;
+            return Ok(expr);
+        }
+
+        // `[1, 2, 3]`, an array.
+        if self.stream.peek().is_some_and(|t| t.kind == TokenKind::LeftBracket) {
+            self.stream.next();
+            self.nest_expr()?;
+            let mut items = Vec::new();
+            while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightBracket) {
+                items.push(self.parse_expr()?);
+                if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                    self.stream.next();
+                } else {
+                    break;
+                }
+            }
+            self.stream.expect(TokenKind::RightBracket)?;
+            self.depth -= 1;
+            return Ok(Expr::Array(items));
+        }
+
+        // `$` and `$$` stand for addresses, named like labels are.
+        if let Some(tok) = self.stream.peek()
+            && matches!(tok.kind, TokenKind::Dollar | TokenKind::DollarDollar)
+        {
+            let name = tok.text.clone();
+            self.stream.next();
+            return Ok(Expr::Symbol(name));
+        }
+
+        // `.loop`, a local label.
+        if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Dot) {
+            self.stream.next();
+            return Ok(Expr::Symbol(format!(".{}", self.ident("a local label")?)));
+        }
 
-        let name = match self.stream.next()?.kind.clone() {
-            TokenKind::Ident(n) => n,
-            t => panic!("expected identifier, got {:?}", t),
+        let taken = self.stream.take_with(|kind| match kind {
+            TokenKind::Ident(name) | TokenKind::NumericLabelRef(name) => {
+                Some(Expr::Symbol(mem::take(name)))
+            }
+            // `%top`, a label a macro expansion renames.
+            TokenKind::Register(name) if name.starts_with('%') => {
+                Some(Expr::Symbol(mem::take(name)))
+            }
+            TokenKind::StrLit(literal) => Some(Expr::Str(mem::take(literal))),
+            TokenKind::CharLit(c) => Some(Expr::Number(Number::Int(i64::from(u32::from(*c))))),
+            _ => None,
+        });
+        let expr = self.taken("an expression", taken)?;
+
+        let Expr::Symbol(mut name) = expr else {
+            return Ok(expr);
         };
+        if name == "stringify"
+            && self.stream.peek().is_some_and(|t| t.kind == TokenKind::Bang)
+        {
+            let param = self.stringify()?;
+            return Ok(Expr::Call {
+                name: "stringify!".to_string(),
+                args: vec![Expr::Symbol(param)],
+            });
+        }
+        // `io::putc`, a name in a module, or `Point.x`, a struct field.
+        while let Some(separator) = self.lookahead_is_path() {
+            self.stream.next();
+            name.push_str(separator);
+            name.push_str(&self.ident("a name")?);
+        }
+
+        // A name right before `(` calls a builtin.
+        if self.stream.newline_before_next()
+            || self.stream.peek().is_none_or(|t| t.kind != TokenKind::LeftParen)
+        {
+            return Ok(Expr::Symbol(name));
+        }
+        self.stream.next();
+        let mut args = Vec::new();
+        while self.stream.peek().is_some_and(|t| t.kind != TokenKind::RightParen) {
+            args.push(self.parse_expr()?);
+            if self.stream.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                self.stream.next();
+            } else {
+                break;
+            }
+        }
+        self.stream.expect(TokenKind::RightParen)?;
+        Ok(Expr::Call { name, args })
+    }
+
+    /// Takes the next token if it's a number literal.
+    pub(crate) fn number_literal(&mut self) -> Option<Number> {
+        let kind = &self.stream.peek()?.kind;
+        if kind.int_value().is_none() && !matches!(kind, TokenKind::FloatLit(_)) {
+            return None;
+        }
+        self.stream.take_number()?.ok()
+    }
+
+    /// Counts one more level of expression nesting against [`MAX_NESTING`],
+    /// which keeps evaluating and dropping the tree off the stack's limits.
+    pub(crate) fn nest_expr(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return self.stream.fail_with(
+                Code::E0003,
+                format_args!("expression nested more than {} deep", MAX_NESTING),
+            );
+        }
+        Ok(())
+    }
+
+    /// Takes an identifier, failing with "expected {what}" on anything
+    /// else.
+    /// The parameter of `stringify!(param)`, from the `!` on.
+    pub(crate) fn stringify(&mut self) -> Result<String, ParseError> {
+        self.stream.expect(TokenKind::Bang)?;
+        self.stream.expect(TokenKind::LeftParen)?;
+        let param = self.ident("a macro parameter")?;
+        self.stream.expect(TokenKind::RightParen)?;
+        Ok(param)
+    }
+
+    pub(crate) fn ident(&mut self, what: &str) -> Result<String, ParseError> {
+        let taken = self.stream.take_ident();
+        self.taken(what, taken)
+    }
+
+    pub(crate) fn register(&mut self) -> Result<String, ParseError> {
+        let taken = self.stream.take_register();
+        self.taken("a register", taken)
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, ParseError> {
+        let taken = self.stream.take_str();
+        self.taken("a string literal", taken)
+    }
 
-        self.stream.expect(TokenKind::Equal);
+    /// What a `take_*` method took, or an error naming the token it found
+    /// instead of `what`.
+    pub(crate) fn taken<T>(
+        &self,
+        what: &str,
+        taken: Option<Result<T, TokenKind>>,
+    ) -> Result<T, ParseError> {
+        match taken {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(found)) => {
+                self.stream.fail(format_args!("expected {}, got {:?}", what, found))
+            }
+            None => self.stream.unexpected_eof(),
+        }
+    }
 
-        let expr = match self.stream.next()?.kind.clone() {
-            TokenKind::IntLit(v) => v,
-            t => panic!("expected integer literal, got {:?}", t),
+    /// Consumes the next token and fails with "expected {what}", naming
+    /// it.
+    pub(crate) fn unexpected<T>(&mut self, what: &str) -> Result<T, ParseError> {
+        let Some(tok) = self.stream.next() else {
+            return self.stream.unexpected_eof();
         };
+        let found = tok.kind.clone();
+        self.stream.fail(format_args!("expected {}, got {:?}", what, found))
+    }
+}
 
-        Some(Statement::ConstAssign { name, expr })
+/// Iterator returned by [`Parser::statements`].
+pub struct Statements<'p> {
+    parser: &'p mut Parser,
+    /// How many of the parser's errors have been yielded.
+    reported: usize,
+    /// A statement parsed along with errors inside it, held back until
+    /// they've been yielded.
+    pending: Option<Statement>,
+    phase: Phase,
+}
+
+impl Iterator for Statements<'_> {
+    type Item = Result<Statement, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        resume!(self.phase);
+        loop {
+            if let Some(err) = self.parser.errors.get(self.reported) {
+                self.reported += 1;
+                return Some(Err(err.clone()));
+            }
+            if let Some(stmt) = self.pending.take() {
+                return Some(Ok(stmt));
+            }
+            if self.parser.stream.eof() {
+                return None;
+            }
+            self.pending = self.parser.step();
+        }
     }
 }
@@ -0,0 +1,243 @@
+//! Writes statements back out as chasm source.
+//!
+//! The text parses back to the same statements, spans aside, so expanded
+//! loops and trees built in code can be saved as assembly and read again:
+//!
+//! ```text
+//! const N = 4
+//! loop:
+//! for!(var i = 0; i < N; i += 1) {
+//!     ld r0, [table + i]
+//! }
+//! ```
+//!
+//! Comments, blank lines, number bases and optional commas aren't in the
+//! tree, so they don't come back. Bodies are indented four spaces a level.
+
+use crate::parser::{SECTIONS, Statement, StatementKind, Visibility};
+use std::fmt::{self, Write};
+
+const INDENT: &str = "    ";
+
+/// `stmts` as source, one statement per line.
+pub fn to_source(stmts: &[Statement]) -> String {
+    let mut out = String::new();
+    // Writing to a `String` can't fail.
+    let _ = write_body(&mut out, stmts, 0);
+    out
+}
+
+impl Statement {
+    /// The statement as source, bodies included. Same as its `Display`.
+    pub fn to_source(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// As chasm would write it, without a trailing newline. Bodied statements
+/// take several lines, their bodies indented.
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_statement(f, self, 0)
+    }
+}
+
+/// Each of `stmts` on its own line, `depth` levels in.
+fn write_body(out: &mut impl Write, stmts: &[Statement], depth: usize) -> fmt::Result {
+    for stmt in stmts {
+        out.write_str(&INDENT.repeat(depth))?;
+        write_statement(out, stmt, depth)?;
+        out.write_char('\n')?;
+    }
+    Ok(())
+}
+
+/// `{`, the body a level in, then `}` back at `depth`.
+fn write_block(out: &mut impl Write, body: &[Statement], depth: usize) -> fmt::Result {
+    out.write_str("{\n")?;
+    write_body(out, body, depth + 1)?;
+    write!(out, "{}}}", INDENT.repeat(depth))
+}
+
+fn write_list<T: fmt::Display>(out: &mut impl Write, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.write_str(", ")?;
+        }
+        write!(out, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// `stmt` from where the cursor is, which is `depth` levels in.
+fn write_statement(out: &mut impl Write, stmt: &Statement, depth: usize) -> fmt::Result {
+    match &stmt.kind {
+        StatementKind::VarAssign { name, expr } => write!(out, "var {} = {}", name, expr),
+        StatementKind::VarUpdate { name, op, expr } => write!(out, "var {} {} {}", name, op, expr),
+        StatementKind::ConstAssign { name, expr } => write!(out, "const {} = {}", name, expr),
+        StatementKind::Equ { name, expr } => write!(out, "{} equ {}", name, expr),
+        StatementKind::Label { name, visibility } => match visibility {
+            Visibility::Global => write!(out, "::{}:", name),
+            Visibility::Local | Visibility::File => write!(out, "{}:", name),
+        },
+        StatementKind::Instruction { name, args, .. } => {
+            out.write_str(name)?;
+            if !args.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, args.as_slice())?;
+            }
+            Ok(())
+        }
+        StatementKind::Directive { name, args } => {
+            write!(out, "@{}", name)?;
+            if !args.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, args.as_slice())?;
+            }
+            Ok(())
+        }
+        StatementKind::Org(address) => write!(out, "@org {}", address),
+        StatementKind::Align { boundary, fill } => {
+            write!(out, "@align {}", boundary)?;
+            if let Some(fill) = fill {
+                write!(out, ", {}", fill)?;
+            }
+            Ok(())
+        }
+        StatementKind::Include(path) => write!(out, "include {}", path),
+        StatementKind::Data { width, values, .. } => {
+            out.write_str(width.as_str())?;
+            if !values.is_empty() {
+                out.write_char(' ')?;
+                write_list(out, values)?;
+            }
+            Ok(())
+        }
+        StatementKind::Section(name) if SECTIONS.contains(&name.as_str()) => {
+            write!(out, ".{}", name)
+        }
+        StatementKind::Section(name) => write!(out, ".section {}", name),
+        StatementKind::StringData {
+            encoding,
+            nul,
+            literal,
+        } => write!(out, "{} {}", encoding.directive(*nul), literal),
+        StatementKind::MacroDef {
+            name,
+            params,
+            defaults,
+            body,
+        } => {
+            write!(out, "macro_rules! {}(", name)?;
+            // The defaults go with the parameters before any variadic one.
+            let fixed =
+                params.len() - usize::from(params.last().is_some_and(|p| p.ends_with("...")));
+            let first_default = fixed - defaults.len();
+            for (i, param) in params.iter().enumerate() {
+                if i > 0 {
+                    out.write_str(", ")?;
+                }
+                out.write_str(param)?;
+                if let Some(default) = i.checked_sub(first_default).and_then(|i| defaults.get(i)) {
+                    write!(out, " = {}", default)?;
+                }
+            }
+            out.write_str(") ")?;
+            write_block(out, body, depth)
+        }
+        StatementKind::MacroCall { name, args } => {
+            write!(out, "{}!(", name)?;
+            write_list(out, args.as_slice())?;
+            out.write_char(')')
+        }
+        StatementKind::ForLoop {
+            var,
+            start,
+            cmp,
+            end,
+            op,
+            step,
+            body,
+        } => {
+            write!(
+                out,
+                "for!(var {v} = {}; {v} {} {}; {v} {} {}) ",
+                start,
+                cmp,
+                end,
+                op,
+                step,
+                v = var
+            )?;
+            write_block(out, body, depth)
+        }
+        StatementKind::WhileLoop { cond, body } => {
+            write!(out, "while!({}) ", cond)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Repeat { count, body } => {
+            write!(out, "repeat!({}) ", count)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Foreach { param, body } => {
+            write!(out, "@foreach {} ", param)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Break => out.write_str("break!"),
+        StatementKind::Continue => out.write_str("continue!"),
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            for (i, branch) in branches.iter().enumerate() {
+                let keyword = if i == 0 { "if!" } else { " elif!" };
+                write!(out, "{}({}) ", keyword, branch.cond)?;
+                write_block(out, &branch.body, depth)?;
+            }
+            // An empty `else` parses the same as none.
+            if otherwise.is_empty() {
+                return Ok(());
+            }
+            out.write_str(" else ")?;
+            write_block(out, otherwise, depth)
+        }
+        StatementKind::Module { name, body } => {
+            write!(out, "module {} ", name)?;
+            write_block(out, body, depth)
+        }
+        StatementKind::Struct { name, fields } => {
+            write!(out, "struct {} {{ ", name)?;
+            for (i, field) in fields.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(
+                    out,
+                    "{}{}: {}",
+                    separator,
+                    field.name,
+                    &field.width.as_str()[1..]
+                )?;
+            }
+            out.write_str(" }")
+        }
+        StatementKind::StructData { name, fields } => {
+            write!(out, "{} {{ ", name)?;
+            for (i, (field, value)) in fields.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(out, "{}{}: {}", separator, field, value)?;
+            }
+            out.write_str(" }")
+        }
+        StatementKind::Enum { name, variants } => {
+            write!(out, "enum {} {{ ", name)?;
+            for (i, (variant, value)) in variants.iter().enumerate() {
+                let separator = if i > 0 { ", " } else { "" };
+                write!(out, "{}{}", separator, variant)?;
+                if let Some(value) = value {
+                    write!(out, " = {}", value)?;
+                }
+            }
+            out.write_str(" }")
+        }
+        StatementKind::Block(body) => write_block(out, body, depth),
+    }
+}
@@ -0,0 +1,114 @@
+//! Several source files assembled as one program.
+//!
+//! Each file is registered with the program's [`SourceManager`] and parsed
+//! on its own, so spans and diagnostics keep pointing at the file they came
+//! from. The statements of every file, in the order the files were added,
+//! make up the program's AST. Unlike `include`, adding a file doesn't
+//! follow the includes in it; see [`Session`](crate::session::Session) for
+//! that.
+
+use crate::diagnostic::Diagnostic;
+use crate::parser::{LexError, LexOptions, ParseError, Parser, Statement};
+use crate::source::{FileId, SourceManager, SourceText, Span};
+use std::io;
+use std::path::Path;
+
+/// One file of a [`Program`] and what parsing it found.
+#[derive(Debug, Clone)]
+pub struct ProgramFile {
+    pub file: FileId,
+    pub ast: Vec<Statement>,
+    /// What the parser dropped, for [`lint::check`](crate::lint::check).
+    pub skipped: Vec<Span>,
+    /// What the lexer didn't recognise.
+    pub lex_errors: Vec<LexError>,
+    /// Syntax errors. The statements around them are still in `ast`.
+    pub parse_errors: Vec<ParseError>,
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    sources: SourceManager,
+    lex_opts: LexOptions,
+    files: Vec<ProgramFile>,
+}
+
+impl Program {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A program whose files are lexed the way `opts` says.
+    pub fn with_options(opts: LexOptions) -> Self {
+        Self {
+            lex_opts: opts,
+            ..Self::default()
+        }
+    }
+
+    /// Adds and parses text named `name`, like [`SourceManager::add`].
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<SourceText>) -> FileId {
+        let file = self.sources.add(name, text);
+        self.parse(file);
+        file
+    }
+
+    /// Reads `path` from disk, adds it and parses it.
+    pub fn load(&mut self, path: &Path) -> io::Result<FileId> {
+        let file = self.sources.load(path)?;
+        self.parse(file);
+        Ok(file)
+    }
+
+    fn parse(&mut self, file: FileId) {
+        let mut parser = Parser::streaming(&self.sources, file, self.lex_opts);
+        let ast = parser.parse();
+        self.files.push(ProgramFile {
+            file,
+            ast,
+            skipped: parser.skipped(),
+            lex_errors: parser.lex_errors().to_vec(),
+            parse_errors: parser.errors().to_vec(),
+        });
+    }
+
+    pub fn sources(&self) -> &SourceManager {
+        &self.sources
+    }
+
+    /// Every file, in the order it was added.
+    pub fn files(&self) -> &[ProgramFile] {
+        &self.files
+    }
+
+    pub fn file(&self, id: FileId) -> Option<&ProgramFile> {
+        self.files.iter().find(|f| f.file == id)
+    }
+
+    /// The top-level statements of every file, one file after another.
+    pub fn statements(&self) -> impl Iterator<Item = &Statement> {
+        self.files.iter().flat_map(|f| &f.ast)
+    }
+
+    /// The whole program as one AST, for passes that take a single list
+    /// of statements.
+    pub fn ast(&self) -> Vec<Statement> {
+        self.statements().cloned().collect()
+    }
+
+    /// What the parser dropped in any file.
+    pub fn skipped(&self) -> Vec<Span> {
+        self.files.iter().flat_map(|f| f.skipped.iter().cloned()).collect()
+    }
+
+    /// Lexing and syntax errors, file by file.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.files
+            .iter()
+            .flat_map(|f| {
+                let lex = f.lex_errors.iter().map(LexError::to_diagnostic);
+                lex.chain(f.parse_errors.iter().map(ParseError::to_diagnostic))
+            })
+            .collect()
+    }
+}
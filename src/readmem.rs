@@ -0,0 +1,106 @@
+//! `--format readmemh` and `--format readmemb`: an image as the text
+//! Verilog's `$readmemh` and `$readmemb` load into a memory, one word per
+//! line in hex or binary digits:
+//!
+//! ```text
+//! @4002
+//! 0010
+//! 2081
+//! @4080
+//! 0201
+//! ```
+//!
+//! `@` markers give word addresses, the way `$readmemh` counts them: the
+//! image's address over the word size. One starts the file and one follows
+//! each of the image's gaps, which aren't written out: space the linker
+//! left between sections, or that an `@org` skipped. Without markers gaps
+//! are written as zero words instead, and the file starts at the image's
+//! first word. Either way the words cover the image
+//! and nothing past it, the first and last padded with zeros where the
+//! image starts or ends partway through one.
+
+use crate::link::Image;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Radix {
+    /// `$readmemh`: hex digits.
+    #[default]
+    Hex,
+    /// `$readmemb`: binary digits.
+    Binary,
+}
+
+/// The order a word's bytes go in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endian {
+    /// Lowest address least significant, like everything else chasm
+    /// writes.
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadmemOptions {
+    pub radix: Radix,
+    /// Bytes per word: 1, 2 or 4.
+    pub word_bytes: u64,
+    pub endian: Endian,
+    /// Skips gaps with `@` markers rather than writing zeros.
+    pub markers: bool,
+}
+
+impl Default for ReadmemOptions {
+    fn default() -> Self {
+        Self {
+            radix: Radix::Hex,
+            word_bytes: 1,
+            endian: Endian::Little,
+            markers: true,
+        }
+    }
+}
+
+/// `image` as `$readmemh` or `$readmemb` text, one word a line.
+pub fn readmem(image: &Image, opts: &ReadmemOptions) -> String {
+    let mut out = String::new();
+    let size = opts.word_bytes.max(1);
+    let end = image.base + image.data.len() as u64;
+    let in_gap = |addr: u64| image.gaps.iter().any(|gap| gap.contains(&addr));
+
+    // The word after the last one written, if it was.
+    let mut next = None;
+    for word in image.base / size..end.div_ceil(size) {
+        let addrs = word * size..(word + 1) * size;
+        if opts.markers
+            && addrs
+                .clone()
+                .all(|addr| addr < image.base || addr >= end || in_gap(addr))
+        {
+            continue;
+        }
+        if opts.markers && next != Some(word) {
+            let _ = writeln!(out, "@{:x}", word);
+        }
+        next = Some(word + 1);
+
+        let mut bytes: Vec<u8> = addrs
+            .map(|addr| match addr.checked_sub(image.base) {
+                Some(offset) => image.data.get(offset as usize).copied().unwrap_or(0),
+                None => 0,
+            })
+            .collect();
+        if opts.endian == Endian::Little {
+            bytes.reverse();
+        }
+        for byte in bytes {
+            let _ = match opts.radix {
+                Radix::Hex => write!(out, "{:02x}", byte),
+                Radix::Binary => write!(out, "{:08b}", byte),
+            };
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,337 @@
+use crate::assemble::{self, Assembled};
+use crate::diagnostic::Diagnostic;
+use crate::expand::{ExpandOptions, expand_traced};
+use crate::includes::{self, IncludeOptions};
+use crate::isa::Isa;
+use crate::parser::{LexError, LexOptions, ParseError, Parser, Statement, StatementKind};
+use crate::source::{FileId, SourceManager, Span};
+use crate::tokens::parse_string;
+use crate::trace::debug;
+use crate::walk::{bodies_mut, iter_deep};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::slice;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Cache hit/miss counts, cumulative over the session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Files whose cached parse was reused.
+    pub hits: usize,
+    /// Files that had to be (re)parsed.
+    pub misses: usize,
+}
+
+/// How long each phase of a build took, and how many of its files were
+/// parsed rather than taken from the cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Reading and parsing, includes and all.
+    pub parse: Duration,
+    pub expand: Duration,
+    /// Layout and encoding.
+    pub assemble: Duration,
+    /// This build's hits and misses, not the session's.
+    pub cache: CacheStats,
+}
+
+/// A line per phase, as `--timings` prints it.
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "parse    {:>10.3?}  {} parsed, {} cached",
+            self.parse, self.cache.misses, self.cache.hits
+        )?;
+        writeln!(f, "expand   {:>10.3?}", self.expand)?;
+        writeln!(f, "assemble {:>10.3?}", self.assemble)
+    }
+}
+
+/// One file of a build, in the order it was first reached.
+#[derive(Debug, Clone)]
+pub struct BuildFile {
+    pub path: PathBuf,
+    pub file: FileId,
+    pub ast: Rc<Vec<Statement>>,
+    /// The files its `include`s name, in the order they're written.
+    pub includes: Vec<PathBuf>,
+    /// What the parser dropped, for [`lint::check`](crate::lint::check).
+    pub skipped: Rc<[Span]>,
+    /// What the lexer didn't recognise.
+    pub lex_errors: Rc<[LexError]>,
+    /// Syntax errors. The statements around them are still in `ast`.
+    pub parse_errors: Rc<[ParseError]>,
+}
+
+#[derive(Clone)]
+struct Parsed {
+    file: FileId,
+    ast: Rc<Vec<Statement>>,
+    skipped: Rc<[Span]>,
+    lex_errors: Rc<[LexError]>,
+    parse_errors: Rc<[ParseError]>,
+}
+
+struct CachedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+    parsed: Parsed,
+}
+
+/// Keeps parsed files between builds so watch-mode rebuilds only reparse
+/// what changed.
+///
+/// A file's parse depends only on its own text, so entries are keyed by
+/// canonical path and validated by mtime/size, falling back to a content
+/// hash when those changed. Include resolution is redone every build, so an
+/// edited file that adds or drops includes is picked up. Changing the
+/// include or lex options drops the whole cache.
+pub struct Session {
+    opts: IncludeOptions,
+    lex_opts: LexOptions,
+    sources: SourceManager,
+    cache: HashMap<PathBuf, CachedFile>,
+    stats: CacheStats,
+    timings: Timings,
+}
+
+impl Session {
+    pub fn new(opts: IncludeOptions) -> Self {
+        Self {
+            opts,
+            lex_opts: LexOptions::default(),
+            sources: SourceManager::new(),
+            cache: HashMap::new(),
+            stats: CacheStats::default(),
+            timings: Timings::default(),
+        }
+    }
+
+    pub fn set_options(&mut self, opts: IncludeOptions) {
+        if opts != self.opts {
+            self.opts = opts;
+            self.cache.clear();
+        }
+    }
+
+    pub fn set_lex_options(&mut self, opts: LexOptions) {
+        if opts != self.lex_opts {
+            self.lex_opts = opts;
+            self.cache.clear();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// What the last [`assemble`](Self::assemble) spent its time on.
+    pub fn timings(&self) -> Timings {
+        self.timings
+    }
+
+    pub fn sources(&self) -> &SourceManager {
+        &self.sources
+    }
+
+    /// Builds `entry` like [`build`](Self::build), then expands and
+    /// assembles the whole program for `isa`. Only files that changed are
+    /// parsed again; expansion and everything after it runs every time.
+    /// The diagnostics are every file's lexing and syntax errors, then
+    /// expansion's and assembly's.
+    pub fn assemble(
+        &mut self,
+        entry: &Path,
+        isa: &Isa,
+        opts: &ExpandOptions,
+    ) -> io::Result<(Assembled, Vec<Diagnostic>)> {
+        let before = self.stats;
+        let start = Instant::now();
+        let files = self.build(entry)?;
+        let ast = splice(&files);
+        let parse = start.elapsed();
+
+        let mut diags: Vec<Diagnostic> = files
+            .iter()
+            .flat_map(|file| {
+                let lex = file.lex_errors.iter().map(LexError::to_diagnostic);
+                lex.chain(file.parse_errors.iter().map(ParseError::to_diagnostic))
+            })
+            .collect();
+        let start = Instant::now();
+        let (expanded, errors, origins) = expand_traced(&ast, opts);
+        diags.extend(errors);
+        let expand = start.elapsed();
+
+        let start = Instant::now();
+        let (assembled, errors) = assemble::assemble(&expanded, &origins, isa, &opts.registers);
+        diags.extend(errors);
+        self.timings = Timings {
+            parse,
+            expand,
+            assemble: start.elapsed(),
+            cache: CacheStats {
+                hits: self.stats.hits - before.hits,
+                misses: self.stats.misses - before.misses,
+            },
+        };
+        Ok((assembled, diags))
+    }
+
+    /// Parses `entry` and everything it includes, reusing cached parses of
+    /// unchanged files. The entry file comes first.
+    pub fn build(&mut self, entry: &Path) -> io::Result<Vec<BuildFile>> {
+        let mut files = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![fs::canonicalize(entry)?];
+
+        while let Some(path) = pending.pop() {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let Parsed {
+                file,
+                ast,
+                skipped,
+                lex_errors,
+                parse_errors,
+            } = self.parse_cached(&path)?;
+
+            let dir = path.parent().unwrap_or(Path::new("."));
+            let mut includes = Vec::new();
+            for stmt in iter_deep(&ast) {
+                if let StatementKind::Include(raw) = &stmt.kind {
+                    let resolved = includes::resolve(dir, &parse_string(raw), &self.opts)?;
+                    debug!(
+                        from = %path.display(),
+                        include = %raw,
+                        path = %resolved.display(),
+                        "include resolved"
+                    );
+                    includes.push(resolved);
+                }
+            }
+            // Reversed so the stack pops them in source order.
+            pending.extend(includes.iter().rev().cloned());
+
+            files.push(BuildFile {
+                path,
+                file,
+                ast,
+                includes,
+                skipped,
+                lex_errors,
+                parse_errors,
+            });
+        }
+
+        Ok(files)
+    }
+
+    fn parse_cached(&mut self, path: &Path) -> io::Result<Parsed> {
+        let meta = fs::metadata(path)?;
+        let modified = meta.modified().ok();
+
+        if let Some(entry) = self.cache.get(path)
+            && entry.modified.is_some()
+            && entry.modified == modified
+            && entry.len == meta.len()
+        {
+            self.stats.hits += 1;
+            return Ok(entry.parsed.clone());
+        }
+
+        let text = fs::read(path)?;
+        let hash = content_hash(&text);
+
+        if let Some(entry) = self.cache.get_mut(path)
+            && entry.hash == hash
+        {
+            entry.modified = modified;
+            entry.len = meta.len();
+            self.stats.hits += 1;
+            return Ok(entry.parsed.clone());
+        }
+
+        self.stats.misses += 1;
+        let file = self.sources.load(path)?;
+        let mut parser = Parser::streaming(&self.sources, file, self.lex_opts);
+        let parsed = Parsed {
+            file,
+            ast: Rc::new(parser.parse()),
+            skipped: parser.skipped().into(),
+            lex_errors: parser.lex_errors().into(),
+            parse_errors: parser.errors().into(),
+        };
+        self.cache.insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len: meta.len(),
+                hash,
+                parsed: parsed.clone(),
+            },
+        );
+        Ok(parsed)
+    }
+}
+
+/// The statements of a build as one program: each `include` in its place
+/// replaced by the statements of the file it names. A file included again
+/// is left out the second time, as [`Session::build`] reaches it only
+/// once.
+pub fn splice(files: &[BuildFile]) -> Vec<Statement> {
+    let Some(entry) = files.first() else {
+        return Vec::new();
+    };
+    let by_path: HashMap<&Path, &BuildFile> = files
+        .iter()
+        .map(|file| (file.path.as_path(), file))
+        .collect();
+    let mut spliced = HashSet::from([entry.path.as_path()]);
+    let mut includes = entry.includes.iter();
+    splice_into(&entry.ast, &mut includes, &by_path, &mut spliced)
+}
+
+/// `stmts` with their `include`s spliced in, `includes` being the files
+/// those name, in order.
+fn splice_into<'a>(
+    stmts: &[Statement],
+    includes: &mut slice::Iter<'a, PathBuf>,
+    by_path: &HashMap<&Path, &'a BuildFile>,
+    spliced: &mut HashSet<&'a Path>,
+) -> Vec<Statement> {
+    let mut out = Vec::with_capacity(stmts.len());
+    for stmt in stmts {
+        if let StatementKind::Include(_) = stmt.kind {
+            if let Some(path) = includes.next()
+                && let Some(file) = by_path.get(path.as_path())
+                && spliced.insert(path)
+            {
+                let mut nested = file.includes.iter();
+                out.extend(splice_into(&file.ast, &mut nested, by_path, spliced));
+            }
+            continue;
+        }
+        let mut stmt = stmt.clone();
+        for body in bodies_mut(&mut stmt) {
+            *body = splice_into(body, includes, by_path, spliced);
+        }
+        out.push(stmt);
+    }
+    out
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
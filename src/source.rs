@@ -0,0 +1,282 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::{Deref, Range};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Handle to a file registered with a [`SourceManager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileId(pub u32);
+
+/// A byte range within one file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub file: FileId,
+    pub range: Range<usize>,
+}
+
+impl Span {
+    pub fn new(file: FileId, range: Range<usize>) -> Self {
+        Self { file, range }
+    }
+
+    /// The smallest span covering both `self` and `other` (same file assumed).
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: self.file,
+            range: self.range.start.min(other.range.start)..self.range.end.max(other.range.end),
+        }
+    }
+}
+
+/// The bytes of a file, either read into memory or mapped from disk.
+/// Always valid UTF-8; derefs to `str`.
+pub enum SourceText {
+    Owned(String),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for SourceText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            SourceText::Owned(s) => s,
+            // SAFETY: validated by `SourceText::mapped` before construction.
+            #[cfg(feature = "mmap")]
+            SourceText::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+impl fmt::Debug for SourceText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceText::Owned(_) => write!(f, "Owned({} bytes)", self.len()),
+            #[cfg(feature = "mmap")]
+            SourceText::Mapped(_) => write!(f, "Mapped({} bytes)", self.len()),
+        }
+    }
+}
+
+impl From<String> for SourceText {
+    fn from(s: String) -> Self {
+        SourceText::Owned(s)
+    }
+}
+
+impl From<&str> for SourceText {
+    fn from(s: &str) -> Self {
+        SourceText::Owned(s.to_string())
+    }
+}
+
+/// How much [`SourceText::read_from`] reads before checking what it read.
+const CHUNK: u64 = 64 * 1024;
+
+impl SourceText {
+    /// Reads a file, reporting the offset of the first invalid UTF-8 byte.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let len = file.metadata().map_or(0, |meta| meta.len());
+        Self::read_from(file, len as usize)
+    }
+
+    /// Reads `reader` to its end a chunk at a time, checking each chunk is
+    /// UTF-8 as it comes in, so the first invalid byte is reported without
+    /// reading past its chunk. `len` is how many bytes are expected, to
+    /// allocate once; it can be 0 if that isn't known.
+    pub fn read_from(mut reader: impl Read, len: usize) -> io::Result<Self> {
+        let mut bytes = Vec::with_capacity(len);
+        // How many of `bytes` are known to be valid. A character cut off
+        // at the end of a chunk is checked with the next one.
+        let mut valid = 0;
+        while (&mut reader).take(CHUNK).read_to_end(&mut bytes)? > 0 {
+            if let Err(e) = std::str::from_utf8(&bytes[valid..]) {
+                if e.error_len().is_some() {
+                    return Err(invalid_utf8(valid + e.valid_up_to()));
+                }
+                valid += e.valid_up_to();
+            } else {
+                valid = bytes.len();
+            }
+        }
+        if valid < bytes.len() {
+            return Err(invalid_utf8(valid));
+        }
+        // SAFETY: just validated.
+        Ok(SourceText::Owned(unsafe { String::from_utf8_unchecked(bytes) }))
+    }
+
+    /// Maps a file instead of reading it, so a very large input isn't
+    /// copied onto the heap before lexing. One that can't be mapped, like
+    /// a pipe or a file in `/proc` that reports no size, is read with
+    /// [`read_from`](Self::read_from) instead.
+    ///
+    /// The file must not be modified while the mapping is alive.
+    #[cfg(feature = "mmap")]
+    pub fn mapped(path: &Path) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        let meta = file.metadata()?;
+        if !meta.is_file() || meta.len() == 0 {
+            return Self::read_from(file, 0);
+        }
+        // SAFETY: see the doc comment; chasm never writes through the map.
+        let map = match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(map) => map,
+            Err(_) => return Self::read_from(file, meta.len() as usize),
+        };
+        validate_utf8(&map)?;
+        Ok(SourceText::Mapped(map))
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn validate_utf8(bytes: &[u8]) -> io::Result<()> {
+    std::str::from_utf8(bytes)
+        .map(|_| ())
+        .map_err(|e| invalid_utf8(e.valid_up_to()))
+}
+
+fn invalid_utf8(offset: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid UTF-8 at byte offset {}", offset),
+    )
+}
+
+/// The UTF-8 byte order mark some Windows editors start files with.
+pub const BOM: &str = "\u{feff}";
+
+/// One file's name and text, with a line index for offset lookups.
+///
+/// A leading [`BOM`] stays in `text`, so offsets always count the file's
+/// actual bytes; it just isn't lexed or counted as a column. Lines end at
+/// `\n`, and a `\r` right before it is part of the line ending.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: SourceText,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<SourceText>) -> Self {
+        let text = text.into();
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            name: name.into(),
+            text,
+            line_starts,
+        }
+    }
+
+    /// Where the source proper starts: past the byte order mark, if any.
+    pub fn start(&self) -> usize {
+        if self.text.starts_with(BOM) {
+            BOM.len()
+        } else {
+            0
+        }
+    }
+
+    /// 1-based line and column of a byte offset. Columns count chars.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.clamp(self.start(), self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line].max(self.start());
+        let mut end = offset;
+        if self.text[offset..].starts_with('\n') && self.text[..offset].ends_with('\r') {
+            end -= 1;
+        }
+        let col = self.text[line_start..end].chars().count();
+        (line + 1, col + 1)
+    }
+
+    /// `name:line:col` for a byte offset.
+    pub fn location(&self, offset: usize) -> Location<'_> {
+        let (line, col) = self.line_col(offset);
+        Location {
+            name: &self.name,
+            line,
+            col,
+        }
+    }
+}
+
+pub struct Location<'a> {
+    pub name: &'a str,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.line, self.col)
+    }
+}
+
+/// Owns every file taking part in an assembly, so spans from different
+/// files can be told apart and mapped back to a name and line.
+#[derive(Debug, Default)]
+pub struct SourceManager {
+    files: Vec<Rc<SourceFile>>,
+}
+
+impl SourceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<SourceText>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(Rc::new(SourceFile::new(name, text)));
+        id
+    }
+
+    /// Adds text that doesn't live on disk (command-line defines, REPL input).
+    /// The name is wrapped in angle brackets, e.g. `<command-line>`.
+    pub fn add_virtual(&mut self, name: &str, text: impl Into<SourceText>) -> FileId {
+        self.add(format!("<{}>", name), text)
+    }
+
+    /// Reads `path` from disk and registers it under its display name.
+    pub fn load(&mut self, path: &Path) -> io::Result<FileId> {
+        let text = SourceText::read(path)?;
+        Ok(self.add(path.display().to_string(), text))
+    }
+
+    /// Like [`load`](Self::load) but memory-maps the file.
+    #[cfg(feature = "mmap")]
+    pub fn load_mapped(&mut self, path: &Path) -> io::Result<FileId> {
+        let text = SourceText::mapped(path)?;
+        Ok(self.add(path.display().to_string(), text))
+    }
+
+    pub fn file(&self, id: FileId) -> &Rc<SourceFile> {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn name(&self, id: FileId) -> &str {
+        &self.file(id).name
+    }
+
+    pub fn text(&self, id: FileId) -> &str {
+        &self.file(id).text
+    }
+
+    pub fn line_col(&self, id: FileId, offset: usize) -> (usize, usize) {
+        self.file(id).line_col(offset)
+    }
+
+    /// `file:line:col` of the start of `span`.
+    pub fn location(&self, span: &Span) -> Location<'_> {
+        self.file(span.file).location(span.range.start)
+    }
+}
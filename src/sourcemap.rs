@@ -0,0 +1,183 @@
+//! A source map for `--emit expanded`: which line of the originals each
+//! line of the expanded text came from, so errors a later tool finds in it
+//! can be pointed back at what was written.
+//!
+//! [`SourceMap::to_json`] writes each range of generated lines, counting
+//! from 1, with the frames behind it, innermost first:
+//!
+//! ```text
+//! {"mappings":[
+//!   {"first":4,"last":4,"frames":[
+//!     {"file":"lib.asm","line":2,"column":5,"via":null},
+//!     {"file":"main.asm","line":7,"column":5,"via":{"Macro":{"name":"push"}}}]}]}
+//! ```
+//!
+//! The first frame is the statement as written, in a macro's body if a
+//! macro call produced it. Each one after is the call or loop that
+//! produced the frame before, `via` saying which: `{"Macro":{"name":...}}`
+//! or `{"Loop":{"keyword":"for!","iteration":2}}`, iterations counting
+//! from 1. A statement printed over several lines, like a macro definition,
+//! is one range. The braces of a block aren't mapped.
+//!
+//! With the `serde` feature the types deserialize from that JSON, so a tool
+//! can read the map back and ask [`SourceMap::original_of`].
+
+use crate::diagnostic::ExpansionKind;
+use crate::expand::{OriginCursor, Origins};
+use crate::json::string;
+use crate::parser::{Statement, StatementKind};
+use crate::source::{SourceManager, Span};
+
+/// What produced a frame's predecessor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Via {
+    Macro { name: String },
+    Loop { keyword: String, iteration: usize },
+}
+
+/// A place in an original file, lines and columns counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// `None` for the statement itself.
+    pub via: Option<Via>,
+}
+
+impl Frame {
+    fn new(span: &Span, via: Option<Via>, sources: &SourceManager) -> Self {
+        let location = sources.location(span);
+        Self {
+            file: location.name.to_string(),
+            line: location.line,
+            column: location.col,
+            via,
+        }
+    }
+}
+
+/// Generated lines `first..=last` and where they came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mapping {
+    pub first: usize,
+    pub last: usize,
+    /// Innermost first.
+    pub frames: Vec<Frame>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceMap {
+    /// In line order, never overlapping.
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// The map of [`to_source`](crate::to_source)`(stmts)`, for statements
+    /// [`expand_traced`](crate::expand::expand_traced) returned with
+    /// `origins`. Spans are looked up in `sources`.
+    pub fn new(stmts: &[Statement], origins: &Origins, sources: &SourceManager) -> Self {
+        let mut builder = Builder {
+            origins: origins.cursor(),
+            sources,
+            line: 1,
+            mappings: Vec::new(),
+        };
+        builder.map(stmts);
+        Self {
+            mappings: builder.mappings,
+        }
+    }
+
+    /// The frames behind generated line `expanded_line`, counting from 1,
+    /// innermost first. Empty for a line that isn't mapped.
+    pub fn original_of(&self, expanded_line: usize) -> Vec<Frame> {
+        let index = self
+            .mappings
+            .partition_point(|mapping| mapping.last < expanded_line);
+        match self.mappings.get(index) {
+            Some(mapping) if mapping.first <= expanded_line => mapping.frames.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mappings: Vec<String> = self.mappings.iter().map(mapping_to_json).collect();
+        format!("{{\"mappings\":[{}]}}", mappings.join(","))
+    }
+}
+
+fn mapping_to_json(mapping: &Mapping) -> String {
+    let frames: Vec<String> = mapping.frames.iter().map(frame_to_json).collect();
+    format!(
+        "{{\"first\":{},\"last\":{},\"frames\":[{}]}}",
+        mapping.first,
+        mapping.last,
+        frames.join(",")
+    )
+}
+
+fn frame_to_json(frame: &Frame) -> String {
+    let via = match &frame.via {
+        None => "null".to_string(),
+        Some(Via::Macro { name }) => format!("{{\"Macro\":{{\"name\":{}}}}}", string(name)),
+        Some(Via::Loop { keyword, iteration }) => format!(
+            "{{\"Loop\":{{\"keyword\":{},\"iteration\":{}}}}}",
+            string(keyword),
+            iteration
+        ),
+    };
+    format!(
+        "{{\"file\":{},\"line\":{},\"column\":{},\"via\":{}}}",
+        string(&frame.file),
+        frame.line,
+        frame.column,
+        via
+    )
+}
+
+struct Builder<'a> {
+    origins: OriginCursor<'a>,
+    sources: &'a SourceManager,
+    /// The generated line the next statement starts on.
+    line: usize,
+    mappings: Vec<Mapping>,
+}
+
+impl Builder<'_> {
+    /// Follows `to_source`, which writes a block as `{`, its body and `}`,
+    /// and anything else on as many lines as it prints to.
+    fn map(&mut self, stmts: &[Statement]) {
+        for stmt in stmts {
+            if let StatementKind::Block(body) = &stmt.kind {
+                self.line += 1;
+                self.map(body);
+                self.line += 1;
+                continue;
+            }
+            let mut frames = vec![Frame::new(&stmt.span, None, self.sources)];
+            let origin = self.origins.next(stmt);
+            for expansion in origin.iter().flat_map(|e| e.frames()) {
+                let via = match &expansion.kind {
+                    ExpansionKind::Macro { name } => Via::Macro { name: name.clone() },
+                    ExpansionKind::Loop { keyword, iteration } => Via::Loop {
+                        keyword: keyword.to_string(),
+                        iteration: *iteration,
+                    },
+                };
+                frames.push(Frame::new(&expansion.call_site, Some(via), self.sources));
+            }
+            let lines = stmt.to_string().lines().count().max(1);
+            self.mappings.push(Mapping {
+                first: self.line,
+                last: self.line + lines - 1,
+                frames,
+            });
+            self.line += lines;
+        }
+    }
+}
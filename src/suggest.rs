@@ -0,0 +1,69 @@
+/// Most suggestions attached to a single diagnostic.
+pub const MAX_SUGGESTIONS: usize = 2;
+
+/// Known names close enough to `name` to be a likely typo, best first.
+///
+/// A candidate qualifies when its edit distance (with adjacent
+/// transpositions counting as one edit) is at most a third of the longer
+/// name, and at least 1. Ties are broken alphabetically so output is stable.
+pub fn suggestions<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .filter_map(|c| {
+            let limit = (name.chars().count().max(c.chars().count()) / 3).max(1);
+            let d = distance(name, c);
+            (d <= limit).then_some((d, c))
+        })
+        .collect();
+
+    scored.sort();
+    scored.dedup();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// `did you mean `a`?` / `did you mean `a` or `b`?`, or `None` if nothing is close.
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let found = suggestions(name, candidates);
+    let quoted: Vec<String> = found.iter().map(|c| format!("`{}`", c)).collect();
+    match quoted.as_slice() {
+        [] => None,
+        [one] => Some(format!("did you mean {}?", one)),
+        [rest @ .., last] => Some(format!("did you mean {} or {}?", rest.join(", "), last)),
+    }
+}
+
+/// Optimal string alignment distance over chars.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
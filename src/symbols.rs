@@ -0,0 +1,726 @@
+use crate::builtins::Value;
+use crate::codes::Code;
+use crate::diagnostic::Diagnostic;
+use crate::eval::eval;
+use crate::parser::{BinaryOp, Expr, Number, Operand, Statement, StatementKind};
+use crate::source::Span;
+use crate::trace::phase;
+use crate::walk::{
+    VisitorMut, walk_expr_mut, walk_operand_mut, walk_statement_mut, walk_statements_mut,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Label,
+    Const,
+    Define,
+    Equ,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolValue {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// A label whose address the pipeline hasn't assigned, or a constant
+    /// computed from one.
+    Unresolved,
+    /// `@size NAME, start, end`: the distance between two labels, known
+    /// once their addresses are.
+    Size {
+        start: String,
+        end: String,
+    },
+    /// `NAME equ expr`: worked out from `expr` whenever it's asked for.
+    Equ(Expr),
+    /// `const NAME = [...]`: the items, worked out whenever the array is
+    /// asked for, like an `equ`'s expression, so they can use labels.
+    Array(Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: SymbolValue,
+    pub span: Span,
+}
+
+/// Named values visible to output writers, in definition order.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// Collects consts, `@define`s, labels, struct layouts and enums from a
+    /// parsed file, those in a `module` under their full names (see
+    /// [`scope_modules`]). A struct gives its size under its own name and
+    /// each field's offset as `Struct.field`; an enum gives each variant's
+    /// value as `Enum.Variant`.
+    ///
+    /// Labels come out `Unresolved`, as do constants whose expressions
+    /// use a label and `@size`s, until [`resolve`](Self::resolve) gives
+    /// them the addresses layout assigned. [`assemble`] hands back the
+    /// table resolved.
+    ///
+    /// [`assemble`]: crate::assemble::assemble
+    pub fn collect(ast: &[Statement]) -> Self {
+        Self::collect_checked(ast).0
+    }
+
+    /// Like [`collect`](Self::collect), but also returns the errors from
+    /// evaluating constant expressions. A constant that fails to evaluate
+    /// comes out `Unresolved`.
+    pub fn collect_checked(ast: &[Statement]) -> (Self, Vec<Diagnostic>) {
+        let ast = &scope_modules(ast);
+        let labels: HashSet<&str> = crate::walk::iter_deep(ast)
+            .filter_map(|stmt| match &stmt.kind {
+                StatementKind::Label { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut table = Self::default();
+        let mut errors = Vec::new();
+        table.collect_from(ast, &labels, &mut errors);
+        (table, errors)
+    }
+
+    fn collect_from(
+        &mut self,
+        ast: &[Statement],
+        labels: &HashSet<&str>,
+        errors: &mut Vec<Diagnostic>,
+    ) {
+        for stmt in ast {
+            let (name, kind, value) = match &stmt.kind {
+                StatementKind::ConstAssign {
+                    name,
+                    expr: Expr::Array(items),
+                } => (
+                    name.clone(),
+                    SymbolKind::Const,
+                    SymbolValue::Array(items.clone()),
+                ),
+                StatementKind::ConstAssign { name, expr } => {
+                    let value = match self.const_value(expr, labels, &stmt.span) {
+                        Ok(value) => value,
+                        Err(diag) => {
+                            errors.push(diag);
+                            SymbolValue::Unresolved
+                        }
+                    };
+                    (name.clone(), SymbolKind::Const, value)
+                }
+                StatementKind::Directive { name, args } if name == "define" => {
+                    let Some(sym) = args.first() else {
+                        continue;
+                    };
+                    let value = match args.get(1) {
+                        Some(v) => match v.parse::<i64>() {
+                            Ok(n) => SymbolValue::Int(n),
+                            Err(_) => SymbolValue::Str(v.clone()),
+                        },
+                        None => SymbolValue::Int(1),
+                    };
+                    (sym.clone(), SymbolKind::Define, value)
+                }
+                StatementKind::Directive { name, args } if name == "size" => {
+                    let [sym, start, end] = &args[..] else {
+                        continue;
+                    };
+                    let value = SymbolValue::Size {
+                        start: start.clone(),
+                        end: end.clone(),
+                    };
+                    (sym.clone(), SymbolKind::Const, value)
+                }
+                StatementKind::Equ { name, expr } => {
+                    (name.clone(), SymbolKind::Equ, SymbolValue::Equ(expr.clone()))
+                }
+                StatementKind::Label { name, .. } => {
+                    (name.clone(), SymbolKind::Label, SymbolValue::Unresolved)
+                }
+                StatementKind::Struct { name, fields } => {
+                    let mut offset = 0;
+                    for field in fields {
+                        self.symbols.push(Symbol {
+                            name: format!("{}.{}", name, field.name),
+                            kind: SymbolKind::Const,
+                            value: SymbolValue::Int(offset),
+                            span: stmt.span.clone(),
+                        });
+                        offset += i64::from(field.width.bits() / 8);
+                    }
+                    (name.clone(), SymbolKind::Const, SymbolValue::Int(offset))
+                }
+                StatementKind::Enum { name, variants } => {
+                    let mut previous: Option<String> = None;
+                    for (variant, value) in variants {
+                        let full = format!("{}.{}", name, variant);
+                        // `Prev + 1`, so a variant after one waiting on a
+                        // label waits too.
+                        let expr = match (value, previous) {
+                            (Some(expr), _) => expr.clone(),
+                            (None, Some(previous)) => Expr::Binary {
+                                op: BinaryOp::Add,
+                                lhs: Box::new(Expr::Symbol(previous)),
+                                rhs: Box::new(Expr::Number(Number::Int(1))),
+                            },
+                            (None, None) => Expr::Number(Number::Int(0)),
+                        };
+                        let value = match self.const_value(&expr, labels, &stmt.span) {
+                            Ok(value) => value,
+                            Err(diag) => {
+                                errors.push(diag);
+                                SymbolValue::Unresolved
+                            }
+                        };
+                        self.symbols.push(Symbol {
+                            name: full.clone(),
+                            kind: SymbolKind::Const,
+                            value,
+                            span: stmt.span.clone(),
+                        });
+                        previous = Some(full);
+                    }
+                    continue;
+                }
+                // Macro bodies only define anything once expanded.
+                StatementKind::Block(body)
+                | StatementKind::ForLoop { body, .. }
+                | StatementKind::WhileLoop { body, .. }
+                | StatementKind::Repeat { body, .. } => {
+                    self.collect_from(body, labels, errors);
+                    continue;
+                }
+                StatementKind::If {
+                    branches,
+                    otherwise,
+                } => {
+                    for branch in branches {
+                        self.collect_from(&branch.body, labels, errors);
+                    }
+                    self.collect_from(otherwise, labels, errors);
+                    continue;
+                }
+                _ => continue,
+            };
+
+            self.symbols.push(Symbol {
+                name,
+                kind,
+                value,
+                span: stmt.span.clone(),
+            });
+        }
+    }
+
+    /// Evaluates a constant's expression against the symbols defined so
+    /// far. One that needs a label's address waits, `Unresolved`, for the
+    /// pipeline to assign addresses.
+    fn const_value(
+        &self,
+        expr: &Expr,
+        labels: &HashSet<&str>,
+        span: &Span,
+    ) -> Result<SymbolValue, Diagnostic> {
+        let waits = |name: &str| labels.contains(name) || self.unresolved(name);
+        if expr.symbols().into_iter().any(waits) {
+            return Ok(SymbolValue::Unresolved);
+        }
+        let lookup = |name: &str| self.value(name);
+        Ok(eval(expr, &lookup, span)?.into())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// `name`'s value, if it has one yet. An `equ` is worked out from its
+    /// expression on every call, so it sees symbols defined after it, and
+    /// labels once they're resolved. One defined in terms of itself has no
+    /// value.
+    pub fn value(&self, name: &str) -> Option<Value> {
+        self.value_with(name, &|_| None)
+    }
+
+    /// Like [`value`](Self::value), taking the addresses of labels the
+    /// table hasn't resolved from `address_of`.
+    pub fn value_with(
+        &self,
+        name: &str,
+        address_of: &dyn Fn(&str) -> Option<u64>,
+    ) -> Option<Value> {
+        self.value_within(name, address_of, &RefCell::new(Vec::new()))
+    }
+
+    /// Like [`value_with`](Self::value_with), with the `equ`s being worked
+    /// out in `active`.
+    fn value_within<'a>(
+        &'a self,
+        name: &str,
+        address_of: &dyn Fn(&str) -> Option<u64>,
+        active: &RefCell<Vec<&'a str>>,
+    ) -> Option<Value> {
+        let sym = self.get(name)?;
+        match &sym.value {
+            SymbolValue::Unresolved if sym.kind == SymbolKind::Label => {
+                address_of(name).map(|address| Value::Int(address as i64))
+            }
+            SymbolValue::Int(n) => Some(Value::Int(*n)),
+            SymbolValue::Float(x) => Some(Value::Float(*x)),
+            SymbolValue::Str(text) => Some(Value::Str(text.clone())),
+            SymbolValue::Unresolved | SymbolValue::Size { .. } => None,
+            SymbolValue::Equ(expr) => self.work_out(sym, address_of, active, |lookup| {
+                eval(expr, lookup, &sym.span).ok()
+            }),
+            SymbolValue::Array(items) => self.work_out(sym, address_of, active, |lookup| {
+                let items = items.iter().map(|item| eval(item, lookup, &sym.span).ok());
+                items.collect::<Option<_>>().map(Value::Array)
+            }),
+        }
+    }
+
+    /// What `compute` makes of `sym`'s expressions, looking names up like
+    /// [`value_within`](Self::value_within). `None` if `sym` is already
+    /// being worked out, which means it's defined in terms of itself.
+    fn work_out<'a>(
+        &'a self,
+        sym: &'a Symbol,
+        address_of: &dyn Fn(&str) -> Option<u64>,
+        active: &RefCell<Vec<&'a str>>,
+        compute: impl FnOnce(&dyn Fn(&str) -> Option<Value>) -> Option<Value>,
+    ) -> Option<Value> {
+        if active.borrow().contains(&sym.name.as_str()) {
+            return None;
+        }
+        active.borrow_mut().push(&sym.name);
+        let value = compute(&|name: &str| self.value_within(name, address_of, active));
+        active.borrow_mut().pop();
+        value
+    }
+
+    /// `sym`'s value as it stands, with an `equ` worked out: `Unresolved`
+    /// if it can't be yet.
+    pub fn settled(&self, sym: &Symbol) -> SymbolValue {
+        match &sym.value {
+            SymbolValue::Equ(_) => self
+                .value(&sym.name)
+                .map_or(SymbolValue::Unresolved, SymbolValue::from),
+            value => value.clone(),
+        }
+    }
+
+    /// Whether `name` is waiting on labels' addresses: a label that hasn't
+    /// got one, or a value computed from one.
+    pub fn unresolved(&self, name: &str) -> bool {
+        self.unresolved_within(name, &mut Vec::new())
+    }
+
+    fn unresolved_within<'a>(&'a self, name: &str, seen: &mut Vec<&'a str>) -> bool {
+        let Some(sym) = self.get(name) else {
+            return false;
+        };
+        if seen.contains(&sym.name.as_str()) {
+            return false;
+        }
+        seen.push(&sym.name);
+        match &sym.value {
+            SymbolValue::Unresolved | SymbolValue::Size { .. } => true,
+            SymbolValue::Equ(expr) => expr
+                .symbols()
+                .into_iter()
+                .any(|name| self.unresolved_within(name, seen)),
+            SymbolValue::Array(items) => items
+                .iter()
+                .flat_map(Expr::symbols)
+                .any(|name| self.unresolved_within(name, seen)),
+            _ => false,
+        }
+    }
+
+    /// Whether the `equ` `name` ends up referring back to itself.
+    fn cyclic(&self, name: &str) -> bool {
+        let mut todo = vec![name];
+        let mut seen = HashSet::new();
+        while let Some(next) = todo.pop() {
+            let Some(SymbolValue::Equ(expr)) = self.get(next).map(|sym| &sym.value) else {
+                continue;
+            };
+            for used in expr.symbols() {
+                if used == name {
+                    return true;
+                }
+                if seen.insert(used) {
+                    todo.push(used);
+                }
+            }
+        }
+        false
+    }
+
+    /// Gives labels their addresses from `address_of`, then computes the
+    /// values that were waiting on them. Deferred values only ever refer to
+    /// labels, never to each other, so that one pass settles everything.
+    ///
+    /// Labels `address_of` doesn't know stay `Unresolved`. A size whose
+    /// labels are unknown, or whose end comes before its start, is an error
+    /// and stays unresolved too.
+    pub fn resolve(&mut self, address_of: impl Fn(&str) -> Option<u64>) -> Vec<Diagnostic> {
+        phase!("resolve");
+        let mut diags = Vec::new();
+
+        for sym in &mut self.symbols {
+            if sym.kind == SymbolKind::Label
+                && let Some(addr) = address_of(&sym.name)
+            {
+                sym.value = SymbolValue::Int(addr as i64);
+            }
+        }
+
+        for sym in &mut self.symbols {
+            let SymbolValue::Size { start, end } = &sym.value else {
+                continue;
+            };
+            let (Some(from), Some(to)) = (address_of(start), address_of(end)) else {
+                for label in [start, end] {
+                    if address_of(label).is_none() {
+                        diags.push(
+                            Diagnostic::error(format!(
+                                "`@size {}` refers to `{}`, which isn't a label",
+                                sym.name, label
+                            ))
+                            .with_code(Code::E0105)
+                            .with_span(sym.span.clone()),
+                        );
+                    }
+                }
+                continue;
+            };
+            if to < from {
+                diags.push(
+                    Diagnostic::error(format!(
+                        "`@size {}` is negative: `{}` comes before `{}`",
+                        sym.name, end, start
+                    ))
+                    .with_code(Code::E0106)
+                    .with_span(sym.span.clone())
+                    .with_note(format!("`{}` is at {:#x}", start, from))
+                    .with_note(format!("`{}` is at {:#x}", end, to)),
+                );
+                continue;
+            }
+            sym.value = SymbolValue::Int((to - from) as i64);
+        }
+
+        diags
+    }
+}
+
+/// Gives each numeric label a name of its own, `1:0` for the first `1:`,
+/// `1:1` for the second and so on, and points every `1f` at the next `1:`
+/// and every `1b` at the last one, so the rest of the pipeline sees plain
+/// labels. A reference with no label in its direction is left as written.
+///
+/// Run it on [`expand`](crate::expand::expand)ed statements, so each copy
+/// of a loop body gets its own labels.
+pub fn name_numeric_labels(ast: &[Statement]) -> Vec<Statement> {
+    let mut defined = HashMap::new();
+    for stmt in crate::walk::iter_deep(ast) {
+        if let StatementKind::Label { name, .. } = &stmt.kind
+            && is_numeric(name)
+        {
+            *defined.entry(name.clone()).or_insert(0) += 1;
+        }
+    }
+    let mut ast = ast.to_vec();
+    let mut rename = Rename(NumericLabels {
+        defined,
+        seen: HashMap::new(),
+    });
+    walk_statements_mut(&mut rename, &mut ast);
+    ast
+}
+
+/// Puts each `.local:` label under the last plain label before it:
+/// `.loop` after `main:` becomes `main.loop`, and so does every `.loop`
+/// referred to before the next plain label. Locals ahead of the first
+/// plain label keep their names. Numeric labels don't start a scope.
+pub fn scope_local_labels(ast: &[Statement]) -> Vec<Statement> {
+    let mut ast = ast.to_vec();
+    walk_statements_mut(&mut Rename(LocalLabels { scope: None }), &mut ast);
+    ast
+}
+
+/// Names what each `module` defines after it: `putc:` in `module io`
+/// becomes `io::putc`, as do the module's constants, `equ`s, structs and
+/// enums (so a field's offset is `io::Point.x`). A name used in a module that
+/// means one of them, innermost module first, is renamed to match, so
+/// code in `io` can say `putc` and code outside `io::putc`. Each module is
+/// left as a plain block.
+///
+/// Local and numeric labels keep their names; the label before them
+/// scopes them. Macro bodies are left alone: they only mean anything once
+/// called.
+pub fn scope_modules(ast: &[Statement]) -> Vec<Statement> {
+    let mut defined = HashSet::new();
+    module_names(ast, &mut Vec::new(), &mut defined);
+    let mut ast = ast.to_vec();
+    walk_statements_mut(
+        &mut Modules {
+            defined,
+            path: Vec::new(),
+        },
+        &mut ast,
+    );
+    ast
+}
+
+/// Adds the full name of everything defined in a module in `ast`, which
+/// sits in the modules on `path`, to `defined`.
+fn module_names(ast: &[Statement], path: &mut Vec<String>, defined: &mut HashSet<String>) {
+    for stmt in ast {
+        match &stmt.kind {
+            StatementKind::Module { name, body } => {
+                path.push(name.clone());
+                module_names(body, path, defined);
+                path.pop();
+            }
+            StatementKind::MacroDef { .. } => {}
+            StatementKind::Label { name, .. }
+            | StatementKind::ConstAssign { name, .. }
+            | StatementKind::Equ { name, .. } => {
+                if !path.is_empty() && !name.starts_with('.') && !is_numeric(name) {
+                    defined.insert(format!("{}::{}", path.join("::"), name));
+                }
+            }
+            StatementKind::Enum { name, variants } if !path.is_empty() => {
+                for (variant, _) in variants {
+                    defined.insert(format!("{}::{}.{}", path.join("::"), name, variant));
+                }
+            }
+            StatementKind::Struct { name, fields } if !path.is_empty() => {
+                let full = format!("{}::{}", path.join("::"), name);
+                for field in fields {
+                    defined.insert(format!("{}.{}", full, field.name));
+                }
+                defined.insert(full);
+            }
+            _ => {
+                for body in crate::walk::bodies(stmt) {
+                    module_names(body, path, defined);
+                }
+            }
+        }
+    }
+}
+
+/// Whether `name` is a numeric label's: digits only.
+fn is_numeric(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// How [`Rename`] renames labels and the names referring to them.
+trait Renamer {
+    /// Renames a label where it's defined.
+    fn label(&mut self, name: &mut String);
+    /// Renames a name an operand or expression refers to.
+    fn reference(&self, name: &mut String);
+}
+
+struct NumericLabels {
+    /// How many times each numeric label is defined in all.
+    defined: HashMap<String, usize>,
+    /// How many times each has been defined so far.
+    seen: HashMap<String, usize>,
+}
+
+impl Renamer for NumericLabels {
+    fn label(&mut self, name: &mut String) {
+        if is_numeric(name) {
+            let seen = self.seen.entry(name.clone()).or_insert(0);
+            *name = format!("{}:{}", name, seen);
+            *seen += 1;
+        }
+    }
+
+    /// Renames `1f` or `1b` after the label it means.
+    fn reference(&self, name: &mut String) {
+        let (label, forward) = match name.strip_suffix('f') {
+            Some(label) => (label, true),
+            None => match name.strip_suffix('b') {
+                Some(label) => (label, false),
+                None => return,
+            },
+        };
+        if !is_numeric(label) {
+            return;
+        }
+        let seen = self.seen.get(label).copied().unwrap_or(0);
+        let index = if forward {
+            let defined = self.defined.get(label).copied().unwrap_or(0);
+            (seen < defined).then_some(seen)
+        } else {
+            seen.checked_sub(1)
+        };
+        if let Some(index) = index {
+            *name = format!("{}:{}", label, index);
+        }
+    }
+}
+
+struct LocalLabels {
+    /// The last plain label.
+    scope: Option<String>,
+}
+
+impl Renamer for LocalLabels {
+    fn label(&mut self, name: &mut String) {
+        if name.starts_with('.') {
+            self.reference(name);
+        } else if !is_numeric(name) {
+            self.scope = Some(name.clone());
+        }
+    }
+
+    fn reference(&self, name: &mut String) {
+        if name.starts_with('.')
+            && let Some(scope) = &self.scope
+        {
+            name.insert_str(0, scope);
+        }
+    }
+}
+
+struct Modules {
+    /// The full name of everything defined in a module.
+    defined: HashSet<String>,
+    /// The modules the statements being walked are in, outermost first.
+    path: Vec<String>,
+}
+
+impl Modules {
+    /// Qualifies a name defined where the walk is.
+    fn define(&self, name: &mut String) {
+        if !self.path.is_empty() && !name.starts_with('.') && !is_numeric(name) {
+            *name = format!("{}::{}", self.path.join("::"), name);
+        }
+    }
+
+    /// Qualifies a name used where the walk is, if a module around it
+    /// defines it.
+    fn reference(&self, name: &mut String) {
+        for depth in (1..=self.path.len()).rev() {
+            let full = format!("{}::{}", self.path[..depth].join("::"), name);
+            if self.defined.contains(&full) {
+                *name = full;
+                return;
+            }
+        }
+    }
+}
+
+impl VisitorMut for Modules {
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        match &mut stmt.kind {
+            StatementKind::Module { name, body } => {
+                self.path.push(mem::take(name));
+                walk_statements_mut(self, body);
+                self.path.pop();
+                stmt.kind = StatementKind::Block(mem::take(body));
+            }
+            StatementKind::MacroDef { .. } => {}
+            StatementKind::Label { name, .. } | StatementKind::Struct { name, .. } => {
+                self.define(name)
+            }
+            StatementKind::Enum { name, .. } => {
+                self.define(name);
+                walk_statement_mut(self, stmt);
+            }
+            StatementKind::StructData { name, .. } => {
+                self.reference(name);
+                walk_statement_mut(self, stmt);
+            }
+            StatementKind::ConstAssign { name, .. } | StatementKind::Equ { name, .. } => {
+                self.define(name);
+                walk_statement_mut(self, stmt);
+            }
+            _ => walk_statement_mut(self, stmt),
+        }
+    }
+
+    fn visit_operand_mut(&mut self, operand: &mut Operand) {
+        match operand {
+            Operand::Symbol(name) => self.reference(name),
+            _ => walk_operand_mut(self, operand),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Symbol(name) => self.reference(name),
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+}
+
+/// Walks statements in source order, bodies included, renaming labels and
+/// references as its [`Renamer`] says.
+struct Rename<R>(R);
+
+impl<R: Renamer> VisitorMut for Rename<R> {
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        match &mut stmt.kind {
+            StatementKind::Label { name, .. } => self.0.label(name),
+            _ => walk_statement_mut(self, stmt),
+        }
+    }
+
+    fn visit_operand_mut(&mut self, operand: &mut Operand) {
+        match operand {
+            Operand::Symbol(name) => self.0.reference(name),
+            _ => walk_operand_mut(self, operand),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::Symbol(name) => self.0.reference(name),
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+}
+
+/// Checks that constant expressions evaluate and that no `equ` is defined
+/// in terms of itself. How many arguments `@size` gets is up to
+/// [`directives::check`](crate::directives::check).
+pub fn check(ast: &[Statement]) -> Vec<Diagnostic> {
+    let (table, mut diags) = SymbolTable::collect_checked(ast);
+    for sym in &table.symbols {
+        if sym.kind == SymbolKind::Equ && table.cyclic(&sym.name) {
+            diags.push(
+                Diagnostic::error(format!("`{}` is defined in terms of itself", sym.name))
+                    .with_code(Code::E0111)
+                    .with_span(sym.span.clone()),
+            );
+        }
+    }
+    diags
+}
+
+impl From<Value> for SymbolValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int(n) => SymbolValue::Int(n),
+            Value::Float(x) => SymbolValue::Float(x),
+            Value::Str(text) => SymbolValue::Str(text),
+            Value::Array(items) => {
+                SymbolValue::Array(items.iter().map(Value::to_expr).collect())
+            }
+        }
+    }
+}
@@ -1,59 +1,88 @@
 use logos::Logos;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
 
 #[derive(Logos, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     #[token("~")]
-
     Tilde,
 
     #[token("`")]
     Grave,
 
-
     #[token("#")]
     Pound,
 
-    #[token("+")]
+    // The current address, and the start of the current section. They stay
+    // symbolic until addresses are assigned.
+    #[token("$")]
+    Dollar,
+    #[token("$$")]
+    DollarDollar,
 
+    #[token("+")]
     Plus,
     #[token("++")]
     PlusPlus,
+    #[token("+=")]
+    PlusEqual,
     #[token("-")]
     Minus,
     #[token("--")]
     MinusMinus,
-
+    #[token("-=")]
+    MinusEqual,
 
     #[token("*")]
     Star,
+    #[token("*=")]
+    StarEqual,
     #[token("/")]
     Slash,
+    #[token("/=")]
+    SlashEqual,
     #[token("%")]
     Mod,
 
     #[token("!")]
     Bang,
+    #[token("!=")]
+    BangEqual,
 
     #[token(">")]
     Greater,
     #[token(">>")]
     GreaterGreater,
+    #[token(">=")]
+    GreaterEqual,
+    #[token(">>=")]
+    GreaterGreaterEqual,
 
     #[token("<")]
     Less,
     #[token("<<")]
     LessLess,
+    #[token("<=")]
+    LessEqual,
+    #[token("<<=")]
+    LessLessEqual,
 
     #[token("&")]
     Amp,
     #[token("&&")]
     AmpAmp,
+    #[token("&=")]
+    AmpEqual,
 
     #[token("|")]
     Pipe,
 
     #[token("||")]
     PipePipe,
+    #[token("|=")]
+    PipeEqual,
 
     #[token("^")]
     Xor,
@@ -78,38 +107,91 @@ pub enum TokenKind {
     #[token("for!")]
     ForBang,
 
+    // --- while!(...) loop ---
+    #[token("while!")]
+    WhileBang,
+
+    // --- repeat!(...) block ---
+    #[token("repeat!")]
+    RepeatBang,
+
+    // --- break! and continue! in loops ---
+    #[token("break!")]
+    BreakBang,
+    #[token("continue!")]
+    ContinueBang,
+
+    // --- if!(...) { } elif!(...) { } else! { } ---
+    #[token("if!")]
+    IfBang,
+    #[token("elif!")]
+    ElifBang,
+    #[token("else!")]
+    ElseBang,
+
     // --- Identifiers ---
-    #[regex(r"[A-Za-z_][A-Za-z0-9_]*", |lex| lex.slice().to_string())]
+    // `handler_##i` is one identifier: expansion pastes its pieces together
+    // once `i` has a value.
+    #[regex(r"[A-Za-z_][A-Za-z0-9_]*(##[A-Za-z0-9_]+)*", |lex| lex.slice().to_string())]
     Ident(String),
 
+    // Not matched here: which identifiers are registers depends on the
+    // target, so `parser::lex` rewrites them afterwards. The payload is the
+    // name as written, sigil and all: `R1`, `%tmp`.
+    Register(String),
+
+    // `1f` and `1b`: the nearest numeric label `1:` after or before. Not
+    // matched here either: `parser::lex` joins a decimal literal and the
+    // `f` or `b` right after it. The payload is the number and direction,
+    // without `_`s: `1f`.
+    NumericLabelRef(String),
+
     // --- Literals ---
-    #[regex(r"0x[0-9A-Fa-f]+", |lex| i64::from_str_radix(&lex.slice()[2..], 16).unwrap())]
+    // Digits may be separated by `_` after the first: `0xFFFF_0000`. One
+    // right after the prefix lexes too, but is an error (see
+    // `separator_after_prefix`), not `0` and the name `x_FF`.
+    #[regex(r"0x[0-9A-Fa-f][0-9A-Fa-f_]*", |lex| int_lit(&lex.slice()[2..], 16))]
+    #[regex(r"0x_[0-9A-Fa-f_]*", |lex| int_lit(&lex.slice()[2..], 16))]
     HexLit(i64),
 
-    #[regex(r"0b[01]+", |lex| i64::from_str_radix(&lex.slice()[2..], 2).unwrap())]
+    #[regex(r"0b[01][01_]*", |lex| int_lit(&lex.slice()[2..], 2))]
+    #[regex(r"0b_[01_]*", |lex| int_lit(&lex.slice()[2..], 2))]
     BinLit(i64),
 
-    #[regex(r"0o[0-7]+", |lex| i64::from_str_radix(&lex.slice()[2..], 8).unwrap())]
+    #[regex(r"0o[0-7][0-7_]*", |lex| int_lit(&lex.slice()[2..], 8))]
+    #[regex(r"0o_[0-7_]*", |lex| int_lit(&lex.slice()[2..], 8))]
     OctLit(i64),
 
-    #[regex(r"[0-9]+", |lex| lex.slice().parse::<i64>().unwrap())]
+    #[regex(r"[0-9][0-9_]*", |lex| int_lit(lex.slice(), 10))]
     IntLit(i64),
 
+    // `1.5`, `0.25`, `1e-3`: a fraction, an exponent or both.
+    #[regex(r"[0-9][0-9_]*\.[0-9][0-9_]*([eE][+-]?[0-9]+)?", |lex| float_lit(lex.slice()))]
+    #[regex(r"[0-9][0-9_]*[eE][+-]?[0-9]+", |lex| float_lit(lex.slice()))]
+    FloatLit(f64),
+
     // --- Strings ---
-    #[regex(r#""([^"\\]|\\.)*""#, |lex| lex.slice().to_string())]
+    // `r"..."` and `r#"..."#` are raw: a backslash is just a backslash, and
+    // the `#`s let the text hold `"`. `"""..."""` holds `"` and `""` as they
+    // are and is laid out for text that runs over several lines (see
+    // `text_block`). Either way the payload is the literal's text, quotes
+    // included.
+    #[regex(r#""([^"\\]|\\.)*""#, |lex| str_lit(lex.slice()))]
+    #[regex(r##"r#*""##, raw_str)]
+    #[token("\"\"\"", text_block_str)]
     StrLit(String),
 
     // --- Character literal ---
-    #[regex(r#"'([^'\\]|\\.)'"#, |lex| {
-        let s = lex.slice();
-
-        parse_char(s) // you define this small helper fn
-    })]
+    // One character or one escape. `'\x41'` and `'\u{263A}'` take more than
+    // one character after the `\`, so anything up to the closing quote does.
+    #[regex(r#"'([^'\\]|\\'|\\[^'\n]+)'"#, |lex| parse_char(lex.slice()))]
     CharLit(char),
 
     // --- Symbols & punctuation ---
     #[token("=")]
     Equal,
+    #[token("==")]
+    EqualEqual,
 
     #[token("(")]
     LeftParen,
@@ -119,6 +201,10 @@ pub enum TokenKind {
     LeftBrace,
     #[token("}")]
     RightBrace,
+    #[token("[")]
+    LeftBracket,
+    #[token("]")]
+    RightBracket,
     #[token(",")]
     Comma,
     #[token(":")]
@@ -127,7 +213,7 @@ pub enum TokenKind {
     // Labels like `.foo:` require DOT token.
     #[token(".")]
     Dot,
-    
+
     #[token(";")]
     Semicolon,
 
@@ -138,78 +224,409 @@ pub enum TokenKind {
     // Ignore whitespace
     #[regex(r"[ \t\r\n]+", logos::skip)]
     Whitespace,
+
+    // A `\` ending a line joins the next one to it.
+    #[regex(r"\\[ \t\r]*\n", logos::skip)]
+    LineContinuation,
+
+    // `// ...` to the end of the line. `;` stays a statement separator.
+    #[regex(r"//[^\n]*", logos::skip)]
+    LineComment,
+
+    // `/* ... */`, which may nest, is skipped too. Only a block comment
+    // still open at the end of input becomes a token, for the parser to
+    // report.
+    #[token("/*", block_comment)]
+    UnterminatedComment,
+
+    // Never lexed: a finished block comment, as kept trivia only (see
+    // `parser::LexOptions::trivia`).
+    BlockComment,
 }
 
-fn parse_char(s: &str) -> char {
-    let inner = &s[1..s.len() - 1]; // remove quotes
-    if inner.starts_with("\\") {
-        match &inner[1..] {
-            "n" => '\n',
-            "t" => '\t',
+/// Skips the rest of a block comment whose `/*` was just lexed.
+fn block_comment(lex: &mut logos::Lexer<TokenKind>) -> logos::Filter<()> {
+    match block_comment_len(lex.remainder().as_bytes()) {
+        Some(len) => {
+            lex.bump(len);
+            logos::Filter::Skip
+        }
+        None => {
+            lex.bump(lex.remainder().len());
+            logos::Filter::Emit(())
+        }
+    }
+}
 
-            "r" => '\r',
-            "'" => '\'',
-            "\\" => '\\',
-            _ => panic!("unknown escape {}", inner),
+/// Length of the rest of a block comment after its `/*`, through the `*/`
+/// that closes it. `None` if nothing does.
+fn block_comment_len(rest: &[u8]) -> Option<usize> {
+    let mut depth = 1;
+    let mut i = 0;
+    while i + 1 < rest.len() {
+        match &rest[i..i + 2] {
+            b"/*" => {
+                depth += 1;
+                i += 2;
+            }
+            b"*/" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => i += 1,
         }
-    } else {
-        inner.chars().next().unwrap()
-    }
-}
-
-fn parse_content(content: &str) -> i64 {
-    if content.starts_with("0x") || content.starts_with("0X") {
-        i64::from_str_radix(&content[2..], 16).unwrap()
-    } else if content.starts_with("0b") || content.starts_with("0B") {
-        i64::from_str_radix(&content[2..], 2).unwrap()
-    } else if content.starts_with("0o") || content.starts_with("0O") {
-        i64::from_str_radix(&content[2..], 8).unwrap()
-    } else if content.starts_with("'") && content.ends_with("'") {
-        let char_content = &content[1..content.len() - 1];
-        if char_content.len() == 1 {
-            char_content.chars().next().unwrap() as i64
-        } else if char_content.starts_with('\\') {
-            match char_content {
-                "\\n" => '\n' as i64,
-                "\\t" => '\t' as i64,
-                "\\r" => '\r' as i64,
-                "\\0" => '\0' as i64,
-                "\\'" => '\'' as i64,
-                "\\\"" => '\"' as i64,
-                "\\\\" => '\\' as i64,
-                _ => '\\' as i64,
+    }
+    None
+}
+
+/// Whether `gap`, input the lexer skipped between two tokens, breaks the
+/// line: it holds a newline that no `\` continues. Newlines inside block
+/// comments count; a `\` inside a comment continues nothing.
+pub(crate) fn breaks_line(gap: &str) -> bool {
+    let bytes = gap.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'/', Some(b'*')) => {
+                depth += 1;
+                i += 2;
+            }
+            (b'*', Some(b'/')) if depth > 0 => {
+                depth -= 1;
+                i += 2;
+            }
+            (b'/', Some(b'/')) if depth == 0 => {
+                i += gap[i..].find('\n').unwrap_or(gap.len() - i);
             }
+            (b'\\', _) if depth == 0 => {
+                let rest = gap[i + 1..].trim_start_matches([' ', '\t', '\r']);
+                i = gap.len() - rest.strip_prefix('\n').unwrap_or(rest).len();
+            }
+            (b'\n', _) => return true,
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+/// Splits `gap`, input the lexer skipped between two tokens, into the
+/// whitespace runs, comments and line continuations it's made of.
+pub(crate) fn split_trivia(gap: &str) -> Vec<(TokenKind, Range<usize>)> {
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while let Some(c) = gap[i..].chars().next() {
+        let rest = &gap[i..];
+        let (kind, len) = if rest.starts_with("//") {
+            (
+                TokenKind::LineComment,
+                rest.find('\n').unwrap_or(rest.len()),
+            )
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            let len = block_comment_len(body.as_bytes()).unwrap_or(body.len());
+            (TokenKind::BlockComment, 2 + len)
+        } else if let Some(after) = rest.strip_prefix('\\') {
+            let line = after.trim_start_matches([' ', '\t', '\r']);
+            let len = rest.len() - line.strip_prefix('\n').unwrap_or(line).len();
+            (TokenKind::LineContinuation, len)
         } else {
-            -1
+            let len = rest.len() - rest.trim_start_matches([' ', '\t', '\r', '\n']).len();
+            (TokenKind::Whitespace, len.max(c.len_utf8()))
+        };
+        pieces.push((kind, i..i + len));
+        i += len;
+    }
+    pieces
+}
+
+/// Finds the end of a raw string whose `r#"` was just lexed: the first `"`
+/// followed by as many `#`s. `None` if there is none.
+fn raw_str(lex: &mut logos::Lexer<TokenKind>) -> Option<String> {
+    let close = format!("\"{}", &lex.slice()[1..lex.slice().len() - 1]);
+    let end = lex.remainder().find(&close)?;
+    lex.bump(end + close.len());
+    Some(str_lit(lex.slice()))
+}
+
+/// Finds the end of a `"""` string whose opening quotes were just lexed:
+/// the first `"""` that isn't escaped. `None` if there is none.
+fn text_block_str(lex: &mut logos::Lexer<TokenKind>) -> Option<String> {
+    let rest = lex.remainder().as_bytes();
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i] {
+            b'\\' => i += 2,
+            b'"' if rest[i..].starts_with(b"\"\"\"") => {
+                lex.bump(i + 3);
+                return Some(str_lit(lex.slice()));
+            }
+            _ => i += 1,
         }
-    } else if content.chars().all(|c| c.is_ascii_digit() || c == '-') {
-        content.parse::<i64>().unwrap()
-    } else {
-        -1
-    }
-}
-fn parse_string(s: &str) -> String {
-    let inner = &s[1..s.len() - 1];
-    let mut result = String::new();
-    let mut chars = inner.chars().peekable();
-
-    while let Some(c) = chars.next() {
-        if c == '\\' {
-            match chars.next() {
-                Some('n') => result.push('\n'),
-                Some('r') => result.push('\r'),
-                Some('t') => result.push('\t'),
-                Some('0') => result.push('\0'),
-                Some('\'') => result.push('\''),
-                Some('"') => result.push('\"'),
-                Some('\\') => result.push('\\'),
-                Some(v) => result.push(v),
-                None => break,
+    }
+    None
+}
+
+/// The value of integer literal digits, `_` separators and all.
+fn int_lit(digits: &str, radix: u32) -> Option<i64> {
+    i64::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+/// `0FFh`, `1010b` or `777o`: an integer literal with its radix as a
+/// suffix, which lexes as a number and an identifier. `None` if `text`
+/// isn't one; `Some(Err(()))` if it is but doesn't fit in 64 bits.
+pub(crate) fn suffix_lit(text: &str) -> Option<Result<TokenKind, ()>> {
+    let (digits, suffix) = text.split_at(text.len().checked_sub(1)?);
+    let (radix, kind): (u32, fn(i64) -> TokenKind) = match suffix {
+        "h" | "H" => (16, TokenKind::HexLit),
+        "b" | "B" => (2, TokenKind::BinLit),
+        "o" | "O" => (8, TokenKind::OctLit),
+        _ => return None,
+    };
+    let valid = digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c == '_' || c.is_digit(radix));
+    if !valid {
+        return None;
+    }
+    Some(int_lit(digits, radix).map(kind).ok_or(()))
+}
+
+fn float_lit(text: &str) -> Option<f64> {
+    text.replace('_', "").parse().ok()
+}
+
+/// The payload of a string literal token: its text, quotes included, with
+/// CRLF line breaks inside it read as plain `\n`.
+pub(crate) fn str_lit(text: &str) -> String {
+    text.replace("\r\n", "\n")
+}
+
+/// `None` unless the literal holds exactly one character. A bad escape
+/// still counts as one; [`check_escapes`] reports it.
+fn parse_char(s: &str) -> Option<char> {
+    let (value, _) = unescape(&s[1..s.len() - 1]);
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// An escape sequence in a string or char literal that doesn't mean
+/// anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadEscape {
+    /// Bytes of the literal the escape covers, from its `\`.
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// The bad escapes in a string or char literal, quotes included. Raw
+/// strings have none.
+pub fn check_escapes(literal: &str) -> Vec<BadEscape> {
+    if literal.starts_with('r') {
+        return Vec::new();
+    }
+    let quotes = if literal.starts_with("\"\"\"") { 3 } else { 1 };
+    let (_, mut bad) = unescape(&literal[quotes..literal.len() - quotes]);
+    for escape in &mut bad {
+        escape.range = escape.range.start + quotes..escape.range.end + quotes;
+    }
+    bad
+}
+
+/// Unescapes the text between a literal's quotes. Each bad escape becomes
+/// U+FFFD and is returned alongside.
+fn unescape(inner: &str) -> (String, Vec<BadEscape>) {
+    let mut out = String::with_capacity(inner.len());
+    let mut bad = Vec::new();
+    let mut chars = inner.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some((_, escape)) = chars.next() else {
+            break;
+        };
+        let value = match escape {
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            't' => Ok('\t'),
+            '0' => Ok('\0'),
+            '\'' => Ok('\''),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'x' => hex_escape(&mut chars),
+            'u' => unicode_escape(&mut chars),
+            other => Err(format!("unknown escape `\\{}`", other)),
+        };
+        match value {
+            Ok(c) => out.push(c),
+            Err(message) => {
+                let end = chars.peek().map_or(inner.len(), |&(i, _)| i);
+                bad.push(BadEscape {
+                    range: start..end,
+                    message,
+                });
+                out.push(char::REPLACEMENT_CHARACTER);
             }
-        } else {
-            result.push(c);
         }
     }
 
-    result
+    (out, bad)
+}
+
+/// The rest of a `\xNN` escape: two hex digits naming an ASCII character.
+fn hex_escape(chars: &mut Peekable<CharIndices>) -> Result<char, String> {
+    let digits: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+    if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("`\\x{}` needs two hex digits", digits));
+    }
+    match u8::from_str_radix(&digits, 16) {
+        Ok(byte) if byte.is_ascii() => Ok(char::from(byte)),
+        _ => Err(format!(
+            "`\\x{}` is past `\\x7F`; write other characters as `\\u{{...}}`",
+            digits
+        )),
+    }
+}
+
+/// The rest of a `\u{...}` escape: one to six hex digits in braces naming a
+/// Unicode scalar value.
+fn unicode_escape(chars: &mut Peekable<CharIndices>) -> Result<char, String> {
+    if chars.next_if(|&(_, c)| c == '{').is_none() {
+        return Err("`\\u` needs braces, as in `\\u{263A}`".to_string());
+    }
+    let mut digits = String::new();
+    loop {
+        match chars.next() {
+            Some((_, '}')) => break,
+            Some((_, c)) => digits.push(c),
+            None => return Err(format!("`\\u{{{}` is missing its `}}`", digits)),
+        }
+    }
+    if digits.is_empty() || digits.len() > 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("`\\u{{{}}}` needs one to six hex digits", digits));
+    }
+    u32::from_str_radix(&digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("`\\u{{{}}}` is not a Unicode character", digits))
+}
+
+/// Whether `text`, an integer literal, has a `_` right after its radix
+/// prefix, like `0x_FF`.
+pub(crate) fn separator_after_prefix(text: &str) -> bool {
+    ["0x_", "0b_", "0o_"].iter().any(|prefix| text.starts_with(prefix))
+}
+
+/// Integer literal bases the lexer understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+impl Radix {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Radix::Bin => "0b",
+            Radix::Oct => "0o",
+            Radix::Dec => "",
+            Radix::Hex => "0x",
+        }
+    }
+}
+
+impl TokenKind {
+    /// The value of an integer literal in any radix.
+    pub fn int_value(&self) -> Option<i64> {
+        match self {
+            TokenKind::IntLit(v)
+            | TokenKind::HexLit(v)
+            | TokenKind::BinLit(v)
+            | TokenKind::OctLit(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `value` as a chasm literal in `radix`. Literals have no sign, so
+/// negative values are `None`.
+pub fn format_int(value: i64, radix: Radix) -> Option<String> {
+    if value < 0 {
+        return None;
+    }
+    Some(match radix {
+        Radix::Bin => format!("0b{:b}", value),
+        Radix::Oct => format!("0o{:o}", value),
+        Radix::Dec => value.to_string(),
+        Radix::Hex => format!("0x{:X}", value),
+    })
+}
+
+/// Quotes `s` as a string literal that [`parse_string`] turns back into `s`.
+pub fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\0' => out.push_str("\\0"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Unescapes a string literal, quotes included, as the lexer produced it.
+/// A raw string just loses its quotes; a `"""` string is laid out first.
+pub fn parse_string(s: &str) -> String {
+    if let Some(raw) = s.strip_prefix('r') {
+        let quoted = raw.trim_matches('#');
+        return quoted[1..quoted.len() - 1].to_string();
+    }
+    if let Some(body) = s.strip_prefix("\"\"\"") {
+        return unescape(&text_block(&body[..body.len() - 3])).0;
+    }
+    unescape(&s[1..s.len() - 1]).0
+}
+
+/// Lays out the text between a `"""` string's quotes. A line break right
+/// after the opening quotes isn't part of the string. When the closing
+/// quotes sit on a line of their own, that line isn't either, and its
+/// indentation comes off the start of every line:
+///
+/// ```text
+///     @message """
+///         usage: tool [options]
+///           -h  help
+///         """
+/// ```
+///
+/// holds `usage: tool [options]\n  -h  help`.
+fn text_block(body: &str) -> String {
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    let Some((lines, indent)) = body.rsplit_once('\n') else {
+        return body.to_string();
+    };
+    if !indent.trim_start_matches([' ', '\t']).is_empty() {
+        return body.to_string();
+    }
+    lines
+        .split('\n')
+        .map(|line| line.strip_prefix(indent).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
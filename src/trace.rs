@@ -0,0 +1,54 @@
+//! Thin wrappers over `tracing` that compile away when the `tracing`
+//! feature is off, so call sites don't need their own `#[cfg]`.
+
+/// Enters a span for a pipeline phase until the end of the enclosing block.
+macro_rules! phase {
+    ($name:literal) => {
+        #[cfg(feature = "tracing")]
+        let _phase = tracing::debug_span!($name).entered();
+    };
+}
+
+/// A phase entered a piece at a time, like a parse driven one statement
+/// at a time: a span made once, or nothing when the feature is off.
+#[cfg(feature = "tracing")]
+pub(crate) type Phase = tracing::Span;
+#[cfg(not(feature = "tracing"))]
+pub(crate) type Phase = ();
+
+/// Makes the [`Phase`] for a pipeline phase, to [`resume`] later.
+macro_rules! piecewise_phase {
+    ($name:literal) => {{
+        #[cfg(feature = "tracing")]
+        let phase = tracing::debug_span!($name);
+        #[cfg(not(feature = "tracing"))]
+        let phase = ();
+        phase
+    }};
+}
+
+/// Enters a [`Phase`] until the end of the enclosing block.
+macro_rules! resume {
+    ($phase:expr) => {
+        #[cfg(feature = "tracing")]
+        let _phase = $phase.enter();
+        #[cfg(not(feature = "tracing"))]
+        let _ = &$phase;
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+pub(crate) use {debug, phase, piecewise_phase, resume, warning};
@@ -0,0 +1,431 @@
+use crate::parser::{Expr, IfBranch, Operand, Statement, StatementKind};
+use std::slice;
+
+/// Pre-order walk over every statement, descending into bodied statements
+/// (`Block`, `MacroDef`, `Module`, `ForLoop`, `WhileLoop`, `Repeat`,
+/// `Foreach`, `If`) in source order.
+pub fn iter_deep(stmts: &[Statement]) -> impl Iterator<Item = &Statement> {
+    iter_deep_with_depth(stmts).map(|(_, stmt)| stmt)
+}
+
+/// Like [`iter_deep`], but also yields the nesting depth (0 for top level).
+pub fn iter_deep_with_depth(stmts: &[Statement]) -> DeepIter<'_> {
+    DeepIter {
+        stack: vec![(0, stmts.iter())],
+    }
+}
+
+/// Every instruction anywhere in the tree, as `(name, args)`.
+pub fn instructions(stmts: &[Statement]) -> impl Iterator<Item = (&str, &[Operand])> {
+    iter_deep(stmts).filter_map(|stmt| match &stmt.kind {
+        StatementKind::Instruction { name, args, .. } => Some((name.as_str(), args.as_slice())),
+        _ => None,
+    })
+}
+
+/// The nested statement lists of a bodied statement, in source order:
+/// one for most, one per branch for an `if!`, its `else` included.
+pub fn bodies(stmt: &Statement) -> Bodies<'_> {
+    let (body, branches, otherwise) = match &stmt.kind {
+        StatementKind::Block(body)
+        | StatementKind::MacroDef { body, .. }
+        | StatementKind::Module { body, .. }
+        | StatementKind::ForLoop { body, .. }
+        | StatementKind::WhileLoop { body, .. }
+        | StatementKind::Repeat { body, .. }
+        | StatementKind::Foreach { body, .. } => (Some(body.as_slice()), &[][..], None),
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => (None, branches.as_slice(), Some(otherwise.as_slice())),
+        _ => (None, &[][..], None),
+    };
+    Bodies {
+        body,
+        branches: branches.iter(),
+        otherwise,
+    }
+}
+
+/// Like [`bodies`], for changing them.
+pub fn bodies_mut(stmt: &mut Statement) -> BodiesMut<'_> {
+    let (body, branches, otherwise) = match &mut stmt.kind {
+        StatementKind::Block(body)
+        | StatementKind::MacroDef { body, .. }
+        | StatementKind::Module { body, .. }
+        | StatementKind::ForLoop { body, .. }
+        | StatementKind::WhileLoop { body, .. }
+        | StatementKind::Repeat { body, .. }
+        | StatementKind::Foreach { body, .. } => (Some(body), &mut [][..], None),
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => (None, branches.as_mut_slice(), Some(otherwise)),
+        _ => (None, &mut [][..], None),
+    };
+    BodiesMut {
+        body,
+        branches: branches.iter_mut(),
+        otherwise,
+    }
+}
+
+/// What [`bodies`] returns. Borrows the statement; allocates nothing.
+pub struct Bodies<'a> {
+    /// The body of anything but an `if!`.
+    body: Option<&'a [Statement]>,
+    branches: slice::Iter<'a, IfBranch>,
+    /// An `if!`'s `else`, empty if it has none.
+    otherwise: Option<&'a [Statement]>,
+}
+
+impl<'a> Iterator for Bodies<'a> {
+    type Item = &'a [Statement];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.body
+            .take()
+            .or_else(|| self.branches.next().map(|branch| branch.body.as_slice()))
+            .or_else(|| self.otherwise.take())
+    }
+}
+
+impl DoubleEndedIterator for Bodies<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.otherwise
+            .take()
+            .or_else(|| {
+                self.branches
+                    .next_back()
+                    .map(|branch| branch.body.as_slice())
+            })
+            .or_else(|| self.body.take())
+    }
+}
+
+/// What [`bodies_mut`] returns.
+pub struct BodiesMut<'a> {
+    body: Option<&'a mut Vec<Statement>>,
+    branches: slice::IterMut<'a, IfBranch>,
+    otherwise: Option<&'a mut Vec<Statement>>,
+}
+
+impl<'a> Iterator for BodiesMut<'a> {
+    type Item = &'a mut Vec<Statement>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.body
+            .take()
+            .or_else(|| self.branches.next().map(|branch| &mut branch.body))
+            .or_else(|| self.otherwise.take())
+    }
+}
+
+pub struct DeepIter<'a> {
+    /// Statement lists still being walked, with their depth.
+    stack: Vec<(usize, slice::Iter<'a, Statement>)>,
+}
+
+impl<'a> Iterator for DeepIter<'a> {
+    type Item = (usize, &'a Statement);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (depth, iter) = self.stack.last_mut()?;
+            let depth = *depth;
+            match iter.next() {
+                Some(stmt) => {
+                    // Reversed, so the first body is walked first.
+                    for body in bodies(stmt).rev() {
+                        self.stack.push((depth + 1, body.iter()));
+                    }
+                    return Some((depth, stmt));
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Walks the AST, one `visit_*` method per kind of node. Each one defaults
+/// to the matching `walk_*` function, which visits the node's children in
+/// source order; override one to act on that kind of node, and call its
+/// `walk_*` function from the override to keep going down. Nodes are
+/// borrowed for `'ast`, so a visitor can hold on to what it finds.
+pub trait Visitor<'ast> {
+    fn visit_statement(&mut self, stmt: &'ast Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_operand(&mut self, operand: &'ast Operand) {
+        walk_operand(self, operand);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visits each of `stmts` in turn.
+pub fn walk_statements<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmts: &'ast [Statement]) {
+    for stmt in stmts {
+        visitor.visit_statement(stmt);
+    }
+}
+
+/// Visits the operands and expressions `stmt` holds, then its bodies. An
+/// `if!` branch's condition comes just before its body.
+pub fn walk_statement<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast Statement) {
+    match &stmt.kind {
+        StatementKind::VarAssign { expr, .. }
+        | StatementKind::VarUpdate { expr, .. }
+        | StatementKind::ConstAssign { expr, .. }
+        | StatementKind::Equ { expr, .. }
+        | StatementKind::Org(expr) => visitor.visit_expr(expr),
+        StatementKind::Instruction { args, .. } | StatementKind::MacroCall { args, .. } => {
+            for arg in args {
+                visitor.visit_operand(arg);
+            }
+        }
+        StatementKind::Data { values, .. } => {
+            for value in values {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::StructData { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::Enum { variants, .. } => {
+            for value in variants.iter().filter_map(|(_, value)| value.as_ref()) {
+                visitor.visit_expr(value);
+            }
+        }
+        StatementKind::Align { boundary, fill } => {
+            visitor.visit_expr(boundary);
+            if let Some(fill) = fill {
+                visitor.visit_expr(fill);
+            }
+        }
+        StatementKind::MacroDef { defaults, body, .. } => {
+            for default in defaults {
+                visitor.visit_operand(default);
+            }
+            walk_statements(visitor, body);
+        }
+        StatementKind::Block(body)
+        | StatementKind::Module { body, .. }
+        | StatementKind::Foreach { body, .. } => walk_statements(visitor, body),
+        StatementKind::ForLoop {
+            start,
+            end,
+            step,
+            body,
+            ..
+        } => {
+            for expr in [start, end, step] {
+                visitor.visit_expr(expr);
+            }
+            walk_statements(visitor, body);
+        }
+        StatementKind::WhileLoop { cond: expr, body }
+        | StatementKind::Repeat { count: expr, body } => {
+            visitor.visit_expr(expr);
+            walk_statements(visitor, body);
+        }
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            for branch in branches {
+                visitor.visit_expr(&branch.cond);
+                walk_statements(visitor, &branch.body);
+            }
+            walk_statements(visitor, otherwise);
+        }
+        StatementKind::Label { .. }
+        | StatementKind::Directive { .. }
+        | StatementKind::Include(_)
+        | StatementKind::Section(_)
+        | StatementKind::StringData { .. }
+        | StatementKind::Struct { .. }
+        | StatementKind::Break
+        | StatementKind::Continue => {}
+    }
+}
+
+/// Visits the expressions in `operand`: its value, or a memory operand's
+/// displacement. A bare `Symbol` holds none, so visitors looking for names
+/// override [`Visitor::visit_operand`] as well as `visit_expr`.
+pub fn walk_operand<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, operand: &'ast Operand) {
+    match operand {
+        Operand::Immediate(expr) | Operand::Expr(expr) => visitor.visit_expr(expr),
+        Operand::Memory(memory) => {
+            if let Some(disp) = &memory.displacement {
+                visitor.visit_expr(disp);
+            }
+        }
+        Operand::Register(_) | Operand::Symbol(_) | Operand::String(_) | Operand::Char(_) => {}
+    }
+}
+
+/// Visits `expr`'s subexpressions, left to right.
+pub fn walk_expr<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, expr: &'ast Expr) {
+    match expr {
+        Expr::Unary { expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Call { args, .. } | Expr::Array(args) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::Index { base, index } => {
+            visitor.visit_expr(base);
+            visitor.visit_expr(index);
+        }
+        Expr::Number(_) | Expr::Str(_) | Expr::Symbol(_) => {}
+    }
+}
+
+/// [`Visitor`] for changing the tree in place.
+pub trait VisitorMut {
+    fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_operand_mut(&mut self, operand: &mut Operand) {
+        walk_operand_mut(self, operand);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Visits each of `stmts` in turn.
+pub fn walk_statements_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmts: &mut [Statement]) {
+    for stmt in stmts {
+        visitor.visit_statement_mut(stmt);
+    }
+}
+
+/// Like [`walk_statement`].
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match &mut stmt.kind {
+        StatementKind::VarAssign { expr, .. }
+        | StatementKind::VarUpdate { expr, .. }
+        | StatementKind::ConstAssign { expr, .. }
+        | StatementKind::Equ { expr, .. }
+        | StatementKind::Org(expr) => visitor.visit_expr_mut(expr),
+        StatementKind::Instruction { args, .. } | StatementKind::MacroCall { args, .. } => {
+            for arg in args {
+                visitor.visit_operand_mut(arg);
+            }
+        }
+        StatementKind::Data { values, .. } => {
+            for value in values {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StatementKind::StructData { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StatementKind::Enum { variants, .. } => {
+            for value in variants.iter_mut().filter_map(|(_, value)| value.as_mut()) {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        StatementKind::Align { boundary, fill } => {
+            visitor.visit_expr_mut(boundary);
+            if let Some(fill) = fill {
+                visitor.visit_expr_mut(fill);
+            }
+        }
+        StatementKind::MacroDef { defaults, body, .. } => {
+            for default in defaults {
+                visitor.visit_operand_mut(default);
+            }
+            walk_statements_mut(visitor, body);
+        }
+        StatementKind::Block(body)
+        | StatementKind::Module { body, .. }
+        | StatementKind::Foreach { body, .. } => walk_statements_mut(visitor, body),
+        StatementKind::ForLoop {
+            start,
+            end,
+            step,
+            body,
+            ..
+        } => {
+            for expr in [start, end, step] {
+                visitor.visit_expr_mut(expr);
+            }
+            walk_statements_mut(visitor, body);
+        }
+        StatementKind::WhileLoop { cond: expr, body }
+        | StatementKind::Repeat { count: expr, body } => {
+            visitor.visit_expr_mut(expr);
+            walk_statements_mut(visitor, body);
+        }
+        StatementKind::If {
+            branches,
+            otherwise,
+        } => {
+            for branch in branches {
+                visitor.visit_expr_mut(&mut branch.cond);
+                walk_statements_mut(visitor, &mut branch.body);
+            }
+            walk_statements_mut(visitor, otherwise);
+        }
+        StatementKind::Label { .. }
+        | StatementKind::Directive { .. }
+        | StatementKind::Include(_)
+        | StatementKind::Section(_)
+        | StatementKind::StringData { .. }
+        | StatementKind::Struct { .. }
+        | StatementKind::Break
+        | StatementKind::Continue => {}
+    }
+}
+
+/// Like [`walk_operand`].
+pub fn walk_operand_mut<V: VisitorMut + ?Sized>(visitor: &mut V, operand: &mut Operand) {
+    match operand {
+        Operand::Immediate(expr) | Operand::Expr(expr) => visitor.visit_expr_mut(expr),
+        Operand::Memory(memory) => {
+            if let Some(disp) = &mut memory.displacement {
+                visitor.visit_expr_mut(disp);
+            }
+        }
+        Operand::Register(_) | Operand::Symbol(_) | Operand::String(_) | Operand::Char(_) => {}
+    }
+}
+
+/// Like [`walk_expr`].
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Unary { expr, .. } => visitor.visit_expr_mut(expr),
+        Expr::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr_mut(lhs);
+            visitor.visit_expr_mut(rhs);
+        }
+        Expr::Call { args, .. } | Expr::Array(args) => {
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::Index { base, index } => {
+            visitor.visit_expr_mut(base);
+            visitor.visit_expr_mut(index);
+        }
+        Expr::Number(_) | Expr::Str(_) | Expr::Symbol(_) => {}
+    }
+}
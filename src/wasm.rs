@@ -0,0 +1,135 @@
+use crate::assemble::{assemble_statements, parse_file};
+use crate::diagnostic::Diagnostic;
+use crate::isa::{Isa, Target};
+use crate::json::diagnostics_to_json;
+use crate::parser::{Statement, StatementKind};
+use crate::source::SourceManager;
+use crate::tokens::parse_string;
+use crate::walk::bodies_mut;
+use std::collections::{HashMap, HashSet};
+use wasm_bindgen::prelude::*;
+
+/// Parses `source` and returns the AST as JSON, or a diagnostics object on failure.
+///
+/// The parser never touches the filesystem (`include` is recorded, not
+/// resolved), so this is safe to call from the browser as-is.
+#[wasm_bindgen]
+pub fn parse_to_json(source: &str) -> String {
+    crate::json::parse_to_json(source)
+}
+
+/// Assembles `source` into a flat binary for the instruction set
+/// `isa_json` describes (see [`Isa`]), or the generic target's if it's
+/// empty. Registers are named like the generic target's.
+///
+/// On failure the error is a diagnostics object, as from
+/// [`parse_to_json`].
+#[wasm_bindgen]
+pub fn assemble(source: &str, isa_json: &str) -> Result<Vec<u8>, JsValue> {
+    assemble_to_bytes(source, isa_json).map_err(|json| JsValue::from_str(&json))
+}
+
+/// Assembles like [`assemble`], with the files `source` can `include`
+/// given by `files_json`, an object from each file's name to its text:
+/// the browser has no filesystem to read them from. An `include` names
+/// its file exactly as the object's key does; an empty `files_json` has
+/// no files.
+#[wasm_bindgen]
+pub fn assemble_with_files(
+    source: &str,
+    isa_json: &str,
+    files_json: &str,
+) -> Result<Vec<u8>, JsValue> {
+    assemble_to_bytes_with_files(source, isa_json, files_json)
+        .map_err(|json| JsValue::from_str(&json))
+}
+
+/// [`assemble`] with a plain error string, callable outside a browser.
+pub fn assemble_to_bytes(source: &str, isa_json: &str) -> Result<Vec<u8>, String> {
+    assemble_to_bytes_with_files(source, isa_json, "")
+}
+
+/// [`assemble_with_files`] with a plain error string, callable outside a
+/// browser.
+pub fn assemble_to_bytes_with_files(
+    source: &str,
+    isa_json: &str,
+    files_json: &str,
+) -> Result<Vec<u8>, String> {
+    let mut sources = SourceManager::new();
+    let file = sources.add_virtual("input", source);
+    let fail = |sources: &SourceManager, what: &str, e: serde_json::Error| {
+        let diag = Diagnostic::error(format!("{}: {}", what, e));
+        diagnostics_to_json(&[diag], sources)
+    };
+    let target = Target::GENERIC;
+    let isa = match isa_json.trim() {
+        "" => target.isa(),
+        json => {
+            serde_json::from_str::<Isa>(json).map_err(|e| fail(&sources, "instruction set", e))?
+        }
+    };
+    let files = match files_json.trim() {
+        "" => HashMap::new(),
+        json => serde_json::from_str::<HashMap<String, String>>(json)
+            .map_err(|e| fail(&sources, "files", e))?,
+    };
+
+    let (ast, mut diags) = parse_file(&sources, file, &target);
+    let mut includes = Includes {
+        files: &files,
+        target: &target,
+        spliced: HashSet::new(),
+        diags: Vec::new(),
+    };
+    let ast = includes.splice(&mut sources, ast);
+    diags.append(&mut includes.diags);
+    if !diags.is_empty() {
+        return Err(diagnostics_to_json(&diags, &sources));
+    }
+    assemble_statements(&ast, &target, &isa).map_err(|diags| diagnostics_to_json(&diags, &sources))
+}
+
+/// Puts the files of a `files_json` in place of the `include`s naming
+/// them, as [`splice`](crate::session::splice) does with files on disk.
+struct Includes<'a> {
+    files: &'a HashMap<String, String>,
+    target: &'a Target,
+    /// The files already spliced in somewhere: one included again is left
+    /// out.
+    spliced: HashSet<&'a str>,
+    /// Includes naming no file, and the included files' lexing and syntax
+    /// errors.
+    diags: Vec<Diagnostic>,
+}
+
+impl<'a> Includes<'a> {
+    fn splice(&mut self, sources: &mut SourceManager, stmts: Vec<Statement>) -> Vec<Statement> {
+        let mut out = Vec::with_capacity(stmts.len());
+        for mut stmt in stmts {
+            let StatementKind::Include(raw) = &stmt.kind else {
+                for body in bodies_mut(&mut stmt) {
+                    *body = self.splice(sources, std::mem::take(body));
+                }
+                out.push(stmt);
+                continue;
+            };
+            let name = parse_string(raw);
+            let Some((name, text)) = self.files.get_key_value(&name) else {
+                self.diags.push(
+                    Diagnostic::error(format!("include \"{}\" not found", name))
+                        .with_span(stmt.span.clone()),
+                );
+                continue;
+            };
+            if !self.spliced.insert(name) {
+                continue;
+            }
+            let file = sources.add_virtual(name, text.as_str());
+            let (ast, mut diags) = parse_file(sources, file, self.target);
+            self.diags.append(&mut diags);
+            out.extend(self.splice(sources, ast));
+        }
+        out
+    }
+}
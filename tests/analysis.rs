@@ -0,0 +1,168 @@
+//! Go to definition and find references, resolving names the way the
+//! assembler does.
+
+use chasm::analysis::{definition_at, references_of};
+use chasm::includes::IncludeOptions;
+use chasm::parser::{LexOptions, Parser, Statement};
+use chasm::session::{Session, splice};
+use chasm::source::{FileId, SourceManager, Span};
+use std::fs;
+
+fn parse(source: &str) -> (SourceManager, FileId, Vec<Statement>) {
+    let mut sources = SourceManager::new();
+    let file = sources.add("main.asm", source);
+    let mut parser = Parser::streaming(&sources, file, LexOptions::default());
+    let ast = parser.parse();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    (sources, file, ast)
+}
+
+/// The offset of the `nth` `needle` in `text`, counting from 0.
+fn at(text: &str, needle: &str, nth: usize) -> usize {
+    text.match_indices(needle).nth(nth).unwrap().0
+}
+
+/// The span of the `nth` `needle` in `file`.
+fn span(sources: &SourceManager, file: FileId, needle: &str, nth: usize) -> Span {
+    let start = at(sources.text(file), needle, nth);
+    Span::new(file, start..start + needle.len())
+}
+
+#[test]
+fn a_label_operand_finds_the_label() {
+    let source = "start:\n    ldi r1 data\n    jmp start\ndata:\n    .byte 1\n";
+    let (sources, file, ast) = parse(source);
+    let jmp = at(source, "start", 1) + 2;
+    assert_eq!(
+        definition_at(&ast, &sources, file, jmp),
+        Some(span(&sources, file, "start", 0))
+    );
+    // On the definition itself.
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "data", 1)),
+        Some(span(&sources, file, "data", 1))
+    );
+    // Not a symbol.
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "r1", 0)),
+        None
+    );
+}
+
+#[test]
+fn a_local_label_resolves_under_its_own_label() {
+    let source = "main:\n.loop:\n    jmp .loop\nother:\n.loop:\n    jmp .loop\n";
+    let (sources, file, ast) = parse(source);
+    for (reference, definition) in [(1, 0), (3, 2)] {
+        let offset = at(source, "loop", reference);
+        assert_eq!(
+            definition_at(&ast, &sources, file, offset),
+            Some(span(&sources, file, "loop", definition)),
+            "the `.loop` at {}",
+            offset
+        );
+    }
+    assert_eq!(
+        references_of(&ast, &sources, &span(&sources, file, "loop", 2)),
+        [span(&sources, file, "loop", 3)]
+    );
+}
+
+#[test]
+fn consts_vars_and_defines() {
+    let source = "\
+const SIZE = 4
+var count = SIZE
+@define FAST
+var count += 1
+    ldi r1 SIZE + count
+    ldi r2 FAST
+";
+    let (sources, file, ast) = parse(source);
+    let size = span(&sources, file, "SIZE", 0);
+    let count = span(&sources, file, "count", 0);
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "SIZE", 2)),
+        Some(size.clone())
+    );
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "FAST", 1)),
+        Some(span(&sources, file, "FAST", 0))
+    );
+    assert_eq!(
+        references_of(&ast, &sources, &size),
+        [
+            span(&sources, file, "SIZE", 1),
+            span(&sources, file, "SIZE", 2)
+        ]
+    );
+    assert_eq!(
+        references_of(&ast, &sources, &count),
+        [
+            span(&sources, file, "count", 1),
+            span(&sources, file, "count", 2)
+        ]
+    );
+}
+
+#[test]
+fn a_macro_call_finds_the_macro_and_a_parameter_stays_in_its_body() {
+    let source = "\
+reg:
+macro_rules! push(reg) {
+    sub sp, 2
+    st [sp], reg
+}
+    push!(r1)
+    ldi r1 reg
+";
+    let (sources, file, ast) = parse(source);
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "push", 1)),
+        Some(span(&sources, file, "push", 0))
+    );
+    let param = span(&sources, file, "reg", 1);
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "reg", 2)),
+        Some(param.clone())
+    );
+    assert_eq!(
+        references_of(&ast, &sources, &param),
+        [span(&sources, file, "reg", 2)]
+    );
+    // Outside the body, `reg` is the label.
+    assert_eq!(
+        definition_at(&ast, &sources, file, at(source, "reg", 3)),
+        Some(span(&sources, file, "reg", 0))
+    );
+}
+
+#[test]
+fn a_definition_in_an_included_file() {
+    let dir = std::env::temp_dir().join(format!("chasm-analysis-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let main = "include \"lib.asm\"\nmain:\n    call putc\n.loop:\n    jmp .loop\n";
+    fs::write(dir.join("main.asm"), main).unwrap();
+    fs::write(dir.join("lib.asm"), "putc:\n.loop:\n    jmp .loop\n").unwrap();
+
+    let mut session = Session::new(IncludeOptions::default());
+    let files = session.build(&dir.join("main.asm")).unwrap();
+    let ast = splice(&files);
+    let sources = session.sources();
+    let (main_file, lib_file) = (files[0].file, files[1].file);
+
+    assert_eq!(
+        definition_at(&ast, sources, main_file, at(main, "putc", 0)),
+        Some(span(sources, lib_file, "putc", 0))
+    );
+    // Each file's `.loop` is under its own label.
+    assert_eq!(
+        definition_at(&ast, sources, main_file, at(main, "loop", 1)),
+        Some(span(sources, main_file, "loop", 0))
+    );
+    assert_eq!(
+        references_of(&ast, sources, &span(sources, lib_file, "putc", 0)),
+        [span(sources, main_file, "putc", 0)]
+    );
+}
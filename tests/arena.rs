@@ -0,0 +1,230 @@
+//! The arena AST against the owned one: the same trees, the same text and
+//! the same walks, built without the owned tree in between.
+
+#![cfg(feature = "arena")]
+
+use chasm::arena::{self, AstArena};
+use chasm::parser::{self, Parser};
+use chasm::print;
+use chasm::walk::{self, Visitor as _};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+
+/// Counts this thread's allocations, so a test can tell how many a parse
+/// made.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    (value, ALLOCATIONS.with(Cell::get) - before)
+}
+
+/// Every golden fixture's input.
+fn fixtures() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut inputs: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let input = fs::read_to_string(path.join("input.asm")).ok()?;
+            Some((path.file_name()?.to_string_lossy().into_owned(), input))
+        })
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+const PROGRAM: &str = r#"
+const SIZE = (4 + 2) * 8
+var count = SIZE
+var count += 1
+LIMIT equ SIZE - 1
+enum Color { Red, Green = 4, Blue }
+struct Point { x: word, y: word }
+origin: Point { x: 1, y: -2 }
+module io {
+    ::putc:
+        st [r1 + r2 * 4 - 8], r0
+        ret
+}
+macro_rules! push(reg, by = 2) {
+    sub sp, by
+    st [sp], reg
+}
+@org 0x100
+@align 4, 0xff
+@define FAST
+start:
+.loop:
+    push!(r1)
+    ldi r1 #io::putc + 1
+    ld r2 'A' + 1
+    for!(var i = 0; i < 4; i++) { add r1 r1 TABLE[i] }
+    if!(FAST) { nop } elif!(0) { halt } else { jmp .loop }
+    while!(count < 10) { var count += 1 }
+    repeat!(2) { break! }
+    .word 1, -SIZE, ~3, !0, strlen("hi")
+    .asciz "done"
+    .data
+    jmp 1f
+1:
+    halt
+"#;
+
+#[test]
+fn parse_in_builds_the_tree_the_owned_parse_does() {
+    for (name, input) in fixtures()
+        .into_iter()
+        .chain([("PROGRAM".to_string(), PROGRAM.into())])
+    {
+        let arena = AstArena::new();
+        let mut direct = Parser::new(&input);
+        let built = direct.parse_in(&arena);
+
+        let mut owned = Parser::new(&input);
+        let ast = owned.parse();
+        let copied = arena.copy(&ast);
+
+        assert_eq!(format!("{:?}", built), format!("{:?}", copied), "{}", name);
+        assert_eq!(
+            format!("{:?}", direct.errors()),
+            format!("{:?}", owned.errors()),
+            "{}",
+            name
+        );
+        assert_eq!(direct.skipped(), owned.skipped(), "{}", name);
+        assert_eq!(arena::to_source(built), print::to_source(&ast), "{}", name);
+    }
+}
+
+#[test]
+fn display_matches_the_owned_tree() {
+    let arena = AstArena::new();
+    let built = Parser::new(PROGRAM).parse_in(&arena);
+    let ast = Parser::new(PROGRAM).parse();
+    assert_eq!(built.len(), ast.len());
+    for (built, owned) in built.iter().zip(&ast) {
+        assert_eq!(built.to_string(), owned.to_string());
+    }
+}
+
+#[test]
+fn syntax_errors_recover_inside_blocks() {
+    let input = "{\n    ld r1 [r2 - r3]\n    nop\n}\nhalt\n";
+    let arena = AstArena::new();
+    let mut parser = Parser::new(input);
+    let built = parser.parse_in(&arena);
+    assert_eq!(
+        parser.errors()[0].message,
+        "registers can only be added in a memory operand"
+    );
+    assert_eq!(arena::to_source(built), "{\n    nop\n}\nhalt\n");
+}
+
+/// The names a walk sees, in order.
+#[derive(Default)]
+struct Names(Vec<String>);
+
+impl<'a> arena::Visitor<'a> for Names {
+    fn visit_operand(&mut self, operand: &'a arena::Operand<'a>) {
+        if let arena::Operand::Symbol(name) = operand {
+            self.0.push(name.to_string());
+        }
+        arena::walk_operand(self, operand);
+    }
+
+    fn visit_expr(&mut self, expr: &'a arena::Expr<'a>) {
+        if let arena::Expr::Symbol(name) = expr {
+            self.0.push(name.to_string());
+        }
+        arena::walk_expr(self, expr);
+    }
+}
+
+impl<'ast> walk::Visitor<'ast> for Names {
+    fn visit_operand(&mut self, operand: &'ast parser::Operand) {
+        if let parser::Operand::Symbol(name) = operand {
+            self.0.push(name.clone());
+        }
+        walk::walk_operand(self, operand);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast parser::Expr) {
+        if let parser::Expr::Symbol(name) = expr {
+            self.0.push(name.clone());
+        }
+        walk::walk_expr(self, expr);
+    }
+}
+
+#[test]
+fn a_visitor_walks_both_trees_alike() {
+    let arena = AstArena::new();
+    let built = Parser::new(PROGRAM).parse_in(&arena);
+    let ast = Parser::new(PROGRAM).parse();
+
+    let mut in_arena = Names::default();
+    arena::walk_statements(&mut in_arena, built);
+    let mut owned = Names::default();
+    for stmt in &ast {
+        owned.visit_statement(stmt);
+    }
+    assert!(
+        in_arena.0.contains(&"io::putc".to_string()),
+        "{:?}",
+        in_arena.0
+    );
+    assert_eq!(in_arena.0, owned.0);
+}
+
+#[test]
+fn parsing_into_the_arena_makes_no_owned_nodes() {
+    let input = PROGRAM.repeat(50);
+    // Lexed up front, so only the parse itself is counted.
+    let mut parser = Parser::new(&input);
+    let (ast, owned) = allocations(|| parser.parse());
+    let arena = AstArena::new();
+    let mut parser = Parser::new(&input);
+    let (built, direct) = allocations(|| parser.parse_in(&arena).len());
+    assert_eq!(built, ast.len());
+    // What's left is the arena's own chunks and the leaf statements the
+    // parser builds before they're copied in: a directive's name and
+    // arguments, a struct's fields, a `.loop:` label's name.
+    assert!(
+        direct * 4 < owned,
+        "{} allocations into the arena, {} owned",
+        direct,
+        owned
+    );
+}
+
+#[test]
+fn reset_frees_the_tree() {
+    let mut arena = AstArena::new();
+    Parser::new(PROGRAM).parse_in(&arena);
+    assert!(arena.allocated_bytes() > 0);
+    arena.reset();
+    let len = Parser::new(PROGRAM).parse_in(&arena).len();
+    assert_eq!(len, Parser::new(PROGRAM).parse().len());
+}
@@ -0,0 +1,106 @@
+//! The C API, called the way C would.
+
+#![cfg(feature = "capi")]
+
+use chasm::capi::*;
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::slice;
+
+/// Runs `chasm_assemble` and hands its result to `check` before freeing it.
+fn assemble(src: &str, options: Option<&ChasmOptions>, check: impl FnOnce(i32, &ChasmResult)) {
+    let src = CString::new(src).unwrap();
+    let options = options.map_or(ptr::null(), ptr::from_ref);
+    let mut out = ptr::null_mut();
+    let status = unsafe { chasm_assemble(src.as_ptr(), options, &mut out) };
+    assert!(!out.is_null());
+    check(status, unsafe { &*out });
+    unsafe { chasm_result_free(out) };
+}
+
+fn text(s: *const std::ffi::c_char) -> Option<String> {
+    (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string())
+}
+
+#[test]
+fn assembles_to_bytes() {
+    assemble(
+        "start:\n    ldi r1 0x1234\n    jmp start\n",
+        None,
+        |status, result| {
+            assert_eq!(status, CHASM_OK);
+            assert_eq!(result.status, CHASM_OK);
+            let bytes = unsafe { slice::from_raw_parts(result.data, result.len) };
+            assert_eq!(bytes, [0x11, 0x01, 0x34, 0x12, 0x20, 0x00, 0x00]);
+            assert!(result.diagnostics.is_null());
+        },
+    );
+}
+
+#[test]
+fn assembles_with_an_instruction_table() {
+    let target = CString::new("generic").unwrap();
+    let isa = CString::new("load 0xa0 reg u8\n").unwrap();
+    let options = ChasmOptions {
+        target: target.as_ptr(),
+        isa: isa.as_ptr(),
+    };
+    assemble("load r2 7\n", Some(&options), |status, result| {
+        assert_eq!(status, CHASM_OK);
+        let bytes = unsafe { slice::from_raw_parts(result.data, result.len) };
+        assert_eq!(bytes, [0xa0, 0x02, 0x07]);
+    });
+}
+
+#[test]
+fn reports_every_diagnostic() {
+    assemble("    jpm 0\n    .byte 300\n", None, |status, result| {
+        assert_eq!(status, CHASM_DIAGNOSTICS);
+        assert!(result.data.is_null());
+        let diags = unsafe { slice::from_raw_parts(result.diagnostics, result.diagnostic_count) };
+        let diags: Vec<_> = diags
+            .iter()
+            .map(|d| (d.severity, text(d.code), text(d.message), d.line, d.column))
+            .collect();
+        assert_eq!(
+            diags,
+            [
+                (
+                    CHASM_ERROR,
+                    Some("E0304".to_string()),
+                    Some("unknown instruction `jpm`".to_string()),
+                    1,
+                    5
+                ),
+                (
+                    CHASM_WARNING,
+                    Some("W0204".to_string()),
+                    Some("`.byte` value 300 doesn't fit in 8 bits".to_string()),
+                    2,
+                    11
+                ),
+            ]
+        );
+    });
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    let src = CString::new("nop\n").unwrap();
+    let mut out = ptr::null_mut();
+    assert_eq!(
+        unsafe { chasm_assemble(ptr::null(), ptr::null(), &mut out) },
+        CHASM_INVALID_ARGUMENT
+    );
+    let target = CString::new("vax").unwrap();
+    let options = ChasmOptions {
+        target: target.as_ptr(),
+        isa: ptr::null(),
+    };
+    assert_eq!(
+        unsafe { chasm_assemble(src.as_ptr(), &options, &mut out) },
+        CHASM_INVALID_ARGUMENT
+    );
+    assert!(out.is_null());
+    unsafe { chasm_result_free(ptr::null_mut()) };
+}
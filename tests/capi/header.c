@@ -0,0 +1,63 @@
+/* Built and run by tests/capi_header.rs against include/chasm.h and the
+ * cdylib: prints the layout the header gives each struct, the constants
+ * it defines and what a round trip through the library returns, for the
+ * test to compare with the Rust side. */
+
+#include <stddef.h>
+#include <stdio.h>
+
+#include "chasm.h"
+
+#define FIELD(type, field) printf(" %s@%zu", #field, offsetof(type, field))
+
+static void assemble(const char *src, const ChasmOptions *options) {
+    ChasmResult *result = NULL;
+    int32_t status = chasm_assemble(src, options, &result);
+    printf("assemble %d %d %zu", status, result->status, result->len);
+    for (size_t i = 0; i < result->len; i++) {
+        printf(" %02x", result->data[i]);
+    }
+    for (size_t i = 0; i < result->diagnostic_count; i++) {
+        const ChasmDiagnostic *d = &result->diagnostics[i];
+        printf(" [%d %s %zu:%zu %s]", d->severity, d->code ? d->code : "-", d->line,
+               d->column, d->message);
+    }
+    printf("\n");
+    chasm_result_free(result);
+}
+
+int main(void) {
+    printf("ChasmResult %zu", sizeof(ChasmResult));
+    FIELD(ChasmResult, status);
+    FIELD(ChasmResult, data);
+    FIELD(ChasmResult, len);
+    FIELD(ChasmResult, diagnostics);
+    FIELD(ChasmResult, diagnostic_count);
+    printf("\nChasmDiagnostic %zu", sizeof(ChasmDiagnostic));
+    FIELD(ChasmDiagnostic, severity);
+    FIELD(ChasmDiagnostic, code);
+    FIELD(ChasmDiagnostic, message);
+    FIELD(ChasmDiagnostic, line);
+    FIELD(ChasmDiagnostic, column);
+    printf("\nChasmOptions %zu", sizeof(ChasmOptions));
+    FIELD(ChasmOptions, target);
+    FIELD(ChasmOptions, isa);
+    printf("\n");
+
+    printf("CHASM_OK %d\n", CHASM_OK);
+    printf("CHASM_DIAGNOSTICS %d\n", CHASM_DIAGNOSTICS);
+    printf("CHASM_INVALID_ARGUMENT %d\n", CHASM_INVALID_ARGUMENT);
+    printf("CHASM_INTERNAL_ERROR %d\n", CHASM_INTERNAL_ERROR);
+    printf("CHASM_ERROR %d\n", CHASM_ERROR);
+    printf("CHASM_WARNING %d\n", CHASM_WARNING);
+
+    ChasmOptions options = {"generic", "load 0xa0 reg u8\n"};
+    assemble("load r2 7\n", &options);
+    assemble("    jpm 0\n", NULL);
+
+    ChasmResult *result = NULL;
+    int32_t status = chasm_parse("nop\n", &result);
+    printf("parse %d %.*s\n", status, (int)result->len, (const char *)result->data);
+    chasm_result_free(result);
+    return 0;
+}
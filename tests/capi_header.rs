@@ -0,0 +1,107 @@
+//! Checks include/chasm.h against the library: a C program built with the
+//! header and linked to the cdylib prints each struct's layout and the
+//! constants as C sees them, and calls through every entry point.
+
+#![cfg(feature = "capi")]
+
+use chasm::capi::*;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fmt::Write;
+use std::io::ErrorKind;
+use std::mem::{offset_of, size_of};
+use std::path::Path;
+use std::process::Command;
+
+macro_rules! layout {
+    ($out:expr, $ty:ident { $($field:ident),* }) => {{
+        write!($out, "{} {}", stringify!($ty), size_of::<$ty>()).unwrap();
+        $(write!($out, " {}@{}", stringify!($field), offset_of!($ty, $field)).unwrap();)*
+        $out.push('\n');
+    }};
+}
+
+macro_rules! constants {
+    ($out:expr, $($name:ident),*) => {
+        $(writeln!($out, "{} {}", stringify!($name), $name).unwrap();)*
+    };
+}
+
+#[test]
+fn the_header_matches_the_library() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Tests run from the directory cargo builds the cdylib into.
+    let deps = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let exe = deps.join(format!("chasm-header-{}", std::process::id()));
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let built = Command::new(&cc)
+        .args(["-std=c11", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/capi/header.c"))
+        // By path: the library has no soname, so that's where it's loaded
+        // from, not whichever build of it is first on the library path.
+        .arg(deps.join(format!("{}chasm{}", DLL_PREFIX, DLL_SUFFIX)))
+        .arg("-o")
+        .arg(&exe)
+        .output();
+    let built = match built {
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            eprintln!("skipped: no C compiler `{}`", cc);
+            return;
+        }
+        built => built.unwrap(),
+    };
+    assert!(
+        built.status.success(),
+        "{}",
+        String::from_utf8_lossy(&built.stderr)
+    );
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+
+    let mut expected = String::new();
+    layout!(
+        expected,
+        ChasmResult {
+            status,
+            data,
+            len,
+            diagnostics,
+            diagnostic_count
+        }
+    );
+    layout!(
+        expected,
+        ChasmDiagnostic {
+            severity,
+            code,
+            message,
+            line,
+            column
+        }
+    );
+    layout!(expected, ChasmOptions { target, isa });
+    constants!(
+        expected,
+        CHASM_OK,
+        CHASM_DIAGNOSTICS,
+        CHASM_INVALID_ARGUMENT,
+        CHASM_INTERNAL_ERROR,
+        CHASM_ERROR,
+        CHASM_WARNING
+    );
+    expected.push_str("assemble 0 0 3 a0 02 07\n");
+    expected.push_str("assemble 1 1 0 [0 E0304 1:5 unknown instruction `jpm`]\n");
+    expected.push_str("parse 0 ");
+    expected.push_str(&chasm::json::parse_to_json("nop\n"));
+    expected.push('\n');
+    assert_eq!(String::from_utf8_lossy(&run.stdout), expected);
+}
@@ -0,0 +1,242 @@
+//! The `chasm` binary, run on files written to a scratch directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A fresh directory for `test`, holding `files`.
+fn scratch(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chasm-cli-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (name, text) in files {
+        fs::write(dir.join(name), text).unwrap();
+    }
+    dir
+}
+
+/// Runs `chasm` in `dir` with `args`.
+fn chasm(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chasm"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn debug_info_maps_each_address_range_to_its_line() {
+    let dir = scratch(
+        "debug-info",
+        &[(
+            "main.asm",
+            "\
+macro_rules! twice() {
+    nop
+    nop
+}
+start:
+    ldi r1 0x1234
+    twice!()
+    repeat!(2) { halt }
+    jmp start
+",
+        )],
+    );
+    let output = chasm(
+        &dir,
+        &[
+            "main.asm",
+            "--emit",
+            "bin",
+            "-o",
+            "main.bin",
+            "--debug-info",
+            "main.dbg",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        fs::read_to_string(dir.join("main.dbg")).unwrap(),
+        "\
+text 0x0000..0x0004 main.asm:6:5
+text 0x0004..0x0005 main.asm:7:5 macro main.asm:2:5
+text 0x0005..0x0006 main.asm:7:5 macro main.asm:3:5
+text 0x0006..0x0007 main.asm:8:18 iteration 1
+text 0x0007..0x0008 main.asm:8:18 iteration 2
+text 0x0008..0x000b main.asm:9:5
+"
+    );
+    assert_eq!(fs::read(dir.join("main.bin")).unwrap().len(), 0xb);
+}
+
+#[test]
+fn debug_info_needs_bytes_to_describe() {
+    let dir = scratch("debug-info-emit", &[("main.asm", "nop\n")]);
+    let output = chasm(
+        &dir,
+        &["main.asm", "--emit", "source", "--debug-info", "main.dbg"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: --debug-info needs --emit bin or obj\n"
+    );
+}
+
+/// Skips `@org`'s gap in `text`, and the one between `text` and `data`.
+const ORG_GAPS: &str = "\
+    ldi r1 0x1234
+@org 0x8
+    halt
+.data
+@org 0x20
+    .byte 1, 2, 3
+";
+
+#[test]
+fn readmemh_jumps_over_org_gaps() {
+    let dir = scratch("readmemh", &[("main.asm", ORG_GAPS)]);
+    let output = chasm(&dir, &["main.asm", "--emit", "bin", "--format", "readmemh"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@0\n11\n01\n34\n12\n@8\n01\n@20\n01\n02\n03\n"
+    );
+
+    let output = chasm(
+        &dir,
+        &[
+            "main.asm",
+            "--emit",
+            "bin",
+            "--format",
+            "readmemh",
+            "--word-width",
+            "16",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "@0\n0111\n1234\n@4\n0001\n@10\n0201\n0003\n"
+    );
+}
+
+#[test]
+fn readmemh_pads_gaps_when_asked() {
+    let dir = scratch("readmemh-pad", &[("main.asm", ORG_GAPS)]);
+    let output = chasm(
+        &dir,
+        &[
+            "main.asm",
+            "--emit",
+            "bin",
+            "--format",
+            "readmemh",
+            "--pad-gaps",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let words: Vec<&str> = stdout.lines().collect();
+    assert_eq!(words.len(), 0x23);
+    assert_eq!(words[..5], ["11", "01", "34", "12", "00"]);
+    assert_eq!(words[8], "01");
+    assert_eq!(words[0x20..], ["01", "02", "03"]);
+}
+
+#[test]
+fn format_needs_an_image() {
+    let dir = scratch("format-emit", &[("main.asm", "nop\n")]);
+    let output = chasm(
+        &dir,
+        &["main.asm", "--emit", "source", "--format", "readmemh"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(stderr(&output), "error: --format needs --emit bin\n");
+}
+
+#[test]
+fn link_takes_emit_for_format() {
+    let dir = scratch("link-emit", &[("main.asm", "::start:\n    jmp start\n")]);
+    let output = chasm(&dir, &["main.asm", "--emit", "obj", "-o", "main.obj"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    for flag in ["--emit", "--format"] {
+        let output = chasm(
+            &dir,
+            &["link", "main.obj", "--base", "0x10", flag, "readmemh"],
+        );
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "@10\n20\n10\n00\n"
+        );
+    }
+}
+
+#[test]
+fn expanded_source_maps_into_included_files() {
+    let dir = scratch(
+        "source-map-include",
+        &[
+            ("main.asm", "include \"lib.asm\"\nstart:\n    call twice\n"),
+            ("lib.asm", "twice:\n    repeat!(2) { nop }\n    ret\n"),
+        ],
+    );
+    let output = chasm(
+        &dir,
+        &["main.asm", "--emit", "expanded", "--source-map", "main.map"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "twice:\nnop\nnop\nret\nstart:\ncall twice\n"
+    );
+    let map = fs::read_to_string(dir.join("main.map")).unwrap();
+    let lib = fs::canonicalize(dir.join("lib.asm")).unwrap();
+    let frame = |file: &Path, line, column| {
+        format!(
+            r#"{{"file":"{}","line":{},"column":{},"via":null}}"#,
+            file.display(),
+            line,
+            column
+        )
+    };
+    assert!(map.contains(&frame(&lib, 1, 1)), "{}", map);
+    assert!(map.contains(&frame(&lib, 2, 18)), "{}", map);
+    assert!(map.contains(&frame(&lib, 3, 5)), "{}", map);
+}
+
+#[test]
+fn expanded_needs_every_include() {
+    let dir = scratch(
+        "expanded-missing-include",
+        &[("main.asm", "include \"missing.asm\"\nnop\n")],
+    );
+    let output = chasm(&dir, &["main.asm", "--emit", "expanded"]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).starts_with("error: include \"missing.asm\" not found"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn timings_print_a_line_per_phase() {
+    let dir = scratch("timings", &[("main.asm", "nop\nhalt\n")]);
+    let output = chasm(&dir, &["main.asm", "--timings", "-o", "main.bin"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stderr = stderr(&output);
+    let phases: Vec<_> = stderr
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+    assert_eq!(phases, ["parse", "expand", "assemble"], "{}", stderr);
+    assert!(stderr.contains("1 parsed, 0 cached"), "{}", stderr);
+}
@@ -0,0 +1,59 @@
+//! Structural diffs between statement trees, and `assert_ast_eq!`, which
+//! fails with them.
+
+use chasm::assert_ast_eq;
+use chasm::diff::{DiffOptions, diff, diff_with};
+use chasm::parser::{LexOptions, Parser, Statement};
+use chasm::source::SourceManager;
+
+fn parse(source: &str) -> Vec<Statement> {
+    let mut sources = SourceManager::new();
+    let file = sources.add("main.asm", source);
+    let mut parser = Parser::streaming(&sources, file, LexOptions::default());
+    let ast = parser.parse();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    ast
+}
+
+const LOOP: &str = "for!(var i = 0; i < 4; i++) {\n    nop\n    push r0\n}\n";
+
+#[test]
+fn identical_trees_have_no_differences() {
+    assert_eq!(diff(&parse(LOOP), &parse(LOOP)), []);
+}
+
+#[test]
+fn spans_are_ignored_unless_asked_for() {
+    let moved = format!("\n\n{}", LOOP);
+    assert_eq!(diff(&parse(LOOP), &parse(&moved)), []);
+    let spans = DiffOptions { spans: true };
+    assert!(!diff_with(&parse(LOOP), &parse(&moved), &spans).is_empty());
+}
+
+#[test]
+fn a_difference_is_reported_at_its_path() {
+    let changed = LOOP.replace("push r0", "push r1");
+    let diffs = diff(&parse(LOOP), &parse(&changed));
+    assert_eq!(diffs.len(), 1, "{:?}", diffs);
+    assert_eq!(diffs[0].path, "stmt[0].ForLoop.body[1].Instruction.args[0]");
+    assert!(diffs[0].left.contains("r0"), "{}", diffs[0]);
+    assert!(diffs[0].right.contains("r1"), "{}", diffs[0]);
+}
+
+#[test]
+fn a_missing_statement_is_named_by_its_variant() {
+    let diffs = diff(&parse("nop\nhalt\n"), &parse("nop\n"));
+    assert_eq!(diffs.len(), 1, "{:?}", diffs);
+    assert_eq!(diffs[0].to_string(), "stmt[1]: Instruction != <missing>");
+}
+
+#[test]
+fn assert_ast_eq_passes_for_identical_trees() {
+    assert_ast_eq!(parse(LOOP), parse(LOOP));
+}
+
+#[test]
+#[should_panic(expected = "ASTs differ:\n  stmt[0].ForLoop.body[1].Instruction.args[0]: ")]
+fn assert_ast_eq_panics_with_the_diff() {
+    assert_ast_eq!(parse(LOOP), parse(&LOOP.replace("push r0", "push r1")));
+}
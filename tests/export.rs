@@ -0,0 +1,99 @@
+//! The Rust module `--emit rust-consts` writes, compiled with `rustc`.
+
+use chasm::assemble::{Assembled, assemble};
+use chasm::codes::Code;
+use chasm::expand::{ExpandOptions, expand_traced};
+use chasm::export::{RustOptions, rust_consts};
+use chasm::isa::Target;
+use chasm::parser::{LexOptions, Parser};
+use chasm::source::SourceManager;
+use std::fs;
+use std::process::Command;
+
+fn assembled(sources: &mut SourceManager, source: &str) -> Assembled {
+    let file = sources.add("input.asm", source);
+    let mut parser = Parser::streaming(sources, file, LexOptions::default());
+    let ast = parser.parse();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    let target = Target::GENERIC;
+    let opts = ExpandOptions {
+        registers: target.registers,
+        ..ExpandOptions::default()
+    };
+    let (stmts, errors, origins) = expand_traced(&ast, &opts);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (assembled, errors) = assemble(&stmts, &origins, &target.isa(), &target.registers);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assembled
+}
+
+#[test]
+fn the_module_compiles() {
+    let mut sources = SourceManager::new();
+    let assembled = assembled(
+        &mut sources,
+        "\
+@allow(unused_const)
+const stackTop = 0xff00
+const RATIO = 0.5
+@define NAME \"chasm\"
+@size CODE_LEN, init, done
+module io {
+    const PORT = 0x40
+}
+::init:
+    jmp done
+local:
+    nop
+@org 0x8000
+::done:
+    halt
+",
+    );
+    let opts = RustOptions::for_isa(&Target::GENERIC.isa());
+    assert_eq!(opts.int_type, "u16");
+    let module = rust_consts(&assembled.exports(), &sources, &opts).unwrap();
+    assert!(
+        module.contains("pub const DONE: u16 = 0x8000;"),
+        "{}",
+        module
+    );
+    assert!(
+        module.contains("pub const CODE_LEN: u16 = 32768;"),
+        "{}",
+        module
+    );
+    assert!(!module.contains("LOCAL"), "{}", module);
+
+    let dir = std::env::temp_dir().join(format!("chasm-export-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("symbols.rs"), &module).unwrap();
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc)
+        .current_dir(&dir)
+        .args(["--edition", "2021", "--crate-type", "lib", "symbols.rs"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{}",
+        module,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn names_the_same_in_upper_snake_case_collide() {
+    let mut sources = SourceManager::new();
+    let assembled = assembled(
+        &mut sources,
+        "::loopStart:\n    nop\n::loop_start:\n    halt\n",
+    );
+    let diag = rust_consts(&assembled.exports(), &sources, &RustOptions::default()).unwrap_err();
+    assert_eq!(diag.code, Some(Code::E0107));
+    assert_eq!(
+        diag.message,
+        "`loopStart` and `loop_start` both become `LOOP_START` in the Rust module"
+    );
+}
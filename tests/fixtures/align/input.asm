@@ -0,0 +1,28 @@
+const PAGE = 16
+
+start:
+    nop
+@align 4
+aligned:
+    .byte 1, 2, 3
+@align 4, 0xff
+    .word 0x1234
+@align 4
+@align PAGE, 0x90
+table:
+    .byte 1
+
+.data
+    .byte 0
+@align 2, 0
+@align 8
+words:
+    .word 1
+
+.text
+@align 0
+@align 4, 0x100
+@align 4, -1
+@align 4, "nop"
+@align "four"
+@align MISSING
@@ -0,0 +1,5 @@
+/* header
+   /* nested */
+   still a comment */
+const SIZE = 4
+start: /* inline */ mov r0 SIZE;
@@ -0,0 +1,27 @@
+var n = 0
+while!(1) {
+    var n += 1
+    if!(n % 2) {
+        continue!
+    }
+    if!(n > 6) {
+        break!
+    }
+    push r0
+}
+
+for!(var i = 0; i < 8; i++) {
+    if!(i == 3) {
+        break!
+    }
+    ld r0, [table + i]
+}
+
+repeat!(3) {
+    continue!
+    halt
+}
+
+break!
+
+table:
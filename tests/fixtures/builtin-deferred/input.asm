@@ -0,0 +1,10 @@
+// Builtins over labels wait for the labels' addresses, like any other
+// label arithmetic.
+BUF equ max(rx_end, tx_end)
+    .byte BUF, min(rx_end, tx_end), clamp(tx_end, 0, 4), abs(rx - tx_end)
+rx:
+    .byte 1, 2, 3
+rx_end:
+tx:
+    .byte 4, 5
+tx_end:
@@ -0,0 +1,14 @@
+// A builtin given arguments it can't work with is an error, and the constant
+// it would have defined is left without a value.
+const NAME = "chasm"
+const LAST = char_at(NAME, 4)
+const PAST = char_at(NAME, 5)
+const BEFORE = char_at(NAME, -1)
+const INSIDE = clamp(12, 0, 10)
+const BACKWARDS = clamp(5, 10, 0)
+const MIN = -9223372036854775807 - 1
+const FLIPPED = abs(MIN)
+const LOWEST = min(3, 1, 2)
+const ALONE = min(1)
+const NONE = max()
+    .byte LAST, INSIDE, LOWEST
@@ -0,0 +1 @@
+== input.asm
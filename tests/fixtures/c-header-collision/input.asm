@@ -0,0 +1,6 @@
+// `io::LIMIT` and `io__LIMIT` are both `io__LIMIT` in C.
+@allow(unused_const)
+module io {
+    const LIMIT = 1
+}
+const io__LIMIT = 2
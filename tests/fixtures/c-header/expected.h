@@ -0,0 +1,15 @@
+== input.asm
+/* Generated by chasm. Do not edit. */
+#ifndef CHASM_SYMBOLS_H
+#define CHASM_SYMBOLS_H
+
+/* NAME: string constant skipped */
+#define NEG (-4)
+/* RATIO: float constant skipped */
+#define STACK_TOP 65280
+#define TOP 65278
+#define entry 0x100
+#define io__PORT 64
+#define vectors 0x102
+
+#endif /* CHASM_SYMBOLS_H */
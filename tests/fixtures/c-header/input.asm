@@ -0,0 +1,21 @@
+// Global labels at their addresses and every constant. File-scoped and
+// local labels aren't exported; strings and floats are skipped.
+@allow(unused_const)
+const STACK_TOP = 0xff00
+const NEG = -4
+const RATIO = 1.5
+TOP equ STACK_TOP - 2
+@define NAME "chasm"
+module io {
+    const PORT = 0x40
+}
+
+    jmp entry
+helper:
+    ret
+@org 0x100
+::entry:
+.loop:
+    br .loop
+::vectors:
+    .word entry, helper
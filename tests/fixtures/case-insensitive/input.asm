@@ -0,0 +1,12 @@
+CONST SIZE = 4
+Var n = 0
+@Define WIDTH 8
+MACRO_RULES! SWAP(x, y) {
+    XOR x y;
+}
+FOR!(VAR i = 0; i < 2; i++) {
+    Swap R0 R1;
+    VAR n += 1
+}
+Start:
+MOV R2 SIZE
@@ -0,0 +1,6 @@
+// a file header
+const SIZE = 4 // trailing
+start:
+    mov r0 SIZE; // after a separator
+    add r0 r1 // no separator
+// nop
@@ -0,0 +1,11 @@
+var offset = 0
+for!(var i = 0; i < 4; i++) {
+    var offset += 4
+}
+var mask <<= 2
+var mask >>= 1
+var mask &= 0xFF
+var mask |= 0b1
+var x -= 1
+var x *= 3
+var x /= 2.5
@@ -0,0 +1,25 @@
+const SQUARES = [0, 1, 4, 9, 16]
+const TABLE = [1, 2, 3, lo(handler), 0x10]
+const NAMES = ["idle", "run"]
+
+// Every item, labels and all.
+table:
+    .byte TABLE
+
+for!(var i = 0; i < len(SQUARES); i++) {
+    .byte SQUARES[i] * 2
+}
+for!(var i = 0; i < len(TABLE); i += 2) {
+    .word TABLE[i]
+}
+
+start:
+    mov r0, SQUARES[3]
+    mov r1, hi(0x1234) + lo(0x1234)
+    mov r2, ([5, 6, 7])[1]
+
+handler:
+    ret
+
+const BAD = SQUARES[5]
+const WRONG = NAMES["run"]
@@ -0,0 +1,19 @@
+const SIZE = 16
+const TOTAL = SIZE * 2 + 1
+const MASK = ~(1 << 4) & 0xFF
+const NEG = -SIZE
+const LEN = strlen("hi")
+const HALF = SIZE / 2.5
+const LATE = done - 4
+const BIG = 1 << 64
+const ODD = NOPE % 2
+var x = (LEN + 1) * -2
+var x -= x >> 1
+done:
+push TOTAL
+push MASK
+push NEG
+push HALF
+push LATE
+push BIG
+push ODD
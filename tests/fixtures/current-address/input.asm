@@ -0,0 +1,6 @@
+start:
+    db $ - start
+    dw $$
+    jmp $ + 4
+@org($)
+@align $$, 4
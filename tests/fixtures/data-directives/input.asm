@@ -0,0 +1,15 @@
+const SIZE = 4
+
+start:
+.word start, end, 0x1234, label+2
+.byte 'A', -1, SIZE * 2,
+.quad
+.dword 1 << 31
+.half 0x1234
+
+for!(var i = 0; i < 3; i++) {
+    .byte i * SIZE
+}
+
+end:
+label:
@@ -0,0 +1,7 @@
+// `data_range` is a lint: denied it's an error, allowed it says nothing.
+@deny(data_range)
+    .byte 256
+@allow(data_range)
+    .word 0x12345
+@warn(data_range)
+    .byte -129
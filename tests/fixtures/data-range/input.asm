@@ -0,0 +1,10 @@
+// Each width takes anything that fits it as unsigned or as two's
+// complement signed: the values at each end are stored as they are, those
+// one past are truncated with a warning.
+    .byte 255, 256, -128, -129
+    .word 65535, 65536, -32768, -32769
+    .dword 0xffffffff, 0x100000000, -2147483648, -2147483649
+// Every 64-bit value fits a `.quad`.
+    .quad 0x7fffffffffffffff, -0x7fffffffffffffff - 1
+    .byte 300, -200
+    .word 0x12345
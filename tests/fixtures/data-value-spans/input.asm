@@ -0,0 +1,8 @@
+// A value too wide for its directive is reported where it's written.
+const TABLE = [1, 300, 2]
+    .byte 1,   0x80 * 4,  7
+    .byte TABLE
+macro_rules! bytes(a, b) {
+    .byte a,    b
+}
+    bytes!(1, 256)
@@ -0,0 +1,27 @@
+// Each address range maps back to the line it came from: a macro call to
+// where it's called, with the line in the macro body alongside, and an
+// unrolled loop to its body, with the iteration.
+macro_rules! push(reg) {
+    sub sp, 2
+    st [sp], reg
+}
+
+macro_rules! save_all() {
+    push!(r0)
+    push!(r1)
+}
+
+start:
+    nop
+    save_all!()
+    for!(var i = 0; i < 2; i += 1) {
+        for!(var j = 0; j < 2; j += 1) {
+            ld r2, [table + i * 2 + j]
+        }
+        push!(r2)
+    }
+    halt
+
+.data
+table:
+    .byte 1, 2, 3, 4
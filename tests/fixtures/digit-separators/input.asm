@@ -0,0 +1,5 @@
+const MASK = 0b1111_0000
+const BIG = 1_000_000
+start:
+    and r0 0xFFFF_0000 0o7_7;
+    or r1 MASK BIG;
@@ -0,0 +1,32 @@
+@define SIZE 32
+@define DEBUG
+@allow(unused_const, W0203)
+@message "hello"
+@table 1, 2.5, $
+@section_flags text, "rx"
+@note anything at all, 1, "two"
+
+@defin LIMIT 8
+@alig 4
+@mesage "typo"
+@bogus
+
+@define
+@define A, 1, 2
+@size LEN, start
+@table
+@message "one", "two"
+
+@define 32 SIZE2
+@size LEN, 0, 16
+@message greeting
+@table 1, two
+
+macro_rules! table_of(first, rest) {
+    @table first, rest
+    @size first, rest, 4
+}
+
+start:
+    nop
+end:
@@ -0,0 +1,13 @@
+// A `beq` reaches 128 bytes back and 127 forward from the end of the
+// `beq`; each one further than that is just out of reach.
+back:
+    repeat!(126) { nop }
+    beq back
+    beq back
+    beq ahead
+    repeat!(127) { nop }
+ahead:
+    beq past
+    repeat!(128) { nop }
+past:
+    halt
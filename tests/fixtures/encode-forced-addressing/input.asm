@@ -0,0 +1,15 @@
+// `rel(...)` or `abs(...)` around a whole operand overrides how its field
+// takes a label.
+start:
+    nop
+back:
+    br rel(back)        // a short branch back, like `br back`: 22 fe
+    br abs(back)        // back's address: 22 01
+    jmp rel(ahead)      // the distance forward: 20 04 00
+    ldi r1 abs(ahead)   // 11 01 0c 00
+ahead:
+    br abs(far)         // far is past what a signed byte holds
+    jmp rel(start)      // a distance back doesn't fit an unsigned field
+    repeat!(130) { nop }
+far:
+    halt
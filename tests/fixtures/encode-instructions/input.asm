@@ -0,0 +1,16 @@
+// Each instruction is its opcode, then its operands, little-endian.
+start:
+    nop
+    mov r1 r2
+    ldi r3 0x1234
+    addi r4 -2
+    st 0x8000 r3
+    push r15
+    call sub
+    jmp start
+sub:
+    ret
+.data
+table:
+    .word start, sub
+    .byte 'A', 2
@@ -0,0 +1,9 @@
+// Operands of the wrong kind or number, and a mnemonic the target
+// doesn't have. Each takes no bytes but its opcode, if it has one.
+    mov r1 5
+    ldi 5 r1
+    push
+    pop r1 r2
+    jpm 0
+    ldi r1 1.5
+    halt
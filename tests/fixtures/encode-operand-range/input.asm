@@ -0,0 +1,9 @@
+// Immediates at each end of their fields, then just past them.
+    addi r1 127
+    addi r1 -128
+    addi r1 128
+    subi r1 -129
+    ldi r2 0xffff
+    ldi r2 0x10000
+    ldi r2 -1
+    ld r3 0
@@ -0,0 +1,9 @@
+// Each operand's errors point at the operand, not at its mnemonic.
+    addi r1,   100 + 28
+    mov  r1, 0x10
+    ldi  5,  r2
+    ldi  r2, 2.5
+macro_rules! load(reg, value) {
+    ldi reg,    value
+}
+    load!(r3, 0x10000)
@@ -0,0 +1,5 @@
+// Strings are stored in their encoding; `.ascii` has no byte for `é`.
+    .asciiz "hi"
+    .utf16 "é"
+    .ascii "café"
+    .byte 0xff
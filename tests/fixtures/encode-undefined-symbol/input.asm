@@ -0,0 +1,12 @@
+// A name nothing defines is suggested the close ones that are.
+const COUNT = 4
+start:
+    jmp strat
+    .word missing, COUNT + CUONT
+main:
+.loop:
+    jmp .lop
+    jmp 3f
+1:
+    br 1b
+    halt
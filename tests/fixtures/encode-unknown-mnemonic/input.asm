@@ -0,0 +1,5 @@
+// An instruction the target doesn't have is suggested close ones it does.
+start:
+    jpm start
+    hlat
+    frobnicate
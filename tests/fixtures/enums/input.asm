@@ -0,0 +1,25 @@
+enum State { Idle, Run = 4, Stop }
+enum Flag {
+    Ready = 1 << 0
+    Busy = 1 << 1,
+    Error = Flag.Busy << 1
+    Next
+}
+
+module uart {
+    enum Mode { Off, Tx, Rx }
+    const DEFAULT = Mode.Rx
+}
+
+if!((State.Stop == 5) & (uart::DEFAULT == uart::Mode.Rx)) {
+    .byte State.Idle, State.Run, State.Stop
+}
+for!(var m = uart::Mode.Off; m <= uart::Mode.Rx; m++) {
+    .byte m
+}
+
+start:
+    mov r0, Flag.Ready | Flag.Error
+    cmp r0, State.Run
+
+enum Broken { A = MISSING, B }
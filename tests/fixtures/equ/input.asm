@@ -0,0 +1,15 @@
+SIZE equ END - START
+START equ 0x100
+END equ START + LEN
+const LEN = 4
+
+@org START
+entry:
+    nop
+STEP Equ 2
+NEXT EQU entry + STEP
+@org NEXT
+    .byte SIZE, NEXT
+
+A equ B + 1
+B equ A
@@ -0,0 +1,4 @@
+@message "tab\there \x41\u{263A}\0"
+mov r0 '\x41' '\u{e9}' '\0'
+@message "bad \q escape"
+mov r1 '\x80' '\u{110000}'
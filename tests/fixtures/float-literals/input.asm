@@ -0,0 +1,5 @@
+const HALF = 0.5
+var scale = 1e-3
+@table 1.5 2.0 0.25
+start:
+    mov r0 HALF;
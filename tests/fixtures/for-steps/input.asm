@@ -0,0 +1,17 @@
+const N = 4
+
+for!(var i = N * 2; i > N; i--) {
+    push r0
+}
+
+for!(var j = 0; j <= N; j += 2) {
+    ld r0, [table + j]
+}
+
+for!(var k = 1; k < 100; k <<= 3) {
+    ld r1, [table + k]
+}
+
+for!(var m = 0; m < 1.5; m++) {}
+
+table:
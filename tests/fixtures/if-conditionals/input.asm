@@ -0,0 +1,27 @@
+const DEBUG = 1
+const TARGET = 2
+
+if!(DEBUG) {
+    brk
+}
+
+if!(TARGET == 1) {
+    mov r0, r1
+} elif!(TARGET == 2) {
+    mov r0, r2
+} else {
+    mov r0, r3
+}
+
+if!(!DEBUG) {
+    halt
+} else! {
+    nop
+}
+
+if!(0.5) {
+    halt
+}
+elif!(TARGET) {
+    halt
+}
@@ -0,0 +1,6 @@
+label:
+    lda #123
+    lda #label + 1
+    ldx #0x10
+    sta label
+    cmp #$
@@ -0,0 +1,4 @@
+include "d.asm"
+from_c:
+    call shared
+    ret
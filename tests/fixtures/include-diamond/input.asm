@@ -0,0 +1,6 @@
+include "b.asm"
+include "c.asm"
+main:
+    call from_b
+    call from_c
+    halt
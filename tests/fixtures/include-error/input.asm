@@ -0,0 +1,6 @@
+// the error in lib/util.asm must point into that file
+start:
+    call helper
+include "lib/util.asm"
+    halt
+    mov r0 (
@@ -0,0 +1,6 @@
+// helpers
+
+helper:
+    nop
+    var bad = *oops
+    ret
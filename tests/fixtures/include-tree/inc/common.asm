@@ -0,0 +1,2 @@
+const WORD = 8
+@define VERSION 2
@@ -0,0 +1,6 @@
+include "lib/util.asm"
+include "common.asm"
+
+main:
+    call util;
+    halt
@@ -0,0 +1,4 @@
+include "../inc/common.asm"
+
+util:
+    ret
@@ -0,0 +1,5 @@
+mov r0 r1
+add r2 r3
+nop
+ret
+push r0; pop r1
@@ -0,0 +1,10 @@
+::start:
+    nop
+helper:
+.loop:
+    jmp .loop
+.data
+    .byte 0
+::table:
+    .byte 1, 2
+1:
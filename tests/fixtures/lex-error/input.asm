@@ -0,0 +1,4 @@
+const A = 1
+start:
+    mov r0 A ?? r1;
+    nop ¤
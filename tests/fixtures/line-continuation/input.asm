@@ -0,0 +1,8 @@
+mov r0 \
+    r1
+@define SIZE \
+    4
+add r2 r3 // not continued \
+halt
+mov r4 \  
+    r5
@@ -0,0 +1,5 @@
+@message "first
+second"
+start:
+    mov r0 r1 )
+const UNUSED = 2
@@ -0,0 +1,7 @@
+const USED = 1
+@allow(unused_const)
+const SCRATCH = 4
+const LEFTOVER = 2
+
+start:
+    mov r0 USED;
@@ -0,0 +1,5 @@
+@deny(W0201)
+
+const LIMIT = 8
+@allow(w0201)
+const SCRATCH = 4
@@ -0,0 +1,10 @@
+@deny(unused_const)
+@allow(skipped_input)
+
+const LIMIT = 8
+
+start:
+    mov r0 r1;
+    ) )
+@warn(unused_const)
+const OVERRIDDEN = 1
@@ -0,0 +1,5 @@
+@allow(unused_cosnt, no_such_lint)
+const A = 1
+
+start:
+    mov r0 A;
@@ -0,0 +1,12 @@
+.start:
+    nop
+main:
+.loop:
+    dec r0
+    jnz .loop
+    jmp .done
+.done:
+    ret
+helper:
+.loop:
+    jmp .loop
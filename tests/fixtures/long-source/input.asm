@@ -0,0 +1,100 @@
+add r0 0
+add r1 1
+add r2 2
+add r3 3
+add r4 4
+add r5 5
+add r6 6
+add r7 7
+add r0 8
+add r1 9
+add r2 10
+add r3 11
+add r4 12
+add r5 13
+add r6 14
+add r7 15
+add r0 16
+add r1 17
+add r2 18
+add r3 19
+add r4 20
+add r5 21
+add r6 22
+add r7 23
+add r0 24
+add r1 25
+add r2 26
+add r3 27
+add r4 28
+add r5 29
+add r6 30
+add r7 31
+add r0 32
+add r1 33
+add r2 34
+add r3 35
+add r4 36
+add r5 37
+add r6 38
+add r7 39
+add r0 40
+add r1 41
+add r2 42
+add r3 43
+add r4 44
+add r5 45
+add r6 46
+add r7 47
+add r0 48
+add r1 49
+add r2 50
+add r3 51
+add r4 52
+add r5 53
+add r6 54
+add r7 55
+add r0 56
+add r1 57
+add r2 58
+add r3 59
+add r4 60
+add r5 61
+add r6 62
+add r7 63
+add r0 64
+add r1 65
+add r2 66
+add r3 67
+add r4 68
+add r5 69
+add r6 70
+add r7 71
+add r0 72
+add r1 73
+add r2 74
+add r3 75
+add r4 76
+add r5 77
+add r6 78
+add r7 79
+add r0 80
+add r1 81
+add r2 82
+add r3 83
+add r4 84
+add r5 85
+add r6 86
+add r7 87
+add r0 88
+add r1 89
+add r2 90
+add r3 91
+add r4 92
+add r5 93
+add r6 94
+add r7 95
+add r0 96
+add r1 97
+add r2 98
+add r3 99
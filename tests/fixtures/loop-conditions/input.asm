@@ -0,0 +1,5 @@
+for!(var i = 0; i <= 3; i++) { nop }
+for!(var j = 8; j >= 8; j++) {}
+for!(var k = 1; k != 5; k++) {}
+for!(var m = 2; m == 2; m++) {}
+for!(var n = 9; n > 1; n++) {}
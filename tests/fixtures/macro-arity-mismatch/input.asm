@@ -0,0 +1,16 @@
+macro_rules! mov(dst, src) {
+    add dst src;
+}
+
+macro_rules! mov(dst, src, shift) {
+    add dst src;
+    shl dst shift;
+}
+
+macro_rules! clear(reg) {
+    xor reg reg;
+}
+
+start:
+    mov r0;
+    clear r0 r1;
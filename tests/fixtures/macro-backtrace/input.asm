@@ -0,0 +1,32 @@
+// Errors found laying out what a macro expanded to point into the macro
+// body and list the calls it came through, innermost first.
+macro_rules! text(fancy) {
+    if!(fancy) {
+        .ascii "\u{2713}"
+    } else {
+        .ascii "-"
+    }
+}
+
+macro_rules! banner(fancy) {
+    .byte 0x1b
+    text!(fancy)
+}
+
+banner 0
+banner 1
+
+for!(var i = 0; i < 3; i += 1) {
+    text!(i == 2)
+}
+
+macro_rules! at(addr) {
+    @org addr
+}
+nop
+nop
+at 0x10
+at 0x1
+
+// Written outside any macro, so there's nothing to trace.
+.ascii "\u{e9}"
@@ -0,0 +1,8 @@
+macro_rules! add2(a, b) {
+    add a b;
+}
+
+add2!(R1, R2);
+add2!(R1);
+ad2!(r0, r1);
+add2!(r0, [r1 + 4]);
@@ -0,0 +1,40 @@
+// Parameters with defaults can be left out of a call, last ones first.
+const SLOW = 100
+
+macro_rules! delay(cycles = 10) {
+    repeat!(cycles / 5) {
+        nop
+    }
+}
+
+macro_rules! fill(dst, value = 0, count = SLOW) {
+    st value, [dst]
+    .word count
+}
+
+macro_rules! log(level = 1, msgs...) {
+    .byte level
+    @foreach msgs {
+        push msgs
+    }
+}
+
+delay
+delay!(20)
+fill r1
+fill r1, r2
+fill!(r1, r2, 3)
+log
+log 2, r1, r2
+
+// Too few arguments for the parameters without defaults.
+fill
+// Overlaps `delay(cycles = 10)`, which already takes none.
+macro_rules! delay() {
+    nop
+}
+
+// A parameter without a default can't follow one with.
+macro_rules! bad(a = 1, b) {
+    nop
+}
@@ -0,0 +1,31 @@
+// 2 + 4 + 8 + 16 + 32 + 64 calls, none nested deeper than six.
+macro_rules! a(n) {
+    .byte n
+}
+macro_rules! b(n) {
+    a!(n)
+    a!(n)
+}
+macro_rules! c(n) {
+    b!(n)
+    b!(n)
+}
+macro_rules! d(n) {
+    c!(n)
+    c!(n)
+}
+macro_rules! e(n) {
+    d!(n)
+    d!(n)
+}
+macro_rules! f(n) {
+    e!(n)
+    e!(n)
+}
+macro_rules! g(n) {
+    f!(n)
+    f!(n)
+}
+
+g!(1)
+g!(2)
@@ -0,0 +1,59 @@
+const STEP = 2
+
+macro_rules! add2(a, b) {
+    add a, b
+}
+
+macro_rules! load(dst, base, off) {
+    ld dst, [base + off]
+}
+
+macro_rules! fill(value, count) {
+    repeat!(count) {
+        .byte value
+    }
+}
+
+macro_rules! twice(a) {
+    add2!(a, a)
+    add2 a, STEP * 2
+}
+
+macro_rules! entry(name) {
+    name:
+    .word name, $ - start
+}
+
+macro_rules! stop_at(n, limit) {
+    if!(n == limit) {
+        break!
+    }
+}
+
+macro_rules! def(name, value) {
+    @define name value
+}
+
+macro_rules! bump(x) {
+    add r0, x + 1
+}
+
+start:
+    add2!(r0, r1)
+    load r0, r1, 8
+    load r0, r1, r2
+    fill 0xff, 3
+    twice r5
+for!(var i = 0; i < 3; i++) {
+    add2 r0, i
+}
+for!(var j = 0; j < 10; j++) {
+    stop_at j, 2
+    .byte j
+}
+    entry handler
+    def SIZE, 4
+
+    def 32, 4
+    bump [r1]
+    load r0, r1, [r2]
@@ -0,0 +1,39 @@
+// `@foreach` repeats its body once per argument of a variadic parameter.
+macro_rules! save(base, regs...) {
+    @foreach regs {
+        st regs, [base]
+        add base, base, 4
+    }
+    .byte len(regs)
+}
+
+macro_rules! checks(values...) {
+    @foreach values {
+        if!(values > 8) {
+            .word values
+        }
+    }
+}
+
+macro_rules! load_all(regs...) {
+    @foreach regs {
+        ld regs, [regs + 4]
+    }
+}
+
+save r0, r1, r2, r3
+save r0
+checks 4, 16, 32
+load_all r4
+
+// Only over the macro's own variadic parameter, and only in a macro.
+macro_rules! wrong(a, rest...) {
+    @foreach a {
+        nop
+    }
+}
+@foreach items {
+    nop
+}
+@foreac x {
+}
@@ -0,0 +1,24 @@
+// `%` and `.` labels in a macro body get a name per expansion, so a
+// macro that branches can be called more than once.
+macro_rules! wait(n) {
+    ld r0, n
+%top:
+    sub r0, r0, 1
+    bnz r0, %top
+    .word %top
+    ld r1, [%top + 2]
+}
+
+macro_rules! clamp(reg, max) {
+    blt reg, max, .ok
+    ld reg, max
+.ok:
+}
+
+start:
+    wait 10
+    wait!(20)
+    clamp r1, 8
+.ok:
+    clamp r2, .ok
+    jmp .ok
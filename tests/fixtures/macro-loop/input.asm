@@ -0,0 +1,18 @@
+const COUNT = 4
+
+macro_rules! swap(x, y) {
+    xor x y;
+    xor y x;
+    xor x y;
+}
+
+start:
+for!(var i = 0; i < 4; i++) {
+    swap r0 r1;
+    add r2 i;
+    {
+        inner:
+        dec r3;
+    }
+}
+halt
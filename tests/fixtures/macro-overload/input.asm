@@ -0,0 +1,12 @@
+macro_rules! mov(dst, src) {
+    add dst src;
+}
+
+macro_rules! mov(dst, src, shift) {
+    add dst src;
+    shl dst shift;
+}
+
+start:
+    mov r0 r1;
+    mov r0 r1 2;
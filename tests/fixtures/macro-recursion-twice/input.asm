@@ -0,0 +1,9 @@
+// Each call makes two more: 2^64 calls before every branch hits the depth
+// limit, unless expansion stops at the first.
+macro_rules! f(n) {
+    f!(n)
+    f!(n)
+}
+
+f!(1)
+nop
@@ -0,0 +1,38 @@
+// Macros can call themselves and each other, up to `macro_depth` calls
+// deep, here 8.
+macro_rules! countdown(n) {
+    .byte n
+    if!(n > 0) {
+        countdown!(n - 1)
+    }
+}
+
+macro_rules! even(n) {
+    if!(n > 0) {
+        odd n - 1
+    } else {
+        .byte 0
+    }
+}
+
+macro_rules! odd(n) {
+    if!(n > 0) {
+        even n - 1
+    } else {
+        .byte 1
+    }
+}
+
+macro_rules! spin(reg) {
+    dec reg
+    spin reg
+}
+
+macro_rules! start(reg) {
+    spin reg
+}
+
+countdown 3
+even 5
+start r1
+countdown 8
@@ -0,0 +1,11 @@
+macro_rules! push(reg) {
+    dec sp;
+    store sp reg;
+}
+
+macro_rules! push(value) {
+    dec sp;
+}
+
+start:
+    push r0;
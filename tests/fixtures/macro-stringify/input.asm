@@ -0,0 +1,29 @@
+// `stringify!(param)` turns a macro argument into a string literal.
+macro_rules! assert_zero(reg) {
+    cmp reg, 0
+    jne fail
+    .asciiz stringify!(reg)
+}
+
+macro_rules! trace(args...) {
+    .ascii stringify!(args)
+    @foreach args {
+        .asciiz stringify!(args)
+    }
+}
+
+macro_rules! name_len(thing) {
+    .byte strlen(stringify!(thing))
+    ld r0, stringify!(thing)
+}
+
+fail:
+assert_zero r1
+assert_zero!([r2 + 4])
+trace r1, 42, "hi"
+name_len loop_top
+
+macro_rules! broken(reg) {
+    .asciiz stringify!(other)
+}
+.asciiz stringify!(reg)
@@ -0,0 +1 @@
+mov r0 r1
@@ -0,0 +1 @@
+ld r0, [r1 + r2 + r3]
@@ -0,0 +1,8 @@
+start:
+    ld R1, [R2 + 4*i]
+    ld r0, [r1]
+    st [r1 + r2 * 4 + 8], r3
+    st [4 * r5 - 16], r0
+    lea r0 [start + 2]
+    ld r0, [r1 - x]
+    ld r0 [r1 + (SIZE << 2)]
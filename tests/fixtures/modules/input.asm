@@ -0,0 +1,19 @@
+module io {
+    const PORT = 0x10
+    putc:
+        st [PORT], r0
+        ret
+    module serial {
+        BAUD equ PORT * 2
+        init:
+            mov r1, BAUD
+            call putc
+            ret
+    }
+}
+
+start:
+    mov r0, io::PORT
+    call io::putc
+    call io::serial::init
+    .word io::serial::BAUD
@@ -0,0 +1,12 @@
+1:
+    dec r0
+    jnz 1b
+    jmp 1f
+    nop
+1:
+    jmp 1b
+repeat!(2) {
+2:
+    jmp 2b
+}
+    jmp 3f
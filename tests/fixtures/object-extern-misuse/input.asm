@@ -0,0 +1,6 @@
+// A relocation is a symbol plus a number, in a field of 8, 16 or 32 bits.
+@extern table
+    .word table * 2
+    .word 2 - table
+    .quad table
+    .dword table + 8    // fine
@@ -0,0 +1,18 @@
+// Every reference a linker could move gets a relocation: those to
+// `@extern` symbols, which are zeros until linked, and absolute ones to
+// labels here. A branch within its own section needs none.
+@extern putc, buffer
+const LEN = 4
+
+::main:
+    ldi r1 buffer + LEN     // abs16 buffer+4
+    call putc               // abs16 putc+0
+    br putc                 // pcrel8, measured from the end of the br
+    jmp rel(putc)           // pcrel16
+.loop:
+    beq .loop               // no relocation
+    jmp .loop               // abs16 main.loop+0
+    halt
+table:
+    .word main, table - 2, putc
+    .byte buffer
@@ -0,0 +1,18 @@
+const BASE = 0x8000
+
+@org BASE
+start:
+    nop
+    jmp start
+@org start + 0x10
+table:
+    .word 1, 2
+.data
+@org 0x200
+    .byte 1
+@org 0x100
+    .byte 2
+@org 1.5
+@org -4
+.text
+@org 0x8001
@@ -0,0 +1,5 @@
+const OK = 1
+start:
+    mov r0 r1;
+var broken = *oops
+nop
@@ -0,0 +1,8 @@
+var = 5
+nop
+macro_rules! load(dst) {
+    ld dst, [r1 - r2]
+    st [r1], dst
+}
+const B = )
+load!(r0)
@@ -0,0 +1,6 @@
+@allow(unused_const)
+
+const H = 0x_FF
+const B = 0b_1010
+const O = 0o__17
+const OK = 0xFF_FF
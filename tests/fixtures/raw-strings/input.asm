@@ -0,0 +1,3 @@
+@message r"C:\path\no\escapes"
+@message r#"say "hi" \q"#
+mov r r"\d+"
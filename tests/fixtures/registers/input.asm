@@ -0,0 +1,3 @@
+mov R1 %tmp
+@reg(r2, %acc)
+mov rx r
@@ -0,0 +1,5 @@
+const OK = 11b
+1:  dec r0
+    bne 1b
+    jmp 2f
+2:  nop
@@ -0,0 +1,26 @@
+const WIDTH = 2
+
+repeat!(3) {
+    nop
+}
+
+for!(var i = 0; i < 2; i++) {
+    repeat!(WIDTH) {
+        push r0
+    }
+    pop r1
+}
+
+repeat!(0) {
+    halt
+}
+
+repeat!(-1) {
+    halt
+}
+
+for!(var j = 0; j < 3; j++) {
+    repeat!(2) {
+        var x = 4 / (1 - j)
+    }
+}
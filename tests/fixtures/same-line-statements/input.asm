@@ -0,0 +1,7 @@
+loop: add r1, r2
+done: ret
+    nop; nop; inc r0
+msg: .asciiz "hi"
+table: .byte; .byte 1, 2; next: .word 3
+var n = 1; var n += 2
+{ push r0; .byte }
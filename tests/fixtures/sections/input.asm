@@ -0,0 +1,22 @@
+start:
+    nop
+    ld r0, [table]
+.data
+table:
+    .word 1, 2, 3
+.rodata
+message:
+    .asciiz "hi"
+.text
+next:
+    jmp start
+.bss
+buffer:
+    .byte 0, 0, 0, 0
+.section vectors
+    .word start
+.data
+tail:
+    .byte 9
+.rodata
+    .ascii "naïve"
@@ -0,0 +1,12 @@
+@size BLOB_LEN, blob_start, blob_end
+@size(VECTORS_LEN, vectors, vectors_end)
+@size TOO_FEW, blob_start
+
+vectors:
+    jmp reset;
+    jmp irq;
+vectors_end:
+
+blob_start:
+    @incbin "blob.bin"
+blob_end:
@@ -0,0 +1,10 @@
+== input.asm
+/* Generated by chasm. Do not edit. */
+#ifndef CHASM_SYMBOLS_H
+#define CHASM_SYMBOLS_H
+
+#define BLOB_LEN 5
+#define VERSION 3
+#define start 0x0
+
+#endif /* CHASM_SYMBOLS_H */
@@ -0,0 +1,10 @@
+// A measured size goes into the C header like any other constant.
+@size BLOB_LEN, blob, blob_end
+const VERSION = 3
+
+::start:
+    jmp start
+    .byte VERSION
+blob:
+    .byte 1, 2, 3, 4, 5
+blob_end:
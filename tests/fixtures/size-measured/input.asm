@@ -0,0 +1,13 @@
+// `@size` measures a region once it's laid out, and the size can be used
+// like any constant, ahead of the region too.
+@size TABLE_LEN, table, table_end
+@size CODE_LEN, start, table
+
+start:
+    ldi r1 TABLE_LEN
+    ldi r2 CODE_LEN * 2
+    .byte TABLE_LEN
+table:
+    .word 1, 2, 3
+    .byte 4
+table_end:
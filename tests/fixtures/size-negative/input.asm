@@ -0,0 +1,8 @@
+// A region whose end comes before its start, and one that isn't a region.
+@size BACKWARDS, after, before
+@size MISSING, before, nowhere
+
+before:
+    nop
+after:
+    .byte BACKWARDS
@@ -0,0 +1,7 @@
+// The lines the included file's macro calls expand to map back into its
+// macro body, then to the calls, then to the loop around one of them.
+include "regs.asm"
+
+start:
+    call save_regs
+    halt
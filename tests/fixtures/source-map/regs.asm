@@ -0,0 +1,11 @@
+macro_rules! push(reg) {
+    sub sp, 2
+    st [sp], reg
+}
+
+save_regs:
+    push!(r0)
+    for!(var i = 1; i < 3; i += 1) {
+        push!(r##i)
+    }
+    ret
@@ -0,0 +1,7 @@
+greeting:
+.ascii "hi"
+.asciiz "line\n"
+.utf8z "café"
+.utf16 r"C:\dos"
+.ASCII "upper"
+.utf16z 42
@@ -0,0 +1,30 @@
+struct Point { x: word, y: word }
+struct Header {
+    magic: dword,
+    count: byte
+    flags: byte
+}
+
+module gfx {
+    struct Color { r: byte, g: byte, b: byte }
+    white:
+        Color { r: 0xff, g: 0xff, b: 0xff }
+}
+
+const POINT_SIZE = sizeof(Point)
+
+start:
+    ld r0, [r1 + Point.y]
+    add r1, Header
+    mov r2, gfx::Color.b
+
+origin:
+    Point { y: 20, x: 10 }
+hdr:
+    Header { magic: 0x4d534843, count: POINT_SIZE }
+black:
+    gfx::Color { }
+bad:
+    Point { x: 1, z: 2 }
+    Point { x: 1, x: 2 }
+    Pont { x: 1 }
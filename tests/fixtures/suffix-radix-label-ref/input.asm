@@ -0,0 +1,4 @@
+1:  dec r0
+    bne 1b
+    mov r1 11b
+    mov r2 0b1
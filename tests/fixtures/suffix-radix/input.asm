@@ -0,0 +1,5 @@
+mov r0 0FFh
+mov r1 1010b
+mov r2 777o
+mov r3 1E3Fh
+mov r4 2b
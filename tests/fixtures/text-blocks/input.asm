@@ -0,0 +1,7 @@
+@message """
+    usage: tool [options]
+      -h  "help"
+    """
+@message """one "quoted" line"""
+@message """tab\tand \q"""
+halt
@@ -0,0 +1,24 @@
+// `##` pastes names together from loop variables, macro parameters and
+// constants.
+const WHICH = 2
+
+for!(var i = 0; i < 3; i += 1) {
+    handler_##i:
+        jmp handler_##i
+}
+
+macro_rules! zero(n) {
+    xor r##n, r##n
+    clear_##n:
+        st r##n, [r##n + 4]
+}
+zero 1
+zero!(2)
+zero [r3]
+
+slot_2:
+    .word slot_##WHICH
+
+for!(var i = 0; i < 1; i += 1) {
+    i##_exit:
+}
@@ -0,0 +1,4 @@
+const OK = 1
+/* open
+   /* closed */
+mov r0 r1;
@@ -0,0 +1,40 @@
+// A last parameter written `name...` takes the rest of the arguments.
+macro_rules! push_all(regs...) {
+    push regs
+}
+
+macro_rules! table(label, values...) {
+    label:
+    .byte len(values), values
+}
+
+macro_rules! say(parts...) {
+    @message parts
+}
+
+macro_rules! pick(values...) {
+    for!(var i = 0; i < len(values); i += 1) {
+        .word values[i] * 2
+    }
+}
+
+macro_rules! pair(a, b) {
+    add a, b
+}
+
+macro_rules! forward(args...) {
+    pair!(args)
+}
+
+push_all r1, r2, r3
+push_all!()
+table primes, 2, 3, 5, 7
+pick 10, 20, 30
+say "a", "b", r"c"
+forward r1, r2
+forward r1, r2, r3
+
+// Only the last parameter can be variadic.
+macro_rules! bad(a..., b) {
+    nop
+}
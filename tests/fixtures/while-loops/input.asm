@@ -0,0 +1,21 @@
+const COUNT = 3
+
+var i = 0
+while!(i < COUNT) {
+    ld r0, [table + i]
+    var i += 1
+}
+
+var j = 0
+while!(j < 4) {
+    var step = 8 / (2 - j)
+    var j += 1
+}
+
+while!(0.5) {
+    nop
+}
+
+for!(var k = 0; k < 100000; k++) {}
+
+table:
@@ -0,0 +1,32 @@
+//! Replays the inputs that once crashed a fuzz target, kept under
+//! `fuzz/regressions/<target>/`, through what that target runs.
+
+use chasm::parser::Parser;
+use std::fs;
+use std::panic;
+use std::path::Path;
+
+/// Every input saved for `parse_bytes` must parse without panicking, like
+/// the target checks: syntax errors are recorded, not raised.
+#[test]
+fn parse_bytes() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions/parse_bytes");
+    let mut inputs: Vec<_> = fs::read_dir(&dir)
+        .expect("fuzz/regressions/parse_bytes is missing")
+        .map(|entry| entry.expect("unreadable regression").path())
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no regressions in {}", dir.display());
+
+    let mut crashed = Vec::new();
+    for path in &inputs {
+        let data = fs::read(path).expect("unreadable regression");
+        let Ok(source) = std::str::from_utf8(&data) else {
+            continue;
+        };
+        if panic::catch_unwind(|| Parser::new(source).parse()).is_err() {
+            crashed.push(path.file_name().unwrap().to_string_lossy().into_owned());
+        }
+    }
+    assert!(crashed.is_empty(), "parse_bytes panics on {:?}", crashed);
+}
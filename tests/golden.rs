@@ -0,0 +1,893 @@
+//! End-to-end fixtures.
+//!
+//! Every directory under `tests/fixtures/` holding an `input.asm` is a case.
+//! It may carry an `options.toml` (`include_dirs = ["..."]`, relative to the
+//! case, `suffix_radix = true`, `case_insensitive = true`, `expand = true`,
+//! `layout = true`, `debug_info = true`, `source_map = true`,
+//! `include_graph = true`, `assemble = true`, `c_header = true`,
+//! `directives = ["message(string)"]` to register directives of other
+//! tools, `macro_depth = 8` to limit macro recursion, `max_expansions =
+//! 100` to limit macro calls in all and `edits = [["old", "new"]]` to edit
+//! the input in an editor) and expected
+//! outputs next to it:
+//!
+//! - `expected.ast`: the parsed statements of every file reached from
+//!   `input.asm`, each file under a `== path` header
+//! - `expected.diags`: lint warnings and errors as rendered by the CLI
+//! - `expected.expanded`: with `expand = true`, the statements of each file
+//!   once its loops have run, laid out like `expected.ast`; errors from
+//!   running them follow the rest in `expected.diags`
+//! - `expected.layout`: with `layout = true`, each section of each file
+//!   with the address of every label and sized statement in it, taking an
+//!   instruction to be a byte plus one per operand, then the global
+//!   symbols an object would export; layout errors follow in
+//!   `expected.diags`
+//! - `expected.debug`: with `debug_info = true`, the source line behind
+//!   each address range of that layout, as
+//!   [`DebugInfo::to_text`](chasm::debuginfo::DebugInfo::to_text) writes it
+//! - `expected.obj`: with `assemble = true`, each file assembled for the
+//!   generic target: every section's bytes from its start address, then
+//!   the symbols an object would list; encoding errors follow in
+//!   `expected.diags`
+//! - `expected.h`: with `c_header = true`, each file assembled for the
+//!   generic target and what it exports as `--emit c-header` writes it;
+//!   export errors follow in `expected.diags`
+//! - `expected.sourcemap`: with `source_map = true`, `input.asm` expanded
+//!   with the files it includes spliced in where it includes them, as
+//!   the CLI expands it; each line of `--emit expanded` text with the
+//!   frames its source map gives it, then the map as `--source-map`
+//!   writes it
+//! - `expected.graph`: with `include_graph = true`, the include graph of
+//!   `input.asm` as `--emit include-graph` writes it, then the depfile
+//!   `--depfile` writes for it
+//! - `expected.reparse`: with `edits`, the statements of `input.asm` after
+//!   each edit, which replaces the first `old` with `new`, reparsed
+//!   incrementally from the parse before it; they must match a fresh parse
+//!   of the edited text (see [`reparse`])
+//!
+//! A directory holding a `link.toml` instead is a linker case. It describes
+//! the objects to link (see [`link_case`]) and expects:
+//!
+//! - `expected.hexdump`: the linked image as `--emit hexdump` prints it
+//! - `expected.readmem`: with a `readmem` table, the image as
+//!   `--emit readmemh` or `readmemb` writes it
+//! - `expected.diags`: link errors
+//!
+//! A missing expected file means the output must be empty. Run with
+//! `UPDATE_EXPECT=1` to rewrite the expected files from the current output.
+//!
+//! Every `input.asm` is also parsed again with a UTF-8 byte order mark and
+//! CRLF line endings, which must not change the statements or where
+//! diagnostics point (see [`check_line_endings`]), lexed keeping trivia,
+//! which must spell it out again (see [`check_trivia`]), and printed back
+//! out as source, which must parse to the same statements (see
+//! [`check_source`]). With the `serde` feature they also go through JSON
+//! and back unchanged (see [`check_serde`]).
+//!
+//! Finally every diagnostic code is checked for an explanation, including
+//! each code that shows up in an `expected.diags` (see [`check_codes`]).
+
+use chasm::assemble::{Assembled, assemble};
+use chasm::codes::Code;
+use chasm::debuginfo::DebugInfo;
+use chasm::directives::{self, Directives};
+use chasm::{detokenize, to_source};
+use chasm::expand::{ExpandOptions, expand_traced};
+use chasm::export::{self, CHeaderOptions};
+use chasm::hexdump::hexdump;
+use chasm::includes::{self, IncludeOptions};
+use chasm::isa::Target;
+use chasm::incremental::{ParseResult, TextEdit};
+use chasm::layout::{Layout, layout_expanded};
+use chasm::link::Linker;
+use chasm::lint::{self, Lint};
+use chasm::macros;
+use chasm::object::{Object, ObjectSymbol, RelocKind, Relocation, Section};
+use chasm::parser::{
+    LexError, LexErrorKind, LexOptions, ParseError, Parser, Statement, TokenStream,
+};
+use chasm::readmem::{Endian, Radix, ReadmemOptions, readmem};
+use chasm::session::{Session, splice};
+use chasm::source::{BOM, FileId, SourceFile, SourceManager};
+use chasm::sourcemap::{SourceMap, Via};
+use chasm::symbols;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+/// Expected file name and actual contents, for each output of a case.
+type Outputs = Vec<(&'static str, String)>;
+
+fn main() {
+    let update = std::env::var_os("UPDATE_EXPECT").is_some_and(|v| v == "1");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let mut cases: Vec<PathBuf> = fs::read_dir(&root)
+        .expect("tests/fixtures is missing")
+        .map(|entry| entry.expect("unreadable fixture dir").path())
+        .filter(|dir| dir.join("input.asm").is_file() || dir.join("link.toml").is_file())
+        .collect();
+    cases.sort();
+
+    let mut failed = 0;
+    for case in &cases {
+        let name = case.file_name().unwrap().to_string_lossy();
+        let outputs = if case.join("link.toml").is_file() {
+            link_case(case)
+        } else {
+            run(case)
+        };
+        let outputs = match outputs {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                println!("golden {} ... FAILED\n  {}", name, e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mut mismatches = Vec::new();
+        for (file, actual) in &outputs {
+            let path = case.join(file);
+            if update {
+                write_expected(&path, actual);
+            } else if let Some(diff) = compare(&path, actual) {
+                mismatches.push(format!("{}:\n{}", file, diff));
+            }
+        }
+
+        if mismatches.is_empty() {
+            println!("golden {} ... ok", name);
+        } else {
+            println!("golden {} ... FAILED", name);
+            for m in mismatches {
+                println!("{}", m);
+            }
+            failed += 1;
+        }
+    }
+
+    #[allow(unused_mut)]
+    let mut checks = vec![
+        ("line endings", check_line_endings(&cases)),
+        ("trivia", check_trivia(&cases)),
+        ("source", check_source(&cases)),
+        ("codes", check_codes(&cases)),
+    ];
+    #[cfg(feature = "serde")]
+    checks.push(("serde", check_serde(&cases)));
+    for (check, problems) in checks {
+        if problems.is_empty() {
+            println!("{} ... ok", check);
+        } else {
+            println!("{} ... FAILED", check);
+            for problem in &problems {
+                println!("  {}", problem);
+            }
+            failed += 1;
+        }
+    }
+
+    println!("\n{} fixtures, {} failed", cases.len(), failed);
+    if failed > 0 {
+        println!("rerun with UPDATE_EXPECT=1 to accept the new output");
+        process::exit(1);
+    }
+}
+
+fn run(case: &Path) -> Result<Outputs, String> {
+    let opts = options(case)?;
+    let mut session = Session::new(opts.include.clone());
+    session.set_lex_options(opts.lex);
+    let entry = case.join("input.asm");
+
+    let mut ast = String::new();
+    let mut expanded = String::new();
+    let mut laid_out = String::new();
+    let mut debug_info = String::new();
+    let mut assembled = String::new();
+    let mut header = String::new();
+    let mut source_map = String::new();
+    let mut graph = String::new();
+    let mut reparsed = String::new();
+    let mut diags = String::new();
+    if !opts.edits.is_empty() {
+        let text = fs::read_to_string(&entry).map_err(|e| e.to_string())?;
+        reparsed = reparse(&text, &opts.edits)?;
+    }
+    if opts.include_graph {
+        let includes = includes::graph(&entry, &opts.include).map_err(|e| e.to_string())?;
+        graph = includes.to_dot() + &includes.to_depfile("input.bin");
+    }
+    match session.build(&entry) {
+        Ok(files) => {
+            for file in &files {
+                let _ = writeln!(ast, "== {}", file.path.display());
+                for stmt in file.ast.iter() {
+                    let _ = writeln!(ast, "{:?}", stmt);
+                }
+                let mut expand_errors = Vec::new();
+                if opts.expand || opts.layout || opts.debug_info || opts.assemble || opts.c_header {
+                    let (stmts, errors, origins) = expand_traced(&file.ast, &opts.expand_opts);
+                    expand_errors = errors;
+                    if opts.expand {
+                        let _ = writeln!(expanded, "== {}", file.path.display());
+                        for stmt in &stmts {
+                            let _ = writeln!(expanded, "{:?}", stmt);
+                        }
+                    }
+                    if opts.layout || opts.debug_info {
+                        let (placed, errors) =
+                            layout_expanded(&stmts, &origins, |_, args| 1 + args.len() as u64);
+                        expand_errors.extend(errors);
+                        if opts.layout {
+                            let _ = writeln!(laid_out, "== {}", file.path.display());
+                            write_layout(&mut laid_out, &placed);
+                        }
+                        if opts.debug_info {
+                            let _ = writeln!(debug_info, "== {}", file.path.display());
+                            debug_info
+                                .push_str(&DebugInfo::new(&placed, session.sources()).to_text());
+                        }
+                    }
+                    if opts.assemble || opts.c_header {
+                        let target = Target::GENERIC;
+                        let (output, errors) =
+                            assemble(&stmts, &origins, &target.isa(), &target.registers);
+                        expand_errors.extend(errors);
+                        if opts.assemble {
+                            let _ = writeln!(assembled, "== {}", file.path.display());
+                            write_object(&mut assembled, &output);
+                        }
+                        if opts.c_header {
+                            let _ = writeln!(header, "== {}", file.path.display());
+                            match export::c_header(&output.exports(), &CHeaderOptions::default()) {
+                                Ok(text) => header.push_str(&text),
+                                Err(diag) => expand_errors.push(diag),
+                            }
+                        }
+                    }
+                }
+                let diags_for_file = file
+                    .lex_errors
+                    .iter()
+                    .map(LexError::to_diagnostic)
+                    .chain(file.parse_errors.iter().map(ParseError::to_diagnostic))
+                    .chain(opts.directives.check(&file.ast))
+                    .chain(macros::check(&file.ast))
+                    .chain(symbols::check(&file.ast))
+                    .chain(lint::check(&file.ast, &file.skipped))
+                    .chain(expand_errors);
+                for diag in diags_for_file {
+                    diags.push_str(&diag.render(session.sources()));
+                }
+            }
+            if opts.source_map {
+                let (stmts, _, origins) = expand_traced(&splice(&files), &opts.expand_opts);
+                let map = SourceMap::new(&stmts, &origins, session.sources());
+                write_source_map(&mut source_map, &to_source(&stmts), &map)?;
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(diags, "error: {}", e);
+        }
+    }
+
+    // Paths are absolute once canonicalized; make them relative to the case.
+    let dir = format!(
+        "{}/",
+        fs::canonicalize(case).map_err(|e| e.to_string())?.display()
+    );
+    Ok(vec![
+        ("expected.ast", ast.replace(&dir, "")),
+        ("expected.expanded", expanded.replace(&dir, "")),
+        ("expected.layout", laid_out.replace(&dir, "")),
+        ("expected.debug", debug_info.replace(&dir, "")),
+        ("expected.obj", assembled.replace(&dir, "")),
+        ("expected.h", header.replace(&dir, "")),
+        ("expected.sourcemap", source_map.replace(&dir, "")),
+        ("expected.graph", graph.replace(&dir, "")),
+        ("expected.reparse", reparsed),
+        ("expected.diags", diags.replace(&dir, "")),
+    ])
+}
+
+/// Each section's start and size, then its labels and statements at their
+/// addresses, then the global symbols by section and offset.
+fn write_layout(out: &mut String, layout: &Layout) {
+    for section in &layout.sections {
+        let _ = writeln!(
+            out,
+            "section {} at {:#06x} ({} bytes)",
+            section.name,
+            section.start,
+            section.size()
+        );
+        for (offset, stmt) in &section.items {
+            let _ = writeln!(out, "{:#06x} {:?}", offset, stmt.kind);
+        }
+    }
+    for sym in layout.object_symbols().iter().filter(|sym| sym.global) {
+        if let Some((section, offset)) = sym.definition {
+            let name = &layout.sections[section as usize].name;
+            let _ = writeln!(out, "global {} = {}+{:#x}", sym.name, name, offset);
+        }
+    }
+}
+
+/// Each section's address range and bytes, sixteen to a row, a run of
+/// rows like the one before shown as `*`, then the object's symbols by
+/// section and offset.
+fn write_object(out: &mut String, assembled: &Assembled) {
+    let sections = assembled.layout.sections.iter();
+    for (section, data) in sections.zip(&assembled.object.sections) {
+        let _ = writeln!(
+            out,
+            "section {} at {:#06x}..{:#06x}",
+            section.name, section.start, section.pc
+        );
+        let mut previous = None;
+        for (row, bytes) in data.data.chunks(16).enumerate() {
+            if previous == Some(bytes) {
+                if !out.ends_with("*\n") {
+                    out.push_str("*\n");
+                }
+                continue;
+            }
+            previous = Some(bytes);
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let address = section.start + row as u64 * 16;
+            let _ = writeln!(out, "{:#06x}  {}", address, hex.join(" "));
+        }
+    }
+    for sym in &assembled.object.symbols {
+        let scope = if sym.global { "global" } else { "local" };
+        match sym.definition {
+            Some((section, offset)) => {
+                let name = &assembled.object.sections[section as usize].name;
+                let _ = writeln!(out, "{} {} = {}+{:#x}", scope, sym.name, name, offset);
+            }
+            None => {
+                let _ = writeln!(out, "{} {} undefined", scope, sym.name);
+            }
+        }
+    }
+    for reloc in &assembled.object.relocations {
+        let _ = writeln!(
+            out,
+            "reloc {}+{:#x} {} {}{:+}",
+            assembled.object.sections[reloc.section as usize].name,
+            reloc.offset,
+            reloc.kind.name(),
+            reloc.symbol,
+            reloc.addend
+        );
+    }
+}
+
+/// Applies `edits` to `text` one at a time, each to an incremental parse of
+/// the text before it. Every update must give the statements, spans
+/// included, and syntax errors of a fresh parse; the statements after each
+/// are listed under the edit.
+fn reparse(text: &str, edits: &[(String, String)]) -> Result<String, String> {
+    let opts = chasm::diff::DiffOptions { spans: true };
+    let mut out = String::new();
+    let mut parse = ParseResult::new(text);
+    for (old, new) in edits {
+        let start = parse
+            .source()
+            .find(old.as_str())
+            .ok_or(format!("edit: `{}` isn't in the text", old))?;
+        parse = parse.update(TextEdit::new(start..start + old.len(), new.as_str()));
+        let fresh = ParseResult::new(parse.source());
+        if let Some(difference) =
+            chasm::diff::diff_with(fresh.statements(), parse.statements(), &opts).first()
+        {
+            return Err(format!("after editing `{}`, {}", old, difference));
+        }
+        if fresh.errors() != parse.errors() {
+            return Err(format!("after editing `{}`, the syntax errors differ", old));
+        }
+        let _ = writeln!(out, "== {:?} -> {:?}", old, new);
+        for stmt in parse.statements() {
+            let _ = writeln!(out, "{:?}", stmt);
+        }
+    }
+    Ok(out)
+}
+
+/// Each line of `text` with the frames `map` gives it, then the map as
+/// JSON. With the `serde` feature the JSON must read back as `map`.
+fn write_source_map(out: &mut String, text: &str, map: &SourceMap) -> Result<(), String> {
+    for (index, line) in text.lines().enumerate() {
+        let _ = writeln!(out, "{:4} | {}", index + 1, line);
+        for frame in map.original_of(index + 1) {
+            let _ = write!(out, "       {}:{}:{}", frame.file, frame.line, frame.column);
+            let _ = match frame.via {
+                None => writeln!(out),
+                Some(Via::Macro { name }) => writeln!(out, " via {}!", name),
+                Some(Via::Loop { keyword, iteration }) => {
+                    writeln!(out, " via {} iteration {}", keyword, iteration)
+                }
+            };
+        }
+    }
+    let json = map.to_json();
+    #[cfg(feature = "serde")]
+    if serde_json::from_str::<SourceMap>(&json).ok().as_ref() != Some(map) {
+        return Err("the source map doesn't read back from its JSON".to_string());
+    }
+    let _ = writeln!(out, "{}", json);
+    Ok(())
+}
+
+/// Parses each case's `input.asm` as is and again as a Windows editor
+/// would save it, with a byte order mark and CRLF line endings. The two
+/// must give the same statements and the same rendered diagnostics,
+/// line and column numbers included.
+fn check_line_endings(cases: &[PathBuf]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for case in cases {
+        let Ok(lf) = fs::read_to_string(case.join("input.asm")) else {
+            continue;
+        };
+        let crlf = format!("{}{}", BOM, lf.replace('\n', "\r\n"));
+        let name = case.file_name().unwrap().to_string_lossy();
+
+        let (lf_ast, lf_diags) = parse_alone(&lf);
+        let (crlf_ast, crlf_diags) = parse_alone(&crlf);
+        for difference in chasm::diff::diff(&lf_ast, &crlf_ast) {
+            problems.push(format!("{}: with CRLF, {}", name, difference));
+        }
+        if lf_diags != crlf_diags {
+            problems.push(format!(
+                "{}: with CRLF, diagnostics differ:\n{}",
+                name, crlf_diags
+            ));
+        }
+    }
+    problems
+}
+
+/// Lexes each case's `input.asm` keeping trivia. Unless some input is
+/// unrecognised, the tokens and trailing trivia must spell the input
+/// exactly.
+fn check_trivia(cases: &[PathBuf]) -> Vec<String> {
+    let opts = LexOptions {
+        trivia: true,
+        ..LexOptions::default()
+    };
+    let mut problems = Vec::new();
+    for case in cases {
+        let Ok(text) = fs::read_to_string(case.join("input.asm")) else {
+            continue;
+        };
+        let source = Rc::new(SourceFile::new("input.asm", text.as_str()));
+        let stream = TokenStream::with_options(FileId::default(), source, opts);
+        if stream.lex_errors().iter().any(|e| e.kind == LexErrorKind::Unrecognised) {
+            continue;
+        }
+        let trailing: String = stream.trailing_trivia().iter().map(|t| t.text.as_str()).collect();
+        let rebuilt = detokenize(&stream.into_tokens()) + &trailing;
+        if rebuilt != text.strip_prefix(BOM).unwrap_or(&text) {
+            let name = case.file_name().unwrap().to_string_lossy();
+            problems.push(format!("{}: rebuilt from trivia as {:?}", name, rebuilt));
+        }
+    }
+    problems
+}
+
+/// Prints each case's statements back out as source with
+/// [`chasm::to_source`]. Parsing that must give the same statements again.
+fn check_source(cases: &[PathBuf]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for case in cases {
+        let Ok(text) = fs::read_to_string(case.join("input.asm")) else {
+            continue;
+        };
+        let name = case.file_name().unwrap().to_string_lossy();
+
+        let (ast, _) = parse_alone(&text);
+        let printed = to_source(&ast);
+        let (reparsed, _) = parse_alone(&printed);
+        for difference in chasm::diff::diff(&ast, &reparsed) {
+            problems.push(format!("{}: printed as source, {}", name, difference));
+        }
+    }
+    problems
+}
+
+/// With the `serde` feature, writes each case's statements out as JSON and
+/// reads them back, which must give the same statements, spans included.
+#[cfg(feature = "serde")]
+fn check_serde(cases: &[PathBuf]) -> Vec<String> {
+    let opts = chasm::diff::DiffOptions { spans: true };
+    let mut problems = Vec::new();
+    for case in cases {
+        let Ok(text) = fs::read_to_string(case.join("input.asm")) else {
+            continue;
+        };
+        let name = case.file_name().unwrap().to_string_lossy();
+
+        let (ast, _) = parse_alone(&text);
+        let json = serde_json::to_string(&ast).expect("statements always serialize");
+        let back: Vec<Statement> = match serde_json::from_str(&json) {
+            Ok(back) => back,
+            Err(e) => {
+                problems.push(format!("{}: read back from JSON: {}", name, e));
+                continue;
+            }
+        };
+        for difference in chasm::diff::diff_with(&ast, &back, &opts) {
+            problems.push(format!("{}: through JSON, {}", name, difference));
+        }
+    }
+    problems
+}
+
+/// Statements and rendered diagnostics for `input.asm` holding `text`,
+/// without following includes. Syntax errors don't stop the parse.
+fn parse_alone(text: &str) -> (Vec<Statement>, String) {
+    let mut sources = SourceManager::new();
+    let file = sources.add("input.asm", text);
+    let mut parser = Parser::for_file(&sources, file);
+
+    let mut ast = Vec::new();
+    let mut diags = String::new();
+    for item in parser.statements() {
+        match item {
+            Ok(stmt) => ast.push(stmt),
+            Err(err) => diags.push_str(&err.to_diagnostic().render(&sources)),
+        }
+    }
+    let checks = parser
+        .lex_errors()
+        .iter()
+        .map(LexError::to_diagnostic)
+        .chain(directives::check(&ast))
+        .chain(macros::check(&ast))
+        .chain(symbols::check(&ast))
+        .chain(lint::check(&ast, &parser.skipped()));
+    for diag in checks {
+        diags.push_str(&diag.render(&sources));
+    }
+    (ast, diags)
+}
+
+/// Every code needs a title and an explanation for `chasm explain`, a
+/// unique spelling, and a block matching its severity. Codes in fixture
+/// output must be known ones, so nothing is emitted that can't be
+/// explained.
+fn check_codes(cases: &[PathBuf]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for &code in Code::ALL {
+        let name = code.as_str();
+        if !seen.insert(name) {
+            problems.push(format!("{} is listed twice", name));
+        }
+        if Code::from_name(name) != Some(code) {
+            problems.push(format!("{} doesn't parse back", name));
+        }
+        if code.title().is_empty() || code.explanation().trim().is_empty() {
+            problems.push(format!("{} has no explanation", name));
+        }
+        if name.starts_with('W') != name.starts_with("W02") {
+            problems.push(format!("{} is outside the lint block", name));
+        }
+    }
+    for &lint in Lint::ALL {
+        if !Code::ALL.contains(&lint.code()) {
+            problems.push(format!("lint `{}` has an unlisted code", lint.name()));
+        }
+    }
+
+    for case in cases {
+        let diags = fs::read_to_string(case.join("expected.diags")).unwrap_or_default();
+        for line in diags.lines() {
+            let Some(code) = line
+                .split_once('[')
+                .filter(|(level, _)| *level == "error" || *level == "warning")
+                .and_then(|(_, rest)| rest.split_once(']'))
+                .map(|(code, _)| code)
+            else {
+                continue;
+            };
+            if Code::from_name(code).is_none() {
+                problems.push(format!(
+                    "{}: unknown code {}",
+                    case.file_name().unwrap().to_string_lossy(),
+                    code
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Links the objects described by `link.toml`:
+///
+/// ```toml
+/// base = 0x8000
+///
+/// [[object]]
+/// name = "a.o"
+/// sections = [{ name = "text", align = 2, data = "10 00 00" }]
+/// symbols = [{ name = "start", global = true, section = 0, offset = 0 }]
+/// relocations = [{ section = 0, offset = 1, kind = "abs16", symbol = "ext" }]
+/// ```
+///
+/// A symbol without `section` is external; `align` defaults to 1 and
+/// `addend` to 0. A `readmem` table asks for `expected.readmem` as well:
+///
+/// ```toml
+/// readmem = { radix = "b", width = 16, endian = "big", markers = false }
+/// ```
+///
+/// where `radix` is `h` or `b`, and the rest default to 8, `little` and
+/// `true`.
+fn link_case(case: &Path) -> Result<Outputs, String> {
+    let text = fs::read_to_string(case.join("link.toml")).map_err(|e| e.to_string())?;
+    let table: toml::Table = text.parse().map_err(|e| format!("link.toml: {}", e))?;
+    let base = int(&table, "base", Some(0))? as u64;
+
+    let mut objects = Vec::new();
+    for obj in array(&table, "object")? {
+        let name = string(obj, "name")?.to_string();
+        objects.push((name, object(obj).map_err(|e| format!("link.toml: {}", e))?));
+    }
+
+    let mut linker = Linker::new(base);
+    for (name, obj) in &objects {
+        linker.add(name, obj);
+    }
+    let readmem_opts = match table.get("readmem").map(toml::Value::as_table) {
+        None => None,
+        Some(None) => return Err("`readmem` must be a table".to_string()),
+        Some(Some(readmem)) => Some(readmem_options(readmem)?),
+    };
+    Ok(match linker.link() {
+        Ok(image) => {
+            let mut outputs = vec![
+                ("expected.hexdump", hexdump(&image)),
+                ("expected.diags", String::new()),
+            ];
+            if let Some(opts) = readmem_opts {
+                outputs.push(("expected.readmem", readmem(&image, &opts)));
+            }
+            outputs
+        }
+        Err(diags) => {
+            let sources = SourceManager::new();
+            let diags = diags.iter().map(|d| d.render(&sources)).collect();
+            vec![
+                ("expected.hexdump", String::new()),
+                ("expected.diags", diags),
+            ]
+        }
+    })
+}
+
+fn readmem_options(table: &toml::Table) -> Result<ReadmemOptions, String> {
+    Ok(ReadmemOptions {
+        radix: match string(table, "radix")? {
+            "h" => Radix::Hex,
+            "b" => Radix::Binary,
+            radix => return Err(format!("unknown radix `{}`", radix)),
+        },
+        word_bytes: match int(table, "width", Some(8))? {
+            width @ (8 | 16 | 32) => width as u64 / 8,
+            width => return Err(format!("a word can't be {} bits", width)),
+        },
+        endian: match table.get("endian").and_then(toml::Value::as_str) {
+            None | Some("little") => Endian::Little,
+            Some("big") => Endian::Big,
+            Some(endian) => return Err(format!("unknown endian `{}`", endian)),
+        },
+        markers: table
+            .get("markers")
+            .and_then(toml::Value::as_bool)
+            .unwrap_or(true),
+    })
+}
+
+fn object(table: &toml::Table) -> Result<Object, String> {
+    let mut obj = Object::default();
+    for section in array(table, "sections")? {
+        let data = string(section, "data")?
+            .split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(|e| format!("`{}`: {}", byte, e)))
+            .collect::<Result<_, _>>()?;
+        obj.sections.push(Section {
+            name: string(section, "name")?.to_string(),
+            align: int(section, "align", Some(1))? as u32,
+            data,
+        });
+    }
+    for sym in array(table, "symbols")? {
+        let definition = match sym.get("section") {
+            Some(_) => Some((
+                int(sym, "section", None)? as u32,
+                int(sym, "offset", None)? as u64,
+            )),
+            None => None,
+        };
+        obj.symbols.push(ObjectSymbol {
+            name: string(sym, "name")?.to_string(),
+            global: sym
+                .get("global")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or(false),
+            definition,
+        });
+    }
+    for reloc in array(table, "relocations")? {
+        let kind = string(reloc, "kind")?;
+        obj.relocations.push(Relocation {
+            section: int(reloc, "section", None)? as u32,
+            offset: int(reloc, "offset", None)? as u64,
+            kind: RelocKind::from_name(kind)
+                .ok_or_else(|| format!("unknown relocation kind `{}`", kind))?,
+            symbol: string(reloc, "symbol")?.to_string(),
+            addend: int(reloc, "addend", Some(0))?,
+        });
+    }
+    Ok(obj)
+}
+
+fn array<'a>(table: &'a toml::Table, key: &str) -> Result<Vec<&'a toml::Table>, String> {
+    match table.get(key) {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_table().ok_or(format!("`{}` must hold tables", key)))
+            .collect(),
+        Some(_) => Err(format!("`{}` must be an array", key)),
+    }
+}
+
+fn string<'a>(table: &'a toml::Table, key: &str) -> Result<&'a str, String> {
+    table
+        .get(key)
+        .and_then(toml::Value::as_str)
+        .ok_or(format!("missing string `{}`", key))
+}
+
+fn int(table: &toml::Table, key: &str, default: Option<i64>) -> Result<i64, String> {
+    match table.get(key) {
+        Some(value) => value
+            .as_integer()
+            .ok_or(format!("`{}` must be an integer", key)),
+        None => default.ok_or(format!("missing integer `{}`", key)),
+    }
+}
+
+/// What a case's `options.toml` asks for.
+#[derive(Default)]
+struct Options {
+    include: IncludeOptions,
+    lex: LexOptions,
+    /// Run the loops and write `expected.expanded`.
+    expand: bool,
+    /// Lay out the expanded statements and write `expected.layout`.
+    layout: bool,
+    /// Lay out the expanded statements and write `expected.debug`.
+    debug_info: bool,
+    /// Expand every file together and write `expected.sourcemap`.
+    source_map: bool,
+    /// Assemble each file and write `expected.obj`.
+    assemble: bool,
+    /// Assemble each file and write `expected.h`.
+    c_header: bool,
+    /// Walk the includes and write `expected.graph`.
+    include_graph: bool,
+    /// The built-in directives and those the case registers.
+    directives: Directives,
+    expand_opts: ExpandOptions,
+    /// Text to replace, and what with, one edit after another.
+    edits: Vec<(String, String)>,
+}
+
+fn options(case: &Path) -> Result<Options, String> {
+    let path = case.join("options.toml");
+    let mut opts = Options::default();
+    if !path.is_file() {
+        return Ok(opts);
+    }
+
+    let text = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let table: toml::Table = text.parse().map_err(|e| format!("options.toml: {}", e))?;
+    for (key, value) in table {
+        match (key.as_str(), value) {
+            ("include_dirs", toml::Value::Array(dirs)) => {
+                for dir in dirs {
+                    let dir = dir
+                        .as_str()
+                        .ok_or("options.toml: include_dirs must be strings")?;
+                    opts.include.include_dirs.push(case.join(dir));
+                }
+            }
+            ("suffix_radix", toml::Value::Boolean(on)) => opts.lex.suffix_radix = on,
+            ("case_insensitive", toml::Value::Boolean(on)) => opts.lex.case_insensitive = on,
+            ("expand", toml::Value::Boolean(on)) => opts.expand = on,
+            ("layout", toml::Value::Boolean(on)) => opts.layout = on,
+            ("debug_info", toml::Value::Boolean(on)) => opts.debug_info = on,
+            ("source_map", toml::Value::Boolean(on)) => opts.source_map = on,
+            ("include_graph", toml::Value::Boolean(on)) => opts.include_graph = on,
+            ("assemble", toml::Value::Boolean(on)) => opts.assemble = on,
+            ("c_header", toml::Value::Boolean(on)) => opts.c_header = on,
+            ("edits", toml::Value::Array(edits)) => {
+                for edit in edits {
+                    match edit.as_array().map(Vec::as_slice) {
+                        Some([toml::Value::String(old), toml::Value::String(new)]) => {
+                            opts.edits.push((old.clone(), new.clone()))
+                        }
+                        _ => return Err("options.toml: edits must be [old, new] pairs".into()),
+                    }
+                }
+            }
+            ("macro_depth", toml::Value::Integer(depth)) => {
+                opts.expand_opts.macro_depth = usize::try_from(depth)
+                    .map_err(|_| "options.toml: macro_depth must not be negative")?;
+            }
+            ("max_expansions", toml::Value::Integer(count)) => {
+                opts.expand_opts.max_expansions = usize::try_from(count)
+                    .map_err(|_| "options.toml: max_expansions must not be negative")?;
+            }
+            ("directives", toml::Value::Array(specs)) => {
+                for spec in specs {
+                    let spec = spec
+                        .as_str()
+                        .ok_or("options.toml: directives must be strings")?
+                        .parse()
+                        .map_err(|e| format!("options.toml: {}", e))?;
+                    opts.directives.register(spec);
+                }
+            }
+            (key, _) => {
+                return Err(format!(
+                    "options.toml: unknown or mistyped option `{}`",
+                    key
+                ));
+            }
+        }
+    }
+    Ok(opts)
+}
+
+/// `None` if `path` holds exactly `actual` (or is absent and `actual` is
+/// empty), otherwise a description of the first differing line.
+fn compare(path: &Path, actual: &str) -> Option<String> {
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected == actual {
+        return None;
+    }
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        let (e, a) = (expected_lines.next(), actual_lines.next());
+        if e.is_none() && a.is_none() {
+            break;
+        }
+        if e != a {
+            return Some(format!(
+                "  first difference at line {}\n  - {}\n  + {}",
+                line,
+                e.unwrap_or("<end of file>"),
+                a.unwrap_or("<end of file>")
+            ));
+        }
+    }
+    Some("  lines match; the files differ in line endings".to_string())
+}
+
+fn write_expected(path: &Path, actual: &str) {
+    if actual.is_empty() {
+        let _ = fs::remove_file(path);
+    } else {
+        fs::write(path, actual).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    }
+}
@@ -0,0 +1,192 @@
+//! Highlighting classes, from the tokens the parser lexes.
+
+use chasm::highlight::{TokenClass, classify};
+use chasm::isa::{RegisterPattern, Target};
+
+/// The class of the `nth` `needle` in `source`, counting from 0, if
+/// exactly that text is classified.
+fn class_of(source: &str, needle: &str, nth: usize) -> Option<TokenClass> {
+    let start = source.match_indices(needle).nth(nth).unwrap().0;
+    classify(source, &Target::GENERIC)
+        .into_iter()
+        .find(|(span, _)| *span == (start..start + needle.len()))
+        .map(|(_, class)| class)
+}
+
+#[test]
+fn keywords() {
+    let source = "\
+const A = 1
+var b = 2
+LIMIT equ 3
+module io {}
+macro_rules! m() { nop }
+for!(var i = 0; i < 2; i++) { nop }
+if!(A) { nop } elif!(b) { nop } else { nop }
+";
+    for (needle, nth) in [
+        ("const", 0),
+        ("var", 0),
+        ("equ", 0),
+        ("module", 0),
+        ("macro_rules!", 0),
+        ("for!", 0),
+        ("if!", 0),
+        ("elif!", 0),
+    ] {
+        assert_eq!(
+            class_of(source, needle, nth),
+            Some(TokenClass::Keyword),
+            "{}",
+            needle
+        );
+    }
+}
+
+#[test]
+fn mnemonics_start_statements() {
+    let source = "start: nop\n    ldi r1 2; halt\n";
+    for needle in ["nop", "ldi", "halt"] {
+        assert_eq!(
+            class_of(source, needle, 0),
+            Some(TokenClass::Mnemonic),
+            "{}",
+            needle
+        );
+    }
+}
+
+#[test]
+fn registers_by_name_and_sigil() {
+    let source = "    mov r1 R15\n    add %tmp r1\n";
+    assert_eq!(class_of(source, "r1", 0), Some(TokenClass::Register));
+    assert_eq!(class_of(source, "R15", 0), Some(TokenClass::Register));
+    assert_eq!(class_of(source, "%tmp", 0), Some(TokenClass::Register));
+    // Never split into a `%` and a label.
+    assert_eq!(class_of(source, "tmp", 0), None);
+}
+
+#[test]
+fn registers_are_the_targets() {
+    let target = Target {
+        registers: RegisterPattern {
+            named: &["sp"],
+            ..Target::GENERIC.registers
+        },
+        ..Target::GENERIC
+    };
+    let source = "    st [sp], r1\n";
+    let classes = classify(source, &target);
+    assert!(
+        classes.contains(&(8..10, TokenClass::Register)),
+        "{:?}",
+        classes
+    );
+    // Without it, `sp` is only a name.
+    assert_eq!(class_of(source, "sp", 0), Some(TokenClass::LabelRef));
+}
+
+#[test]
+fn label_definitions_and_references() {
+    let source = "\
+start:
+.loop:
+    jmp .loop
+    call later
+    jmp missing
+%top:
+    jmp %top
+1:
+    jmp 1b
+    jmp 1f
+1:
+later:
+";
+    assert_eq!(class_of(source, "start", 0), Some(TokenClass::LabelDef));
+    assert_eq!(class_of(source, "loop", 0), Some(TokenClass::LabelDef));
+    assert_eq!(class_of(source, "loop", 1), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "later", 0), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "later", 1), Some(TokenClass::LabelDef));
+    // Referenced but never defined.
+    assert_eq!(class_of(source, "missing", 0), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "%top", 0), Some(TokenClass::LabelDef));
+    assert_eq!(class_of(source, "%top", 1), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "1", 0), Some(TokenClass::LabelDef));
+    assert_eq!(class_of(source, "1b", 0), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "1f", 0), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "1", 3), Some(TokenClass::LabelDef));
+}
+
+#[test]
+fn consts_and_macro_names() {
+    let source = "\
+const SIZE = 4
+@define FAST
+macro_rules! push(reg) { st [sp], reg }
+    push!(r1)
+    ldi r1 SIZE + FAST
+";
+    assert_eq!(class_of(source, "SIZE", 0), Some(TokenClass::Const));
+    assert_eq!(class_of(source, "SIZE", 1), Some(TokenClass::Const));
+    assert_eq!(class_of(source, "FAST", 1), Some(TokenClass::Const));
+    assert_eq!(class_of(source, "push", 0), Some(TokenClass::MacroName));
+    assert_eq!(class_of(source, "push", 1), Some(TokenClass::MacroName));
+}
+
+#[test]
+fn directives() {
+    let source = "@org 0x100\n    .word 1\n";
+    assert_eq!(class_of(source, "@org", 0), Some(TokenClass::Directive));
+    assert_eq!(class_of(source, "word", 0), Some(TokenClass::Directive));
+}
+
+#[test]
+fn numbers_and_strings() {
+    let source = "    .byte 0x10, 0b1, 7, 'A'\n    .ascii \"hi\"\n";
+    for needle in ["0x10", "0b1", "7"] {
+        assert_eq!(
+            class_of(source, needle, 0),
+            Some(TokenClass::Number),
+            "{}",
+            needle
+        );
+    }
+    assert_eq!(class_of(source, "'A'", 0), Some(TokenClass::String));
+    assert_eq!(class_of(source, "\"hi\"", 0), Some(TokenClass::String));
+}
+
+#[test]
+fn comments() {
+    let source = "// top\n    nop /* inline */ // after\n/* trailing /* nested */ */\n// last";
+    for needle in [
+        "// top",
+        "/* inline */",
+        "// after",
+        "/* trailing /* nested */ */",
+        "// last",
+    ] {
+        assert_eq!(
+            class_of(source, needle, 0),
+            Some(TokenClass::Comment),
+            "{}",
+            needle
+        );
+    }
+    assert_eq!(
+        class_of("    nop /* open", "/* open", 0),
+        Some(TokenClass::Comment)
+    );
+}
+
+#[test]
+fn spans_are_sorted_and_disjoint_past_errors() {
+    let source = "start: ld r1 [r2 - ] // broken\n    $ ` jmp start\n";
+    let classes = classify(source, &Target::GENERIC);
+    assert!(
+        classes.windows(2).all(|w| w[0].0.end <= w[1].0.start),
+        "{:?}",
+        classes
+    );
+    assert_eq!(class_of(source, "start", 1), Some(TokenClass::LabelRef));
+    assert_eq!(class_of(source, "// broken", 0), Some(TokenClass::Comment));
+}
@@ -0,0 +1,176 @@
+//! Properties of the lexer and the literal helpers in `chasm::tokens`,
+//! over generated values and sources. `fuzz/fuzz_targets/lex_props.rs`
+//! checks the same ones under the fuzzer.
+
+use chasm::detokenize;
+use chasm::parser::{LexErrorKind, LexOptions, Token, TokenStream};
+use chasm::source::{BOM, FileId, SourceFile};
+use chasm::tokens::{self, Radix, TokenKind};
+use logos::Logos;
+use proptest::prelude::*;
+use std::rc::Rc;
+
+/// Characters worth putting in a string literal: plain text, everything
+/// that needs escaping, and something outside ASCII.
+const ALPHABET: &[char] = &[
+    'a', 'Z', '0', ' ', '\'', '"', '\\', '\n', '\r', '\t', '\0', 'é', '✓',
+];
+
+/// Pieces of source that lex into every kind of token, and some that put
+/// tokens right against each other.
+const PIECES: &[&str] = &[
+    "mov",
+    "r1",
+    "%sp",
+    "x",
+    "xFF",
+    "_tmp",
+    "a##b",
+    "0",
+    "12",
+    "0x1F",
+    "0b10",
+    "0o7",
+    "1_000",
+    "1.5",
+    "2e3",
+    "1b",
+    "2f",
+    "\"s\"",
+    "\"a\\\"b\"",
+    "r#\"q\"#",
+    "'a'",
+    "'\\n'",
+    "$",
+    "$$",
+    "+",
+    "++",
+    "-",
+    "-=",
+    "*",
+    "/",
+    "%",
+    "!",
+    "!=",
+    "<",
+    "<<",
+    "<<=",
+    ">",
+    ">>=",
+    "&",
+    "&&",
+    "|",
+    "||",
+    "^",
+    "~",
+    "=",
+    "==",
+    "(",
+    ")",
+    "[",
+    "]",
+    "{",
+    "}",
+    ",",
+    ":",
+    "::",
+    ".",
+    ";",
+    "#",
+    "`",
+    "@x",
+    "var",
+    "const",
+    "include",
+    "for!",
+    "if!",
+    "else!",
+    "macro_rules!",
+    " ",
+    "\t",
+    "\n",
+    "\\\n",
+    "// c\n",
+    "/* c */",
+];
+
+/// Sources made of [`PIECES`] glued together, or any text at all.
+fn source() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::collection::vec(prop::sample::select(PIECES), 0..24).prop_map(|p| p.concat()),
+        any::<String>(),
+    ]
+}
+
+fn kinds(tokens: &[Token]) -> Vec<TokenKind> {
+    tokens.iter().map(|t| t.kind.clone()).collect()
+}
+
+fn radix() -> impl Strategy<Value = Radix> {
+    prop::sample::select(&[Radix::Bin, Radix::Oct, Radix::Dec, Radix::Hex][..])
+}
+
+proptest! {
+    /// `format_int` writes a literal that lexes as one token of the same
+    /// value; negative values have no literal.
+    #[test]
+    fn int_roundtrip(value in any::<i64>(), radix in radix()) {
+        let Some(text) = tokens::format_int(value, radix) else {
+            prop_assert!(value < 0);
+            return Ok(());
+        };
+        let lexed: Vec<_> = TokenKind::lexer(&text).collect();
+        prop_assert_eq!(lexed.len(), 1, "{} lexed as {:?}", text, lexed);
+        prop_assert_eq!(lexed[0].as_ref().ok().and_then(TokenKind::int_value), Some(value));
+    }
+
+    /// `escape_string` quotes a string as one literal that `parse_string`
+    /// turns back into it.
+    #[test]
+    fn string_roundtrip(s in prop::collection::vec(prop::sample::select(ALPHABET), 0..32)) {
+        let s: String = s.into_iter().collect();
+        let literal = tokens::escape_string(&s);
+        let lexed: Vec<_> = TokenKind::lexer(&literal).collect();
+        match lexed.as_slice() {
+            [Ok(TokenKind::StrLit(raw))] => prop_assert_eq!(tokens::parse_string(raw), s),
+            other => prop_assert!(false, "{:?} lexed as {:?}", literal, other),
+        }
+    }
+
+    /// Tokens come out in order, each non-empty and inside the input.
+    #[test]
+    fn spans_in_order(source in source()) {
+        let mut end = 0;
+        for (_, span) in TokenKind::lexer(&source).spanned() {
+            prop_assert!(span.start >= end && span.start < span.end && span.end <= source.len());
+            end = span.end;
+        }
+    }
+
+    /// With trivia kept, the tokens spell the source again, unless some of
+    /// it didn't lex.
+    #[test]
+    fn trivia_roundtrip(source in source()) {
+        let opts = LexOptions {
+            trivia: true,
+            ..LexOptions::default()
+        };
+        let file = Rc::new(SourceFile::new("<input>", source.as_str()));
+        let stream = TokenStream::with_options(FileId::default(), file, opts);
+        if stream.lex_errors().iter().any(|e| e.kind == LexErrorKind::Unrecognised) {
+            return Ok(());
+        }
+        let trailing: String = stream.trailing_trivia().iter().map(|t| t.text.as_str()).collect();
+        let text = detokenize(&stream.into_tokens()) + &trailing;
+        prop_assert_eq!(text.as_str(), source.strip_prefix(BOM).unwrap_or(&source));
+    }
+
+    /// Tokens written back out with `detokenize` lex to the same kinds.
+    #[test]
+    fn detokenize_roundtrip(source in source()) {
+        let tokens = TokenStream::new(&source).into_tokens();
+        let text = detokenize(&tokens);
+        let relexed = TokenStream::new(&text).into_tokens();
+        prop_assert_eq!(kinds(&tokens), kinds(&relexed), "written as {:?}", text);
+    }
+}
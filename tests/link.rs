@@ -0,0 +1,92 @@
+//! Linking objects that refer to each other gives the bytes assembling
+//! their sources as one program does.
+
+use chasm::assemble::{Assembled, assemble};
+use chasm::codes::Code;
+use chasm::expand::{ExpandOptions, expand_traced};
+use chasm::isa::Target;
+use chasm::link::Linker;
+use chasm::object::Object;
+use chasm::parser::Parser;
+
+/// Calls `print` and loads `message`, both in [`LIB`], and exports `main`.
+const MAIN: &str = "\
+@extern print, message
+::main:
+    ldi r1 message + 1
+    call print
+    br print
+.loop:
+    beq .loop
+    jmp .loop
+    halt
+";
+
+/// Refers back to `main`, and to its own labels.
+const LIB: &str = "\
+@extern main
+::print:
+    ld r2 message
+    jmp done
+done:
+    ret
+::message:
+    .byte 1, 2
+    .word main, print, done
+";
+
+fn assembled(source: &str) -> Assembled {
+    let mut parser = Parser::new(source);
+    let ast = parser.parse();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    let target = Target::GENERIC;
+    let opts = ExpandOptions {
+        registers: target.registers,
+        ..ExpandOptions::default()
+    };
+    let (stmts, errors, origins) = expand_traced(&ast, &opts);
+    assert!(errors.is_empty(), "{:?}", errors);
+    let (assembled, errors) = assemble(&stmts, &origins, &target.isa(), &target.registers);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assembled
+}
+
+/// The object for `source`, through its on-disk form.
+fn object(source: &str) -> Object {
+    Object::from_bytes(&assembled(source).object.to_bytes()).unwrap()
+}
+
+#[test]
+fn linked_objects_match_the_program_assembled_whole() {
+    let (main, lib) = (object(MAIN), object(LIB));
+    let linked = Linker::new(0)
+        .add("main.obj", &main)
+        .add("lib.obj", &lib)
+        .link()
+        .unwrap();
+
+    let whole = assembled(&format!("{}{}", MAIN, LIB)).image().unwrap();
+    assert_eq!(linked.data, whole.data);
+    assert_eq!(linked.symbols, whole.symbols);
+    // Not just zeros where the references are.
+    assert!(!main.relocations.is_empty() && !lib.relocations.is_empty());
+}
+
+#[test]
+fn an_extern_nothing_linked_defines_is_undefined() {
+    let main = object(MAIN);
+    let errors = Linker::new(0).add("main.obj", &main).link().unwrap_err();
+    let undefined: Vec<_> = errors
+        .iter()
+        .filter(|diag| diag.code == Some(Code::E0402))
+        .map(|diag| diag.message.as_str())
+        .collect();
+    assert_eq!(
+        undefined,
+        [
+            "undefined reference to `message`",
+            "undefined reference to `print`",
+            "undefined reference to `print`",
+        ]
+    );
+}
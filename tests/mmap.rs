@@ -0,0 +1,92 @@
+//! Mapped source against source read into memory: the same text, and the
+//! same bytes assembled from it.
+
+#![cfg(feature = "mmap")]
+
+use chasm::assemble::assemble_file;
+use chasm::isa::Target;
+use chasm::source::{FileId, SourceManager, SourceText};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A program of `lines` lines, mostly comments describing its tables, with
+/// the little code and data that fits a 16-bit address space.
+fn program(lines: usize) -> String {
+    let mut out = String::from("start:\n");
+    for i in 0..lines {
+        let _ = match i % 64 {
+            0 => writeln!(out, "table_{}:", i),
+            1 => writeln!(out, "    ldi r1 table_{}", i - 1),
+            2 => writeln!(out, "    .word start, table_{}, {:#x}", i - 2, i),
+            3 => writeln!(out, "    .byte {}, {}, {}, {}", i % 256, 1, 2, 3),
+            _ => writeln!(
+                out,
+                "// row {}: entry {:#06x} of table_{} (é)",
+                i,
+                i,
+                i / 64 * 64
+            ),
+        };
+    }
+    out.push_str("    halt\n");
+    out
+}
+
+fn temp_file(name: &str, text: impl AsRef<[u8]>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chasm-mmap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path
+}
+
+fn assemble(sources: &SourceManager, file: FileId) -> Vec<u8> {
+    let target = Target::GENERIC;
+    assemble_file(sources, file, &target, &target.isa()).unwrap()
+}
+
+#[test]
+fn a_mapped_file_reads_the_same_as_one_in_memory() {
+    let text = program(150_000);
+    assert!(text.len() > 4 << 20, "{} bytes", text.len());
+    let path = temp_file("large.asm", &text);
+
+    let mapped = SourceText::mapped(&path).unwrap();
+    assert!(format!("{:?}", mapped).starts_with("Mapped("));
+    assert_eq!(mapped.len(), text.len());
+    assert!(*mapped == *text);
+}
+
+#[test]
+fn a_mapped_file_assembles_to_the_same_bytes() {
+    let text = program(150_000);
+    let path = temp_file("assemble.asm", &text);
+    assert!(text.len() > 4 << 20, "{} bytes", text.len());
+
+    let mut mapped = SourceManager::new();
+    let mapped_file = mapped.load_mapped(&path).unwrap();
+    let mut in_memory = SourceManager::new();
+    let in_memory_file = in_memory.add(path.display().to_string(), text);
+
+    let bytes = assemble(&mapped, mapped_file);
+    assert!(bytes.len() > 30_000, "{} bytes", bytes.len());
+    assert!(bytes == assemble(&in_memory, in_memory_file));
+}
+
+#[test]
+fn invalid_utf8_in_a_mapped_file_is_reported_at_its_offset() {
+    let path = temp_file("invalid.asm", b"nop\nhalt \xc3(\n");
+    let err = SourceText::mapped(&path).unwrap_err();
+    assert_eq!(err.to_string(), "invalid UTF-8 at byte offset 9");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn a_file_that_cant_be_mapped_is_read_instead() {
+    // Reports a size of 0, so mapping it would see nothing.
+    let path = Path::new("/proc/self/status");
+    let text = SourceText::mapped(path).unwrap();
+    assert!(format!("{:?}", text).starts_with("Owned("));
+    assert!(text.starts_with("Name:"), "{}", &*text);
+}
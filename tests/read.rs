@@ -0,0 +1,66 @@
+//! Reading source a chunk at a time, checking it's UTF-8 as it comes in.
+
+use chasm::source::SourceText;
+use std::io::{self, Read};
+
+/// Hands out at most `step` bytes per read, so characters straddle reads.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    step: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.step.min(buf.len()).min(self.bytes.len());
+        buf[..n].copy_from_slice(&self.bytes[..n]);
+        self.bytes = &self.bytes[n..];
+        Ok(n)
+    }
+}
+
+fn error_message(result: io::Result<SourceText>) -> String {
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    err.to_string()
+}
+
+#[test]
+fn characters_split_across_chunks_come_back_whole() {
+    // Two- and three-byte characters, over several 64 KiB chunks.
+    let text = "é✓ mov r1 r2\n".repeat(20_000);
+    for step in [1, 7, 4096, usize::MAX] {
+        let reader = Trickle {
+            bytes: text.as_bytes(),
+            step,
+        };
+        let read = SourceText::read_from(reader, 0).unwrap();
+        assert_eq!(&*read, text, "{} bytes a read", step);
+    }
+}
+
+#[test]
+fn the_first_invalid_byte_stops_the_read() {
+    // Never ends: only a read that stops at the bad chunk gets an answer.
+    let endless = (&b"nop\nhalt \xff\n"[..]).chain(io::repeat(b'a'));
+    assert_eq!(
+        error_message(SourceText::read_from(endless, 0)),
+        "invalid UTF-8 at byte offset 9"
+    );
+    // Past the first chunk.
+    let mut bytes = vec![b'a'; 200_000];
+    bytes[150_001] = 0x80;
+    assert_eq!(
+        error_message(SourceText::read_from(&bytes[..], bytes.len())),
+        "invalid UTF-8 at byte offset 150001"
+    );
+}
+
+#[test]
+fn a_character_cut_off_at_the_end_is_invalid() {
+    let bytes = "nop ✓".as_bytes();
+    let cut = &bytes[..bytes.len() - 1];
+    assert_eq!(
+        error_message(SourceText::read_from(cut, 0)),
+        "invalid UTF-8 at byte offset 4"
+    );
+}
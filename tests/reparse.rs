@@ -0,0 +1,90 @@
+//! Random edits through `ParseResult::update`, each checked against parsing
+//! the edited text from scratch, like `fuzz/fuzz_targets/reparse.rs` but
+//! from a fixed seed so every run tries the same edits.
+
+use chasm::diff::{self, DiffOptions};
+use chasm::incremental::{ParseResult, TextEdit};
+use chasm::tokens::TokenKind;
+use std::ops::Range;
+
+/// Source fragments to build text from, so that most programs parse and
+/// edits land on interesting boundaries.
+#[rustfmt::skip]
+const PIECES: &[&str] = &[
+    " ", "\n", "\n", "mov", "r0", "r1", "a", "b", "f", "1", "2", "0x", "F", "0b", "0", ":", "::",
+    ".", ";", ",", "{", "}", "(", ")", "[", "]", "\"", "'", "\\", "x", "var", "const", "=",
+    "include", "@define", "macro_rules!", "for!", "while!", "if!", "else", "<", "++", "for", "!",
+    "é", "1b", "1f", "2:", "jmp", "#", "/*", "*/", "//",
+];
+
+/// Programs to start from, and how many edits to make to each.
+const RUNS: u64 = 200;
+const EDITS: usize = 40;
+
+/// xorshift64*: small, and the same everywhere.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn text(&mut self, max_pieces: usize) -> String {
+        (0..self.below(max_pieces + 1))
+            .map(|_| PIECES[self.below(PIECES.len())])
+            .collect()
+    }
+}
+
+fn floor_boundary(s: &str, mut i: usize) -> usize {
+    i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Each token's kind, payload included, and where it is.
+fn tokens(parse: &ParseResult) -> Vec<(TokenKind, Range<usize>)> {
+    parse
+        .tokens()
+        .iter()
+        .map(|t| (t.kind.clone(), t.span.range.clone()))
+        .collect()
+}
+
+#[test]
+fn random_edits_match_a_fresh_parse() {
+    let spans = DiffOptions { spans: true };
+    for run in 0..RUNS {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15 ^ run);
+        let mut current = ParseResult::new(&rng.text(60));
+
+        for _ in 0..EDITS {
+            let source = current.source();
+            let start = floor_boundary(source, rng.below(source.len() + 1));
+            let end = floor_boundary(source, start + rng.below(8));
+            let edit = TextEdit::new(start..end, rng.text(4));
+
+            let mut text = source.to_string();
+            text.replace_range(edit.range.clone(), &edit.text);
+            let expected = ParseResult::new(&text);
+            let before = source.to_string();
+            let actual = current.update(edit.clone());
+
+            let context = format!("run {}, {:?} applied to {:?}", run, edit, before);
+            assert_eq!(tokens(&expected), tokens(&actual), "{}", context);
+            let diffs = diff::diff_with(expected.statements(), actual.statements(), &spans);
+            assert!(diffs.is_empty(), "{:?}: {}", diffs, context);
+            assert_eq!(expected.errors(), actual.errors(), "{}", context);
+            current = actual;
+        }
+    }
+}
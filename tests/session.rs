@@ -0,0 +1,53 @@
+//! Rebuilding through a `Session`: only edited files are parsed again,
+//! and each build's timings count them.
+
+use chasm::expand::ExpandOptions;
+use chasm::includes::IncludeOptions;
+use chasm::isa::Target;
+use chasm::session::Session;
+use std::fs;
+use std::path::PathBuf;
+
+fn scratch(test: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chasm-session-{}-{}", std::process::id(), test));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (name, text) in files {
+        fs::write(dir.join(name), text).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn editing_one_include_reparses_only_that_file() {
+    let dir = scratch(
+        "edit",
+        &[
+            ("main.asm", "include \"a.asm\"\ninclude \"b.asm\"\nhalt\n"),
+            ("a.asm", ".byte 1\n"),
+            ("b.asm", ".byte 2\n"),
+        ],
+    );
+    let entry = dir.join("main.asm");
+    let isa = Target::DEFAULT.isa();
+    let opts = ExpandOptions::default();
+    let mut session = Session::new(IncludeOptions::default());
+
+    let (first, diags) = session.assemble(&entry, &isa, &opts).unwrap();
+    assert!(diags.is_empty(), "{diags:?}");
+    assert_eq!(session.timings().cache.misses, 3);
+    assert_eq!(session.timings().cache.hits, 0);
+
+    let (again, _) = session.assemble(&entry, &isa, &opts).unwrap();
+    assert_eq!(session.timings().cache.misses, 0);
+    assert_eq!(session.timings().cache.hits, 3);
+    assert_eq!(again.object, first.object);
+
+    fs::write(dir.join("a.asm"), ".byte 1, 3\n").unwrap();
+    let (edited, diags) = session.assemble(&entry, &isa, &opts).unwrap();
+    assert!(diags.is_empty(), "{diags:?}");
+    assert_eq!(session.timings().cache.misses, 1);
+    assert_eq!(session.timings().cache.hits, 2);
+    let data = &edited.object.sections[0].data;
+    assert_eq!(&data[..3], [1, 3, 2]);
+}
@@ -0,0 +1,39 @@
+//! Parses a large generated program against a generous time bound, to
+//! catch an accidentally quadratic loop in the lexer or parser. The
+//! benchmarks in `benches/` measure; this only has to notice.
+
+#[allow(dead_code)]
+#[path = "../benches/fixtures.rs"]
+mod fixtures;
+
+use chasm::parser::Parser;
+use std::time::{Duration, Instant};
+
+const LINES: usize = 100_000;
+
+/// An unoptimised build parses the input in under a second; anything
+/// quadratic in 100k lines takes minutes.
+const BOUND: Duration = Duration::from_secs(10);
+
+#[test]
+fn parsing_100k_lines_is_quick() {
+    let mut input = fixtures::program(LINES);
+    // The worst cases, once each, in the same input.
+    input.push_str(&fixtures::long_operands(1_000));
+    input.push_str(&fixtures::nested(100));
+
+    let start = Instant::now();
+    let mut parser = Parser::new(&input);
+    let ast = parser.parse();
+    let elapsed = start.elapsed();
+
+    assert!(parser.errors().is_empty(), "{:?}", &parser.errors()[..1]);
+    assert_eq!(ast.len(), LINES + 2);
+    assert!(
+        elapsed < BOUND,
+        "parsing {} lines took {:?}, over {:?}",
+        LINES,
+        elapsed,
+        BOUND
+    );
+}
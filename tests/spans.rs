@@ -0,0 +1,115 @@
+//! Where tokens and statements point back into their file: every token
+//! and every kind of statement carries a span, and a span gives the file,
+//! line and column it starts at.
+
+use chasm::parser::{Parser, TokenStream};
+use chasm::source::SourceManager;
+use chasm::walk::iter_deep;
+use std::collections::HashSet;
+use std::mem;
+
+/// One of every kind of statement, each on a line of its own.
+///
+/// [`KINDS`] counts the variants of `StatementKind`.
+const EVERY_KIND: &str = r#"var a = 1
+var a += 2
+const B = 3
+LIMIT equ B - 1
+start:
+    mov r1 r2
+    @define WIDTH 8
+@org 0x100
+@align 4
+include "lib.asm"
+.word 1, 2
+.data
+.asciiz "hi"
+macro_rules! m(x) {
+    add x x
+}
+m!(r1)
+for!(var i = 0; i < 2; i++) {
+    nop
+}
+while!(0) {
+    nop
+}
+repeat!(2) {
+    break!
+}
+macro_rules! each(regs...) {
+    @foreach regs {
+        continue!
+    }
+}
+if!(1) {
+    nop
+}
+module io {
+    port:
+}
+struct Point { x: word, y: word }
+Point { x: 1, y: 2 }
+enum State { Idle, Run }
+{
+    nop
+}
+"#;
+
+const KINDS: usize = 27;
+
+#[test]
+fn tokens_carry_their_spans() {
+    for token in TokenStream::new(EVERY_KIND).into_tokens() {
+        assert_eq!(
+            &EVERY_KIND[token.span.range.clone()],
+            token.text,
+            "{:?}",
+            token
+        );
+    }
+}
+
+/// Each statement's span starts where it's written, on its own line, and
+/// covers its text, nested statements included.
+#[test]
+fn every_statement_kind_carries_its_span() {
+    let mut sources = SourceManager::new();
+    let file = sources.add("every.asm", EVERY_KIND);
+    let mut parser = Parser::for_file(&sources, file);
+    let ast = parser.parse();
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+
+    let kinds: HashSet<_> = iter_deep(&ast)
+        .map(|s| mem::discriminant(&s.kind))
+        .collect();
+    assert_eq!(kinds.len(), KINDS);
+
+    let mut lines = Vec::new();
+    for stmt in iter_deep(&ast) {
+        assert_eq!(stmt.span.file, file);
+        let location = sources.location(&stmt.span);
+        let line = EVERY_KIND.lines().nth(location.line - 1).unwrap();
+        let text = &EVERY_KIND[stmt.span.range.clone()];
+        assert!(
+            text.starts_with(line.trim_start()) || line.trim_start().starts_with(text),
+            "{:?} at {} spans {:?}, not line {:?}",
+            stmt.kind,
+            location,
+            text,
+            line
+        );
+        assert_eq!(location.name, "every.asm");
+        assert_eq!(location.col, line.len() - line.trim_start().len() + 1);
+        lines.push(location.line);
+    }
+    // Source order, and every line that starts a statement.
+    assert!(lines.is_sorted());
+    let expected = EVERY_KIND
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line.trim(), "}" | ""))
+        .map(|(index, _)| index + 1);
+    lines.dedup();
+    assert_eq!(lines, expected.collect::<Vec<_>>());
+}
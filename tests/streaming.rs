@@ -0,0 +1,69 @@
+//! The streaming parser, which lexes only as far as it has parsed: it
+//! reads the same program as a parse of the whole token list, and one
+//! stopped early never lexes the rest.
+
+use chasm::diff::{DiffOptions, diff_with};
+use chasm::parser::{LexOptions, Parser, Statement};
+use chasm::source::{FileId, SourceManager};
+use std::fs;
+use std::path::Path;
+
+/// What `parser` makes of its file: the statements, and the syntax errors,
+/// lexing errors and skipped tokens, printed.
+fn parse(parser: &mut Parser) -> (Vec<Statement>, String) {
+    let ast = parser.parse();
+    let rest = format!(
+        "{:?}\n{:?}\n{:?}",
+        parser.errors(),
+        parser.lex_errors(),
+        parser.skipped()
+    );
+    (ast, rest)
+}
+
+fn batch(sources: &SourceManager, file: FileId) -> (Vec<Statement>, String) {
+    parse(&mut Parser::for_file(sources, file))
+}
+
+fn streamed(sources: &SourceManager, file: FileId) -> (Vec<Statement>, String) {
+    parse(&mut Parser::streaming(sources, file, LexOptions::default()))
+}
+
+#[test]
+fn streamed_statements_equal_the_batch_parse() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut sources = SourceManager::new();
+    let mut checked = 0;
+    for entry in fs::read_dir(fixtures).unwrap() {
+        let path = entry.unwrap().path().join("input.asm");
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let file = sources.add(path.display().to_string(), text);
+        let (expected, expected_rest) = batch(&sources, file);
+        let (actual, actual_rest) = streamed(&sources, file);
+        let spans = DiffOptions { spans: true };
+        let diffs = diff_with(&expected, &actual, &spans);
+        assert!(diffs.is_empty(), "{}: {:?}", path.display(), diffs);
+        assert_eq!(actual_rest, expected_rest, "{}", path.display());
+        checked += 1;
+    }
+    assert!(checked > 50, "only {} fixtures", checked);
+}
+
+#[test]
+fn stopping_early_leaves_the_rest_unlexed() {
+    // The last line doesn't lex, so whether it has been lexed shows.
+    let source = format!("nop\n{}mov r1 ¤ r2\n", "push r0\n".repeat(10_000));
+    let mut sources = SourceManager::new();
+    let file = sources.add("main.asm", source);
+    let mut parser = Parser::streaming(&sources, file, LexOptions::default());
+
+    let first = parser.statements().next().unwrap().unwrap();
+    assert_eq!(first.span.range, 0..3);
+    assert_eq!(parser.lex_errors(), []);
+
+    let rest = parser.statements().count();
+    assert_eq!(rest, 10_001);
+    assert_eq!(parser.lex_errors().len(), 1, "{:?}", parser.lex_errors());
+}
@@ -0,0 +1,72 @@
+//! Seeking in a `TokenStream`: going back puts back every payload the
+//! `take_*` methods moved out, so the tokens can be parsed again.
+
+use chasm::parser::{LexOptions, Token, TokenStream};
+use chasm::source::{FileId, SourceFile};
+use chasm::tokens::TokenKind;
+use std::mem;
+use std::rc::Rc;
+
+/// Identifiers, registers, string literals (raw and CRLF ones included)
+/// and numeric label references, `_`s and leading zeros included.
+const SOURCE: &str =
+    "1: mov r1 \"hi\\n\" r#\"raw\"# name\n jmp 1b\r\n jmp 1_0f\n jmp 01b\n.asciiz \"a\r\nb\"";
+
+fn kinds(tokens: &[Token]) -> Vec<TokenKind> {
+    tokens.iter().map(|t| t.kind.clone()).collect()
+}
+
+/// Consumes every token left, moving out any payload a `take_*` method
+/// would.
+fn take_all(stream: &mut TokenStream) {
+    while !stream.eof() {
+        stream.take_with(|kind| match kind {
+            TokenKind::Ident(s)
+            | TokenKind::Register(s)
+            | TokenKind::StrLit(s)
+            | TokenKind::NumericLabelRef(s) => Some(mem::take(s)),
+            _ => None,
+        });
+    }
+}
+
+#[test]
+fn seeking_back_restores_payloads() {
+    let fresh = kinds(&TokenStream::new(SOURCE).into_tokens());
+    let mut stream = TokenStream::new(SOURCE);
+
+    take_all(&mut stream);
+    stream.set_position(0);
+    assert_eq!(kinds(&stream.iter().cloned().collect::<Vec<_>>()), fresh);
+
+    // Part way back, then all the way.
+    take_all(&mut stream);
+    let middle = stream.len() / 2;
+    stream.set_position(middle);
+    assert_eq!(kinds(stream.slice(middle..stream.len())), fresh[middle..]);
+    take_all(&mut stream);
+    stream.set_position(0);
+    assert_eq!(kinds(&stream.into_tokens()), fresh);
+}
+
+#[test]
+fn seeking_forward_leaves_payloads_taken() {
+    let mut stream = TokenStream::new("name other");
+    take_all(&mut stream);
+    stream.set_position(1);
+    stream.set_position(2);
+    assert_eq!(stream.slice(0..1)[0].kind, TokenKind::Ident(String::new()));
+}
+
+#[test]
+fn streaming_seeks_back_within_the_window() {
+    let source = Rc::new(SourceFile::new("<input>", SOURCE));
+    let mut stream = TokenStream::streaming(FileId::default(), source, LexOptions::default());
+    let fresh = kinds(&TokenStream::new(SOURCE).into_tokens());
+
+    // A streaming stream keeps what hasn't been released between
+    // statements, which nothing here does.
+    take_all(&mut stream);
+    stream.set_position(3);
+    assert_eq!(kinds(stream.slice(3..stream.len())), fresh[3..]);
+}
@@ -0,0 +1,184 @@
+//! With the `tracing` feature, assembling reports its phases as spans and
+//! what it finds along the way as events. A subscriber that records them
+//! checks which ones come out, and in which span.
+
+#![cfg(feature = "tracing")]
+
+use chasm::assemble::assemble_file;
+use chasm::includes::IncludeOptions;
+use chasm::isa::Target;
+use chasm::parser::Parser;
+use chasm::session::Session;
+use chasm::source::SourceManager;
+use std::fmt::Debug;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Registry;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// An event as recorded: its level, the span it happened in, its message
+/// and its other fields, formatted.
+#[derive(Debug, Clone, PartialEq)]
+struct Recorded {
+    level: Level,
+    span: Option<&'static str>,
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => self.fields.push((name, format!("{:?}", value))),
+        }
+    }
+}
+
+/// Records every span opened and every event, in order.
+#[derive(Clone, Default)]
+struct Capture {
+    spans: Arc<Mutex<Vec<&'static str>>>,
+    events: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+        self.spans.lock().unwrap().push(attrs.metadata().name());
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(Recorded {
+            level: *event.metadata().level(),
+            span: ctx.event_span(event).map(|span| span.name()),
+            message: fields.message,
+            fields: fields.fields,
+        });
+    }
+}
+
+/// Runs `f` with a new capture listening.
+fn capture<T>(f: impl FnOnce() -> T) -> Capture {
+    let capture = Capture::default();
+    let subscriber = Registry::default().with(capture.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    capture
+}
+
+/// Parses `source` with a capture listening.
+fn parse(source: &str) -> Capture {
+    capture(|| Parser::new(source).parse())
+}
+
+fn event(capture: &Capture, message: &str) -> Recorded {
+    let events = capture.events.lock().unwrap();
+    let found = events.iter().find(|e| e.message == message);
+    found
+        .unwrap_or_else(|| panic!("no `{}` event in {:#?}", message, events))
+        .clone()
+}
+
+#[test]
+fn lexing_and_parsing_are_spans() {
+    let capture = parse("mov r1 r2\n");
+    assert_eq!(*capture.spans.lock().unwrap(), ["lex", "parse"]);
+
+    let lexed = event(&capture, "lexed tokens");
+    assert_eq!(lexed.level, Level::DEBUG);
+    assert_eq!(lexed.span, Some("lex"));
+    assert_eq!(lexed.fields, [("count", "3".to_string())]);
+}
+
+#[test]
+fn findings_are_events_in_their_phase() {
+    let capture = parse("include \"lib.asm\"\nmacro_rules! m(a, b) { nop }\n§\nmov r1 (\n");
+
+    let skipped = event(&capture, "skipping unrecognised input");
+    assert_eq!(skipped.level, Level::WARN);
+    assert_eq!(skipped.span, Some("lex"));
+    assert!(skipped.fields.contains(&("text", "\"§\"".to_string())));
+
+    let include = event(&capture, "include");
+    assert_eq!(include.span, Some("parse"));
+    assert_eq!(include.fields, [("file", "\"lib.asm\"".to_string())]);
+
+    let def = event(&capture, "macro definition");
+    assert_eq!(def.span, Some("parse"));
+    assert_eq!(
+        def.fields,
+        [("name", "m".to_string()), ("params", "2".to_string())]
+    );
+
+    let recovering = event(&capture, "recovering from syntax error");
+    assert_eq!(recovering.span, Some("parse"));
+    assert_eq!(recovering.fields.len(), 1);
+    assert_eq!(recovering.fields[0].0, "error");
+}
+
+#[test]
+fn assembling_is_a_span_per_phase() {
+    let source = "\
+macro_rules! load(reg, value) {
+    ldi reg value
+}
+start:
+    load!(r1, start)
+    halt
+";
+    let mut sources = SourceManager::new();
+    let file = sources.add("main.asm", source);
+    let target = Target::GENERIC;
+    let capture = capture(|| assemble_file(&sources, file, &target, &target.isa()).unwrap());
+    let spans = capture.spans.lock().unwrap().clone();
+    let phases: Vec<_> = ["lex", "parse", "expand", "resolve", "encode"]
+        .into_iter()
+        .map(|phase| spans.iter().position(|span| *span == phase))
+        .collect();
+    assert!(phases.iter().all(Option::is_some), "{:?}", spans);
+    assert!(phases.is_sorted(), "{:?}", spans);
+
+    let expansion = event(&capture, "macro expansion");
+    assert_eq!(expansion.span, Some("expand"));
+    assert_eq!(
+        expansion.fields,
+        [
+            ("name", "load".to_string()),
+            ("args", "2".to_string()),
+            ("depth", "0".to_string())
+        ]
+    );
+}
+
+#[test]
+fn a_session_reports_each_include_it_resolves() {
+    let dir = std::env::temp_dir().join(format!("chasm-tracing-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("main.asm"), "include \"lib/io.asm\"\nhalt\n").unwrap();
+    fs::write(dir.join("lib/io.asm"), "nop\n").unwrap();
+
+    let mut session = Session::new(IncludeOptions::default());
+    let capture = capture(|| session.build(&dir.join("main.asm")).unwrap());
+    let resolved = event(&capture, "include resolved");
+    let dir = fs::canonicalize(&dir).unwrap();
+    assert_eq!(
+        resolved.fields,
+        [
+            ("from", dir.join("main.asm").display().to_string()),
+            ("include", "\"lib/io.asm\"".to_string()),
+            ("path", dir.join("lib/io.asm").display().to_string())
+        ]
+    );
+}
@@ -0,0 +1,111 @@
+//! `iter_deep` and friends against hand-flattened trees.
+
+use chasm::parser::{Parser, Statement, StatementKind};
+use chasm::walk::{bodies, instructions, iter_deep, iter_deep_with_depth};
+
+/// Every kind of bodied statement, nested, with one instruction at each
+/// place a statement can go, named for where it is.
+const NESTED: &str = r#"
+top0
+{
+    block1
+    { block2 }
+}
+macro_rules! m(x) {
+    macro1
+    for!(var i = 0; i < 2; i += 1) { for2 }
+}
+if!(1) { if1 } elif!(2) { elif1 } else! { else1 }
+while!(0) { while1 }
+repeat!(2) { repeat1 }
+module io { module1 }
+top1
+"#;
+
+/// What a statement is, for comparing: an instruction's name, or the kind
+/// of anything else.
+fn name(stmt: &Statement) -> String {
+    match &stmt.kind {
+        StatementKind::Instruction { name, .. } => name.clone(),
+        kind => format!("{:?}", kind)
+            .split([' ', '(', '{'])
+            .next()
+            .unwrap()
+            .to_string(),
+    }
+}
+
+#[test]
+fn walks_in_source_order_with_depths() {
+    let ast = Parser::new(NESTED).parse();
+    let walked: Vec<(usize, String)> = iter_deep_with_depth(&ast)
+        .map(|(depth, stmt)| (depth, name(stmt)))
+        .collect();
+    let expected = [
+        (0, "top0"),
+        (0, "Block"),
+        (1, "block1"),
+        (1, "Block"),
+        (2, "block2"),
+        (0, "MacroDef"),
+        (1, "macro1"),
+        (1, "ForLoop"),
+        (2, "for2"),
+        (0, "If"),
+        (1, "if1"),
+        (1, "elif1"),
+        (1, "else1"),
+        (0, "WhileLoop"),
+        (1, "while1"),
+        (0, "Repeat"),
+        (1, "repeat1"),
+        (0, "Module"),
+        (1, "module1"),
+        (0, "top1"),
+    ];
+    let expected: Vec<(usize, String)> = expected
+        .iter()
+        .map(|&(depth, name)| (depth, name.to_string()))
+        .collect();
+    assert_eq!(walked, expected);
+
+    let flat: Vec<String> = iter_deep(&ast).map(name).collect();
+    let names: Vec<String> = expected.into_iter().map(|(_, name)| name).collect();
+    assert_eq!(flat, names);
+}
+
+#[test]
+fn instructions_skip_everything_else() {
+    let ast = Parser::new("a r1 2\n{ b\n repeat!(2) { c r3 } }\nconst K = 1\nd").parse();
+    let found: Vec<(&str, usize)> = instructions(&ast)
+        .map(|(name, args)| (name, args.len()))
+        .collect();
+    assert_eq!(found, [("a", 2), ("b", 0), ("c", 1), ("d", 0)]);
+}
+
+#[test]
+fn stops_early_and_resumes() {
+    let ast = Parser::new(NESTED).parse();
+    let mut walk = iter_deep(&ast);
+
+    // `find` stops at the first match, deep inside, and the walk carries
+    // on from there.
+    let found = walk.by_ref().find(|stmt| name(stmt) == "for2");
+    assert_eq!(found.map(name).as_deref(), Some("for2"));
+    assert_eq!(walk.next().map(name).as_deref(), Some("If"));
+
+    let rest: Vec<String> = walk.take(3).map(name).collect();
+    assert_eq!(rest, ["if1", "elif1", "else1"]);
+}
+
+#[test]
+fn bodies_in_order_both_ways() {
+    let ast = Parser::new("if!(1) { a } elif!(2) { b } elif!(3) { c } else! { d }").parse();
+    let first = |body: &[Statement]| name(&body[0]);
+    let forward: Vec<String> = bodies(&ast[0]).map(first).collect();
+    let backward: Vec<String> = bodies(&ast[0]).rev().map(first).collect();
+    assert_eq!(forward, ["a", "b", "c", "d"]);
+    assert_eq!(backward, ["d", "c", "b", "a"]);
+    assert_eq!(bodies(&ast[0]).count(), 4);
+    assert_eq!(bodies(&Parser::new("nop").parse()[0]).count(), 0);
+}
@@ -0,0 +1,92 @@
+//! The browser entry points, called natively.
+
+#![cfg(feature = "wasm")]
+
+use chasm::wasm::{assemble_to_bytes, assemble_to_bytes_with_files, parse_to_json};
+
+#[test]
+fn assembles_for_the_generic_target_without_an_instruction_set() {
+    let bytes = assemble_to_bytes("start:\n    ldi r1 0x1234\n    jmp start\n", "").unwrap();
+    assert_eq!(bytes, [0x11, 0x01, 0x34, 0x12, 0x20, 0x00, 0x00]);
+}
+
+#[test]
+fn assembles_for_an_instruction_set_given_as_json() {
+    let isa = r#"{"instructions":[
+        {"mnemonic":"load","opcode":160,"operands":["reg","u8"]},
+        {"mnemonic":"loop","opcode":161,"operands":["rel8"]}
+    ]}"#;
+    let bytes = assemble_to_bytes("top:\n    load r2 7\n    loop top\n", isa).unwrap();
+    assert_eq!(bytes, [0xa0, 0x02, 0x07, 0xa1, 0xfb]);
+}
+
+#[test]
+fn errors_are_a_diagnostics_object() {
+    let error = assemble_to_bytes("    jpm 0\n", "").unwrap_err();
+    assert_eq!(
+        error,
+        r#"{"diagnostics":[{"severity":"error","code":"E0304","message":"unknown instruction `jpm`","file":"<input>","line":1,"column":5}]}"#
+    );
+
+    let error = assemble_to_bytes(
+        "nop\n",
+        r#"{"instructions":[{"mnemonic":"nop","opcode":0,"operands":["u7"]}]}"#,
+    )
+    .unwrap_err();
+    assert!(error.contains("`u7` is not an operand"), "{}", error);
+}
+
+#[test]
+fn includes_come_from_the_files_given() {
+    let files = r#"{
+        "regs.asm": "const BASE = 0x40\n",
+        "io.asm": "include \"regs.asm\"\nout:\n    ldi r1 BASE\n"
+    }"#;
+    let source = "include \"io.asm\"\ninclude \"regs.asm\"\n    jmp out\n";
+    let bytes = assemble_to_bytes_with_files(source, "", files).unwrap();
+    assert_eq!(bytes, [0x11, 0x01, 0x40, 0x00, 0x20, 0x00, 0x00]);
+}
+
+#[test]
+fn errors_in_an_included_file_name_it() {
+    let files = r#"{"lib.asm": "nop\n    jpm 0\n"}"#;
+    let error = assemble_to_bytes_with_files("include \"lib.asm\"\n", "", files).unwrap_err();
+    assert_eq!(
+        error,
+        r#"{"diagnostics":[{"severity":"error","code":"E0304","message":"unknown instruction `jpm`","file":"<lib.asm>","line":2,"column":5}]}"#
+    );
+}
+
+#[test]
+fn an_include_naming_no_file_is_an_error() {
+    let error = assemble_to_bytes_with_files("nop\ninclude \"lib.asm\"\n", "", "{}").unwrap_err();
+    assert_eq!(
+        error,
+        r#"{"diagnostics":[{"severity":"error","code":null,"message":"include \"lib.asm\" not found","file":"<input>","line":2,"column":1}]}"#
+    );
+
+    let error = assemble_to_bytes_with_files("nop\n", "", "[1]").unwrap_err();
+    assert!(
+        error.contains(r#""message":"files: invalid type"#),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn parses_to_an_ast() {
+    let json = parse_to_json("start:\n    ldi r1 2\n");
+    assert!(json.starts_with(r#"{"ast":[{"kind":"Label""#), "{}", json);
+    assert!(json.contains(r#""name":"ldi""#), "{}", json);
+}
+
+#[test]
+fn a_parse_error_is_a_diagnostics_object() {
+    let json = parse_to_json("nop\nconst = 4\n");
+    assert!(
+        json.starts_with(r#"{"diagnostics":[{"severity":"error""#),
+        "{}",
+        json
+    );
+    assert!(json.contains(r#""file":"<input>","line":2"#), "{}", json);
+}